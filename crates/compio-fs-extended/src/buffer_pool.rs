@@ -0,0 +1,440 @@
+//! Registered buffer pool for fixed-buffer reads and writes
+//!
+//! Every plain io_uring read or write makes the kernel look up and pin the
+//! pages of its buffer. A [`BufferPool`] allocates one slab of equally sized
+//! buffers up front and registers it with the current compio ring
+//! (`IORING_REGISTER_BUFFERS`), so reads and writes through a
+//! [`BufferLease`] use `IORING_OP_READ_FIXED`/`WRITE_FIXED` and skip that
+//! work. Reusing leased buffers also avoids allocating and zeroing a fresh
+//! `Vec` for every chunk.
+//!
+//! A ring holds at most one set of registered buffers, so create one pool
+//! per runtime thread and keep it. Registered memory counts against
+//! `RLIMIT_MEMLOCK` unless the process has `CAP_IPC_LOCK`; registration
+//! fails when the limit is too low, and callers should fall back to plain
+//! buffers.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use compio::fs::File;
+//! use compio_fs_extended::buffer_pool::BufferPool;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let pool = BufferPool::new(16, 64 * 1024)?;
+//! let src = File::open("input.bin").await?;
+//! let dst = File::create("output.bin").await?;
+//!
+//! let lease = pool.try_lease().expect("a buffer is free");
+//! let (read, lease) = lease.read_at(&src, 0, None).await;
+//! let (written, _lease) = lease.write_at(&dst, 0, None).await;
+//! assert_eq!(read?, written?);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{invalid_parameters_error, Result};
+use crate::ioprio::IoPriority;
+use compio::driver::OpCode;
+use compio::fs::File;
+use compio::runtime::{submit, Runtime};
+use io_uring::{opcode, types};
+use std::cell::{RefCell, UnsafeCell};
+use std::io;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::pin::Pin;
+use std::rc::Rc;
+
+/// `IORING_REGISTER_BUFFERS` from `linux/io_uring.h`
+const IORING_REGISTER_BUFFERS: libc::c_uint = 0;
+
+/// `IORING_UNREGISTER_BUFFERS` from `linux/io_uring.h`
+const IORING_UNREGISTER_BUFFERS: libc::c_uint = 1;
+
+/// Most buffers one ring can register (`UIO_MAXIOV`)
+const MAX_BUFFERS: usize = 1024;
+
+/// A slab of buffers registered with the current thread's ring
+///
+/// Cloning a `BufferPool` shares the same buffers. The pool is not `Send`:
+/// its buffers are registered with one ring and can only be used by
+/// operations submitted to it.
+#[derive(Debug, Clone)]
+pub struct BufferPool {
+    /// Memory and free list shared with outstanding leases
+    inner: Rc<PoolInner>,
+}
+
+/// State shared by a pool and its leases
+#[derive(Debug)]
+struct PoolInner {
+    /// The registered memory, `count * buffer_size` bytes
+    memory: Box<[UnsafeCell<u8>]>,
+    /// Size of each buffer
+    buffer_size: usize,
+    /// Indexes of buffers not leased out
+    free: RefCell<Vec<u16>>,
+    /// Duplicate of the ring's descriptor, so unregistering on drop always
+    /// reaches the same ring
+    ring: OwnedFd,
+    /// The runtime's own descriptor for the ring
+    runtime_fd: RawFd,
+}
+
+impl BufferPool {
+    /// Allocate `count` buffers of `buffer_size` bytes and register them with
+    /// the current compio runtime's ring
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - `count` is 0 or above 1024, or `buffer_size` is 0 or above 1 GiB
+    /// - The ring already has registered buffers (`EBUSY`)
+    /// - The memory cannot be locked (`ENOMEM` with a low `RLIMIT_MEMLOCK`)
+    ///
+    /// # Panics
+    ///
+    /// Panics when called outside a compio runtime.
+    pub fn new(count: usize, buffer_size: usize) -> Result<Self> {
+        if count == 0 || count > MAX_BUFFERS || buffer_size == 0 || buffer_size > 1 << 30 {
+            return Err(invalid_parameters_error(&format!(
+                "cannot register {count} buffers of {buffer_size} bytes"
+            )));
+        }
+        let memory: Box<[UnsafeCell<u8>]> = (0..count * buffer_size)
+            .map(|_| UnsafeCell::new(0))
+            .collect();
+        let base = UnsafeCell::raw_get(memory.as_ptr());
+        let iovecs: Vec<libc::iovec> = (0..count)
+            .map(|i| libc::iovec {
+                // SAFETY: buffer `i` lies within the allocation
+                iov_base: unsafe { base.add(i * buffer_size) }.cast(),
+                iov_len: buffer_size,
+            })
+            .collect();
+
+        let ring_fd = Runtime::with_current(AsRawFd::as_raw_fd);
+        // SAFETY: dup only creates a new descriptor
+        let dup = unsafe { libc::fcntl(ring_fd, libc::F_DUPFD_CLOEXEC, 0) };
+        if dup < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        // SAFETY: the descriptor was just created and is owned by nobody else
+        let ring = unsafe { OwnedFd::from_raw_fd(dup) };
+
+        // SAFETY: the iovecs describe memory owned by the pool, which stays
+        // registered until the pool is dropped
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_io_uring_register,
+                ring.as_raw_fd(),
+                IORING_REGISTER_BUFFERS,
+                iovecs.as_ptr(),
+                libc::c_uint::try_from(count).unwrap_or(libc::c_uint::MAX),
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        Ok(Self {
+            inner: Rc::new(PoolInner {
+                memory,
+                buffer_size,
+                // Leases are handed out lowest index first
+                free: RefCell::new(
+                    (0..count)
+                        .rev()
+                        .filter_map(|i| u16::try_from(i).ok())
+                        .collect(),
+                ),
+                ring,
+                runtime_fd: ring_fd,
+            }),
+        })
+    }
+
+    /// Whether the pool was registered with the current runtime's ring
+    ///
+    /// Only meaningful on threads that may run more than one runtime in
+    /// turn; compares descriptor numbers, so it panics outside a runtime.
+    #[must_use]
+    pub fn is_current(&self) -> bool {
+        Runtime::with_current(AsRawFd::as_raw_fd) == self.inner.runtime_fd
+    }
+
+    /// Size of each buffer in the pool
+    #[must_use]
+    pub fn buffer_size(&self) -> usize {
+        self.inner.buffer_size
+    }
+
+    /// Number of buffers not currently leased
+    #[must_use]
+    pub fn available(&self) -> usize {
+        self.inner.free.borrow().len()
+    }
+
+    /// Lease a buffer, or `None` if all are in use
+    ///
+    /// The lease starts out covering the whole buffer; the buffer goes back
+    /// to the pool when the lease is dropped.
+    #[must_use]
+    pub fn try_lease(&self) -> Option<BufferLease> {
+        let index = self.inner.free.borrow_mut().pop()?;
+        Some(BufferLease {
+            pool: Rc::clone(&self.inner),
+            index,
+            len: self.inner.buffer_size,
+        })
+    }
+}
+
+impl Drop for PoolInner {
+    fn drop(&mut self) {
+        // SAFETY: unregistering takes no arguments; no lease (and so no
+        // operation using the buffers) can outlive the pool
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_io_uring_register,
+                self.ring.as_raw_fd(),
+                IORING_UNREGISTER_BUFFERS,
+                std::ptr::null::<libc::c_void>(),
+                0,
+            )
+        };
+        if res < 0 {
+            log::debug!(
+                "Failed to unregister buffers: {}",
+                io::Error::last_os_error()
+            );
+        }
+    }
+}
+
+/// One buffer of a [`BufferPool`], held exclusively until dropped
+///
+/// The lease has a length: the number of bytes of the buffer in use, which
+/// [`write_at`](Self::write_at) writes and [`read_at`](Self::read_at)
+/// requests.
+#[derive(Debug)]
+pub struct BufferLease {
+    /// Pool the buffer belongs to
+    pool: Rc<PoolInner>,
+    /// Index of the buffer in the pool
+    index: u16,
+    /// Bytes of the buffer in use
+    len: usize,
+}
+
+impl BufferLease {
+    /// Bytes of the buffer in use
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether no bytes of the buffer are in use
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Size of the whole buffer
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.pool.buffer_size
+    }
+
+    /// Use the first `len` bytes of the buffer, clamped to its capacity
+    pub fn set_len(&mut self, len: usize) {
+        self.len = len.min(self.capacity());
+    }
+
+    /// The bytes in use
+    #[must_use]
+    pub fn as_slice(&self) -> &[u8] {
+        // SAFETY: the lease has exclusive use of its buffer, and no operation
+        // is writing into it while the lease can be borrowed
+        unsafe { std::slice::from_raw_parts(self.as_ptr(), self.len) }
+    }
+
+    /// The bytes in use, mutably
+    #[must_use]
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        // SAFETY: as for `as_slice`, and `&mut self` rules out other borrows
+        unsafe { std::slice::from_raw_parts_mut(self.as_ptr(), self.len) }
+    }
+
+    /// Start of the leased buffer
+    fn as_ptr(&self) -> *mut u8 {
+        let buffer = &self.pool.memory[usize::from(self.index) * self.pool.buffer_size];
+        UnsafeCell::raw_get(buffer)
+    }
+
+    /// Read up to [`len`](Self::len) bytes of `file` at `offset` into the buffer
+    ///
+    /// Returns the number of bytes read and the lease, whose length is left
+    /// unchanged. With `priority`, the read carries that I/O priority.
+    pub async fn read_at(
+        self,
+        file: &File,
+        offset: u64,
+        priority: Option<IoPriority>,
+    ) -> (io::Result<usize>, Self) {
+        let op = FixedOp {
+            fd: file.as_raw_fd(),
+            offset,
+            lease: self,
+            write: false,
+            ioprio: priority.map_or(0, IoPriority::to_raw),
+        };
+        let compio::BufResult(result, op) = submit(op).await;
+        (result, op.lease)
+    }
+
+    /// Write the [`len`](Self::len) bytes in use to `file` at `offset`
+    ///
+    /// Returns the number of bytes written and the lease. With `priority`,
+    /// the write carries that I/O priority.
+    pub async fn write_at(
+        self,
+        file: &File,
+        offset: u64,
+        priority: Option<IoPriority>,
+    ) -> (io::Result<usize>, Self) {
+        let op = FixedOp {
+            fd: file.as_raw_fd(),
+            offset,
+            lease: self,
+            write: true,
+            ioprio: priority.map_or(0, IoPriority::to_raw),
+        };
+        let compio::BufResult(result, op) = submit(op).await;
+        (result, op.lease)
+    }
+}
+
+impl Drop for BufferLease {
+    fn drop(&mut self) {
+        self.pool.free.borrow_mut().push(self.index);
+    }
+}
+
+/// io_uring `READ_FIXED`/`WRITE_FIXED` through a leased buffer
+///
+/// The operation owns the lease, so the buffer cannot be handed out again
+/// while the kernel may still be using it.
+struct FixedOp {
+    /// File read or written
+    fd: i32,
+    /// File offset
+    offset: u64,
+    /// Buffer used
+    lease: BufferLease,
+    /// Write the buffer instead of reading into it
+    write: bool,
+    /// `sqe->ioprio`
+    ioprio: u16,
+}
+
+impl OpCode for FixedOp {
+    fn create_entry(self: Pin<&mut Self>) -> compio::driver::OpEntry {
+        let ptr = self.lease.as_ptr();
+        let len = u32::try_from(self.lease.len).unwrap_or(u32::MAX);
+        let entry = if self.write {
+            opcode::WriteFixed::new(types::Fd(self.fd), ptr, len, self.lease.index)
+                .offset(self.offset)
+                .ioprio(self.ioprio)
+                .build()
+        } else {
+            opcode::ReadFixed::new(types::Fd(self.fd), ptr, len, self.lease.index)
+                .offset(self.offset)
+                .ioprio(self.ioprio)
+                .build()
+        };
+        compio::driver::OpEntry::Submission(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[compio::test]
+    async fn test_buffer_pool_round_trip() {
+        let pool = match BufferPool::new(4, 4096) {
+            Ok(pool) => pool,
+            // No memlock allowance in this environment
+            Err(crate::ExtendedError::Io(e)) if e.raw_os_error() == Some(libc::ENOMEM) => return,
+            Err(e) => panic!("registering buffers failed: {e}"),
+        };
+        let temp_dir = TempDir::new().unwrap();
+        let content: Vec<u8> = (0..6000).map(|i| (i % 251) as u8).collect();
+        fs::write(temp_dir.path().join("src"), &content).unwrap();
+        let src = File::open(temp_dir.path().join("src")).await.unwrap();
+        let dst = File::create(temp_dir.path().join("dst")).await.unwrap();
+
+        // Copy in pool-sized chunks through one lease
+        let mut lease = pool.try_lease().unwrap();
+        assert_eq!(pool.available(), 3);
+        let mut offset = 0;
+        loop {
+            lease.set_len(lease.capacity());
+            let (read, returned) = lease.read_at(&src, offset, None).await;
+            lease = returned;
+            let read = read.unwrap();
+            if read == 0 {
+                break;
+            }
+            lease.set_len(read);
+            let (written, returned) = lease
+                .write_at(&dst, offset, Some(IoPriority::best_effort(4)))
+                .await;
+            lease = returned;
+            assert_eq!(written.unwrap(), read);
+            offset += read as u64;
+        }
+        drop(lease);
+
+        assert_eq!(fs::read(temp_dir.path().join("dst")).unwrap(), content);
+        assert_eq!(pool.available(), 4);
+    }
+
+    #[compio::test]
+    async fn test_buffer_pool_leases_are_exclusive() {
+        let Ok(pool) = BufferPool::new(2, 16) else {
+            return;
+        };
+        let mut first = pool.try_lease().unwrap();
+        let mut second = pool.try_lease().unwrap();
+        assert!(pool.try_lease().is_none());
+
+        first.as_mut_slice().fill(1);
+        second.as_mut_slice().fill(2);
+        assert_eq!(first.as_slice(), [1; 16]);
+        second.set_len(100);
+        assert_eq!(second.len(), 16);
+
+        drop(first);
+        let third = pool.try_lease().unwrap();
+        assert_eq!(third.as_slice(), [1; 16]);
+        assert!(pool.is_current());
+
+        // A ring has only one set of registered buffers
+        assert!(BufferPool::new(1, 16).is_err());
+    }
+
+    #[compio::test]
+    async fn test_buffer_pool_invalid_geometry() {
+        assert!(BufferPool::new(0, 4096)
+            .unwrap_err()
+            .is_invalid_parameters());
+        assert!(BufferPool::new(4, 0).unwrap_err().is_invalid_parameters());
+        assert!(BufferPool::new(4096, 4096)
+            .unwrap_err()
+            .is_invalid_parameters());
+    }
+}
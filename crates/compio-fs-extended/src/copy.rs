@@ -2,7 +2,7 @@
 
 use crate::error::{copy_file_range_error, Result};
 use compio::fs::File;
-use std::os::unix::io::AsRawFd;
+use std::os::unix::io::{AsRawFd, RawFd};
 
 /// Trait for copy_file_range operations
 #[allow(async_fn_in_trait)]
@@ -60,6 +60,9 @@ pub trait CopyFileRange {
 
 /// Implementation of copy_file_range using direct syscalls
 ///
+/// Makes a single call, which may copy fewer than `len` bytes; see
+/// [`copy_file_range_all`] for a loop that completes the range.
+///
 /// # Errors
 ///
 /// This function will return an error if the copy_file_range operation fails
@@ -70,10 +73,26 @@ pub async fn copy_file_range_impl(
     dst_offset: u64,
     len: u64,
 ) -> Result<usize> {
-    // Get raw file descriptors
     let src_fd = src.as_raw_fd();
     let dst_fd = dst.as_raw_fd();
+    // NOTE: io_uring has no copy_file_range opcode - using a blocking thread
+    compio::runtime::spawn_blocking(move || {
+        copy_file_range_raw(src_fd, dst_fd, src_offset, dst_offset, len).map_err(|errno| {
+            copy_file_range_error(&format!("copy_file_range syscall failed: {}", errno))
+        })
+    })
+    .await
+    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
 
+/// Make one copy_file_range syscall between explicit offsets
+fn copy_file_range_raw(
+    src_fd: RawFd,
+    dst_fd: RawFd,
+    src_offset: u64,
+    dst_offset: u64,
+    len: u64,
+) -> std::io::Result<usize> {
     // Perform the copy_file_range syscall
     let result = unsafe {
         let mut src_off = src_offset as i64;
@@ -84,22 +103,120 @@ pub async fn copy_file_range_impl(
             &mut src_off,
             dst_fd,
             &mut dst_off,
-            len as usize,
+            usize::try_from(len).unwrap_or(usize::MAX),
             0, // flags
         )
     };
 
     if result < 0 {
-        let errno = std::io::Error::last_os_error();
-        return Err(copy_file_range_error(&format!(
-            "copy_file_range syscall failed: {}",
-            errno
-        )));
+        return Err(std::io::Error::last_os_error());
     }
 
     Ok(result as usize)
 }
 
+/// Result of [`copy_file_range_all`]
+#[derive(Debug)]
+pub enum CopyRangeOutcome {
+    /// This many bytes were copied: all of the requested length, or fewer if
+    /// the source ended first
+    Copied(u64),
+    /// `copy_file_range` cannot copy between these files (e.g. `EXDEV` on
+    /// older kernels, or a filesystem without support) after `copied` bytes;
+    /// the caller should copy the rest with read/write
+    Unsupported {
+        /// Bytes copied before the failure
+        copied: u64,
+        /// Why `copy_file_range` could not be used
+        error: std::io::Error,
+    },
+}
+
+/// Whether a copy_file_range error means the files need a read/write copy
+/// rather than that the copy itself failed
+fn needs_fallback(error: &std::io::Error) -> bool {
+    matches!(
+        error.raw_os_error(),
+        Some(libc::EXDEV | libc::EOPNOTSUPP | libc::ENOSYS | libc::EINVAL)
+    )
+}
+
+/// Copy `len` bytes between explicit offsets with repeated copy_file_range calls
+///
+/// Each call may copy less than asked (short copies are normal, e.g. at
+/// filesystem or kernel chunk limits), so this keeps calling until the range
+/// is complete, retrying calls interrupted by a signal (`EINTR`). A call
+/// copying nothing means the source ended and stops the loop early.
+///
+/// # Errors
+///
+/// Errors that mean `copy_file_range` is unusable for these files are
+/// returned as [`CopyRangeOutcome::Unsupported`] instead, so the caller can
+/// fall back to read/write. Any other failure (e.g. `EIO` or `ENOSPC`) is an
+/// error.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use compio_fs_extended::copy::{copy_file_range_all, CopyRangeOutcome};
+/// use compio::fs::File;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let src = File::open("source.bin").await?;
+/// let dst = File::create("destination.bin").await?;
+/// let len = src.metadata().await?.len();
+///
+/// match copy_file_range_all(&src, &dst, 0, 0, len).await? {
+///     CopyRangeOutcome::Copied(copied) => println!("Copied {} bytes", copied),
+///     CopyRangeOutcome::Unsupported { copied, error } => {
+///         println!("Falling back to read/write after {} bytes: {}", copied, error);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn copy_file_range_all(
+    src: &File,
+    dst: &File,
+    src_offset: u64,
+    dst_offset: u64,
+    len: u64,
+) -> Result<CopyRangeOutcome> {
+    let src_fd = src.as_raw_fd();
+    let dst_fd = dst.as_raw_fd();
+    // NOTE: io_uring has no copy_file_range opcode, so the whole range is
+    // copied on a blocking thread rather than stalling the runtime. The
+    // caller's borrows keep both fds open until this task is awaited.
+    compio::runtime::spawn_blocking(move || {
+        let mut copied = 0u64;
+        while copied < len {
+            match copy_file_range_raw(
+                src_fd,
+                dst_fd,
+                src_offset + copied,
+                dst_offset + copied,
+                len - copied,
+            ) {
+                Ok(0) => break,
+                Ok(n) => copied += n as u64,
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(error) if needs_fallback(&error) => {
+                    return Ok(CopyRangeOutcome::Unsupported { copied, error });
+                }
+                Err(error) => {
+                    return Err(copy_file_range_error(&format!(
+                        "copy_file_range failed after {} of {} bytes: {}",
+                        copied, len, error
+                    )));
+                }
+            }
+        }
+        Ok(CopyRangeOutcome::Copied(copied))
+    })
+    .await
+    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
 /// Check if copy_file_range is supported for the given file descriptors
 ///
 /// # Arguments
@@ -164,6 +281,40 @@ pub async fn copy_file_range_with_fallback(
     }
 }
 
+/// Clone the whole of `src` into `dst` using the `FICLONE` ioctl (reflink)
+///
+/// On filesystems with shared extents (btrfs, XFS with reflink=1) this makes
+/// `dst` share `src`'s data blocks instead of copying them.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The filesystem does not support reflinks (`EOPNOTSUPP`)
+/// - The files are on different filesystems (`EXDEV`)
+/// - The file descriptors are invalid
+pub async fn clone_file(src: &File, dst: &File) -> Result<()> {
+    let src_fd = src.as_raw_fd();
+    let dst_fd = dst.as_raw_fd();
+
+    // NOTE: FICLONE can take as long as the extent map is large and io_uring
+    // has no opcode for it - using a blocking thread
+    compio::runtime::spawn_blocking(move || {
+        // SAFETY: FICLONE takes the source descriptor by value; the caller's
+        // borrows keep both fds open until this task is awaited
+        let result = unsafe { libc::ioctl(dst_fd, libc::FICLONE, src_fd) };
+        if result < 0 {
+            let errno = std::io::Error::last_os_error();
+            return Err(copy_file_range_error(&format!(
+                "FICLONE ioctl failed: {}",
+                errno
+            )));
+        }
+        Ok(())
+    })
+    .await
+    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -220,4 +371,117 @@ mod tests {
         // This might be true or false depending on the filesystem
         println!("copy_file_range supported: {}", supported);
     }
+
+    #[compio::test]
+    async fn test_copy_file_range_all() {
+        let temp_dir = TempDir::new().unwrap();
+        let src_path = temp_dir.path().join("source.bin");
+        let dst_path = temp_dir.path().join("destination.bin");
+
+        let data: Vec<u8> = (0..3 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        write(&src_path, &data).unwrap();
+
+        let src_file = File::open(&src_path).await.unwrap();
+        let dst_file = File::create(&dst_path).await.unwrap();
+
+        match copy_file_range_all(&src_file, &dst_file, 0, 0, data.len() as u64)
+            .await
+            .unwrap()
+        {
+            CopyRangeOutcome::Copied(copied) => {
+                assert_eq!(copied, data.len() as u64);
+                assert_eq!(std::fs::read(&dst_path).unwrap(), data);
+            }
+            CopyRangeOutcome::Unsupported { copied, error } => {
+                assert_eq!(copied, 0);
+                println!(
+                    "copy_file_range not supported on this filesystem: {}",
+                    error
+                );
+            }
+        }
+    }
+
+    #[compio::test]
+    async fn test_copy_file_range_all_offsets_and_eof() {
+        let temp_dir = TempDir::new().unwrap();
+        let src_path = temp_dir.path().join("source.txt");
+        let dst_path = temp_dir.path().join("destination.txt");
+
+        write(&src_path, "0123456789").unwrap();
+        write(&dst_path, "abcdefghij").unwrap();
+
+        let src_file = File::open(&src_path).await.unwrap();
+        let dst_file = compio::fs::OpenOptions::new()
+            .write(true)
+            .open(&dst_path)
+            .await
+            .unwrap();
+
+        // Asking for more than the source holds stops at its end
+        match copy_file_range_all(&src_file, &dst_file, 6, 2, 100)
+            .await
+            .unwrap()
+        {
+            CopyRangeOutcome::Copied(copied) => {
+                assert_eq!(copied, 4);
+                assert_eq!(std::fs::read(&dst_path).unwrap(), b"ab6789ghij");
+            }
+            CopyRangeOutcome::Unsupported { error, .. } => {
+                println!(
+                    "copy_file_range not supported on this filesystem: {}",
+                    error
+                );
+            }
+        }
+    }
+
+    #[compio::test]
+    async fn test_copy_file_range_all_read_only_destination() {
+        let temp_dir = TempDir::new().unwrap();
+        let src_path = temp_dir.path().join("source.txt");
+        let dst_path = temp_dir.path().join("destination.txt");
+
+        write(&src_path, "data").unwrap();
+        write(&dst_path, "").unwrap();
+
+        let src_file = File::open(&src_path).await.unwrap();
+        let dst_file = File::open(&dst_path).await.unwrap();
+
+        // EBADF is a real failure, not a reason to fall back
+        assert!(copy_file_range_all(&src_file, &dst_file, 0, 0, 4)
+            .await
+            .is_err());
+    }
+
+    #[compio::test]
+    async fn test_clone_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let src_path = temp_dir.path().join("source.txt");
+        let dst_path = temp_dir.path().join("destination.txt");
+
+        write(&src_path, "Hello, reflink!").unwrap();
+
+        let src_file = File::open(&src_path).await.unwrap();
+        let dst_file = File::create(&dst_path).await.unwrap();
+
+        // Reflinks are only available on some filesystems (btrfs, XFS);
+        // anything but "not supported here" is a real failure
+        match clone_file(&src_file, &dst_file).await {
+            Ok(()) => {
+                let content = std::fs::read(&dst_path).unwrap();
+                assert_eq!(content, b"Hello, reflink!");
+            }
+            Err(e) => {
+                let message = e.to_string();
+                let unsupported = [libc::EOPNOTSUPP, libc::EXDEV, libc::EINVAL]
+                    .into_iter()
+                    .any(|errno| {
+                        message.contains(&std::io::Error::from_raw_os_error(errno).to_string())
+                    });
+                assert!(unsupported, "FICLONE failed unexpectedly: {message}");
+                println!("Skipping test: FICLONE not supported on this filesystem");
+            }
+        }
+    }
 }
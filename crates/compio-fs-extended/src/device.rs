@@ -11,6 +11,10 @@
 //! - **Block Devices**: Hard drives, SSDs, etc.
 //! - **Sockets**: Network and Unix domain sockets
 //!
+//! io_uring has no `mknodat` opcode, so nodes are created on a blocking
+//! thread. [`mknod_at`] creates them relative to a [`DirectoryFd`], like the
+//! other `*_at` operations of this crate.
+//!
 //! # Usage
 //!
 //! ```rust,no_run
@@ -29,11 +33,71 @@
 //! # }
 //! ```
 
+use crate::directory::{path_cstring, DirectoryFd};
 use crate::error::{ExtendedError, Result};
 use nix::sys::stat;
-use nix::unistd;
 use std::path::Path;
 
+/// Combine major and minor numbers into a device number for [`mknod_at`]
+///
+/// Uses the same encoding as glibc's `makedev(3)`, which supports 32-bit
+/// major and minor numbers.
+#[must_use]
+pub const fn makedev(major: u32, minor: u32) -> u64 {
+    libc::makedev(major, minor)
+}
+
+/// Run `mknodat(2)` for `pathname` relative to `dirfd` on a blocking thread
+async fn submit_mknodat(dirfd: i32, pathname: &Path, mode: u32, dev: u64) -> Result<()> {
+    let pathname = path_cstring(pathname)?;
+    compio::runtime::spawn_blocking(move || {
+        // SAFETY: pathname is NUL-terminated, and dirfd is AT_FDCWD or
+        // borrowed from a DirectoryFd the caller keeps alive until this
+        // task has been awaited
+        if unsafe { libc::mknodat(dirfd, pathname.as_ptr(), mode, dev) } == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error().into())
+        }
+    })
+    .await
+    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+/// Create the node `name` in `dir` (`mknodat(2)`)
+///
+/// # Arguments
+///
+/// * `dir` - Directory to create the node in
+/// * `name` - Name of the node, relative to `dir`
+/// * `mode` - File type (`S_IFIFO`, `S_IFSOCK`, `S_IFCHR`, `S_IFBLK` or
+///   `S_IFREG`) and permission bits; the umask applies to the permissions
+/// * `dev` - Device number for character and block devices, see [`makedev`]
+///
+/// # Errors
+///
+/// Failures are returned as [`ExtendedError::Io`], e.g.:
+/// - `AlreadyExists` if `name` exists
+/// - `EPERM` when creating a device node without `CAP_MKNOD`
+/// - `EINVAL` for an unsupported file type
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use compio_fs_extended::device::{makedev, mknod_at};
+/// use compio_fs_extended::directory::DirectoryFd;
+/// use std::path::Path;
+///
+/// # async fn example() -> compio_fs_extended::Result<()> {
+/// let dir = DirectoryFd::open(Path::new("/srv/chroot/dev")).await?;
+/// mknod_at(&dir, Path::new("null"), libc::S_IFCHR | 0o666, makedev(1, 3)).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn mknod_at(dir: &DirectoryFd, name: &Path, mode: u32, dev: u64) -> Result<()> {
+    submit_mknodat(dir.as_raw_fd(), name, mode, dev).await
+}
+
 /// Create a special file at the given path using async spawn
 ///
 /// # Arguments
@@ -54,19 +118,10 @@ use std::path::Path;
 /// - Invalid mode or device number
 /// - The operation fails due to I/O errors
 pub async fn create_special_file_at_path(path: &Path, mode: u32, dev: u64) -> Result<()> {
-    let path = path.to_path_buf();
-
-    compio::runtime::spawn(async move {
-        stat::mknod(
-            &path,
-            stat::SFlag::from_bits_truncate(mode),
-            stat::Mode::from_bits_truncate(mode & 0o777),
-            dev,
-        )
+    let mode = (mode & libc::S_IFMT) | (mode & 0o777);
+    submit_mknodat(libc::AT_FDCWD, path, mode, dev)
+        .await
         .map_err(|e| device_error(&format!("mknod failed: {}", e)))
-    })
-    .await
-    .map_err(|e| device_error(&format!("spawn failed: {:?}", e)))?
 }
 
 /// Create a named pipe (FIFO) at the given path using async spawn
@@ -87,14 +142,9 @@ pub async fn create_special_file_at_path(path: &Path, mode: u32, dev: u64) -> Re
 /// - Permission is denied
 /// - The operation fails due to I/O errors
 pub async fn create_named_pipe_at_path(path: &Path, mode: u32) -> Result<()> {
-    let path = path.to_path_buf();
-
-    compio::runtime::spawn(async move {
-        unistd::mkfifo(&path, stat::Mode::from_bits_truncate(mode & 0o777))
-            .map_err(|e| device_error(&format!("mkfifo failed: {}", e)))
-    })
-    .await
-    .map_err(|e| device_error(&format!("spawn failed: {:?}", e)))?
+    submit_mknodat(libc::AT_FDCWD, path, libc::S_IFIFO | (mode & 0o777), 0)
+        .await
+        .map_err(|e| device_error(&format!("mkfifo failed: {}", e)))
 }
 
 /// Create a character device at the given path
@@ -123,9 +173,7 @@ pub async fn create_char_device_at_path(
     major: u32,
     minor: u32,
 ) -> Result<()> {
-    let dev = ((major as u64 & 0xfff) << 8)
-        | (minor as u64 & 0xff)
-        | (((major as u64 >> 12) & 0xfffff) << 32);
+    let dev = makedev(major, minor);
     let device_mode = stat::SFlag::S_IFCHR.bits() | (mode & 0o777);
 
     create_special_file_at_path(path, device_mode, dev).await
@@ -157,9 +205,7 @@ pub async fn create_block_device_at_path(
     major: u32,
     minor: u32,
 ) -> Result<()> {
-    let dev = ((major as u64 & 0xfff) << 8)
-        | (minor as u64 & 0xff)
-        | (((major as u64 >> 12) & 0xfffff) << 32);
+    let dev = makedev(major, minor);
     let device_mode = stat::SFlag::S_IFBLK.bits() | (mode & 0o777);
 
     create_special_file_at_path(path, device_mode, dev).await
@@ -259,6 +305,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_makedev_large_numbers() {
+        let dev = makedev(0x1234, 0x5678);
+        assert_eq!(libc::major(dev), 0x1234);
+        assert_eq!(libc::minor(dev), 0x5678);
+    }
+
+    #[compio::test]
+    async fn test_mknod_at_fifo() {
+        use std::os::unix::fs::FileTypeExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let dir = DirectoryFd::open(temp_dir.path()).await.unwrap();
+
+        mknod_at(&dir, Path::new("fifo"), libc::S_IFIFO | 0o600, 0)
+            .await
+            .unwrap();
+        let metadata = std::fs::symlink_metadata(temp_dir.path().join("fifo")).unwrap();
+        assert!(metadata.file_type().is_fifo());
+
+        let err = mknod_at(&dir, Path::new("fifo"), libc::S_IFIFO | 0o600, 0)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, ExtendedError::Io(e) if e.kind() == std::io::ErrorKind::AlreadyExists),
+            "unexpected error: {err}"
+        );
+    }
+
     #[compio::test]
     async fn test_create_socket_basic() {
         let temp_dir = TempDir::new().unwrap();
@@ -1,9 +1,26 @@
 //! Directory file descriptor for secure directory-based operations
+//!
+//! A tree can be walked entirely through [`DirectoryFd`]s: each directory is
+//! opened relative to its parent with [`DirectoryFd::open_at`], listed with
+//! [`DirectoryFd::read_dir`], and its entries are examined with
+//! [`DirectoryFd::symlink_metadata_at`] and opened with
+//! [`DirectoryFd::open_file_at`]. Nothing re-resolves the path from the root,
+//! and a directory renamed or swapped for a symlink while it is being walked
+//! cannot redirect the walk outside the tree.
 
-use crate::error::{directory_error, Result};
-use compio::fs::File;
-use std::os::unix::io::AsRawFd;
+use crate::error::{directory_error, invalid_parameters_error, ExtendedError, Result};
+use crate::metadata::{statx_path_at_fd, StatxMetadata};
+use crate::probe::{entry_or_blocking, syscall_result};
+use compio::driver::OpCode;
+use compio::fs::{File, Metadata};
+use compio::runtime::submit;
+use io_uring::{opcode, types};
+use std::ffi::{CString, OsStr, OsString};
+use std::io::ErrorKind;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
 use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use std::sync::Arc;
 
 /// A directory file descriptor for secure directory-based operations
@@ -130,22 +147,455 @@ impl DirectoryFd {
     /// # }
     /// ```
     pub async fn create_directory(&self, name: &str, mode: u32) -> Result<()> {
-        // TODO: Implement using io_uring MkdirAt opcode when available
-        // For now, use nix with spawn for security
-        let dir_fd = self.as_raw_fd();
-        let name_owned = name.to_string();
-
-        compio::runtime::spawn(async move {
-            nix::sys::stat::mkdirat(
-                Some(dir_fd),
-                std::path::Path::new(&name_owned),
-                nix::sys::stat::Mode::from_bits_truncate(mode),
-            )
-            .map_err(|e| directory_error(&format!("mkdirat failed for '{}': {}", name_owned, e)))
+        submit_mkdir(self.as_raw_fd(), Path::new(name), mode)
+            .await
+            .map_err(|e| directory_error(&format!("mkdirat failed for '{}': {}", name, e)))
+    }
+
+    /// Open the subdirectory `name` of this directory
+    ///
+    /// `name` is resolved relative to this descriptor and must not be a
+    /// symlink, so a subdirectory replaced by a symlink fails instead of
+    /// being followed.
+    ///
+    /// # Errors
+    ///
+    /// Failures are returned as [`ExtendedError::Io`], e.g. `NotFound`, or
+    /// `ENOTDIR` if `name` is not a directory (including a symlink to one).
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use compio_fs_extended::directory::DirectoryFd;
+    /// use std::path::Path;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let root = DirectoryFd::open(Path::new("/srv/data")).await?;
+    /// let child = root.open_at(Path::new("photos")).await?;
+    /// assert_eq!(child.path(), Path::new("/srv/data/photos"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn open_at(&self, name: &Path) -> Result<Self> {
+        let file = submit_open(
+            self.as_raw_fd(),
+            name,
+            libc::O_RDONLY | libc::O_DIRECTORY | libc::O_NOFOLLOW | libc::O_CLOEXEC,
+        )
+        .await?;
+        Ok(Self {
+            file: Arc::new(file),
+            path: self.path.join(name),
+        })
+    }
+
+    /// Open the file `name` of this directory for reading
+    ///
+    /// Like [`open_at`](Self::open_at), a symlink is not followed.
+    ///
+    /// # Errors
+    ///
+    /// Failures are returned as [`ExtendedError::Io`], e.g. `NotFound` or
+    /// `ELOOP` if `name` is a symlink.
+    pub async fn open_file_at(&self, name: &Path) -> Result<File> {
+        self.open_file_at_with_flags(name, 0).await
+    }
+
+    /// Open the file `name` of this directory for reading, adding `flags`
+    /// (e.g. `O_NOATIME`) to the usual ones
+    ///
+    /// # Errors
+    ///
+    /// Same as [`open_file_at`](Self::open_file_at).
+    pub async fn open_file_at_with_flags(&self, name: &Path, flags: i32) -> Result<File> {
+        submit_open(
+            self.as_raw_fd(),
+            name,
+            libc::O_RDONLY | libc::O_NOFOLLOW | libc::O_CLOEXEC | flags,
+        )
+        .await
+    }
+
+    /// Get the metadata of `name` in this directory without following symlinks
+    ///
+    /// Uses io_uring `IORING_OP_STATX` relative to this descriptor.
+    ///
+    /// # Errors
+    ///
+    /// Failures are returned as [`ExtendedError::Io`], e.g. `NotFound`.
+    pub async fn symlink_metadata_at(&self, name: &Path) -> Result<Metadata> {
+        Ok(self
+            .statx_at(name, libc::STATX_BASIC_STATS)
+            .await?
+            .metadata())
+    }
+
+    /// Get the `statx` fields selected by `mask` for `name` in this directory
+    /// without following symlinks
+    ///
+    /// # Errors
+    ///
+    /// Failures are returned as [`ExtendedError::Io`], e.g. `NotFound`.
+    pub async fn statx_at(&self, name: &Path, mask: u32) -> Result<StatxMetadata> {
+        statx_path_at_fd(self.as_raw_fd(), name, mask).await
+    }
+
+    /// List the entries of this directory, without `.` and `..`
+    ///
+    /// The listing reads through this descriptor rather than its path, so it
+    /// is the directory that was opened even if it has since been renamed.
+    /// io_uring has no opcode for listing directories, so the entries are
+    /// read with `getdents64` on a blocking thread, which also yields each
+    /// entry's type and inode number without a `statx` per entry.
+    ///
+    /// # Errors
+    ///
+    /// Failures are returned as [`ExtendedError::Io`].
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use compio_fs_extended::directory::{DirectoryFd, EntryType};
+    /// use std::path::Path;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let dir = DirectoryFd::open(Path::new("/srv/data")).await?;
+    /// for entry in dir.read_dir().await? {
+    ///     if entry.file_type() == Some(EntryType::File) {
+    ///         let file = dir.open_file_at(Path::new(entry.name())).await?;
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn read_dir(&self) -> Result<Vec<DirEntryAt>> {
+        let fd = self.as_raw_fd();
+        let entries = compio::runtime::spawn_blocking(move || {
+            // Open "." through the descriptor so the listing gets its own
+            // position and is independent of this DirectoryFd
+            // SAFETY: the path is a valid C string and the flags are valid
+            let raw = unsafe {
+                libc::openat(
+                    fd,
+                    c".".as_ptr(),
+                    libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC,
+                )
+            };
+            if raw < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            // SAFETY: openat just returned this descriptor
+            let listing = unsafe { OwnedFd::from_raw_fd(raw) };
+
+            let mut buf = vec![0u8; GETDENTS_BUFFER_SIZE];
+            let mut entries = Vec::new();
+            loop {
+                // SAFETY: buf is valid for writes of its whole length
+                let n = unsafe {
+                    libc::syscall(
+                        libc::SYS_getdents64,
+                        listing.as_raw_fd(),
+                        buf.as_mut_ptr(),
+                        buf.len(),
+                    )
+                };
+                let n = match usize::try_from(n) {
+                    Ok(0) => break,
+                    Ok(n) => n,
+                    Err(_) => return Err(std::io::Error::last_os_error()),
+                };
+                entries.extend(
+                    Dirents::new(&buf[..n])
+                        .filter(|(_, _, name)| *name != "." && *name != "..")
+                        .map(|(ino, file_type, name)| DirEntryAt {
+                            name: name.to_os_string(),
+                            file_type,
+                            ino,
+                        }),
+                );
+            }
+            Ok(entries)
         })
         .await
-        .map_err(|e| directory_error(&format!("spawn failed: {:?}", e)))?
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))?;
+        Ok(entries)
+    }
+}
+
+/// Size of the buffer each `getdents64` call fills
+const GETDENTS_BUFFER_SIZE: usize = 32 * 1024;
+
+/// Offset of `d_name` in a `struct linux_dirent64`, after `d_ino`, `d_off`,
+/// `d_reclen` and `d_type`
+const DIRENT_NAME_OFFSET: usize = 19;
+
+/// The `(inode, type, name)` records in a buffer filled by `getdents64`
+///
+/// Iteration stops at the first malformed record.
+struct Dirents<'a> {
+    /// Records not yet returned
+    buf: &'a [u8],
+}
+
+impl<'a> Dirents<'a> {
+    /// Iterate over the records in `buf`
+    const fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+}
+
+impl<'a> Iterator for Dirents<'a> {
+    type Item = (u64, Option<EntryType>, &'a OsStr);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let header = self.buf.get(..DIRENT_NAME_OFFSET)?;
+        let ino = u64::from_ne_bytes(header[0..8].try_into().ok()?);
+        let reclen = usize::from(u16::from_ne_bytes(header[16..18].try_into().ok()?));
+        let record = self.buf.get(DIRENT_NAME_OFFSET..reclen)?;
+        let name_len = record.iter().position(|&b| b == 0)?;
+        let file_type = match header[18] {
+            libc::DT_REG => Some(EntryType::File),
+            libc::DT_DIR => Some(EntryType::Directory),
+            libc::DT_LNK => Some(EntryType::Symlink),
+            libc::DT_FIFO => Some(EntryType::Fifo),
+            libc::DT_SOCK => Some(EntryType::Socket),
+            libc::DT_CHR => Some(EntryType::CharDevice),
+            libc::DT_BLK => Some(EntryType::BlockDevice),
+            _ => None,
+        };
+        self.buf = &self.buf[reclen..];
+        Some((ino, file_type, OsStr::from_bytes(&record[..name_len])))
+    }
+}
+
+/// Type of a directory entry as reported by the directory listing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryType {
+    /// Regular file
+    File,
+    /// Directory
+    Directory,
+    /// Symbolic link
+    Symlink,
+    /// Named pipe
+    Fifo,
+    /// Unix domain socket
+    Socket,
+    /// Character device
+    CharDevice,
+    /// Block device
+    BlockDevice,
+}
+
+/// An entry of a directory listed with [`DirectoryFd::read_dir`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntryAt {
+    /// Name within the directory
+    name: OsString,
+    /// Type from the listing, if the filesystem reports one
+    file_type: Option<EntryType>,
+    /// Inode number from the listing
+    ino: u64,
+}
+
+impl DirEntryAt {
+    /// Name of the entry within its directory
+    #[must_use]
+    pub fn name(&self) -> &OsStr {
+        &self.name
+    }
+
+    /// Type of the entry, or `None` if the filesystem does not report types
+    /// in listings (use [`DirectoryFd::symlink_metadata_at`] then)
+    #[must_use]
+    pub fn file_type(&self) -> Option<EntryType> {
+        self.file_type
+    }
+
+    /// Inode number of the entry
+    ///
+    /// This is the number within the directory's filesystem; for a mount
+    /// point it is that of the directory underneath, not the mounted root.
+    #[must_use]
+    pub const fn ino(&self) -> u64 {
+        self.ino
+    }
+}
+
+/// io_uring openat operation
+struct OpenAtOp {
+    /// Directory `pathname` is relative to
+    dirfd: i32,
+    /// Path to open
+    pathname: CString,
+    /// `open(2)` flags
+    flags: i32,
+    /// Mode of a file created by the open
+    mode: u32,
+}
+
+impl OpCode for OpenAtOp {
+    fn create_entry(self: Pin<&mut Self>) -> compio::driver::OpEntry {
+        entry_or_blocking(opcode::OpenAt::CODE, || {
+            opcode::OpenAt::new(types::Fd(self.dirfd), self.pathname.as_ptr())
+                .flags(self.flags)
+                .mode(self.mode)
+                .build()
+        })
+    }
+
+    fn call_blocking(self: Pin<&mut Self>) -> std::io::Result<usize> {
+        // SAFETY: pathname is a valid C string
+        syscall_result(unsafe {
+            libc::openat(self.dirfd, self.pathname.as_ptr(), self.flags, self.mode)
+        })
+    }
+}
+
+/// Submit an openat relative to `dirfd`
+async fn submit_open(dirfd: i32, pathname: &Path, flags: i32) -> Result<File> {
+    submit_open_with_mode(dirfd, pathname, flags, 0).await
+}
+
+/// Submit an openat relative to `dirfd` that may create a file with `mode`
+pub(crate) async fn submit_open_with_mode(
+    dirfd: i32,
+    pathname: &Path,
+    flags: i32,
+    mode: u32,
+) -> Result<File> {
+    let op = OpenAtOp {
+        dirfd,
+        pathname: path_cstring(pathname)?,
+        flags,
+        mode,
+    };
+    let fd = submit(op).await.0?;
+    let fd = i32::try_from(fd).map_err(|_| directory_error("openat returned an invalid fd"))?;
+    // SAFETY: the kernel just returned this descriptor and nothing else owns it
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+/// io_uring mkdirat operation
+struct MkdirOp {
+    /// Directory `pathname` is relative to
+    dirfd: i32,
+    /// Directory to create
+    pathname: CString,
+    /// Permissions, before the umask is applied
+    mode: libc::mode_t,
+}
+
+impl OpCode for MkdirOp {
+    fn create_entry(self: Pin<&mut Self>) -> compio::driver::OpEntry {
+        entry_or_blocking(opcode::MkDirAt::CODE, || {
+            opcode::MkDirAt::new(types::Fd(self.dirfd), self.pathname.as_ptr())
+                .mode(self.mode)
+                .build()
+        })
     }
+
+    fn call_blocking(self: Pin<&mut Self>) -> std::io::Result<usize> {
+        // SAFETY: pathname is a valid C string
+        syscall_result(unsafe { libc::mkdirat(self.dirfd, self.pathname.as_ptr(), self.mode) })
+    }
+}
+
+/// Submit a mkdirat relative to `dirfd`
+async fn submit_mkdir(dirfd: i32, pathname: &Path, mode: u32) -> Result<()> {
+    let op = MkdirOp {
+        dirfd,
+        pathname: path_cstring(pathname)?,
+        mode,
+    };
+    submit(op).await.0?;
+    Ok(())
+}
+
+/// Create a directory with permissions `mode` (subject to the umask)
+///
+/// Uses io_uring `IORING_OP_MKDIRAT`. Failures are returned as
+/// [`ExtendedError::Io`] so callers can match on the `ErrorKind`.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The path contains a NUL byte
+/// - The path already exists (`AlreadyExists`)
+/// - The parent directory does not exist (`NotFound`)
+/// - Permission is denied
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use compio_fs_extended::directory::create_dir;
+/// use std::path::Path;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// create_dir(Path::new("/tmp/new_dir"), 0o755).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn create_dir(path: &Path, mode: u32) -> Result<()> {
+    submit_mkdir(libc::AT_FDCWD, path, mode).await
+}
+
+/// Create a directory and any missing parents with permissions `mode`
+///
+/// Like `std::fs::create_dir_all`, succeeds if the directory already exists
+/// and tolerates another task creating part of the path concurrently.
+///
+/// # Errors
+///
+/// This function will return an error if a component exists but is not a
+/// directory, or a directory cannot be created.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use compio_fs_extended::directory::create_dir_all;
+/// use std::path::Path;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// create_dir_all(Path::new("/tmp/a/b/c"), 0o777).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn create_dir_all(path: &Path, mode: u32) -> Result<()> {
+    // Walk up to the deepest ancestor that exists (or can be created), then
+    // create the missing components top-down
+    let mut missing = Vec::new();
+    let mut current = path;
+    loop {
+        if current.as_os_str().is_empty() {
+            break;
+        }
+        match create_dir(current, mode).await {
+            Ok(()) => break,
+            Err(ExtendedError::Io(e)) if e.kind() == ErrorKind::NotFound => {
+                missing.push(current);
+                match current.parent() {
+                    Some(parent) => current = parent,
+                    None => return Err(ExtendedError::Io(e)),
+                }
+            }
+            Err(ExtendedError::Io(e)) if e.kind() == ErrorKind::AlreadyExists => {
+                if current.is_dir() {
+                    break;
+                }
+                return Err(ExtendedError::Io(e));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    for dir in missing.into_iter().rev() {
+        match create_dir(dir, mode).await {
+            Ok(()) => {}
+            Err(ExtendedError::Io(e)) if e.kind() == ErrorKind::AlreadyExists && dir.is_dir() => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
 }
 
 /// Read directory entries
@@ -204,6 +654,12 @@ pub async fn read_dir(path: &Path) -> Result<std::fs::ReadDir> {
     .map_err(|e| directory_error(&format!("spawn failed: {:?}", e)))?
 }
 
+/// Convert a path to a C string for an `*at` syscall
+pub(crate) fn path_cstring(path: &Path) -> Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| invalid_parameters_error(&format!("Invalid path {}: {e}", path.display())))
+}
+
 impl Clone for DirectoryFd {
     fn clone(&self) -> Self {
         Self {
@@ -213,8 +669,7 @@ impl Clone for DirectoryFd {
     }
 }
 
-// Note: Basic directory operations (create_dir, remove_dir, etc.) are provided by compio::fs
-// This module only provides DirectoryFd for secure *at operations
+// Note: Removing directories is provided by the `unlink` module
 
 #[cfg(test)]
 mod tests {
@@ -381,4 +836,198 @@ mod tests {
             assert!(created_path.is_dir());
         }
     }
+
+    #[compio::test]
+    async fn test_directory_fd_walk_relative() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("sub")).unwrap();
+        fs::write(temp_dir.path().join("sub/file"), "data").unwrap();
+        std::os::unix::fs::symlink("file", temp_dir.path().join("sub/link")).unwrap();
+
+        let root = DirectoryFd::open(temp_dir.path()).await.unwrap();
+        let sub = root.open_at(Path::new("sub")).await.unwrap();
+        assert_eq!(sub.path(), temp_dir.path().join("sub"));
+
+        // The walk keeps working through the descriptor after a rename
+        fs::rename(temp_dir.path().join("sub"), temp_dir.path().join("moved")).unwrap();
+
+        let mut entries = sub.read_dir().await.unwrap();
+        entries.sort_by(|a, b| a.name().cmp(b.name()));
+        let names: Vec<_> = entries.iter().map(DirEntryAt::name).collect();
+        assert_eq!(names, [OsStr::new("file"), OsStr::new("link")]);
+
+        let metadata = sub.symlink_metadata_at(Path::new("file")).await.unwrap();
+        assert!(metadata.is_file());
+        assert_eq!(metadata.len(), 4);
+        let metadata = sub.symlink_metadata_at(Path::new("link")).await.unwrap();
+        assert!(metadata.is_symlink());
+
+        let file = sub.open_file_at(Path::new("file")).await.unwrap();
+        let (_, buf) = compio::io::AsyncReadAtExt::read_to_end_at(&file, Vec::new(), 0)
+            .await
+            .unwrap();
+        assert_eq!(buf, b"data");
+    }
+
+    #[compio::test]
+    async fn test_directory_fd_does_not_follow_symlinks() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("real")).unwrap();
+        fs::write(temp_dir.path().join("real/file"), "data").unwrap();
+        std::os::unix::fs::symlink("real", temp_dir.path().join("dir_link")).unwrap();
+        std::os::unix::fs::symlink("real/file", temp_dir.path().join("file_link")).unwrap();
+
+        let root = DirectoryFd::open(temp_dir.path()).await.unwrap();
+        let err = root.open_at(Path::new("dir_link")).await.unwrap_err();
+        assert!(
+            matches!(&err, ExtendedError::Io(e) if e.raw_os_error() == Some(libc::ENOTDIR)),
+            "unexpected error: {err}"
+        );
+        let err = root.open_file_at(Path::new("file_link")).await.unwrap_err();
+        assert!(
+            matches!(&err, ExtendedError::Io(e) if e.raw_os_error() == Some(libc::ELOOP)),
+            "unexpected error: {err}"
+        );
+
+        let err = root.open_at(Path::new("real/file")).await.unwrap_err();
+        assert!(matches!(err, ExtendedError::Io(_)));
+        let Err(err) = root.symlink_metadata_at(Path::new("missing")).await else {
+            panic!("missing entry has metadata");
+        };
+        assert!(
+            matches!(&err, ExtendedError::Io(e) if e.kind() == ErrorKind::NotFound),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[compio::test]
+    async fn test_directory_fd_read_dir_types() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("dir")).unwrap();
+        fs::write(temp_dir.path().join("file"), "").unwrap();
+
+        let root = DirectoryFd::open(temp_dir.path()).await.unwrap();
+        for entry in root.read_dir().await.unwrap() {
+            // Filesystems may leave the type unknown; otherwise it must match
+            let expected = if entry.name() == "dir" {
+                EntryType::Directory
+            } else {
+                EntryType::File
+            };
+            assert!(entry.file_type().is_none_or(|t| t == expected));
+        }
+    }
+
+    #[compio::test]
+    async fn test_directory_fd_read_dir_many_entries() {
+        use std::os::unix::fs::MetadataExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        // Enough long names to need several getdents64 calls
+        for i in 0..1000 {
+            fs::write(temp_dir.path().join(format!("{i:0>100}")), "").unwrap();
+        }
+
+        let root = DirectoryFd::open(temp_dir.path()).await.unwrap();
+        let entries = root.read_dir().await.unwrap();
+        assert_eq!(entries.len(), 1000);
+        for entry in &entries {
+            let path = temp_dir.path().join(entry.name());
+            assert_eq!(entry.ino(), fs::symlink_metadata(path).unwrap().ino());
+        }
+    }
+
+    #[test]
+    fn test_dirents_parse() {
+        /// Append a `linux_dirent64` record padded to 8 bytes
+        fn record(buf: &mut Vec<u8>, ino: u64, d_type: u8, name: &[u8]) {
+            let reclen = (DIRENT_NAME_OFFSET + name.len() + 1).next_multiple_of(8);
+            buf.extend_from_slice(&ino.to_ne_bytes());
+            buf.extend_from_slice(&0i64.to_ne_bytes());
+            buf.extend_from_slice(&u16::try_from(reclen).unwrap().to_ne_bytes());
+            buf.push(d_type);
+            buf.extend_from_slice(name);
+            buf.resize(buf.len() + reclen - DIRENT_NAME_OFFSET - name.len(), 0);
+        }
+
+        let mut buf = Vec::new();
+        record(&mut buf, 2, libc::DT_DIR, b".");
+        record(&mut buf, 12, libc::DT_REG, b"file.txt");
+        record(&mut buf, 13, libc::DT_UNKNOWN, b"x");
+        let entries: Vec<_> = Dirents::new(&buf).collect();
+        assert_eq!(
+            entries,
+            [
+                (2, Some(EntryType::Directory), OsStr::new(".")),
+                (12, Some(EntryType::File), OsStr::new("file.txt")),
+                (13, None, OsStr::new("x")),
+            ]
+        );
+
+        // A record claiming to run past the buffer ends the iteration
+        buf.truncate(buf.len() - 1);
+        assert_eq!(Dirents::new(&buf).count(), 2);
+    }
+
+    #[compio::test]
+    async fn test_create_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("new_dir");
+
+        create_dir(&path, 0o700).await.unwrap();
+        assert!(path.is_dir());
+        let mode =
+            std::os::unix::fs::PermissionsExt::mode(&fs::metadata(&path).unwrap().permissions());
+        assert_eq!(mode & 0o777, 0o700);
+
+        let err = create_dir(&path, 0o700).await.unwrap_err();
+        assert!(
+            matches!(&err, ExtendedError::Io(e) if e.kind() == ErrorKind::AlreadyExists),
+            "unexpected error: {err}"
+        );
+
+        let err = create_dir(&temp_dir.path().join("missing/child"), 0o755)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, ExtendedError::Io(e) if e.kind() == ErrorKind::NotFound),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[compio::test]
+    async fn test_create_dir_all() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("a/b/c");
+
+        create_dir_all(&path, 0o755).await.unwrap();
+        assert!(path.is_dir());
+
+        // Existing directories are fine
+        create_dir_all(&path, 0o755).await.unwrap();
+        create_dir_all(temp_dir.path(), 0o755).await.unwrap();
+
+        // A file in the way is not
+        let file = temp_dir.path().join("file");
+        fs::write(&file, "data").unwrap();
+        assert!(create_dir_all(&file, 0o755).await.is_err());
+        assert!(create_dir_all(&file.join("child"), 0o755).await.is_err());
+    }
+
+    #[compio::test]
+    async fn test_create_dir_all_concurrent() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().to_path_buf();
+
+        let results = futures::future::join_all((0..8).map(|i| {
+            let path = root.join(format!("shared/nested/{i}"));
+            async move { create_dir_all(&path, 0o755).await }
+        }))
+        .await;
+
+        for result in results {
+            result.unwrap();
+        }
+        assert_eq!(fs::read_dir(root.join("shared/nested")).unwrap().count(), 8);
+    }
 }
@@ -7,6 +7,7 @@ use crate::fadvise::{Fadvise, FadviseAdvice};
 use crate::fallocate::Fallocate;
 use crate::hardlink::HardlinkOps;
 use crate::symlink::SymlinkOps;
+use crate::truncate::Ftruncate;
 #[cfg(feature = "xattr")]
 use crate::xattr::XattrOps;
 use compio::fs::File;
@@ -17,6 +18,7 @@ use compio::fs::File;
 /// operations that are not available in the base compio-fs crate, including:
 /// - `copy_file_range` for efficient same-filesystem copies
 /// - `fadvise` for file access pattern optimization
+/// - `ftruncate` for setting the file length
 /// - Symlink operations
 /// - Hardlink operations
 /// - Extended attributes (xattr) operations
@@ -145,6 +147,14 @@ impl Fallocate for ExtendedFile {
     }
 }
 
+// Implement Ftruncate trait
+impl Ftruncate for ExtendedFile {
+    async fn ftruncate(&self, len: u64) -> Result<()> {
+        // Delegate to the truncate module implementation
+        crate::truncate::ftruncate(&self.inner, len).await
+    }
+}
+
 // Implement SymlinkOps trait
 impl SymlinkOps for ExtendedFile {
     async fn read_symlink(&self) -> Result<std::path::PathBuf> {
@@ -189,6 +199,11 @@ impl XattrOps for ExtendedFile {
         // Delegate to the xattr module implementation
         crate::xattr::list_xattr_impl(&self.inner).await
     }
+
+    async fn remove_xattr(&self, name: &str) -> Result<()> {
+        // Delegate to the xattr module implementation
+        crate::xattr::remove_xattr_impl(&self.inner, name).await
+    }
 }
 
 // Conversion traits
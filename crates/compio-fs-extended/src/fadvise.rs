@@ -1,6 +1,7 @@
 //! fadvise operations for file access pattern optimization using io_uring
 
-use crate::error::{fadvise_error, Result};
+use crate::error::{fadvise_error, invalid_parameters_error, Result};
+use crate::probe::entry_or_blocking;
 use compio::driver::OpCode;
 use compio::fs::File;
 use compio::runtime::submit;
@@ -119,11 +120,20 @@ impl FadviseOp {
 
 impl OpCode for FadviseOp {
     fn create_entry(self: Pin<&mut Self>) -> compio::driver::OpEntry {
-        compio::driver::OpEntry::Submission(
+        entry_or_blocking(opcode::Fadvise::CODE, || {
             opcode::Fadvise::new(types::Fd(self.fd), self.len, self.advice)
                 .offset(self.offset as u64)
-                .build(),
-        )
+                .build()
+        })
+    }
+
+    fn call_blocking(self: Pin<&mut Self>) -> std::io::Result<usize> {
+        // SAFETY: plain syscall on a descriptor the caller keeps open
+        match unsafe { libc::posix_fadvise(self.fd, self.offset, self.len, self.advice) } {
+            0 => Ok(0),
+            // posix_fadvise returns the error rather than setting errno
+            errno => Err(std::io::Error::from_raw_os_error(errno)),
+        }
     }
 }
 
@@ -137,7 +147,7 @@ impl OpCode for FadviseOp {
 /// * `file` - The file to apply advice to
 /// * `advice` - The fadvise advice constant
 /// * `offset` - File offset to start the advice
-/// * `len` - Length of the region to apply advice to
+/// * `len` - Length of the region to apply advice to (0 = to the end of the file)
 ///
 /// # Returns
 ///
@@ -150,6 +160,7 @@ impl OpCode for FadviseOp {
 /// - The file descriptor is invalid
 /// - The kernel doesn't support fadvise io_uring operations
 /// - The advice parameter is invalid
+/// - `offset` or `len` is negative
 ///
 /// # Example
 ///
@@ -164,18 +175,39 @@ impl OpCode for FadviseOp {
 /// # }
 /// ```
 pub async fn fadvise(file: &File, advice: FadviseAdvice, offset: i64, len: i64) -> Result<()> {
+    if offset < 0 || len < 0 {
+        return Err(invalid_parameters_error(&format!(
+            "fadvise range must not be negative (offset {offset}, len {len})"
+        )));
+    }
     let fd = file.as_raw_fd();
 
-    // Submit io_uring fadvise operation using compio's runtime
-    let result = submit(FadviseOp::new(fd, offset, len, advice.to_posix())).await;
+    // The SQE length field is 32 bits wide, so longer ranges are advised in
+    // pieces; a length of 0 (to the end of the file) is a single request
+    let mut offset = offset;
+    let mut remaining = len;
+    loop {
+        let chunk = remaining.min(MAX_SQE_LEN);
+
+        // Submit io_uring fadvise operation using compio's runtime
+        let result = submit(FadviseOp::new(fd, offset, chunk, advice.to_posix())).await;
 
-    // Minimal mapping: preserve underlying error string without extra context
-    match result.0 {
-        Ok(_) => Ok(()),
-        Err(e) => Err(fadvise_error(&e.to_string())),
+        // Minimal mapping: preserve underlying error string without extra context
+        if let Err(e) = result.0 {
+            return Err(fadvise_error(&e.to_string()));
+        }
+
+        remaining -= chunk;
+        if remaining == 0 {
+            return Ok(());
+        }
+        offset = offset.saturating_add(chunk);
     }
 }
 
+/// Longest range a single io_uring fadvise can cover, rounded down to a page
+const MAX_SQE_LEN: i64 = 0xffff_f000;
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -289,4 +321,55 @@ mod tests {
             Err(e) => println!("fadvise with large offset failed as expected: {}", e),
         }
     }
+
+    #[compio::test]
+    async fn test_fadvise_will_need_range() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        write(&file_path, "x".repeat(8192)).unwrap();
+
+        let file = File::open(&file_path).await.unwrap();
+
+        // Prefetch only the second page, then release it again
+        fadvise(&file, FadviseAdvice::WillNeed, 4096, 4096)
+            .await
+            .unwrap();
+        fadvise(&file, FadviseAdvice::DontNeed, 4096, 4096)
+            .await
+            .unwrap();
+        fadvise(&file, FadviseAdvice::NoReuse, 0, 0).await.unwrap();
+        fadvise(&file, FadviseAdvice::Normal, 0, 0).await.unwrap();
+    }
+
+    #[compio::test]
+    async fn test_fadvise_negative_range() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        write(&file_path, "test data").unwrap();
+
+        let file = File::open(&file_path).await.unwrap();
+
+        let err = fadvise(&file, FadviseAdvice::WillNeed, 0, -1)
+            .await
+            .unwrap_err();
+        assert!(err.is_invalid_parameters());
+        let err = fadvise(&file, FadviseAdvice::WillNeed, -1, 0)
+            .await
+            .unwrap_err();
+        assert!(err.is_invalid_parameters());
+    }
+
+    #[compio::test]
+    async fn test_fadvise_range_longer_than_sqe_len() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        write(&file_path, "test data").unwrap();
+
+        let file = File::open(&file_path).await.unwrap();
+
+        // Covers more than 4GiB, so it is split across several requests
+        fadvise(&file, FadviseAdvice::DontNeed, 0, 3 * MAX_SQE_LEN + 1)
+            .await
+            .unwrap();
+    }
 }
@@ -1,6 +1,19 @@
 //! fallocate operations for file preallocation using io_uring opcodes
-
-use crate::error::{fallocate_error, Result};
+//!
+//! Besides preallocation, the mode flags cover the operations needed to keep
+//! copies sparse and to apply in-place updates without rewriting data:
+//!
+//! - [`mode::KEEP_SIZE`]: allocate beyond the end without changing the size
+//! - [`mode::PUNCH_HOLE`]: deallocate a range, which then reads as zeros
+//!   (must be combined with `KEEP_SIZE`)
+//! - [`mode::ZERO_RANGE`]: zero a range without writing the zeros
+//!
+//! Filesystems that do not implement a mode fail with
+//! [`ExtendedError::NotSupported`](crate::ExtendedError::NotSupported), so
+//! callers can fall back to writing zeros.
+
+use crate::error::{fallocate_error, invalid_parameters_error, not_supported_error, Result};
+use crate::probe::{entry_or_blocking, syscall_result};
 use compio::driver::OpCode;
 use compio::fs::File;
 use compio::runtime::submit;
@@ -49,6 +62,36 @@ pub trait Fallocate {
     /// ```
     #[allow(async_fn_in_trait)]
     async fn fallocate(&self, offset: u64, len: u64, mode: u32) -> Result<()>;
+
+    /// Deallocate `len` bytes at `offset`, leaving a hole that reads as zeros
+    ///
+    /// The file size is never changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ExtendedError::NotSupported`](crate::ExtendedError::NotSupported)
+    /// if the filesystem cannot punch holes, or an error if the operation fails.
+    #[allow(async_fn_in_trait)]
+    async fn punch_hole(&self, offset: u64, len: u64) -> Result<()> {
+        self.fallocate(offset, len, mode::PUNCH_HOLE | mode::KEEP_SIZE)
+            .await
+    }
+
+    /// Zero `len` bytes at `offset` without writing them
+    ///
+    /// With `keep_size` a range extending past the end of the file does not
+    /// change its size.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ExtendedError::NotSupported`](crate::ExtendedError::NotSupported)
+    /// if the filesystem cannot zero ranges, or an error if the operation fails.
+    #[allow(async_fn_in_trait)]
+    async fn zero_range(&self, offset: u64, len: u64, keep_size: bool) -> Result<()> {
+        let keep_size = if keep_size { mode::KEEP_SIZE } else { 0 };
+        self.fallocate(offset, len, mode::ZERO_RANGE | keep_size)
+            .await
+    }
 }
 
 /// File allocation mode constants
@@ -56,19 +99,36 @@ pub mod mode {
     /// Default allocation mode (allocate space)
     pub const DEFAULT: u32 = 0;
     /// Keep file size unchanged (FALLOC_FL_KEEP_SIZE)
-    pub const KEEP_SIZE: u32 = 1;
-    /// Punch hole in file (FALLOC_FL_PUNCH_HOLE)
-    pub const PUNCH_HOLE: u32 = 2;
+    pub const KEEP_SIZE: u32 = libc::FALLOC_FL_KEEP_SIZE as u32;
+    /// Punch hole in file (FALLOC_FL_PUNCH_HOLE), requires `KEEP_SIZE`
+    pub const PUNCH_HOLE: u32 = libc::FALLOC_FL_PUNCH_HOLE as u32;
     /// Don't update file size (FALLOC_FL_NO_HIDE_STALE)
     pub const NO_HIDE_STALE: u32 = 4;
     /// Collapse range (FALLOC_FL_COLLAPSE_RANGE)
-    pub const COLLAPSE_RANGE: u32 = 8;
+    pub const COLLAPSE_RANGE: u32 = libc::FALLOC_FL_COLLAPSE_RANGE as u32;
     /// Zero range (FALLOC_FL_ZERO_RANGE)
-    pub const ZERO_RANGE: u32 = 16;
+    pub const ZERO_RANGE: u32 = libc::FALLOC_FL_ZERO_RANGE as u32;
     /// Insert range (FALLOC_FL_INSERT_RANGE)
-    pub const INSERT_RANGE: u32 = 32;
+    pub const INSERT_RANGE: u32 = libc::FALLOC_FL_INSERT_RANGE as u32;
     /// Unshare range (FALLOC_FL_UNSHARE_RANGE)
-    pub const UNSHARE_RANGE: u32 = 64;
+    pub const UNSHARE_RANGE: u32 = libc::FALLOC_FL_UNSHARE_RANGE as u32;
+}
+
+/// Reject flag combinations the kernel would refuse with a bare `EINVAL`
+fn validate_mode(mode_flags: u32) -> Result<()> {
+    if mode_flags & mode::PUNCH_HOLE != 0 {
+        if mode_flags & mode::KEEP_SIZE == 0 {
+            return Err(invalid_parameters_error(
+                "PUNCH_HOLE must be combined with KEEP_SIZE",
+            ));
+        }
+        if mode_flags & mode::ZERO_RANGE != 0 {
+            return Err(invalid_parameters_error(
+                "PUNCH_HOLE and ZERO_RANGE are mutually exclusive",
+            ));
+        }
+    }
+    Ok(())
 }
 
 /// Custom fallocate operation that implements compio's OpCode trait
@@ -105,12 +165,20 @@ impl FallocateOp {
 
 impl OpCode for FallocateOp {
     fn create_entry(self: Pin<&mut Self>) -> compio::driver::OpEntry {
-        compio::driver::OpEntry::Submission(
+        entry_or_blocking(opcode::Fallocate::CODE, || {
             opcode::Fallocate::new(types::Fd(self.fd), self.len)
                 .offset(self.offset)
                 .mode(self.mode)
-                .build(),
-        )
+                .build()
+        })
+    }
+
+    fn call_blocking(self: Pin<&mut Self>) -> std::io::Result<usize> {
+        let invalid = |_| std::io::Error::from_raw_os_error(libc::EINVAL);
+        let offset = libc::off_t::try_from(self.offset).map_err(invalid)?;
+        let len = libc::off_t::try_from(self.len).map_err(invalid)?;
+        // SAFETY: plain syscall on a descriptor the caller keeps open
+        syscall_result(unsafe { libc::fallocate(self.fd, self.mode, offset, len) })
     }
 }
 
@@ -129,14 +197,22 @@ impl OpCode for FallocateOp {
 ///
 /// # Errors
 ///
-/// This function will return an error if the underlying fallocate operation fails.
+/// This function will return an error if:
+/// - The mode combines `PUNCH_HOLE` without `KEEP_SIZE`, or with `ZERO_RANGE`
+/// - The filesystem does not support the mode (`NotSupported`)
+/// - The underlying fallocate operation fails
 pub async fn fallocate(file: &File, offset: u64, len: u64, mode: u32) -> Result<()> {
+    validate_mode(mode)?;
+
     // Submit io_uring fallocate operation using compio's runtime
     let result = submit(FallocateOp::new(file, offset, len, mode)).await;
 
     // Minimal mapping: preserve underlying error string without extra context
     match result.0 {
         Ok(_) => Ok(()),
+        Err(e) if e.raw_os_error() == Some(libc::EOPNOTSUPP) => Err(not_supported_error(&format!(
+            "fallocate mode {mode:#x}: {e}"
+        ))),
         Err(e) => Err(fallocate_error(&e.to_string())),
     }
 }
@@ -166,23 +242,25 @@ pub async fn preallocate_keep_size(file: &File, offset: u64, len: u64) -> Result
 /// Punch a hole in a file (deallocate space)
 ///
 /// This removes the allocated space for the specified range, creating a hole.
+/// The file size is unchanged.
 ///
 /// # Errors
 ///
 /// This function will return an error if the fallocate operation fails
 pub async fn punch_hole(file: &File, offset: u64, len: u64) -> Result<()> {
-    fallocate(file, offset, len, mode::PUNCH_HOLE).await
+    fallocate(file, offset, len, mode::PUNCH_HOLE | mode::KEEP_SIZE).await
 }
 
 /// Zero out a range in a file
 ///
-/// This writes zeros to the specified range without changing the file size.
+/// This zeros the specified range without writing the zeros or changing the
+/// file size.
 ///
 /// # Errors
 ///
 /// This function will return an error if the fallocate operation fails
 pub async fn zero_range(file: &File, offset: u64, len: u64) -> Result<()> {
-    fallocate(file, offset, len, mode::ZERO_RANGE).await
+    fallocate(file, offset, len, mode::ZERO_RANGE | mode::KEEP_SIZE).await
 }
 
 #[cfg(test)]
@@ -291,4 +369,77 @@ mod tests {
             }
         }
     }
+
+    #[compio::test]
+    async fn test_punch_hole_reads_zeros() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        fs::write(&file_path, vec![0xaau8; 16384]).unwrap();
+
+        let file = compio::fs::OpenOptions::new()
+            .write(true)
+            .open(&file_path)
+            .await
+            .unwrap();
+        let extended = crate::ExtendedFile::new(file);
+
+        match extended.punch_hole(4096, 4096).await {
+            Ok(()) => {
+                let data = fs::read(&file_path).unwrap();
+                assert_eq!(data.len(), 16384);
+                assert!(data[..4096].iter().all(|&b| b == 0xaa));
+                assert!(data[4096..8192].iter().all(|&b| b == 0));
+                assert!(data[8192..].iter().all(|&b| b == 0xaa));
+            }
+            // Not every filesystem can punch holes
+            Err(e) => assert!(e.is_not_supported(), "unexpected error: {e}"),
+        }
+    }
+
+    #[compio::test]
+    async fn test_zero_range_keep_size() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        fs::write(&file_path, vec![0xaau8; 8192]).unwrap();
+
+        let file = compio::fs::OpenOptions::new()
+            .write(true)
+            .open(&file_path)
+            .await
+            .unwrap();
+        let extended = crate::ExtendedFile::new(file);
+
+        match extended.zero_range(4096, 8192, true).await {
+            Ok(()) => {
+                let data = fs::read(&file_path).unwrap();
+                assert_eq!(data.len(), 8192);
+                assert!(data[..4096].iter().all(|&b| b == 0xaa));
+                assert!(data[4096..].iter().all(|&b| b == 0));
+            }
+            Err(e) => assert!(e.is_not_supported(), "unexpected error: {e}"),
+        }
+    }
+
+    #[compio::test]
+    async fn test_invalid_mode_combinations() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = File::create(temp_dir.path().join("test.txt"))
+            .await
+            .unwrap();
+
+        let err = fallocate(&file, 0, 512, mode::PUNCH_HOLE)
+            .await
+            .unwrap_err();
+        assert!(err.is_invalid_parameters());
+
+        let err = fallocate(
+            &file,
+            0,
+            512,
+            mode::PUNCH_HOLE | mode::KEEP_SIZE | mode::ZERO_RANGE,
+        )
+        .await
+        .unwrap_err();
+        assert!(err.is_invalid_parameters());
+    }
 }
@@ -1,6 +1,7 @@
 //! Hardlink operations for creating hard links
 
 use crate::error::{hardlink_error, Result};
+use crate::probe::{entry_or_blocking, syscall_result};
 use compio::driver::OpCode;
 use compio::fs::File;
 use io_uring::{opcode, types};
@@ -107,14 +108,27 @@ impl HardlinkOp {
 impl OpCode for HardlinkOp {
     fn create_entry(self: Pin<&mut Self>) -> compio::driver::OpEntry {
         // Use AT_FDCWD for both paths
-        compio::driver::OpEntry::Submission(
+        entry_or_blocking(opcode::LinkAt::CODE, || {
             opcode::LinkAt::new(
                 types::Fd(libc::AT_FDCWD),
                 self.oldpath.as_ptr(),
                 types::Fd(libc::AT_FDCWD),
                 self.newpath.as_ptr(),
             )
-            .build(),
-        )
+            .build()
+        })
+    }
+
+    fn call_blocking(self: Pin<&mut Self>) -> std::io::Result<usize> {
+        // SAFETY: both paths are valid C strings
+        syscall_result(unsafe {
+            libc::linkat(
+                libc::AT_FDCWD,
+                self.oldpath.as_ptr(),
+                libc::AT_FDCWD,
+                self.newpath.as_ptr(),
+                0,
+            )
+        })
     }
 }
@@ -0,0 +1,227 @@
+//! Reads and writes with an I/O priority using io_uring
+//!
+//! io_uring carries an I/O priority in every submission queue entry
+//! (`sqe->ioprio`). compio's own `read_at`/`write_at` always leave it at 0,
+//! which means "use the submitting thread's priority", so this module
+//! provides equivalents that set it explicitly. [`set_thread_priority`]
+//! covers I/O that does not go through an SQE (e.g. `copy_file_range`).
+
+use crate::probe::{entry_or_blocking, syscall_result};
+use compio::driver::OpCode;
+use compio::fs::File;
+use compio::runtime::submit;
+use compio::BufResult;
+use io_uring::{opcode, types};
+use std::os::unix::io::AsRawFd;
+use std::pin::Pin;
+
+/// `IOPRIO_CLASS_SHIFT` from `linux/ioprio.h`
+const CLASS_SHIFT: u16 = 13;
+
+/// I/O scheduling class
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoPriorityClass {
+    /// Served in priority order alongside other best-effort I/O
+    BestEffort,
+    /// Only served when no other I/O is pending on the device
+    Idle,
+}
+
+/// I/O priority: a scheduling class plus a level within it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoPriority {
+    /// Scheduling class
+    pub class: IoPriorityClass,
+    /// Level within the class, 0 (highest) to 7 (lowest); ignored for idle
+    pub level: u8,
+}
+
+impl IoPriority {
+    /// Idle class
+    #[must_use]
+    pub const fn idle() -> Self {
+        Self {
+            class: IoPriorityClass::Idle,
+            level: 0,
+        }
+    }
+
+    /// Best-effort class at `level` (clamped to 7)
+    #[must_use]
+    pub const fn best_effort(level: u8) -> Self {
+        Self {
+            class: IoPriorityClass::BestEffort,
+            level: if level > 7 { 7 } else { level },
+        }
+    }
+
+    /// Value for `sqe->ioprio` and `ioprio_set(2)`
+    #[must_use]
+    pub const fn to_raw(self) -> u16 {
+        match self.class {
+            // IOPRIO_CLASS_BE
+            IoPriorityClass::BestEffort => (2 << CLASS_SHIFT) | self.level as u16,
+            // IOPRIO_CLASS_IDLE
+            IoPriorityClass::Idle => 3 << CLASS_SHIFT,
+        }
+    }
+}
+
+/// io_uring read with an explicit priority
+struct ReadAtOp {
+    fd: i32,
+    offset: u64,
+    buffer: Vec<u8>,
+    ioprio: u16,
+}
+
+impl OpCode for ReadAtOp {
+    fn create_entry(mut self: Pin<&mut Self>) -> compio::driver::OpEntry {
+        let len = u32::try_from(self.buffer.len()).unwrap_or(u32::MAX);
+        entry_or_blocking(opcode::Read::CODE, || {
+            opcode::Read::new(types::Fd(self.fd), self.buffer.as_mut_ptr(), len)
+                .offset(self.offset)
+                .ioprio(self.ioprio)
+                .build()
+        })
+    }
+
+    fn call_blocking(mut self: Pin<&mut Self>) -> std::io::Result<usize> {
+        // The blocking thread reads at its own priority, not the SQE's
+        let offset = libc::off_t::try_from(self.offset)
+            .map_err(|_| std::io::Error::from_raw_os_error(libc::EINVAL))?;
+        let (fd, len) = (self.fd, self.buffer.len());
+        // SAFETY: the buffer is valid for writes of len bytes
+        syscall_result(unsafe { libc::pread(fd, self.buffer.as_mut_ptr().cast(), len, offset) })
+    }
+}
+
+/// io_uring write with an explicit priority
+struct WriteAtOp {
+    fd: i32,
+    offset: u64,
+    buffer: Vec<u8>,
+    ioprio: u16,
+}
+
+impl OpCode for WriteAtOp {
+    fn create_entry(self: Pin<&mut Self>) -> compio::driver::OpEntry {
+        let len = u32::try_from(self.buffer.len()).unwrap_or(u32::MAX);
+        entry_or_blocking(opcode::Write::CODE, || {
+            opcode::Write::new(types::Fd(self.fd), self.buffer.as_ptr(), len)
+                .offset(self.offset)
+                .ioprio(self.ioprio)
+                .build()
+        })
+    }
+
+    fn call_blocking(self: Pin<&mut Self>) -> std::io::Result<usize> {
+        let offset = libc::off_t::try_from(self.offset)
+            .map_err(|_| std::io::Error::from_raw_os_error(libc::EINVAL))?;
+        // SAFETY: the buffer is valid for reads of its length
+        syscall_result(unsafe {
+            libc::pwrite(
+                self.fd,
+                self.buffer.as_ptr().cast(),
+                self.buffer.len(),
+                offset,
+            )
+        })
+    }
+}
+
+/// Read into `buffer` at `offset` with I/O priority `priority`
+///
+/// Like `compio::io::AsyncReadAt::read_at`, the buffer is returned alongside
+/// the number of bytes read; its length is left unchanged.
+pub async fn read_at(
+    file: &File,
+    buffer: Vec<u8>,
+    offset: u64,
+    priority: IoPriority,
+) -> BufResult<usize, Vec<u8>> {
+    let op = ReadAtOp {
+        fd: file.as_raw_fd(),
+        offset,
+        buffer,
+        ioprio: priority.to_raw(),
+    };
+    let BufResult(result, op) = submit(op).await;
+    BufResult(result, op.buffer)
+}
+
+/// Write `buffer` at `offset` with I/O priority `priority`
+pub async fn write_at(
+    file: &File,
+    buffer: Vec<u8>,
+    offset: u64,
+    priority: IoPriority,
+) -> BufResult<usize, Vec<u8>> {
+    let op = WriteAtOp {
+        fd: file.as_raw_fd(),
+        offset,
+        buffer,
+        ioprio: priority.to_raw(),
+    };
+    let BufResult(result, op) = submit(op).await;
+    BufResult(result, op.buffer)
+}
+
+/// Set the I/O priority of the calling thread with `ioprio_set(2)`
+///
+/// Threads created afterwards inherit it.
+///
+/// # Errors
+///
+/// Returns the OS error if the priority cannot be set.
+pub fn set_thread_priority(priority: IoPriority) -> std::io::Result<()> {
+    // IOPRIO_WHO_PROCESS with id 0 targets the calling thread
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    // SAFETY: ioprio_set takes only integer arguments
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_ioprio_set,
+            IOPRIO_WHO_PROCESS,
+            0,
+            libc::c_int::from(priority.to_raw()),
+        )
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_raw_priority_values() {
+        assert_eq!(IoPriority::idle().to_raw(), 0x6000);
+        assert_eq!(IoPriority::best_effort(4).to_raw(), 0x4004);
+        assert_eq!(IoPriority::best_effort(9), IoPriority::best_effort(7));
+    }
+
+    #[compio::test]
+    async fn test_read_write_with_priority() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("file");
+        std::fs::write(&path, b"").unwrap();
+        let file = compio::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .await
+            .unwrap();
+
+        let BufResult(written, _) = write_at(&file, b"hello".to_vec(), 0, IoPriority::idle()).await;
+        assert_eq!(written.unwrap(), 5);
+
+        let BufResult(read, buffer) =
+            read_at(&file, vec![0u8; 16], 0, IoPriority::best_effort(7)).await;
+        assert_eq!(&buffer[..read.unwrap()], b"hello");
+    }
+}
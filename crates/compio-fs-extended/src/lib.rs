@@ -5,8 +5,18 @@
 //! - `fadvise` for file access pattern optimization
 //! - Symlink operations (create, read, metadata)
 //! - Hardlink operations
+//! - Linked operation chains (`IOSQE_IO_LINK`) for copying small files in
+//!   one submission
+//! - Renames with `RENAME_NOREPLACE` and `RENAME_EXCHANGE`
+//! - Unlinking files and removing directories
+//! - `ftruncate` for shrinking files updated in place
+//! - Anonymous `O_TMPFILE` files linked into place once complete
+//! - Reads and writes with an explicit I/O priority
+//! - A pool of buffers registered with the ring for fixed-buffer I/O
 //! - Extended attributes (xattr) using io_uring opcodes
-//! - Directory operations
+//! - Directory operations, including creation via `mkdirat`
+//! - `statx` with any mask: birth time, mount ID and direct I/O alignment
+//! - Probing which io_uring opcodes and setup flags the kernel supports
 //!
 //! This crate extends `compio::fs::File` with additional operations that are not
 //! available in the base compio-fs crate, using direct syscalls integrated with
@@ -34,6 +44,7 @@
 //! # }
 //! ```
 
+pub mod buffer_pool;
 pub mod copy;
 pub mod device;
 pub mod directory;
@@ -42,9 +53,16 @@ pub mod extended_file;
 pub mod fadvise;
 pub mod fallocate;
 pub mod hardlink;
+pub mod ioprio;
+pub mod linked;
 pub mod metadata;
 pub mod ownership;
+pub mod probe;
+pub mod rename;
 pub mod symlink;
+pub mod tmpfile;
+pub mod truncate;
+pub mod unlink;
 pub mod xattr;
 
 // Re-export main types
@@ -59,6 +77,7 @@ pub use fallocate::Fallocate;
 pub use hardlink::HardlinkOps;
 pub use ownership::OwnershipOps;
 pub use symlink::SymlinkOps;
+pub use truncate::Ftruncate;
 pub use xattr::XattrOps;
 
 /// Version information
@@ -0,0 +1,894 @@
+//! Linked SQE chains (`IOSQE_IO_LINK`)
+//!
+//! Copying a tiny file one operation at a time costs a submission round trip
+//! for each of open, open, read, write, close and close. A [`LinkedChain`]
+//! submits all of them at once: each entry is linked to the next, so the
+//! kernel runs them in order without returning to userspace in between.
+//!
+//! Files opened by a chain are io_uring direct descriptors (slots in a
+//! registered file table), which is what lets a read later in the chain
+//! refer to a file that does not exist yet when the chain is submitted.
+//! Every file a chain opens is closed at its end.
+//!
+//! If an operation fails, or a read or write transfers fewer bytes than
+//! requested, the kernel cancels the rest of the chain and those operations
+//! complete with `ECANCELED`. [`ChainResults::check`] reports the operation
+//! that broke the chain.
+//!
+//! compio's ring only accepts one entry per operation, and entries pushed
+//! one by one may be split across submissions when its queue fills up,
+//! which would break the ordering a chain depends on. Chains therefore run
+//! on a small ring of their own per thread, whose completions are awaited
+//! through compio's ring.
+
+use crate::directory::{path_cstring, DirectoryFd};
+use crate::error::Result;
+use compio::driver::OpCode;
+use compio::runtime::submit;
+use io_uring::{cqueue, opcode, squeue, types, IoUring};
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::future::Future;
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+/// Submission queue size of the per-thread chain ring
+///
+/// This is also the longest chain that can be submitted.
+const RING_ENTRIES: u32 = 128;
+
+/// Direct descriptor slots shared by the chains in flight on one thread
+const FILE_SLOTS: u32 = 64;
+
+thread_local! {
+    /// Chain ring of this thread, created on first use
+    static CHAIN_RING: RefCell<RingState> = const { RefCell::new(RingState::Uninit) };
+}
+
+/// Lifecycle of a thread's chain ring
+enum RingState {
+    /// No chain has been submitted on this thread yet
+    Uninit,
+    /// The ring is set up
+    Ready(Box<ChainRing>),
+    /// The kernel could not set up the ring; the OS error code, if any
+    Unavailable(Option<i32>),
+}
+
+/// A file opened by a [`LinkedChain`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainFile {
+    /// Index of the file among those opened by the chain
+    index: usize,
+    /// The open operation
+    op: ChainOp,
+}
+
+impl ChainFile {
+    /// The operation that opens the file
+    #[must_use]
+    pub const fn op(&self) -> ChainOp {
+        self.op
+    }
+}
+
+/// A buffer filled or consumed by a [`LinkedChain`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainBuf {
+    /// Index of the buffer in the chain
+    index: usize,
+    /// The read that fills it, if any
+    op: Option<ChainOp>,
+}
+
+/// An operation of a [`LinkedChain`], used to look up its result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainOp(usize);
+
+/// One operation of a chain, before slots are assigned
+#[derive(Debug)]
+enum Step {
+    /// `openat` into the direct descriptor of file `file`
+    Open {
+        /// Directory `path` is relative to
+        dirfd: RawFd,
+        /// Index of the path in the chain
+        path: usize,
+        /// `open(2)` flags
+        flags: i32,
+        /// Mode of a created file
+        mode: u32,
+        /// Chain file opened
+        file: usize,
+    },
+    /// Read into buffer `buf` from `offset`
+    Read {
+        /// Chain file read from
+        file: usize,
+        /// Buffer read into
+        buf: usize,
+        /// File offset
+        offset: u64,
+    },
+    /// Write buffer `buf` at `offset`
+    Write {
+        /// Chain file written to
+        file: usize,
+        /// Buffer written
+        buf: usize,
+        /// File offset
+        offset: u64,
+    },
+    /// Close the direct descriptor of file `file`
+    Close {
+        /// Chain file closed
+        file: usize,
+    },
+}
+
+/// A sequence of operations submitted to the kernel in one go
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use compio_fs_extended::directory::DirectoryFd;
+/// use compio_fs_extended::linked::LinkedChain;
+/// use std::path::Path;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let dir = DirectoryFd::open(Path::new("/etc")).await?;
+///
+/// let mut chain = LinkedChain::new();
+/// let file = chain.open_at(&dir, Path::new("hostname"), libc::O_RDONLY, 0)?;
+/// let buf = chain.read(file, 256, 0);
+/// let results = chain.submit().await?;
+///
+/// println!("{}", String::from_utf8_lossy(results.buffer(buf)));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct LinkedChain {
+    /// Operations in submission order
+    steps: Vec<Step>,
+    /// Paths of the open operations
+    paths: Vec<CString>,
+    /// Buffers of the reads and writes
+    buffers: Vec<Vec<u8>>,
+    /// Number of files the chain opens
+    files: usize,
+}
+
+impl LinkedChain {
+    /// Start an empty chain
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open `name` in `dir`
+    ///
+    /// The file is closed automatically at the end of the chain. `dir` only
+    /// needs to stay open until [`submit`](Self::submit) returns.
+    /// `O_CLOEXEC` is implied and must not be passed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `name` contains a NUL byte.
+    pub fn open_at(
+        &mut self,
+        dir: &DirectoryFd,
+        name: &Path,
+        flags: i32,
+        mode: u32,
+    ) -> Result<ChainFile> {
+        self.paths.push(path_cstring(name)?);
+        let file = self.files;
+        self.files += 1;
+        let op = self.push(Step::Open {
+            dirfd: dir.as_raw_fd(),
+            path: self.paths.len() - 1,
+            flags: flags & !libc::O_CLOEXEC,
+            mode,
+            file,
+        });
+        Ok(ChainFile { index: file, op })
+    }
+
+    /// Read up to `len` bytes of `file` from `offset`
+    ///
+    /// Reading fewer than `len` bytes ends the chain.
+    pub fn read(&mut self, file: ChainFile, len: usize, offset: u64) -> ChainBuf {
+        self.buffers.push(vec![0; len]);
+        let buf = self.buffers.len() - 1;
+        let op = self.push(Step::Read {
+            file: file.index,
+            buf,
+            offset,
+        });
+        ChainBuf {
+            index: buf,
+            op: Some(op),
+        }
+    }
+
+    /// Add a buffer holding `data`, to be written by [`write`](Self::write)
+    pub fn buffer(&mut self, data: Vec<u8>) -> ChainBuf {
+        self.buffers.push(data);
+        ChainBuf {
+            index: self.buffers.len() - 1,
+            op: None,
+        }
+    }
+
+    /// Write all of `buf` to `file` at `offset`
+    ///
+    /// A buffer filled by [`read`](Self::read) is written at its requested
+    /// length; a short read ends the chain before the write runs.
+    pub fn write(&mut self, file: ChainFile, buf: ChainBuf, offset: u64) -> ChainOp {
+        self.push(Step::Write {
+            file: file.index,
+            buf: buf.index,
+            offset,
+        })
+    }
+
+    /// Number of operations in the chain, including the final closes
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.steps.len() + self.files
+    }
+
+    /// Whether the chain has no operations
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Append an operation and return its handle
+    fn push(&mut self, step: Step) -> ChainOp {
+        self.steps.push(step);
+        ChainOp(self.steps.len() - 1)
+    }
+
+    /// Submit the chain and wait for all of its operations to complete
+    ///
+    /// Individual operations can fail without this returning an error; see
+    /// [`ChainResults::check`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The kernel cannot set up the chain ring (`Unsupported` on kernels
+    ///   without registered file tables)
+    /// - The chain is longer than the ring (`InvalidInput`)
+    /// - Too many files are open by chains on this thread (`WouldBlock`)
+    pub async fn submit(mut self) -> Result<ChainResults> {
+        for file in 0..self.files {
+            self.steps.push(Step::Close { file });
+        }
+        if self.steps.is_empty() {
+            return Ok(ChainResults {
+                results: Vec::new(),
+                expected: Vec::new(),
+                buffers: Vec::new(),
+            });
+        }
+        let (id, start_reaper) = with_ring(|ring| ring.start(self))?;
+        let completion = ChainCompletion { id };
+        if start_reaper {
+            compio::runtime::spawn(reap_chains()).detach();
+        }
+        Ok(completion.await)
+    }
+}
+
+/// Results of the operations of a submitted [`LinkedChain`]
+#[derive(Debug)]
+pub struct ChainResults {
+    /// Raw completion result of each operation
+    results: Vec<i32>,
+    /// Byte count each read or write must transfer, if any
+    expected: Vec<Option<usize>>,
+    /// The chain's buffers
+    buffers: Vec<Vec<u8>>,
+}
+
+impl ChainResults {
+    /// Result of `op`: bytes transferred for reads and writes, 0 for opens
+    ///
+    /// # Errors
+    ///
+    /// Returns the error the operation failed with; operations after the
+    /// one that broke the chain fail with `ECANCELED`.
+    pub fn result(&self, op: ChainOp) -> Result<usize> {
+        let res = self.results[op.0];
+        usize::try_from(res).map_err(|_| io::Error::from_raw_os_error(-res).into())
+    }
+
+    /// Data read into `buf`, or the data it was created with
+    ///
+    /// A buffer whose read failed is empty.
+    #[must_use]
+    pub fn buffer(&self, buf: ChainBuf) -> &[u8] {
+        let data = &self.buffers[buf.index];
+        match buf.op {
+            Some(op) => &data[..self.result(op).unwrap_or(0).min(data.len())],
+            None => data,
+        }
+    }
+
+    /// Check that every operation completed in full
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the first operation that failed, or
+    /// `UnexpectedEof` for a read or write that transferred fewer bytes than
+    /// requested.
+    pub fn check(&self) -> Result<()> {
+        for (op, expected) in self.expected.iter().enumerate() {
+            let done = self.result(ChainOp(op))?;
+            if expected.is_some_and(|expected| done < expected) {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("chain operation {op} transferred {done} bytes of {expected:?}"),
+                )
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of operations, including the closes at the end of the chain
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    /// Whether the chain had no operations
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+}
+
+/// A chain that has been submitted to the kernel
+#[derive(Debug)]
+struct InFlightChain {
+    /// Operations of the chain; also keeps its paths and buffers alive
+    chain: LinkedChain,
+    /// Direct descriptor slot of each file
+    slots: Vec<u32>,
+    /// Completion result of each operation, as they arrive
+    results: Vec<Option<i32>>,
+    /// Operations not completed yet
+    remaining: usize,
+    /// Task waiting for the chain
+    waker: Option<Waker>,
+    /// The waiting task went away; discard the chain when it completes
+    abandoned: bool,
+}
+
+/// The per-thread ring chains are submitted to
+struct ChainRing {
+    /// The ring, with a sparse registered file table of `FILE_SLOTS`
+    ring: IoUring,
+    /// Direct descriptor slots not used by any chain
+    free_slots: Vec<u32>,
+    /// Submitted chains, indexed by id
+    chains: Vec<Option<InFlightChain>>,
+    /// Chains that have not completed
+    in_flight: usize,
+    /// Whether a task is reaping completions
+    reaping: bool,
+}
+
+impl ChainRing {
+    /// Set up the ring and its file table
+    fn new() -> io::Result<Self> {
+        let caps = crate::probe::capabilities();
+        let needed = [
+            opcode::OpenAt::CODE,
+            opcode::Read::CODE,
+            opcode::Write::CODE,
+            opcode::Close::CODE,
+            opcode::PollAdd::CODE,
+        ];
+        if !needed.into_iter().all(|code| caps.supports(code)) {
+            // Chained steps have no syscall fallback
+            return Err(io::Error::from_raw_os_error(libc::EOPNOTSUPP));
+        }
+        // Only the owning thread submits, so the kernel can skip locking
+        let ring = crate::probe::capabilities()
+            .ring_builder()
+            .build(RING_ENTRIES)?;
+        ring.submitter().register_files_sparse(FILE_SLOTS)?;
+        Ok(Self {
+            ring,
+            free_slots: (0..FILE_SLOTS).rev().collect(),
+            chains: Vec::new(),
+            in_flight: 0,
+            reaping: false,
+        })
+    }
+
+    /// Submit `chain`, returning its id and whether a reaper must be started
+    fn start(&mut self, chain: LinkedChain) -> Result<(usize, bool)> {
+        if chain.steps.len() > RING_ENTRIES as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "chain of {} operations exceeds the ring size of {RING_ENTRIES}",
+                    chain.steps.len()
+                ),
+            )
+            .into());
+        }
+        if chain.files > self.free_slots.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "too many files open by linked chains",
+            )
+            .into());
+        }
+        let slots = self
+            .free_slots
+            .split_off(self.free_slots.len() - chain.files);
+
+        let id = self
+            .chains
+            .iter()
+            .position(Option::is_none)
+            .unwrap_or_else(|| {
+                self.chains.push(None);
+                self.chains.len() - 1
+            });
+        let entries = build_entries(id, &chain, &slots);
+        // SAFETY: the paths and buffers the entries point to are owned by
+        // the chain, which stays in `self.chains` until every entry completes
+        let pushed = unsafe { self.ring.submission().push_multiple(&entries) };
+        if pushed.is_err() {
+            // Earlier chains are always submitted right away, so the queue
+            // only fills up if submitting them failed
+            self.free_slots.extend(slots);
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "chain submission queue is full",
+            )
+            .into());
+        }
+        let operations = chain.steps.len();
+        self.chains[id] = Some(InFlightChain {
+            chain,
+            slots,
+            results: vec![None; operations],
+            remaining: operations,
+            waker: None,
+            abandoned: false,
+        });
+        self.in_flight += 1;
+        // Entries left in the queue by a failed submit go out with the
+        // reaper's next one
+        if let Err(e) = self.ring.submit() {
+            log::debug!("Deferred submitting a linked chain: {e}");
+        }
+        Ok((id, !std::mem::replace(&mut self.reaping, true)))
+    }
+
+    /// Record all available completions, returning the wakers of finished chains
+    fn reap(&mut self) -> Vec<Waker> {
+        if !self.ring.submission().is_empty() {
+            if let Err(e) = self.ring.submit() {
+                log::debug!("Deferred submitting a linked chain: {e}");
+            }
+        }
+        let completions: Vec<cqueue::Entry> = self.ring.completion().collect();
+        let mut wakers = Vec::new();
+        for entry in completions {
+            let (id, op) = split_user_data(entry.user_data());
+            let Some(chain) = self.chains.get_mut(id).and_then(Option::as_mut) else {
+                continue;
+            };
+            chain.results[op] = Some(entry.result());
+            chain.remaining -= 1;
+            if chain.remaining > 0 {
+                continue;
+            }
+            self.in_flight -= 1;
+            let (slots, abandoned) = (chain.slots.clone(), chain.abandoned);
+            let waker = chain.waker.take();
+            self.release_slots(id, slots);
+            if abandoned {
+                self.chains[id] = None;
+            } else if let Some(waker) = waker {
+                wakers.push(waker);
+            }
+        }
+        wakers
+    }
+
+    /// Return the slots of completed chain `id`, emptying any left open
+    fn release_slots(&mut self, id: usize, slots: Vec<u32>) {
+        if let Some(chain) = &self.chains[id] {
+            let closes = &chain.results[chain.results.len() - slots.len()..];
+            for (slot, closed) in slots.iter().zip(closes) {
+                // A close cancelled by a broken chain leaves its file open
+                if *closed != Some(0) {
+                    let _ = self.ring.submitter().register_files_update(*slot, &[-1]);
+                }
+            }
+        }
+        self.free_slots.extend(slots);
+    }
+}
+
+impl Drop for ChainRing {
+    fn drop(&mut self) {
+        // The kernel may still be writing into buffers owned by the chains
+        while self.in_flight > 0 {
+            if self.ring.submit_and_wait(1).is_err() {
+                break;
+            }
+            self.reap();
+        }
+    }
+}
+
+/// Build the linked submission entries of chain `id`
+fn build_entries(id: usize, chain: &LinkedChain, slots: &[u32]) -> Vec<squeue::Entry> {
+    let last = chain.steps.len() - 1;
+    chain
+        .steps
+        .iter()
+        .enumerate()
+        .map(|(op, step)| {
+            let entry = match *step {
+                Step::Open {
+                    dirfd,
+                    path,
+                    flags,
+                    mode,
+                    file,
+                } => opcode::OpenAt::new(types::Fd(dirfd), chain.paths[path].as_ptr())
+                    .flags(flags)
+                    .mode(mode)
+                    .file_index(types::DestinationSlot::try_from_slot_target(slots[file]).ok())
+                    .build(),
+                Step::Read { file, buf, offset } => {
+                    let buffer = &chain.buffers[buf];
+                    // Reads longer than u32::MAX are truncated and end the chain
+                    let len = u32::try_from(buffer.len()).unwrap_or(u32::MAX);
+                    opcode::Read::new(types::Fixed(slots[file]), buffer.as_ptr().cast_mut(), len)
+                        .offset(offset)
+                        .build()
+                }
+                Step::Write { file, buf, offset } => {
+                    let buffer = &chain.buffers[buf];
+                    let len = u32::try_from(buffer.len()).unwrap_or(u32::MAX);
+                    opcode::Write::new(types::Fixed(slots[file]), buffer.as_ptr(), len)
+                        .offset(offset)
+                        .build()
+                }
+                Step::Close { file } => opcode::Close::new(types::Fixed(slots[file])).build(),
+            };
+            let entry = entry.user_data(join_user_data(id, op));
+            if op == last {
+                entry
+            } else {
+                entry.flags(squeue::Flags::IO_LINK)
+            }
+        })
+        .collect()
+}
+
+/// Encode a chain id and operation index as completion user data
+const fn join_user_data(id: usize, op: usize) -> u64 {
+    ((id as u64) << 32) | op as u64
+}
+
+/// Decode completion user data into a chain id and operation index
+#[allow(clippy::cast_possible_truncation)]
+const fn split_user_data(user_data: u64) -> (usize, usize) {
+    (
+        (user_data >> 32) as usize,
+        (user_data & 0xffff_ffff) as usize,
+    )
+}
+
+/// Run `f` on this thread's chain ring, setting it up first if needed
+fn with_ring<T>(f: impl FnOnce(&mut ChainRing) -> Result<T>) -> Result<T> {
+    CHAIN_RING.with(|cell| {
+        let mut state = cell.borrow_mut();
+        if matches!(*state, RingState::Uninit) {
+            *state = match ChainRing::new() {
+                Ok(ring) => RingState::Ready(Box::new(ring)),
+                Err(e) => {
+                    log::debug!("Linked chains unavailable: {e}");
+                    RingState::Unavailable(e.raw_os_error())
+                }
+            };
+        }
+        match &mut *state {
+            RingState::Ready(ring) => f(ring),
+            RingState::Unavailable(code) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "linked chains are unavailable: {}",
+                    code.map_or_else(
+                        || "unknown error".to_string(),
+                        |code| io::Error::from_raw_os_error(code).to_string()
+                    )
+                ),
+            )
+            .into()),
+            RingState::Uninit => unreachable!("the ring was just set up"),
+        }
+    })
+}
+
+/// Future completing when chain `id` has finished
+struct ChainCompletion {
+    /// Id of the chain in the ring
+    id: usize,
+}
+
+impl Future for ChainCompletion {
+    type Output = ChainResults;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let id = self.id;
+        let finished = with_ring(|ring| {
+            let slot = &mut ring.chains[id];
+            let chain = slot.as_mut().expect("a polled chain is in flight");
+            if chain.remaining > 0 {
+                chain.waker = Some(cx.waker().clone());
+                return Ok(None);
+            }
+            Ok(slot.take())
+        });
+        match finished {
+            Ok(Some(chain)) => Poll::Ready(chain.into_results()),
+            _ => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for ChainCompletion {
+    fn drop(&mut self) {
+        let id = self.id;
+        // The thread-local may already be gone during thread teardown
+        let _ = CHAIN_RING.try_with(|cell| {
+            if let RingState::Ready(ring) = &mut *cell.borrow_mut() {
+                if let Some(chain) = ring.chains[id].as_mut() {
+                    if chain.remaining > 0 {
+                        chain.abandoned = true;
+                    } else {
+                        ring.chains[id] = None;
+                    }
+                }
+            }
+        });
+    }
+}
+
+impl InFlightChain {
+    /// Turn a completed chain into its results
+    fn into_results(self) -> ChainResults {
+        let LinkedChain { steps, buffers, .. } = self.chain;
+        let expected = steps
+            .iter()
+            .map(|step| match *step {
+                Step::Read { buf, .. } | Step::Write { buf, .. } => Some(buffers[buf].len()),
+                Step::Open { .. } | Step::Close { .. } => None,
+            })
+            .collect();
+        ChainResults {
+            results: self
+                .results
+                .into_iter()
+                .map(|res| res.unwrap_or(-libc::ECANCELED))
+                .collect(),
+            expected,
+            buffers,
+        }
+    }
+}
+
+/// io_uring poll for the chain ring's completion queue
+struct PollRingOp {
+    /// The chain ring's descriptor
+    fd: RawFd,
+}
+
+impl OpCode for PollRingOp {
+    fn create_entry(self: Pin<&mut Self>) -> compio::driver::OpEntry {
+        compio::driver::OpEntry::Submission(
+            opcode::PollAdd::new(types::Fd(self.fd), libc::POLLIN as u32).build(),
+        )
+    }
+}
+
+/// Deliver chain completions until no chain is in flight
+async fn reap_chains() {
+    loop {
+        let step = with_ring(|ring| {
+            let wakers = ring.reap();
+            if ring.in_flight == 0 {
+                ring.reaping = false;
+                return Ok((wakers, None));
+            }
+            Ok((wakers, Some(ring.ring.as_raw_fd())))
+        });
+        let Ok((wakers, fd)) = step else { return };
+        wakers.into_iter().for_each(Waker::wake);
+        let Some(fd) = fd else { return };
+
+        // The ring's descriptor is readable while completions are queued
+        if let Err(e) = submit(PollRingOp { fd }).await.0 {
+            log::warn!("Polling the linked chain ring failed, waiting in place: {e}");
+            let _ = with_ring(|ring| Ok(ring.ring.submit_and_wait(1)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExtendedError;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Run `chain`, or return `None` where the kernel cannot run chains
+    async fn submit_or_skip(chain: LinkedChain) -> Option<ChainResults> {
+        match chain.submit().await {
+            Ok(results) => Some(results),
+            Err(ExtendedError::Io(e)) if e.kind() == io::ErrorKind::Unsupported => None,
+            Err(e) => panic!("chain submission failed: {e}"),
+        }
+    }
+
+    /// Copy the first `len` bytes of `src_name` to `dst_name` in one chain
+    #[allow(clippy::too_many_arguments)]
+    async fn copy_small_file(
+        src_dir: &DirectoryFd,
+        src_name: &Path,
+        dst_dir: &DirectoryFd,
+        dst_name: &Path,
+        len: usize,
+        mode: u32,
+    ) -> Result<usize> {
+        let mut chain = LinkedChain::new();
+        let src = chain.open_at(src_dir, src_name, libc::O_RDONLY | libc::O_NOFOLLOW, 0)?;
+        let dst = chain.open_at(
+            dst_dir,
+            dst_name,
+            libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC | libc::O_NOFOLLOW,
+            mode,
+        )?;
+        if len > 0 {
+            let buf = chain.read(src, len, 0);
+            chain.write(dst, buf, 0);
+        }
+        chain.submit().await?.check()?;
+        Ok(len)
+    }
+
+    #[compio::test]
+    async fn test_chain_reads_file() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("file"), "chained read").unwrap();
+        let dir = DirectoryFd::open(temp_dir.path()).await.unwrap();
+
+        let mut chain = LinkedChain::new();
+        let file = chain
+            .open_at(&dir, Path::new("file"), libc::O_RDONLY, 0)
+            .unwrap();
+        let buf = chain.read(file, 4, 8);
+        assert_eq!(chain.len(), 3);
+        let Some(results) = submit_or_skip(chain).await else {
+            return;
+        };
+
+        results.check().unwrap();
+        assert_eq!(results.buffer(buf), b"read");
+        assert_eq!(results.len(), 3);
+    }
+
+    #[compio::test]
+    async fn test_copy_small_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let src = temp_dir.path().join("src");
+        let dst = temp_dir.path().join("dst");
+        fs::create_dir(&src).unwrap();
+        fs::create_dir(&dst).unwrap();
+        let src_dir = DirectoryFd::open(&src).await.unwrap();
+        let dst_dir = DirectoryFd::open(&dst).await.unwrap();
+
+        // Many copies in flight at once share the thread's ring and slots
+        let mut copies = Vec::new();
+        for i in 0..20 {
+            let name = format!("file{i}");
+            fs::write(src.join(&name), name.repeat(i + 1)).unwrap();
+            copies.push((name.clone(), name.len() * (i + 1)));
+        }
+        let results = futures::future::join_all(copies.iter().map(|(name, len)| {
+            copy_small_file(
+                &src_dir,
+                Path::new(name),
+                &dst_dir,
+                Path::new(name),
+                *len,
+                0o600,
+            )
+        }))
+        .await;
+
+        for ((name, len), result) in copies.iter().zip(results) {
+            match result {
+                Ok(copied) => assert_eq!(copied, *len),
+                Err(ExtendedError::Io(e)) if e.kind() == io::ErrorKind::Unsupported => return,
+                Err(e) => panic!("copy of {name} failed: {e}"),
+            }
+            assert_eq!(
+                fs::read(dst.join(name)).unwrap(),
+                fs::read(src.join(name)).unwrap()
+            );
+        }
+    }
+
+    #[compio::test]
+    async fn test_broken_chain_is_cancelled_and_closes_files() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("short"), "abc").unwrap();
+        let dir = DirectoryFd::open(temp_dir.path()).await.unwrap();
+
+        // A short read breaks the chain, so the write never happens
+        let mut chain = LinkedChain::new();
+        let src = chain
+            .open_at(&dir, Path::new("short"), libc::O_RDONLY, 0)
+            .unwrap();
+        let dst = chain
+            .open_at(
+                &dir,
+                Path::new("out"),
+                libc::O_WRONLY | libc::O_CREAT,
+                0o600,
+            )
+            .unwrap();
+        let buf = chain.read(src, 100, 0);
+        let write = chain.write(dst, buf, 0);
+        let Some(results) = submit_or_skip(chain).await else {
+            return;
+        };
+
+        assert_eq!(results.buffer(buf), b"abc");
+        let err = results.check().unwrap_err();
+        assert!(
+            matches!(&err, ExtendedError::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof),
+            "unexpected error: {err}"
+        );
+        assert!(matches!(
+            results.result(write),
+            Err(ExtendedError::Io(e)) if e.raw_os_error() == Some(libc::ECANCELED)
+        ));
+        assert_eq!(fs::metadata(temp_dir.path().join("out")).unwrap().len(), 0);
+
+        // Every slot was returned even though the closes were cancelled
+        let free = with_ring(|ring| Ok(ring.free_slots.len())).unwrap();
+        assert_eq!(free, FILE_SLOTS as usize);
+
+        // A missing source fails at the open
+        let Err(err) =
+            copy_small_file(&dir, Path::new("missing"), &dir, Path::new("out"), 1, 0o600).await
+        else {
+            panic!("copying a missing file succeeded");
+        };
+        assert!(
+            matches!(&err, ExtendedError::Io(e) if e.kind() == io::ErrorKind::NotFound),
+            "unexpected error: {err}"
+        );
+    }
+}
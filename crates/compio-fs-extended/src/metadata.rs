@@ -5,12 +5,19 @@
 //! # Operations
 //!
 //! - **statx_at**: Get file metadata with nanosecond timestamps (io_uring STATX)
+//! - **statx_btime**: Get a file's creation (birth) time (io_uring STATX)
+//! - **symlink_metadata**: Get full metadata without following symlinks (io_uring STATX)
+//! - **statx_fd / statx_path**: Request any `statx` mask, including birth time, mount ID and direct I/O alignment
 //! - **fchmodat**: Change file permissions using file descriptors
 //! - **futimesat**: Change file timestamps using file descriptors
 //! - **fchownat**: Change file ownership using file descriptors
+//! - **lchown / chown_at / chmod_at**: Act on a symlink itself (`AT_SYMLINK_NOFOLLOW`), optionally relative to a DirectoryFd
+//! - **futimens_fd / utimens_at**: Set nanosecond timestamps through a descriptor, or on a symlink itself
 //! - **fchmodat_with_dirfd**: Change file permissions using DirectoryFd (most efficient)
 //! - **futimesat_with_dirfd**: Change file timestamps using DirectoryFd (most efficient)
 //! - **fchownat_with_dirfd**: Change file ownership using DirectoryFd (most efficient)
+//! - **inode_flags / set_inode_flags**: Read and change `chattr` flags such as `FS_NOCOW_FL`
+//! - **filesystem_magic**: Identify the filesystem holding a file descriptor (fstatfs)
 //!
 //! # Usage
 //!
@@ -36,7 +43,9 @@
 //! # }
 //! ```
 
+use crate::directory::DirectoryFd;
 use crate::error::{metadata_error, ExtendedError, Result};
+use crate::probe::{entry_or_blocking, syscall_result};
 use compio::driver::OpCode;
 use compio::runtime::submit;
 use filetime::{set_file_times, FileTime};
@@ -88,11 +97,17 @@ impl StatxOp {
             mask,
         }
     }
+
+    /// The statx result, once the operation has completed
+    #[must_use]
+    pub fn statx(&self) -> &libc::statx {
+        &self.statxbuf
+    }
 }
 
 impl OpCode for StatxOp {
     fn create_entry(mut self: Pin<&mut Self>) -> compio::driver::OpEntry {
-        compio::driver::OpEntry::Submission(
+        entry_or_blocking(opcode::Statx::CODE, || {
             opcode::Statx::new(
                 types::Fd(self.dirfd),
                 self.pathname.as_ptr(),
@@ -100,8 +115,15 @@ impl OpCode for StatxOp {
             )
             .flags(self.flags)
             .mask(self.mask)
-            .build(),
-        )
+            .build()
+        })
+    }
+
+    fn call_blocking(mut self: Pin<&mut Self>) -> std::io::Result<usize> {
+        let (dirfd, flags, mask) = (self.dirfd, self.flags, self.mask);
+        let pathname = self.pathname.as_ptr();
+        // SAFETY: pathname is a valid C string and statxbuf a valid buffer
+        syscall_result(unsafe { libc::statx(dirfd, pathname, flags, mask, &mut *self.statxbuf) })
     }
 }
 
@@ -149,6 +171,221 @@ pub async fn statx_at(path: &Path) -> Result<(SystemTime, SystemTime)> {
     }
 }
 
+/// Get the creation (birth) time of an open file using io_uring STATX
+///
+/// # Arguments
+///
+/// * `fd` - File descriptor of the file
+///
+/// # Returns
+///
+/// The birth time, or `None` if the filesystem does not record one
+///
+/// # Errors
+///
+/// Returns an error if the statx operation fails
+pub async fn statx_btime(fd: i32) -> Result<Option<SystemTime>> {
+    statx_fd(fd, libc::STATX_BTIME)
+        .await
+        .map(|statx| statx.btime())
+        .map_err(|e| metadata_error(&format!("statx failed: {}", e)))
+}
+
+/// Direct I/O alignment requirements reported by `STATX_DIOALIGN`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DioAlignment {
+    /// Required alignment of user buffers, in bytes
+    pub memory: u32,
+    /// Required alignment of file offsets and lengths, in bytes
+    pub offset: u32,
+}
+
+/// Result of a `statx` call made with a caller-chosen mask
+///
+/// `stat` has no room for the birth time, the mount ID or the direct I/O
+/// alignment, so those are only available here. Each extended field is
+/// `None` unless it was requested and the kernel and filesystem reported it;
+/// the basic fields are available through [`StatxMetadata::metadata`].
+#[derive(Clone, Copy)]
+pub struct StatxMetadata {
+    /// Raw statx result
+    statx: libc::statx,
+}
+
+impl StatxMetadata {
+    /// Fields the kernel filled in (`STATX_*` bits)
+    ///
+    /// This can contain fewer bits than requested, e.g. `STATX_BTIME` on a
+    /// filesystem without birth times, and occasionally more.
+    #[must_use]
+    pub const fn mask(&self) -> u32 {
+        self.statx.stx_mask
+    }
+
+    /// The basic fields as [`compio::fs::Metadata`]
+    #[must_use]
+    pub fn metadata(&self) -> compio::fs::Metadata {
+        compio::fs::Metadata::from_stat(statx_to_stat(&self.statx))
+    }
+
+    /// Creation (birth) time, if reported (`STATX_BTIME`)
+    #[must_use]
+    pub fn btime(&self) -> Option<SystemTime> {
+        if self.mask() & libc::STATX_BTIME == 0 {
+            return None;
+        }
+        let secs = u64::try_from(self.statx.stx_btime.tv_sec).unwrap_or(0);
+        Some(SystemTime::UNIX_EPOCH + std::time::Duration::new(secs, self.statx.stx_btime.tv_nsec))
+    }
+
+    /// ID of the mount holding the file, if reported (`STATX_MNT_ID`, Linux 5.8+)
+    ///
+    /// Unlike the device number, this differs between bind mounts of the
+    /// same filesystem, so it detects every mount boundary.
+    #[must_use]
+    pub const fn mount_id(&self) -> Option<u64> {
+        if self.mask() & libc::STATX_MNT_ID == 0 {
+            return None;
+        }
+        Some(self.statx.stx_mnt_id)
+    }
+
+    /// Direct I/O alignment, if reported (`STATX_DIOALIGN`, Linux 6.1+)
+    ///
+    /// Both alignments are 0 if the file does not support direct I/O.
+    #[must_use]
+    pub const fn dio_alignment(&self) -> Option<DioAlignment> {
+        if self.mask() & libc::STATX_DIOALIGN == 0 {
+            return None;
+        }
+        Some(DioAlignment {
+            memory: self.statx.stx_dio_mem_align,
+            offset: self.statx.stx_dio_offset_align,
+        })
+    }
+}
+
+impl std::fmt::Debug for StatxMetadata {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StatxMetadata")
+            .field("mask", &format_args!("{:#x}", self.mask()))
+            .field("ino", &self.statx.stx_ino)
+            .field("size", &self.statx.stx_size)
+            .field("btime", &self.btime())
+            .field("mount_id", &self.mount_id())
+            .field("dio_alignment", &self.dio_alignment())
+            .finish()
+    }
+}
+
+/// Submit a statx and keep the whole result
+async fn submit_statx(
+    dirfd: i32,
+    pathname: CString,
+    flags: i32,
+    mask: u32,
+) -> Result<StatxMetadata> {
+    let result = submit(StatxOp::new(dirfd, pathname, flags, mask)).await;
+    result.0?;
+    Ok(StatxMetadata {
+        statx: *result.1.statx(),
+    })
+}
+
+/// Get the fields selected by `mask` for an open file using io_uring STATX
+///
+/// # Arguments
+///
+/// * `fd` - File descriptor of the file
+/// * `mask` - `STATX_*` bits to request, e.g.
+///   `libc::STATX_BASIC_STATS | libc::STATX_BTIME | libc::STATX_MNT_ID`
+///
+/// # Errors
+///
+/// Failures are returned as [`ExtendedError::Io`](crate::ExtendedError::Io).
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use compio_fs_extended::metadata::statx_fd;
+/// use std::os::unix::io::AsRawFd;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let file = compio::fs::File::open("data.bin").await?;
+/// let statx = statx_fd(file.as_raw_fd(), libc::STATX_DIOALIGN).await?;
+/// if let Some(align) = statx.dio_alignment() {
+///     println!("O_DIRECT needs {}-byte aligned offsets", align.offset);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn statx_fd(fd: i32, mask: u32) -> Result<StatxMetadata> {
+    submit_statx(fd, CString::default(), libc::AT_EMPTY_PATH, mask).await
+}
+
+/// Get the fields selected by `mask` for `path` without following symlinks
+///
+/// # Errors
+///
+/// Failures are returned as [`ExtendedError::Io`](crate::ExtendedError::Io),
+/// e.g. `NotFound`.
+pub async fn statx_path(path: &Path, mask: u32) -> Result<StatxMetadata> {
+    statx_path_at_fd(libc::AT_FDCWD, path, mask).await
+}
+
+/// Get the fields selected by `mask` for `pathname` relative to `dirfd`
+/// without following symlinks
+pub(crate) async fn statx_path_at_fd(
+    dirfd: i32,
+    pathname: &Path,
+    mask: u32,
+) -> Result<StatxMetadata> {
+    submit_statx(
+        dirfd,
+        crate::directory::path_cstring(pathname)?,
+        libc::AT_SYMLINK_NOFOLLOW,
+        mask,
+    )
+    .await
+}
+
+/// Get the metadata of `path` without following symlinks
+///
+/// Unlike `compio::fs::symlink_metadata`, which submits `statx` with an
+/// empty mask, this requests `STATX_BASIC_STATS`, so timestamps are filled
+/// in on kernels and filesystems that only return what was asked for.
+///
+/// # Errors
+///
+/// Failures are returned as [`ExtendedError::Io`](crate::ExtendedError::Io),
+/// e.g. `NotFound`.
+pub async fn symlink_metadata(path: &Path) -> Result<compio::fs::Metadata> {
+    Ok(statx_path(path, libc::STATX_BASIC_STATS).await?.metadata())
+}
+
+/// Convert a statx result into a `stat` for [`compio::fs::Metadata::from_stat`]
+fn statx_to_stat(statx: &libc::statx) -> libc::stat {
+    // SAFETY: stat is plain old data; every field is assigned or stays zero
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    stat.st_dev = libc::makedev(statx.stx_dev_major, statx.stx_dev_minor);
+    stat.st_ino = statx.stx_ino;
+    stat.st_nlink = statx.stx_nlink.into();
+    stat.st_mode = statx.stx_mode.into();
+    stat.st_uid = statx.stx_uid;
+    stat.st_gid = statx.stx_gid;
+    stat.st_rdev = libc::makedev(statx.stx_rdev_major, statx.stx_rdev_minor);
+    stat.st_size = statx.stx_size as libc::off_t;
+    stat.st_blksize = statx.stx_blksize as libc::blksize_t;
+    stat.st_blocks = statx.stx_blocks as libc::blkcnt_t;
+    stat.st_atime = statx.stx_atime.tv_sec;
+    stat.st_atime_nsec = statx.stx_atime.tv_nsec.into();
+    stat.st_mtime = statx.stx_mtime.tv_sec;
+    stat.st_mtime_nsec = statx.stx_mtime.tv_nsec.into();
+    stat.st_ctime = statx.stx_ctime.tv_sec;
+    stat.st_ctime_nsec = statx.stx_ctime.tv_nsec.into();
+    stat
+}
+
 /// Join a directory file descriptor path with a relative pathname
 fn join_dirfd_path(dir_fd: i32, pathname: &str) -> Result<PathBuf> {
     let dir_path = std::fs::read_link(proc_fd_path(dir_fd))?;
@@ -211,8 +448,8 @@ pub async fn futimesat(path: &Path, accessed: SystemTime, modified: SystemTime)
     // NOTE: Kernel doesn't have IORING_OP_UTIMENSAT - using safe nix wrapper
     let inner = compio::runtime::spawn(async move {
         // Convert SystemTime to TimeSpec for nix
-        let atime = system_time_to_timespec(accessed)?;
-        let mtime = system_time_to_timespec(modified)?;
+        let atime = system_time_to_timespec(accessed);
+        let mtime = system_time_to_timespec(modified);
 
         nix::sys::stat::utimensat(
             None, // Use path directly (AT_FDCWD)
@@ -230,15 +467,82 @@ pub async fn futimesat(path: &Path, accessed: SystemTime, modified: SystemTime)
 }
 
 /// Helper to convert SystemTime to nix TimeSpec
-fn system_time_to_timespec(time: SystemTime) -> Result<TimeSpec> {
-    let duration = time
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .map_err(|e| metadata_error(&format!("Invalid time: {}", e)))?;
-
-    Ok(TimeSpec::new(
-        duration.as_secs() as i64,
-        duration.subsec_nanos() as i64,
-    ))
+///
+/// Times before the epoch are supported: the seconds go negative while the
+/// nanoseconds stay in `0..1_000_000_000`, as `utimensat(2)` requires.
+fn system_time_to_timespec(time: SystemTime) -> TimeSpec {
+    match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(after) => TimeSpec::new(after.as_secs() as i64, i64::from(after.subsec_nanos())),
+        Err(e) => {
+            let before = e.duration();
+            let secs = -(before.as_secs() as i64);
+            match before.subsec_nanos() {
+                0 => TimeSpec::new(secs, 0),
+                nanos => TimeSpec::new(secs - 1, 1_000_000_000 - i64::from(nanos)),
+            }
+        }
+    }
+}
+
+/// Set the timestamps of `name` in `dir` with nanosecond precision
+///
+/// With [`FollowSymlinks::NoFollow`] the times of a symlink itself are set
+/// (`lutimes` semantics), so copied symlinks keep their original times.
+///
+/// # Arguments
+///
+/// * `dir` - Directory holding the entry
+/// * `name` - Name of the entry, relative to `dir`
+/// * `accessed` - New access time
+/// * `modified` - New modification time
+/// * `follow` - Whether a final symlink is followed
+///
+/// # Errors
+///
+/// Failures are returned as [`ExtendedError::Io`], e.g. `NotFound` or
+/// `EPERM`.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use compio_fs_extended::directory::DirectoryFd;
+/// use compio_fs_extended::metadata::{utimens_at, FollowSymlinks};
+/// use std::path::Path;
+/// use std::time::SystemTime;
+///
+/// # async fn example() -> compio_fs_extended::Result<()> {
+/// let dir = DirectoryFd::open(Path::new("/backup")).await?;
+/// let when = SystemTime::UNIX_EPOCH;
+/// utimens_at(&dir, Path::new("latest"), when, when, FollowSymlinks::NoFollow).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn utimens_at(
+    dir: &DirectoryFd,
+    name: &Path,
+    accessed: SystemTime,
+    modified: SystemTime,
+    follow: FollowSymlinks,
+) -> Result<()> {
+    let dirfd = dir.as_raw_fd();
+    let name = name.to_path_buf();
+    let flag = match follow {
+        FollowSymlinks::Follow => UtimensatFlags::FollowSymlink,
+        FollowSymlinks::NoFollow => UtimensatFlags::NoFollowSymlink,
+    };
+    // NOTE: Kernel doesn't have IORING_OP_UTIMENSAT - using a blocking thread
+    compio::runtime::spawn_blocking(move || {
+        nix::sys::stat::utimensat(
+            Some(dirfd),
+            &name,
+            &system_time_to_timespec(accessed),
+            &system_time_to_timespec(modified),
+            flag,
+        )
+        .map_err(|errno| std::io::Error::from(errno).into())
+    })
+    .await
+    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
 }
 
 /// Change file timestamps using file descriptor (FD-based, more efficient)
@@ -265,17 +569,96 @@ fn system_time_to_timespec(time: SystemTime) -> Result<TimeSpec> {
 pub async fn futimens_fd(fd: i32, accessed: SystemTime, modified: SystemTime) -> Result<()> {
     // NOTE: Kernel doesn't have IORING_OP_FUTIMENS - using safe nix wrapper
     // futimens is FD-based, better than path-based utimensat (no TOCTOU)
-    let inner = compio::runtime::spawn(async move {
-        let atime = system_time_to_timespec(accessed)?;
-        let mtime = system_time_to_timespec(modified)?;
+    compio::runtime::spawn_blocking(move || {
+        let atime = system_time_to_timespec(accessed);
+        let mtime = system_time_to_timespec(modified);
 
         nix::sys::stat::futimens(fd, &atime, &mtime)
             .map_err(|e| metadata_error(&format!("futimens failed: {}", e)))
     })
     .await
+    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+/// Inode flag disabling copy-on-write for a file (`chattr +C`)
+pub const FS_NOCOW_FL: u32 = 0x0080_0000;
+
+/// Read the inode flags (`lsattr`) of a file descriptor
+///
+/// # Errors
+///
+/// This function will return an error if the filesystem does not support
+/// inode flags or the file descriptor is invalid
+pub async fn inode_flags(fd: i32) -> Result<u32> {
+    let inner = compio::runtime::spawn(async move {
+        let mut flags: libc::c_int = 0;
+        // SAFETY: FS_IOC_GETFLAGS writes a single int through the pointer
+        let result = unsafe { libc::ioctl(fd, libc::FS_IOC_GETFLAGS, &raw mut flags) };
+        if result < 0 {
+            return Err(metadata_error(&format!(
+                "FS_IOC_GETFLAGS failed: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+        #[allow(clippy::cast_sign_loss)]
+        Ok(flags as u32)
+    })
+    .await
     .map_err(ExtendedError::SpawnJoin)?;
-    inner?;
-    Ok(())
+    inner
+}
+
+/// Replace the inode flags (`chattr`) of a file descriptor
+///
+/// Some flags, like `FS_NOCOW_FL`, only take effect on empty files.
+///
+/// # Errors
+///
+/// This function will return an error if the filesystem does not support
+/// the flags, permission is denied or the file descriptor is invalid
+pub async fn set_inode_flags(fd: i32, flags: u32) -> Result<()> {
+    let inner = compio::runtime::spawn(async move {
+        #[allow(clippy::cast_possible_wrap)]
+        let flags = flags as libc::c_int;
+        // SAFETY: FS_IOC_SETFLAGS reads a single int through the pointer
+        let result = unsafe { libc::ioctl(fd, libc::FS_IOC_SETFLAGS, &raw const flags) };
+        if result < 0 {
+            return Err(metadata_error(&format!(
+                "FS_IOC_SETFLAGS failed: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+        Ok(())
+    })
+    .await
+    .map_err(ExtendedError::SpawnJoin)?;
+    inner
+}
+
+/// Get the filesystem magic number (`statfs.f_type`) for a file descriptor
+///
+/// # Errors
+///
+/// This function will return an error if the file descriptor is invalid
+pub async fn filesystem_magic(fd: i32) -> Result<u64> {
+    let inner = compio::runtime::spawn(async move {
+        // SAFETY: statfs is plain old data, fully written by fstatfs on success
+        let mut buf: libc::statfs = unsafe { std::mem::zeroed() };
+        // SAFETY: buf is valid for writes for the duration of the call
+        let result = unsafe { libc::fstatfs(fd, &raw mut buf) };
+        if result < 0 {
+            return Err(crate::error::filesystem_detection_error(&format!(
+                "fstatfs failed: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+        // f_type is a signed word on some targets; magic numbers are 32-bit
+        #[allow(clippy::cast_sign_loss, clippy::unnecessary_cast)]
+        Ok(buf.f_type as u64 & 0xffff_ffff)
+    })
+    .await
+    .map_err(ExtendedError::SpawnJoin)?;
+    inner
 }
 
 /// Change file ownership using file descriptor
@@ -297,10 +680,139 @@ pub async fn futimens_fd(fd: i32, accessed: SystemTime, modified: SystemTime) ->
 /// - Permission is denied
 /// - Invalid user/group IDs
 /// - The operation fails due to I/O errors
-pub async fn fchownat(_path: &Path, _uid: u32, _gid: u32) -> Result<()> {
-    Err(metadata_error(
-        "chown is not supported via std::fs; enable a libc-based path if required",
-    ))
+pub async fn fchownat(path: &Path, uid: u32, gid: u32) -> Result<()> {
+    submit_fchownat(libc::AT_FDCWD, path, uid, gid, FollowSymlinks::Follow).await
+}
+
+/// Change the ownership of a symlink itself rather than its target (`lchown`)
+///
+/// # Arguments
+///
+/// * `path` - Path to the symlink (other file types are changed as usual)
+/// * `uid` - New user ID (use `u32::MAX` to not change)
+/// * `gid` - New group ID (use `u32::MAX` to not change)
+///
+/// # Errors
+///
+/// Failures are returned as [`ExtendedError::Io`], e.g. `EPERM` when
+/// lacking `CAP_CHOWN`.
+pub async fn lchown(path: &Path, uid: u32, gid: u32) -> Result<()> {
+    submit_fchownat(libc::AT_FDCWD, path, uid, gid, FollowSymlinks::NoFollow).await
+}
+
+/// Whether an `*at` metadata operation acts on a final symlink or its target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FollowSymlinks {
+    /// Act on the file a final symlink points to
+    #[default]
+    Follow,
+    /// Act on a final symlink itself (`AT_SYMLINK_NOFOLLOW`)
+    NoFollow,
+}
+
+impl FollowSymlinks {
+    /// Flags for the `*at` system calls
+    #[must_use]
+    pub const fn flags(self) -> i32 {
+        match self {
+            Self::Follow => 0,
+            Self::NoFollow => libc::AT_SYMLINK_NOFOLLOW,
+        }
+    }
+}
+
+/// Change the ownership of `name` in `dir`
+///
+/// With [`FollowSymlinks::NoFollow`] a symlink's own ownership is changed,
+/// which is what preserving the owner of a copied symlink needs.
+///
+/// # Arguments
+///
+/// * `dir` - Directory holding the entry
+/// * `name` - Name of the entry, relative to `dir`
+/// * `uid` - New user ID (use `u32::MAX` to not change)
+/// * `gid` - New group ID (use `u32::MAX` to not change)
+/// * `follow` - Whether a final symlink is followed
+///
+/// # Errors
+///
+/// Failures are returned as [`ExtendedError::Io`], e.g. `NotFound`, or
+/// `EPERM` when lacking `CAP_CHOWN`.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use compio_fs_extended::directory::DirectoryFd;
+/// use compio_fs_extended::metadata::{chown_at, FollowSymlinks};
+/// use std::path::Path;
+///
+/// # async fn example() -> compio_fs_extended::Result<()> {
+/// let dir = DirectoryFd::open(Path::new("/backup")).await?;
+/// chown_at(&dir, Path::new("current"), 1000, 1000, FollowSymlinks::NoFollow).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn chown_at(
+    dir: &DirectoryFd,
+    name: &Path,
+    uid: u32,
+    gid: u32,
+    follow: FollowSymlinks,
+) -> Result<()> {
+    submit_fchownat(dir.as_raw_fd(), name, uid, gid, follow).await
+}
+
+/// Change the permissions of `name` in `dir`
+///
+/// Linux symlinks have no permissions of their own, so with
+/// [`FollowSymlinks::NoFollow`] a symlink fails with `EOPNOTSUPP` while any
+/// other file type is changed as usual.
+///
+/// # Errors
+///
+/// Failures are returned as [`ExtendedError::Io`], e.g. `NotFound`.
+pub async fn chmod_at(
+    dir: &DirectoryFd,
+    name: &Path,
+    mode: u32,
+    follow: FollowSymlinks,
+) -> Result<()> {
+    let dirfd = dir.as_raw_fd();
+    let name = crate::directory::path_cstring(name)?;
+    compio::runtime::spawn_blocking(move || {
+        // SAFETY: name is NUL-terminated and the caller's borrow keeps dirfd
+        // open until this task has been awaited; glibc emulates
+        // AT_SYMLINK_NOFOLLOW on kernels without fchmodat2
+        if unsafe { libc::fchmodat(dirfd, name.as_ptr(), mode, follow.flags()) } == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error().into())
+        }
+    })
+    .await
+    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+/// Run `fchownat(2)` for `pathname` relative to `dirfd` on a blocking thread
+async fn submit_fchownat(
+    dirfd: i32,
+    pathname: &Path,
+    uid: u32,
+    gid: u32,
+    follow: FollowSymlinks,
+) -> Result<()> {
+    let pathname = crate::directory::path_cstring(pathname)?;
+    compio::runtime::spawn_blocking(move || {
+        // SAFETY: pathname is NUL-terminated, and dirfd is AT_FDCWD or kept
+        // open by the caller until this task has been awaited
+        if unsafe { libc::fchownat(dirfd, pathname.as_ptr(), uid, gid, follow.flags()) } == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error().into())
+        }
+    })
+    .await
+    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
 }
 
 /// Change file permissions using file descriptor (more efficient)
@@ -481,13 +993,13 @@ pub async fn futimesat_with_dirfd(
 /// - Permission is denied
 /// - Invalid user/group IDs
 /// - The operation fails due to I/O errors
-pub async fn fchownat_with_dirfd(
-    _dir_fd: i32,
-    _pathname: &str,
-    _uid: u32,
-    _gid: u32,
-) -> Result<()> {
-    Err(metadata_error(
-        "chown is not supported via std::fs; enable a libc-based path if required",
-    ))
+pub async fn fchownat_with_dirfd(dir_fd: i32, pathname: &str, uid: u32, gid: u32) -> Result<()> {
+    submit_fchownat(
+        dir_fd,
+        Path::new(pathname),
+        uid,
+        gid,
+        FollowSymlinks::Follow,
+    )
+    .await
 }
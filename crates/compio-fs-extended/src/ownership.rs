@@ -173,7 +173,7 @@
 
 use crate::error::{filesystem_error, Result};
 use compio::fs::File;
-use std::os::fd::AsRawFd;
+use std::os::fd::{AsRawFd, RawFd};
 use std::os::unix::fs::MetadataExt;
 use std::path::Path;
 
@@ -522,6 +522,128 @@ impl OwnershipOps for File {
     }
 }
 
+/// Change ownership of an open file via `fchownat(fd, "", uid, gid, AT_EMPTY_PATH)`
+///
+/// Unlike `fchown`, this works on `O_PATH` descriptors, so it can change the
+/// ownership of FIFOs, sockets and device nodes without opening them for I/O.
+/// Passing `u32::MAX` for either id leaves that id unchanged.
+///
+/// # Arguments
+///
+/// * `fd` - File descriptor (may be opened with `O_PATH`)
+/// * `uid` - New owner, or `u32::MAX` to keep the current one
+/// * `gid` - New group, or `u32::MAX` to keep the current one
+///
+/// # Errors
+///
+/// Returns [`ExtendedError::Io`](crate::ExtendedError::Io) carrying the OS
+/// error (e.g. `EPERM` when lacking `CAP_CHOWN`) so callers can inspect
+/// `raw_os_error()`.
+pub async fn fchown_empty_path(fd: RawFd, uid: u32, gid: u32) -> Result<()> {
+    compio::runtime::spawn(async move {
+        // SAFETY: the path is a valid NUL-terminated empty string and the
+        // caller guarantees `fd` is open for the duration of the call.
+        let ret = unsafe { libc::fchownat(fd, c"".as_ptr(), uid, gid, libc::AT_EMPTY_PATH) };
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error().into())
+        }
+    })
+    .await
+    .map_err(|e| filesystem_error(&format!("spawn failed: {e:?}")))?
+}
+
+/// Look up the user name for a numeric user ID
+///
+/// Uses the system user database (`/etc/passwd` or any configured NSS source).
+///
+/// # Returns
+///
+/// `Ok(Some(name))` if the user exists, `Ok(None)` if it does not.
+///
+/// # Errors
+///
+/// This function will return an error if the user database lookup fails.
+pub async fn user_name_for_uid(uid: u32) -> Result<Option<String>> {
+    use nix::unistd::{Uid, User};
+
+    compio::runtime::spawn(async move {
+        User::from_uid(Uid::from_raw(uid))
+            .map(|user| user.map(|u| u.name))
+            .map_err(|e| filesystem_error(&format!("getpwuid_r failed: {}", e)))
+    })
+    .await
+    .map_err(|e| filesystem_error(&format!("spawn failed: {e:?}")))?
+}
+
+/// Look up the numeric user ID for a user name
+///
+/// # Returns
+///
+/// `Ok(Some(uid))` if the user exists, `Ok(None)` if it does not.
+///
+/// # Errors
+///
+/// This function will return an error if the user database lookup fails.
+pub async fn uid_for_user_name(name: &str) -> Result<Option<u32>> {
+    use nix::unistd::User;
+
+    let name = name.to_string();
+    compio::runtime::spawn(async move {
+        User::from_name(&name)
+            .map(|user| user.map(|u| u.uid.as_raw()))
+            .map_err(|e| filesystem_error(&format!("getpwnam_r failed: {}", e)))
+    })
+    .await
+    .map_err(|e| filesystem_error(&format!("spawn failed: {e:?}")))?
+}
+
+/// Look up the group name for a numeric group ID
+///
+/// Uses the system group database (`/etc/group` or any configured NSS source).
+///
+/// # Returns
+///
+/// `Ok(Some(name))` if the group exists, `Ok(None)` if it does not.
+///
+/// # Errors
+///
+/// This function will return an error if the group database lookup fails.
+pub async fn group_name_for_gid(gid: u32) -> Result<Option<String>> {
+    use nix::unistd::{Gid, Group};
+
+    compio::runtime::spawn(async move {
+        Group::from_gid(Gid::from_raw(gid))
+            .map(|group| group.map(|g| g.name))
+            .map_err(|e| filesystem_error(&format!("getgrgid_r failed: {}", e)))
+    })
+    .await
+    .map_err(|e| filesystem_error(&format!("spawn failed: {e:?}")))?
+}
+
+/// Look up the numeric group ID for a group name
+///
+/// # Returns
+///
+/// `Ok(Some(gid))` if the group exists, `Ok(None)` if it does not.
+///
+/// # Errors
+///
+/// This function will return an error if the group database lookup fails.
+pub async fn gid_for_group_name(name: &str) -> Result<Option<u32>> {
+    use nix::unistd::Group;
+
+    let name = name.to_string();
+    compio::runtime::spawn(async move {
+        Group::from_name(&name)
+            .map(|group| group.map(|g| g.gid.as_raw()))
+            .map_err(|e| filesystem_error(&format!("getgrnam_r failed: {}", e)))
+    })
+    .await
+    .map_err(|e| filesystem_error(&format!("spawn failed: {e:?}")))?
+}
+
 #[cfg(test)]
 mod tests {
     //! Comprehensive test suite for file ownership operations
@@ -699,4 +821,47 @@ mod tests {
             }
         }
     }
+
+    /// Test user and group name lookups round-trip for root (uid/gid 0)
+    #[compio::test]
+    async fn test_name_lookups_root() {
+        let user = user_name_for_uid(0).await.unwrap();
+        assert_eq!(user.as_deref(), Some("root"));
+        assert_eq!(uid_for_user_name("root").await.unwrap(), Some(0));
+
+        let group = group_name_for_gid(0).await.unwrap();
+        assert!(group.is_some());
+        assert_eq!(
+            gid_for_group_name(group.as_deref().unwrap()).await.unwrap(),
+            Some(0)
+        );
+
+        assert_eq!(
+            uid_for_user_name("arsync-no-such-user").await.unwrap(),
+            None
+        );
+    }
+
+    /// Test fchownat with AT_EMPTY_PATH on an O_PATH descriptor (no-op chown)
+    #[compio::test]
+    async fn test_fchown_empty_path_o_path() {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test_file.txt");
+        fs::write(&file_path, "test content").unwrap();
+        let metadata = fs::metadata(&file_path).unwrap();
+
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_PATH)
+            .open(&file_path)
+            .unwrap();
+
+        // Changing to the current owner is always permitted
+        fchown_empty_path(file.as_raw_fd(), metadata.uid(), u32::MAX)
+            .await
+            .unwrap();
+        assert_eq!(fs::metadata(&file_path).unwrap().uid(), metadata.uid());
+    }
 }
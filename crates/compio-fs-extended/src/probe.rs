@@ -0,0 +1,588 @@
+//! io_uring feature probing (`IORING_REGISTER_PROBE`)
+//!
+//! Which opcodes and setup flags io_uring supports depends on the kernel
+//! version and configuration. [`capabilities`] asks the kernel once per
+//! process, so callers can choose an approach up front (e.g. skip
+//! preallocation when `IORING_OP_FALLOCATE` is missing) instead of
+//! discovering the gap as an `EINVAL` in the middle of a copy.
+//!
+//! # Example
+//!
+//! ```rust
+//! use compio_fs_extended::probe::{capabilities, SetupFlag};
+//! use io_uring::opcode;
+//!
+//! let caps = capabilities();
+//! if !caps.supports(opcode::Fallocate::CODE) {
+//!     println!("no io_uring fallocate: {caps}");
+//! }
+//! let _single_issuer = caps.supports_setup(SetupFlag::SingleIssuer);
+//! ```
+//!
+//! # Fallbacks
+//!
+//! Operations whose opcode the kernel lacks are made as the equivalent
+//! blocking syscall on compio's thread pool instead (see [`Fallback`]), so
+//! older kernels get the same results, only with a thread hop. The
+//! capabilities' `Display` output is a one-line summary of that plan.
+//!
+//! # Ring setup
+//!
+//! [`IoUringCapabilities::proactor_builder`] configures compio's rings and
+//! [`IoUringCapabilities::ring_builder`] the crate's own, each with the setup
+//! flags that cut completion overhead where the kernel has them:
+//! `COOP_TASKRUN` skips the interrupt that would otherwise run completion
+//! work as soon as it is queued, and `SINGLE_ISSUER` lets the kernel drop
+//! locking for a ring only one thread submits to. The crate's own rings can
+//! also use 128-byte SQEs and 32-byte CQEs (Linux 5.19) by choosing the
+//! extended entry types.
+//!
+//! compio 0.16's `ProactorBuilder` has no option for `SINGLE_ISSUER` (or
+//! `DEFER_TASKRUN`), so only the crate's own rings get it; the compio rings
+//! that do the actual copying run with `COOP_TASKRUN` alone. The flags are
+//! set because the kernel documents them as cheaper. No reduction in
+//! completion overhead has been measured for arsync's copies, and the copy
+//! rings will not get `SINGLE_ISSUER` or `DEFER_TASKRUN` until compio can
+//! request them.
+
+use compio::driver::{OpEntry, ProactorBuilder};
+use io_uring::{cqueue, opcode, squeue, Builder, IoUring, Probe};
+use std::fmt;
+use std::io;
+use std::sync::OnceLock;
+
+/// What the crate does when the kernel lacks an opcode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fallback {
+    /// The equivalent syscall is made on compio's blocking thread pool
+    Syscall,
+    /// The named feature, which has no syscall equivalent, is not available
+    Disables(&'static str),
+}
+
+/// Opcodes used by this crate, with the names used in diagnostics and what
+/// replaces each one on kernels without it
+pub const KNOWN_OPCODES: &[(u8, &str, Fallback)] = &[
+    (opcode::Read::CODE, "read", Fallback::Syscall),
+    (opcode::Write::CODE, "write", Fallback::Syscall),
+    (
+        opcode::ReadFixed::CODE,
+        "read_fixed",
+        Fallback::Disables("registered buffers"),
+    ),
+    (
+        opcode::WriteFixed::CODE,
+        "write_fixed",
+        Fallback::Disables("registered buffers"),
+    ),
+    (
+        opcode::PollAdd::CODE,
+        "poll_add",
+        Fallback::Disables("linked chains"),
+    ),
+    (opcode::OpenAt::CODE, "openat", Fallback::Syscall),
+    (
+        opcode::Close::CODE,
+        "close",
+        Fallback::Disables("linked chains"),
+    ),
+    (opcode::Statx::CODE, "statx", Fallback::Syscall),
+    (opcode::Fallocate::CODE, "fallocate", Fallback::Syscall),
+    (opcode::Fadvise::CODE, "fadvise", Fallback::Syscall),
+    (opcode::RenameAt::CODE, "renameat", Fallback::Syscall),
+    (opcode::UnlinkAt::CODE, "unlinkat", Fallback::Syscall),
+    (opcode::MkDirAt::CODE, "mkdirat", Fallback::Syscall),
+    (opcode::SymlinkAt::CODE, "symlinkat", Fallback::Syscall),
+    (opcode::LinkAt::CODE, "linkat", Fallback::Syscall),
+    (opcode::FGetXattr::CODE, "fgetxattr", Fallback::Syscall),
+    (opcode::FSetXattr::CODE, "fsetxattr", Fallback::Syscall),
+    (opcode::Ftruncate::CODE, "ftruncate", Fallback::Syscall),
+];
+
+/// Most submission queue entries a ring can have (`IORING_MAX_ENTRIES`)
+pub const MAX_RING_ENTRIES: u32 = 32_768;
+
+/// Submission entries a ring asked for `requested` is set up with
+///
+/// The count is clamped to `1..=`[`MAX_RING_ENTRIES`], which the kernel
+/// would otherwise reject with `EINVAL`.
+#[must_use]
+pub fn ring_entries(requested: u32) -> u32 {
+    requested.clamp(1, MAX_RING_ENTRIES)
+}
+
+/// Ring setup flags that can be probed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetupFlag {
+    /// `IORING_SETUP_SINGLE_ISSUER` (Linux 6.0)
+    SingleIssuer,
+    /// `IORING_SETUP_COOP_TASKRUN` (Linux 5.19)
+    CoopTaskrun,
+    /// `IORING_SETUP_DEFER_TASKRUN` (Linux 6.1), which implies single issuer
+    DeferTaskrun,
+    /// `IORING_SETUP_SQE128` (Linux 5.19)
+    Sqe128,
+    /// `IORING_SETUP_CQE32` (Linux 5.19)
+    Cqe32,
+}
+
+impl SetupFlag {
+    /// Every probed flag
+    pub const ALL: [Self; 5] = [
+        Self::SingleIssuer,
+        Self::CoopTaskrun,
+        Self::DeferTaskrun,
+        Self::Sqe128,
+        Self::Cqe32,
+    ];
+
+    /// Name used in diagnostics
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::SingleIssuer => "single_issuer",
+            Self::CoopTaskrun => "coop_taskrun",
+            Self::DeferTaskrun => "defer_taskrun",
+            Self::Sqe128 => "sqe128",
+            Self::Cqe32 => "cqe32",
+        }
+    }
+
+    /// Whether a ring can be set up with this flag
+    fn probe(self) -> bool {
+        let ring = match self {
+            Self::SingleIssuer => IoUring::<squeue::Entry, cqueue::Entry>::builder()
+                .setup_single_issuer()
+                .build(2)
+                .map(drop),
+            Self::CoopTaskrun => IoUring::<squeue::Entry, cqueue::Entry>::builder()
+                .setup_coop_taskrun()
+                .build(2)
+                .map(drop),
+            Self::DeferTaskrun => IoUring::<squeue::Entry, cqueue::Entry>::builder()
+                .setup_single_issuer()
+                .setup_defer_taskrun()
+                .build(2)
+                .map(drop),
+            Self::Sqe128 => IoUring::<squeue::Entry128, cqueue::Entry>::builder()
+                .build(2)
+                .map(drop),
+            Self::Cqe32 => IoUring::<squeue::Entry, cqueue::Entry32>::builder()
+                .build(2)
+                .map(drop),
+        };
+        ring.is_ok()
+    }
+}
+
+/// What the running kernel's io_uring supports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IoUringCapabilities {
+    /// Why io_uring cannot be used at all, if it cannot
+    unavailable: Option<String>,
+    /// Supported opcodes, one bit per opcode
+    opcodes: [u64; 4],
+    /// Supported setup flags
+    setup: Vec<SetupFlag>,
+    /// Whether ring memory is charged to `RLIMIT_MEMLOCK`
+    locked_rings: bool,
+}
+
+impl IoUringCapabilities {
+    /// Capabilities of a kernel without io_uring: nothing is supported
+    #[must_use]
+    pub fn none(reason: &str) -> Self {
+        Self {
+            unavailable: Some(reason.to_string()),
+            opcodes: [0; 4],
+            setup: Vec::new(),
+            locked_rings: false,
+        }
+    }
+
+    /// Capabilities with exactly the given opcodes and setup flags
+    #[must_use]
+    pub fn with(opcodes: &[u8], setup: &[SetupFlag]) -> Self {
+        let mut caps = Self {
+            unavailable: None,
+            opcodes: [0; 4],
+            setup: setup.to_vec(),
+            locked_rings: false,
+        };
+        for &code in opcodes {
+            caps.opcodes[usize::from(code / 64)] |= 1 << (code % 64);
+        }
+        caps
+    }
+
+    /// Whether io_uring can be used at all
+    #[must_use]
+    pub const fn is_available(&self) -> bool {
+        self.unavailable.is_none()
+    }
+
+    /// Whether the kernel supports opcode `code`, e.g. `opcode::Statx::CODE`
+    #[must_use]
+    pub const fn supports(&self, code: u8) -> bool {
+        self.opcodes[(code / 64) as usize] & (1 << (code % 64)) != 0
+    }
+
+    /// Whether a ring can be set up with `flag`
+    #[must_use]
+    pub fn supports_setup(&self, flag: SetupFlag) -> bool {
+        self.setup.contains(&flag)
+    }
+
+    /// Whether ring memory counts against `RLIMIT_MEMLOCK`
+    ///
+    /// Before Linux 5.12 the kernel charged the rings themselves to the
+    /// locked-memory limit; since then only registered buffers are.
+    #[must_use]
+    pub const fn rings_count_against_memlock(&self) -> bool {
+        self.locked_rings
+    }
+
+    /// Whether `IORING_OP_READ_FIXED` and `IORING_OP_WRITE_FIXED` are usable,
+    /// as [`BufferPool`](crate::buffer_pool::BufferPool) needs
+    #[must_use]
+    pub const fn supports_fixed_buffers(&self) -> bool {
+        self.supports(opcode::ReadFixed::CODE) && self.supports(opcode::WriteFixed::CODE)
+    }
+
+    /// Whether `IORING_OP_FALLOCATE` is usable
+    #[must_use]
+    pub const fn supports_fallocate(&self) -> bool {
+        self.supports(opcode::Fallocate::CODE)
+    }
+
+    /// Whether `IORING_OP_FADVISE` is usable
+    #[must_use]
+    pub const fn supports_fadvise(&self) -> bool {
+        self.supports(opcode::Fadvise::CODE)
+    }
+
+    /// Builder for a compio ring of `entries` submission entries
+    ///
+    /// `entries` is clamped by [`ring_entries`]. `COOP_TASKRUN` is set
+    /// when the kernel has it; compio enters the ring whenever it waits, so
+    /// deferring completion work to that point costs no latency.
+    /// `SINGLE_ISSUER` is not set: `ProactorBuilder` cannot request it.
+    #[must_use]
+    pub fn proactor_builder(&self, entries: u32) -> ProactorBuilder {
+        let mut builder = ProactorBuilder::new();
+        builder.capacity(ring_entries(entries));
+        if self.supports_setup(SetupFlag::CoopTaskrun) {
+            builder.coop_taskrun(true).taskrun_flag(true);
+        }
+        builder
+    }
+
+    /// Builder for a ring only the calling thread will submit to
+    ///
+    /// Sets `SINGLE_ISSUER` when the kernel has it, and `SQE128`/`CQE32` when
+    /// `S`/`C` are the extended entry types. The ring must not be shared with
+    /// or submitted from another thread.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use compio_fs_extended::probe::{capabilities, SetupFlag};
+    /// use io_uring::{cqueue, squeue};
+    ///
+    /// let caps = capabilities();
+    /// if caps.supports_setup(SetupFlag::Sqe128) && caps.supports_setup(SetupFlag::Cqe32) {
+    ///     let ring = caps
+    ///         .ring_builder::<squeue::Entry128, cqueue::Entry32>()
+    ///         .build(8)
+    ///         .unwrap();
+    ///     assert_eq!(ring.params().sq_entries(), 8);
+    /// }
+    /// ```
+    #[must_use]
+    pub fn ring_builder<S: squeue::EntryMarker, C: cqueue::EntryMarker>(&self) -> Builder<S, C> {
+        let mut builder = IoUring::<S, C>::builder();
+        if self.supports_setup(SetupFlag::SingleIssuer) {
+            builder.setup_single_issuer();
+        }
+        builder
+    }
+
+    /// Names of the [`KNOWN_OPCODES`] the kernel lacks
+    #[must_use]
+    pub fn missing_opcodes(&self) -> Vec<&'static str> {
+        self.fallbacks().into_iter().map(|(name, _)| name).collect()
+    }
+
+    /// The [`KNOWN_OPCODES`] the kernel lacks, with what replaces each
+    #[must_use]
+    pub fn fallbacks(&self) -> Vec<(&'static str, Fallback)> {
+        KNOWN_OPCODES
+            .iter()
+            .filter(|(code, _, _)| !self.supports(*code))
+            .map(|&(_, name, fallback)| (name, fallback))
+            .collect()
+    }
+}
+
+impl fmt::Display for IoUringCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(reason) = &self.unavailable {
+            return write!(f, "io_uring unavailable ({reason})");
+        }
+        let fallbacks = self.fallbacks();
+        if fallbacks.is_empty() {
+            write!(f, "all {} opcodes in use supported", KNOWN_OPCODES.len())?;
+        } else {
+            let syscalls: Vec<_> = fallbacks
+                .iter()
+                .filter(|(_, fallback)| *fallback == Fallback::Syscall)
+                .map(|(name, _)| *name)
+                .collect();
+            let mut disabled: Vec<_> = fallbacks
+                .iter()
+                .filter_map(|(_, fallback)| match fallback {
+                    Fallback::Disables(feature) => Some(*feature),
+                    Fallback::Syscall => None,
+                })
+                .collect();
+            disabled.dedup();
+            write!(f, "missing opcodes: {}", self.missing_opcodes().join(", "))?;
+            if !syscalls.is_empty() {
+                write!(f, "; blocking syscalls for {}", syscalls.join(", "))?;
+            }
+            if !disabled.is_empty() {
+                write!(f, "; without {}", disabled.join(", "))?;
+            }
+        }
+        let setup: Vec<_> = self.setup.iter().map(|flag| flag.name()).collect();
+        if setup.is_empty() {
+            write!(f, "; no optional setup flags")
+        } else {
+            write!(f, "; setup flags: {}", setup.join(", "))
+        }
+    }
+}
+
+/// Submission for opcode `code`, or a blocking call where the kernel lacks it
+///
+/// Operations return this from `create_entry` and implement `call_blocking`
+/// with the equivalent syscall, which compio then runs on its thread pool.
+pub(crate) fn entry_or_blocking(code: u8, entry: impl FnOnce() -> squeue::Entry) -> OpEntry {
+    #[cfg(test)]
+    if tests::FORCE_SYSCALLS.get() {
+        return OpEntry::Blocking;
+    }
+    if capabilities().supports(code) {
+        OpEntry::Submission(entry())
+    } else {
+        OpEntry::Blocking
+    }
+}
+
+/// Result of a syscall that returns a count, or -1 with `errno` set
+pub(crate) fn syscall_result<T: TryInto<usize>>(ret: T) -> io::Result<usize> {
+    ret.try_into().map_err(|_| io::Error::last_os_error())
+}
+
+/// Query the kernel for its io_uring capabilities
+///
+/// Sets up a few tiny rings, so prefer the cached [`capabilities`].
+#[must_use]
+pub fn probe() -> IoUringCapabilities {
+    let ring = match IoUring::new(2) {
+        Ok(ring) => ring,
+        Err(e) => return IoUringCapabilities::none(&e.to_string()),
+    };
+    let mut probe = Probe::new();
+    if let Err(e) = ring.submitter().register_probe(&mut probe) {
+        // Probing arrived in Linux 5.6; assume only the basics before that
+        log::debug!("io_uring probe unavailable: {e}");
+        let mut caps = IoUringCapabilities::with(&[opcode::Read::CODE, opcode::Write::CODE], &[]);
+        caps.locked_rings = true;
+        return caps;
+    }
+    let opcodes: Vec<u8> = (0..=u8::MAX)
+        .filter(|&code| probe.is_supported(code))
+        .collect();
+    let setup: Vec<SetupFlag> = SetupFlag::ALL
+        .into_iter()
+        .filter(|flag| flag.probe())
+        .collect();
+    let mut caps = IoUringCapabilities::with(&opcodes, &setup);
+    // Native io workers and memcg ring accounting both arrived in 5.12
+    caps.locked_rings = !ring.params().is_feature_native_workers();
+    caps
+}
+
+/// The io_uring capabilities of this kernel, probed on first use
+#[must_use]
+pub fn capabilities() -> &'static IoUringCapabilities {
+    /// Result of the one probe per process
+    static CAPABILITIES: OnceLock<IoUringCapabilities> = OnceLock::new();
+    CAPABILITIES.get_or_init(probe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::directory::DirectoryFd;
+    use crate::ioprio::{self, IoPriority};
+    use std::cell::Cell;
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    thread_local! {
+        /// Make every operation on this thread take its syscall fallback
+        pub(super) static FORCE_SYSCALLS: Cell<bool> = const { Cell::new(false) };
+    }
+
+    #[test]
+    fn test_probe_finds_basic_opcodes() {
+        let caps = capabilities();
+        // The test suite runs on io_uring, so the basics are always there
+        assert!(caps.is_available(), "{caps}");
+        assert!(caps.supports(opcode::Read::CODE));
+        assert!(caps.supports(opcode::Write::CODE));
+        assert!(!caps.missing_opcodes().contains(&"read"));
+    }
+
+    #[test]
+    fn test_capabilities_with() {
+        let caps = IoUringCapabilities::with(
+            &[opcode::Read::CODE, opcode::Statx::CODE, 200],
+            &[SetupFlag::Cqe32],
+        );
+        assert!(caps.supports(opcode::Statx::CODE));
+        assert!(caps.supports(200));
+        assert!(!caps.supports(opcode::Fallocate::CODE));
+        assert!(caps.supports_setup(SetupFlag::Cqe32));
+        assert!(!caps.supports_setup(SetupFlag::Sqe128));
+        assert!(caps.missing_opcodes().contains(&"fallocate"));
+        assert!(!caps.supports_fallocate());
+        assert!(!caps.supports_fixed_buffers());
+        assert!(caps.to_string().contains("setup flags: cqe32"));
+        assert!(caps
+            .fallbacks()
+            .contains(&("read_fixed", Fallback::Disables("registered buffers"))));
+        let summary = caps.to_string();
+        assert!(
+            summary.contains("blocking syscalls for write, openat"),
+            "{summary}"
+        );
+        assert!(
+            summary.contains("without registered buffers, linked chains"),
+            "{summary}"
+        );
+
+        let none = IoUringCapabilities::none("disabled");
+        assert!(!none.is_available());
+        assert!(!none.supports(opcode::Read::CODE));
+        assert_eq!(none.to_string(), "io_uring unavailable (disabled)");
+    }
+
+    #[compio::test]
+    async fn test_syscall_fallbacks() {
+        FORCE_SYSCALLS.set(true);
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let dir = DirectoryFd::open(root).await.unwrap();
+
+        dir.create_directory("sub", 0o755).await.unwrap();
+        assert!(root.join("sub").is_dir());
+
+        let file = crate::directory::submit_open_with_mode(
+            dir.as_raw_fd(),
+            Path::new("file"),
+            libc::O_CREAT | libc::O_RDWR | libc::O_CLOEXEC,
+            0o644,
+        )
+        .await
+        .unwrap();
+        let (written, _) = ioprio::write_at(&file, b"fallback".to_vec(), 0, IoPriority::idle())
+            .await
+            .into();
+        assert_eq!(written.unwrap(), 8);
+        let (read, buf) = ioprio::read_at(&file, vec![0; 4], 4, IoPriority::idle())
+            .await
+            .into();
+        assert_eq!(read.unwrap(), 4);
+        assert_eq!(buf, b"back");
+
+        crate::fallocate::preallocate(&file, 4096).await.unwrap();
+        crate::truncate::ftruncate(&file, 3).await.unwrap();
+        crate::fadvise::fadvise(&file, crate::fadvise::FadviseAdvice::Sequential, 0, 0)
+            .await
+            .unwrap();
+        let stat = crate::metadata::statx_path(&root.join("file"), libc::STATX_BASIC_STATS)
+            .await
+            .unwrap();
+        assert_eq!(stat.metadata().len(), 3);
+
+        match crate::xattr::set_xattr_impl(&file, "user.fallback", b"yes").await {
+            Ok(()) => assert_eq!(
+                crate::xattr::get_xattr_impl(&file, "user.fallback")
+                    .await
+                    .unwrap(),
+                b"yes"
+            ),
+            // Not every filesystem takes user xattrs
+            Err(e) => assert!(e.to_string().contains("not supported"), "{e}"),
+        }
+
+        crate::symlink::create_symlink_at_dirfd(&dir, "file", "link")
+            .await
+            .unwrap();
+        assert_eq!(fs::read_link(root.join("link")).unwrap(), Path::new("file"));
+        crate::hardlink::create_hardlink_at_path(&root.join("file"), &root.join("hard"))
+            .await
+            .unwrap();
+        crate::rename::rename_at(
+            &dir,
+            Path::new("hard"),
+            &dir,
+            Path::new("renamed"),
+            crate::rename::RenameMode::NoReplace,
+        )
+        .await
+        .unwrap();
+        crate::unlink::unlink_at(&dir, Path::new("renamed"))
+            .await
+            .unwrap();
+        assert!(!root.join("renamed").exists());
+        assert_eq!(fs::read(root.join("file")).unwrap(), b"fal");
+        FORCE_SYSCALLS.set(false);
+    }
+
+    #[test]
+    fn test_ring_builders() {
+        let caps = capabilities();
+        let ring = caps
+            .ring_builder::<squeue::Entry, cqueue::Entry>()
+            .build(8)
+            .unwrap();
+        assert_eq!(ring.params().sq_entries(), 8);
+
+        if caps.supports_setup(SetupFlag::Sqe128) && caps.supports_setup(SetupFlag::Cqe32) {
+            let ring = caps
+                .ring_builder::<squeue::Entry128, cqueue::Entry32>()
+                .build(8)
+                .unwrap();
+            assert_eq!(ring.params().sq_entries(), 8);
+        }
+
+        // Oversized requests are clamped rather than rejected by the kernel
+        assert_eq!(ring_entries(u32::MAX), MAX_RING_ENTRIES);
+        assert_eq!(ring_entries(0), 1);
+        assert_eq!(ring_entries(256), 256);
+
+        let runtime = compio::runtime::RuntimeBuilder::new()
+            .with_proactor(caps.proactor_builder(8))
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let file = compio::fs::File::open("/proc/self/stat").await.unwrap();
+            assert!(file.metadata().await.unwrap().is_file());
+        });
+    }
+}
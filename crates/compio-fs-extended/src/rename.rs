@@ -0,0 +1,268 @@
+//! Rename operations using io_uring (`renameat2`)
+//!
+//! compio's own `rename` always replaces the destination. `renameat2(2)` adds
+//! two modes that cannot be emulated safely from userspace:
+//!
+//! - [`RenameMode::NoReplace`] fails with `EEXIST` instead of replacing an
+//!   existing destination, so promoting a temp file cannot clobber a file
+//!   created concurrently
+//! - [`RenameMode::Exchange`] atomically swaps two existing paths, so the old
+//!   version stays available under the other name
+//!
+//! Failures are returned as [`ExtendedError::Io`](crate::ExtendedError::Io)
+//! so callers can match on the `ErrorKind` (e.g. `AlreadyExists`).
+
+use crate::directory::{path_cstring, DirectoryFd};
+use crate::error::Result;
+use crate::probe::{entry_or_blocking, syscall_result};
+use compio::driver::OpCode;
+use compio::runtime::submit;
+use io_uring::{opcode, types};
+use std::ffi::CString;
+use std::path::Path;
+use std::pin::Pin;
+
+/// How a rename treats an existing destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenameMode {
+    /// Replace the destination if it exists (plain `rename(2)`)
+    #[default]
+    Replace,
+    /// Fail with `EEXIST` if the destination exists (`RENAME_NOREPLACE`)
+    NoReplace,
+    /// Atomically swap source and destination, which must both exist
+    /// (`RENAME_EXCHANGE`)
+    Exchange,
+}
+
+impl RenameMode {
+    /// Flags for `renameat2(2)`
+    #[must_use]
+    pub const fn flags(self) -> u32 {
+        match self {
+            Self::Replace => 0,
+            Self::NoReplace => libc::RENAME_NOREPLACE,
+            Self::Exchange => libc::RENAME_EXCHANGE,
+        }
+    }
+}
+
+/// io_uring renameat2 operation
+struct RenameOp {
+    /// Directory `oldpath` is relative to
+    olddirfd: i32,
+    /// Path to rename
+    oldpath: CString,
+    /// Directory `newpath` is relative to
+    newdirfd: i32,
+    /// New name
+    newpath: CString,
+    /// `renameat2` flags
+    flags: u32,
+}
+
+impl OpCode for RenameOp {
+    fn create_entry(self: Pin<&mut Self>) -> compio::driver::OpEntry {
+        entry_or_blocking(opcode::RenameAt::CODE, || {
+            opcode::RenameAt::new(
+                types::Fd(self.olddirfd),
+                self.oldpath.as_ptr(),
+                types::Fd(self.newdirfd),
+                self.newpath.as_ptr(),
+            )
+            .flags(self.flags)
+            .build()
+        })
+    }
+
+    fn call_blocking(self: Pin<&mut Self>) -> std::io::Result<usize> {
+        // SAFETY: both paths are valid C strings
+        syscall_result(unsafe {
+            libc::renameat2(
+                self.olddirfd,
+                self.oldpath.as_ptr(),
+                self.newdirfd,
+                self.newpath.as_ptr(),
+                self.flags,
+            )
+        })
+    }
+}
+
+/// Submit a renameat2 between two directory descriptors
+async fn submit_rename(
+    olddirfd: i32,
+    oldpath: &Path,
+    newdirfd: i32,
+    newpath: &Path,
+    mode: RenameMode,
+) -> Result<()> {
+    let op = RenameOp {
+        olddirfd,
+        oldpath: path_cstring(oldpath)?,
+        newdirfd,
+        newpath: path_cstring(newpath)?,
+        flags: mode.flags(),
+    };
+    submit(op).await.0?;
+    Ok(())
+}
+
+/// Rename `from` to `to`
+///
+/// # Arguments
+///
+/// * `from` - Existing path
+/// * `to` - New path
+/// * `mode` - What to do if `to` exists
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - A path contains a NUL byte
+/// - `to` exists and `mode` is [`RenameMode::NoReplace`] (`AlreadyExists`)
+/// - Either path is missing and `mode` is [`RenameMode::Exchange`]
+/// - The paths are on different filesystems
+/// - The filesystem does not support the requested mode (`EINVAL`)
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use compio_fs_extended::rename::{rename, RenameMode};
+/// use std::path::Path;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// // Promote a temp file without overwriting anything
+/// rename(Path::new("file.tmp"), Path::new("file"), RenameMode::NoReplace).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn rename(from: &Path, to: &Path, mode: RenameMode) -> Result<()> {
+    submit_rename(libc::AT_FDCWD, from, libc::AT_FDCWD, to, mode).await
+}
+
+/// Rename `from_name` in `from_dir` to `to_name` in `to_dir`
+///
+/// Names are resolved relative to the directory descriptors, so the
+/// directories cannot be swapped out underneath the rename.
+///
+/// # Errors
+///
+/// Same as [`rename`].
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use compio_fs_extended::directory::DirectoryFd;
+/// use compio_fs_extended::rename::{rename_at, RenameMode};
+/// use std::path::Path;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let dir = DirectoryFd::open(Path::new("/some/directory")).await?;
+/// rename_at(&dir, Path::new("new"), &dir, Path::new("current"), RenameMode::Exchange).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn rename_at(
+    from_dir: &DirectoryFd,
+    from_name: &Path,
+    to_dir: &DirectoryFd,
+    to_name: &Path,
+    mode: RenameMode,
+) -> Result<()> {
+    submit_rename(
+        from_dir.as_raw_fd(),
+        from_name,
+        to_dir.as_raw_fd(),
+        to_name,
+        mode,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExtendedError;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[compio::test]
+    async fn test_rename_replace() {
+        let temp_dir = TempDir::new().unwrap();
+        let from = temp_dir.path().join("from");
+        let to = temp_dir.path().join("to");
+        fs::write(&from, "new").unwrap();
+        fs::write(&to, "old").unwrap();
+
+        rename(&from, &to, RenameMode::Replace).await.unwrap();
+
+        assert!(!from.exists());
+        assert_eq!(fs::read_to_string(&to).unwrap(), "new");
+    }
+
+    #[compio::test]
+    async fn test_rename_noreplace() {
+        let temp_dir = TempDir::new().unwrap();
+        let from = temp_dir.path().join("from");
+        let to = temp_dir.path().join("to");
+        fs::write(&from, "new").unwrap();
+        fs::write(&to, "old").unwrap();
+
+        let err = rename(&from, &to, RenameMode::NoReplace).await.unwrap_err();
+        assert!(
+            matches!(&err, ExtendedError::Io(e) if e.kind() == std::io::ErrorKind::AlreadyExists),
+            "unexpected error: {err}"
+        );
+        assert_eq!(fs::read_to_string(&to).unwrap(), "old");
+
+        fs::remove_file(&to).unwrap();
+        rename(&from, &to, RenameMode::NoReplace).await.unwrap();
+        assert_eq!(fs::read_to_string(&to).unwrap(), "new");
+    }
+
+    #[compio::test]
+    async fn test_rename_exchange() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = DirectoryFd::open(temp_dir.path()).await.unwrap();
+        fs::write(temp_dir.path().join("a"), "a").unwrap();
+        fs::write(temp_dir.path().join("b"), "b").unwrap();
+
+        match rename_at(
+            &dir,
+            Path::new("a"),
+            &dir,
+            Path::new("b"),
+            RenameMode::Exchange,
+        )
+        .await
+        {
+            Ok(()) => {
+                assert_eq!(fs::read_to_string(temp_dir.path().join("a")).unwrap(), "b");
+                assert_eq!(fs::read_to_string(temp_dir.path().join("b")).unwrap(), "a");
+            }
+            // Some filesystems (e.g. older overlayfs) do not support exchange
+            Err(ExtendedError::Io(e)) if e.raw_os_error() == Some(libc::EINVAL) => {}
+            Err(e) => panic!("exchange failed: {e}"),
+        }
+
+        // Exchange requires both paths to exist
+        assert!(rename_at(
+            &dir,
+            Path::new("a"),
+            &dir,
+            Path::new("missing"),
+            RenameMode::Exchange
+        )
+        .await
+        .is_err());
+    }
+
+    #[compio::test]
+    async fn test_rename_invalid_path() {
+        let err = rename(Path::new("a\0b"), Path::new("c"), RenameMode::Replace)
+            .await
+            .unwrap_err();
+        assert!(err.is_invalid_parameters());
+    }
+}
@@ -1,6 +1,7 @@
 //! Symlink operations for creating and reading symbolic links
 
 use crate::error::{symlink_error, ExtendedError, Result};
+use crate::probe::{entry_or_blocking, syscall_result};
 use compio::driver::OpCode;
 use compio::fs::File;
 use compio::runtime::submit;
@@ -58,14 +59,25 @@ impl SymlinkOp {
 
 impl OpCode for SymlinkOp {
     fn create_entry(self: Pin<&mut Self>) -> compio::driver::OpEntry {
-        compio::driver::OpEntry::Submission(
+        entry_or_blocking(opcode::SymlinkAt::CODE, || {
             opcode::SymlinkAt::new(
                 types::Fd(self.dir_fd.unwrap_or(libc::AT_FDCWD)),
                 self.target.as_ptr(),
                 self.link_path.as_ptr(),
             )
-            .build(),
-        )
+            .build()
+        })
+    }
+
+    fn call_blocking(self: Pin<&mut Self>) -> std::io::Result<usize> {
+        // SAFETY: both paths are valid C strings
+        syscall_result(unsafe {
+            libc::symlinkat(
+                self.target.as_ptr(),
+                self.dir_fd.unwrap_or(libc::AT_FDCWD),
+                self.link_path.as_ptr(),
+            )
+        })
     }
 }
 
@@ -0,0 +1,213 @@
+//! Anonymous files using `O_TMPFILE` and `linkat(AT_EMPTY_PATH)`
+//!
+//! A file opened with `O_TMPFILE` has no name until it is linked into a
+//! directory. Writing the whole file first and linking it afterwards means a
+//! crash or error leaves nothing behind, and no reader ever sees a partial
+//! file or a temporary name.
+//!
+//! Linking by descriptor (`AT_EMPTY_PATH`) needs `CAP_DAC_READ_SEARCH` on
+//! many kernels; without it the file is linked through `/proc/self/fd`
+//! instead, which any process may do for its own descriptors.
+//!
+//! Not every filesystem supports `O_TMPFILE`; [`is_unsupported`] recognizes
+//! the errors that mean a caller should fall back to creating the file by
+//! name. Failures are returned as [`ExtendedError::Io`].
+
+use crate::directory::{path_cstring, submit_open_with_mode, DirectoryFd};
+use crate::error::{ExtendedError, Result};
+use crate::probe::{entry_or_blocking, syscall_result};
+use compio::driver::OpCode;
+use compio::fs::File;
+use compio::runtime::submit;
+use io_uring::{opcode, types};
+use std::ffi::CString;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::pin::Pin;
+
+/// io_uring linkat operation between two directory descriptors
+struct LinkAtOp {
+    /// Directory (or, with `AT_EMPTY_PATH`, file) `oldpath` is relative to
+    olddirfd: i32,
+    /// Existing path, empty with `AT_EMPTY_PATH`
+    oldpath: CString,
+    /// Directory `newpath` is relative to
+    newdirfd: i32,
+    /// Name of the new link
+    newpath: CString,
+    /// `linkat` flags
+    flags: i32,
+}
+
+impl OpCode for LinkAtOp {
+    fn create_entry(self: Pin<&mut Self>) -> compio::driver::OpEntry {
+        entry_or_blocking(opcode::LinkAt::CODE, || {
+            opcode::LinkAt::new(
+                types::Fd(self.olddirfd),
+                self.oldpath.as_ptr(),
+                types::Fd(self.newdirfd),
+                self.newpath.as_ptr(),
+            )
+            .flags(self.flags)
+            .build()
+        })
+    }
+
+    fn call_blocking(self: Pin<&mut Self>) -> std::io::Result<usize> {
+        // SAFETY: both paths are valid C strings
+        syscall_result(unsafe {
+            libc::linkat(
+                self.olddirfd,
+                self.oldpath.as_ptr(),
+                self.newdirfd,
+                self.newpath.as_ptr(),
+                self.flags,
+            )
+        })
+    }
+}
+
+/// Create an anonymous file in `dir`, open for reading and writing
+///
+/// `mode` is subject to the umask, as for any newly created file. The file
+/// disappears when closed unless it is linked with [`link_tmpfile`].
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The filesystem does not support `O_TMPFILE` (see [`is_unsupported`])
+/// - Permission to create files in `dir` is denied
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use compio::io::AsyncWriteAtExt;
+/// use compio_fs_extended::directory::DirectoryFd;
+/// use compio_fs_extended::tmpfile::{link_tmpfile, open_tmpfile};
+/// use std::path::Path;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let dir = DirectoryFd::open(Path::new("/some/directory")).await?;
+/// let mut file = open_tmpfile(&dir, 0o644).await?;
+/// file.write_all_at(b"complete contents", 0).await.0?;
+/// file.sync_all().await?;
+/// link_tmpfile(&file, &dir, Path::new("report.txt")).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn open_tmpfile(dir: &DirectoryFd, mode: u32) -> Result<File> {
+    submit_open_with_mode(
+        dir.as_raw_fd(),
+        Path::new("."),
+        libc::O_TMPFILE | libc::O_RDWR | libc::O_CLOEXEC,
+        mode,
+    )
+    .await
+}
+
+/// Give the anonymous `file` the name `name` in `dir`
+///
+/// The file must have been created by [`open_tmpfile`]. Like `link(2)`, this
+/// never replaces an existing entry.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - `name` already exists (`AlreadyExists`)
+/// - `dir` is on a different filesystem than `file` (`EXDEV`)
+/// - `name` contains a NUL byte
+pub async fn link_tmpfile(file: &File, dir: &DirectoryFd, name: &Path) -> Result<()> {
+    let op = LinkAtOp {
+        olddirfd: file.as_raw_fd(),
+        oldpath: CString::default(),
+        newdirfd: dir.as_raw_fd(),
+        newpath: path_cstring(name)?,
+        flags: libc::AT_EMPTY_PATH,
+    };
+    match submit(op).await.0 {
+        Ok(_) => Ok(()),
+        // Without CAP_DAC_READ_SEARCH the kernel refuses AT_EMPTY_PATH with
+        // ENOENT; the magic link in /proc resolves to the same inode
+        Err(e) if e.raw_os_error() == Some(libc::ENOENT) => {
+            let proc_path = format!("/proc/self/fd/{}", file.as_raw_fd());
+            let op = LinkAtOp {
+                olddirfd: libc::AT_FDCWD,
+                oldpath: path_cstring(Path::new(&proc_path))?,
+                newdirfd: dir.as_raw_fd(),
+                newpath: path_cstring(name)?,
+                flags: libc::AT_SYMLINK_FOLLOW,
+            };
+            submit(op).await.0?;
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Whether `err` from [`open_tmpfile`] means the filesystem lacks `O_TMPFILE`
+///
+/// Kernels before 3.11 reject the flag with `EISDIR` or `ENOENT`, and
+/// filesystems without support return `EOPNOTSUPP`.
+#[must_use]
+pub fn is_unsupported(err: &ExtendedError) -> bool {
+    matches!(
+        err,
+        ExtendedError::Io(e) if matches!(
+            e.raw_os_error(),
+            Some(libc::EOPNOTSUPP | libc::EISDIR | libc::ENOENT)
+        )
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use compio::io::AsyncWriteAtExt;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[compio::test]
+    async fn test_tmpfile_is_invisible_until_linked() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = DirectoryFd::open(temp_dir.path()).await.unwrap();
+
+        let mut file = match open_tmpfile(&dir, 0o600).await {
+            Ok(file) => file,
+            Err(e) if is_unsupported(&e) => return,
+            Err(e) => panic!("open_tmpfile failed: {e}"),
+        };
+        file.write_all_at(b"contents", 0).await.0.unwrap();
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+
+        link_tmpfile(&file, &dir, Path::new("file")).await.unwrap();
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("file")).unwrap(),
+            "contents"
+        );
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+
+    #[compio::test]
+    async fn test_link_tmpfile_does_not_replace() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = DirectoryFd::open(temp_dir.path()).await.unwrap();
+        fs::write(temp_dir.path().join("file"), "old").unwrap();
+
+        let file = match open_tmpfile(&dir, 0o600).await {
+            Ok(file) => file,
+            Err(e) if is_unsupported(&e) => return,
+            Err(e) => panic!("open_tmpfile failed: {e}"),
+        };
+        let err = link_tmpfile(&file, &dir, Path::new("file"))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, ExtendedError::Io(e) if e.kind() == std::io::ErrorKind::AlreadyExists),
+            "unexpected error: {err}"
+        );
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("file")).unwrap(),
+            "old"
+        );
+    }
+}
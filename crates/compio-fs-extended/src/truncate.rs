@@ -0,0 +1,142 @@
+//! ftruncate operations using io_uring (`IORING_OP_FTRUNCATE`)
+//!
+//! Updating a file in place (`--inplace`) rewrites only the blocks that
+//! changed, so a destination that was longer than the new source must be
+//! shrunk afterwards. `IORING_OP_FTRUNCATE` (Linux 6.9+) keeps that on the
+//! ring; on older kernels the syscall is made on a blocking thread instead
+//! (see [`probe`](crate::probe)).
+//!
+//! Failures are returned as [`ExtendedError::Io`](crate::ExtendedError::Io)
+//! so callers can match on the `ErrorKind`.
+
+use crate::error::{invalid_parameters_error, Result};
+use crate::probe::{entry_or_blocking, syscall_result};
+use compio::driver::OpCode;
+use compio::fs::File;
+use compio::runtime::submit;
+use io_uring::{opcode, types};
+use std::os::unix::io::AsRawFd;
+use std::pin::Pin;
+
+/// Trait for ftruncate operations
+pub trait Ftruncate {
+    /// Set the length of the file to `len` bytes
+    ///
+    /// Shrinking discards the data beyond `len`; growing extends the file
+    /// with a hole that reads as zeros. The file must be open for writing.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - `len` does not fit in an `off_t`
+    /// - The file is not open for writing (`EINVAL` or `EBADF`)
+    /// - The file is immutable or append-only (`EPERM`)
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use compio_fs_extended::{ExtendedFile, Ftruncate};
+    /// use compio::fs::OpenOptions;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let file = OpenOptions::new().write(true).open("existing.txt").await?;
+    /// let extended_file = ExtendedFile::new(file);
+    ///
+    /// // Drop everything after the first 4KiB
+    /// extended_file.ftruncate(4096).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[allow(async_fn_in_trait)]
+    async fn ftruncate(&self, len: u64) -> Result<()>;
+}
+
+/// io_uring ftruncate operation
+struct FtruncateOp {
+    /// File descriptor to truncate
+    fd: i32,
+    /// New file length
+    len: u64,
+}
+
+impl OpCode for FtruncateOp {
+    fn create_entry(self: Pin<&mut Self>) -> compio::driver::OpEntry {
+        entry_or_blocking(opcode::Ftruncate::CODE, || {
+            opcode::Ftruncate::new(types::Fd(self.fd), self.len).build()
+        })
+    }
+
+    fn call_blocking(self: Pin<&mut Self>) -> std::io::Result<usize> {
+        let len = libc::off_t::try_from(self.len)
+            .map_err(|_| std::io::Error::from_raw_os_error(libc::EINVAL))?;
+        // SAFETY: plain syscall on a descriptor the caller keeps open
+        syscall_result(unsafe { libc::ftruncate(self.fd, len) })
+    }
+}
+
+/// Set the length of `file` to `len` bytes
+///
+/// # Errors
+///
+/// See [`Ftruncate::ftruncate`].
+pub async fn ftruncate(file: &File, len: u64) -> Result<()> {
+    if libc::off_t::try_from(len).is_err() {
+        return Err(invalid_parameters_error(&format!(
+            "ftruncate length {len} is too large"
+        )));
+    }
+    let fd = file.as_raw_fd();
+    submit(FtruncateOp { fd, len }).await.0?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExtendedError;
+    use compio::fs::OpenOptions;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[compio::test]
+    async fn test_ftruncate_shrink_and_grow() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("file");
+        fs::write(&path, "0123456789").unwrap();
+
+        let file = OpenOptions::new().write(true).open(&path).await.unwrap();
+
+        ftruncate(&file, 4).await.unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"0123");
+
+        ftruncate(&file, 8).await.unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"0123\0\0\0\0");
+
+        ftruncate(&file, 0).await.unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+    }
+
+    #[compio::test]
+    async fn test_ftruncate_read_only_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("file");
+        fs::write(&path, "data").unwrap();
+
+        let file = File::open(&path).await.unwrap();
+        let err = ftruncate(&file, 0).await.unwrap_err();
+        assert!(
+            matches!(err, ExtendedError::Io(_)),
+            "unexpected error: {err}"
+        );
+        assert_eq!(fs::read(&path).unwrap(), b"data");
+    }
+
+    #[compio::test]
+    async fn test_ftruncate_length_too_large() {
+        let temp_dir = TempDir::new().unwrap();
+        let file = File::create(temp_dir.path().join("file")).await.unwrap();
+
+        let err = ftruncate(&file, u64::MAX).await.unwrap_err();
+        assert!(err.is_invalid_parameters());
+    }
+}
@@ -0,0 +1,191 @@
+//! Unlink and rmdir operations using io_uring (`unlinkat`)
+//!
+//! Removing files and empty directories through `IORING_OP_UNLINKAT` keeps
+//! cleanup (temp files, staging directories, and deleting extraneous
+//! destination entries) on the ring instead of blocking a worker thread in
+//! `std::fs::remove_file`. The `*_at` variants resolve names relative to a
+//! [`DirectoryFd`], so a directory being emptied cannot be swapped for a
+//! symlink halfway through.
+//!
+//! Failures are returned as [`ExtendedError::Io`](crate::ExtendedError::Io)
+//! so callers can match on the `ErrorKind` (e.g. `NotFound`).
+
+use crate::directory::{path_cstring, DirectoryFd};
+use crate::error::Result;
+use crate::probe::{entry_or_blocking, syscall_result};
+use compio::driver::OpCode;
+use compio::runtime::submit;
+use io_uring::{opcode, types};
+use std::ffi::CString;
+use std::path::Path;
+use std::pin::Pin;
+
+/// io_uring unlinkat operation
+struct UnlinkOp {
+    /// Directory `pathname` is relative to
+    dirfd: i32,
+    /// Path to remove
+    pathname: CString,
+    /// `AT_REMOVEDIR` to remove a directory, 0 for anything else
+    flags: i32,
+}
+
+impl OpCode for UnlinkOp {
+    fn create_entry(self: Pin<&mut Self>) -> compio::driver::OpEntry {
+        entry_or_blocking(opcode::UnlinkAt::CODE, || {
+            opcode::UnlinkAt::new(types::Fd(self.dirfd), self.pathname.as_ptr())
+                .flags(self.flags)
+                .build()
+        })
+    }
+
+    fn call_blocking(self: Pin<&mut Self>) -> std::io::Result<usize> {
+        // SAFETY: pathname is a valid C string
+        syscall_result(unsafe { libc::unlinkat(self.dirfd, self.pathname.as_ptr(), self.flags) })
+    }
+}
+
+/// Submit an unlinkat relative to `dirfd`
+async fn submit_unlink(dirfd: i32, pathname: &Path, flags: i32) -> Result<()> {
+    let op = UnlinkOp {
+        dirfd,
+        pathname: path_cstring(pathname)?,
+        flags,
+    };
+    submit(op).await.0?;
+    Ok(())
+}
+
+/// Remove a file, symlink or other non-directory entry
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The path contains a NUL byte
+/// - The path does not exist (`NotFound`)
+/// - The path is a directory (`EISDIR`)
+/// - Permission is denied
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use compio_fs_extended::unlink::unlink;
+/// use std::path::Path;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// unlink(Path::new("partial.tmp")).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn unlink(path: &Path) -> Result<()> {
+    submit_unlink(libc::AT_FDCWD, path, 0).await
+}
+
+/// Remove an empty directory
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - The path contains a NUL byte
+/// - The path does not exist (`NotFound`)
+/// - The path is not a directory (`ENOTDIR`)
+/// - The directory is not empty (`ENOTEMPTY`)
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use compio_fs_extended::unlink::remove_dir;
+/// use std::path::Path;
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// remove_dir(Path::new("staging")).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn remove_dir(path: &Path) -> Result<()> {
+    submit_unlink(libc::AT_FDCWD, path, libc::AT_REMOVEDIR).await
+}
+
+/// Remove the non-directory entry `name` in `dir`
+///
+/// # Errors
+///
+/// Same as [`unlink`].
+pub async fn unlink_at(dir: &DirectoryFd, name: &Path) -> Result<()> {
+    submit_unlink(dir.as_raw_fd(), name, 0).await
+}
+
+/// Remove the empty directory `name` in `dir`
+///
+/// # Errors
+///
+/// Same as [`remove_dir`].
+pub async fn remove_dir_at(dir: &DirectoryFd, name: &Path) -> Result<()> {
+    submit_unlink(dir.as_raw_fd(), name, libc::AT_REMOVEDIR).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExtendedError;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[compio::test]
+    async fn test_unlink_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("file");
+        fs::write(&path, "data").unwrap();
+
+        unlink(&path).await.unwrap();
+        assert!(!path.exists());
+
+        let err = unlink(&path).await.unwrap_err();
+        assert!(
+            matches!(&err, ExtendedError::Io(e) if e.kind() == std::io::ErrorKind::NotFound),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[compio::test]
+    async fn test_unlink_symlink_keeps_target() {
+        let temp_dir = TempDir::new().unwrap();
+        let target = temp_dir.path().join("target");
+        let link = temp_dir.path().join("link");
+        fs::write(&target, "data").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        unlink(&link).await.unwrap();
+        assert!(fs::symlink_metadata(&link).is_err());
+        assert!(target.exists());
+    }
+
+    #[compio::test]
+    async fn test_remove_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("dir");
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join("file"), "data").unwrap();
+
+        // Only empty directories can be removed, and unlink refuses directories
+        assert!(remove_dir(&dir).await.is_err());
+        assert!(unlink(&dir).await.is_err());
+
+        fs::remove_file(dir.join("file")).unwrap();
+        remove_dir(&dir).await.unwrap();
+        assert!(!dir.exists());
+    }
+
+    #[compio::test]
+    async fn test_unlink_at_directory_fd() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = DirectoryFd::open(temp_dir.path()).await.unwrap();
+        fs::write(temp_dir.path().join("file"), "data").unwrap();
+        fs::create_dir(temp_dir.path().join("sub")).unwrap();
+
+        unlink_at(&dir, Path::new("file")).await.unwrap();
+        remove_dir_at(&dir, Path::new("sub")).await.unwrap();
+
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+    }
+}
@@ -1,6 +1,13 @@
 //! Extended attributes (xattr) operations using io_uring opcodes
+//!
+//! The `*_impl` functions work on an open file descriptor (`fgetxattr`,
+//! `fsetxattr`, `flistxattr`, `fremovexattr`), so the attributes read and
+//! written belong to the inode that was opened even if its path is replaced
+//! in the meantime. The `*_at_path` variants resolve the path on every call
+//! and are meant for entries that cannot be opened.
 
 use crate::error::{xattr_error, Result};
+use crate::probe::{entry_or_blocking, syscall_result};
 use compio::driver::OpCode;
 use compio::fs::File;
 use compio::runtime::submit;
@@ -105,6 +112,35 @@ pub trait XattrOps {
     /// # }
     /// ```
     async fn list_xattr(&self) -> Result<Vec<String>>;
+
+    /// Remove an extended attribute
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the extended attribute
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if:
+    /// - The extended attribute doesn't exist
+    /// - Permission is denied
+    /// - The operation fails due to I/O errors
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use compio_fs_extended::{ExtendedFile, XattrOps};
+    /// use compio::fs::File;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let file = File::open("file.txt").await?;
+    /// let extended_file = ExtendedFile::new(file);
+    ///
+    /// extended_file.remove_xattr("user.stale").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    async fn remove_xattr(&self, name: &str) -> Result<()>;
 }
 
 /// io_uring getxattr operation
@@ -130,15 +166,22 @@ impl GetXattrOp {
 
 impl OpCode for GetXattrOp {
     fn create_entry(mut self: Pin<&mut Self>) -> compio::driver::OpEntry {
-        compio::driver::OpEntry::Submission(
+        entry_or_blocking(opcode::FGetXattr::CODE, || {
             opcode::FGetXattr::new(
                 types::Fd(self.fd),
                 self.name.as_ptr(),
                 self.buffer.as_mut_ptr() as *mut libc::c_void,
                 self.buffer.len() as u32,
             )
-            .build(),
-        )
+            .build()
+        })
+    }
+
+    fn call_blocking(mut self: Pin<&mut Self>) -> std::io::Result<usize> {
+        let (fd, name) = (self.fd, self.name.as_ptr());
+        let len = self.buffer.len();
+        // SAFETY: name is a valid C string and the buffer holds len bytes
+        syscall_result(unsafe { libc::fgetxattr(fd, name, self.buffer.as_mut_ptr().cast(), len) })
     }
 }
 
@@ -161,7 +204,7 @@ impl SetXattrOp {
 
 impl OpCode for SetXattrOp {
     fn create_entry(self: Pin<&mut Self>) -> compio::driver::OpEntry {
-        compio::driver::OpEntry::Submission(
+        entry_or_blocking(opcode::FSetXattr::CODE, || {
             opcode::FSetXattr::new(
                 types::Fd(self.fd),
                 self.name.as_ptr(),
@@ -169,8 +212,22 @@ impl OpCode for SetXattrOp {
                 self.value.len() as u32,
             )
             .flags(0) // No flags
-            .build(),
-        )
+            .build()
+        })
+    }
+
+    fn call_blocking(self: Pin<&mut Self>) -> std::io::Result<usize> {
+        // SAFETY: name is a valid C string and the value holds its length
+        let ret = unsafe {
+            libc::fsetxattr(
+                self.fd,
+                self.name.as_ptr(),
+                self.value.as_ptr().cast(),
+                self.value.len(),
+                0,
+            )
+        };
+        syscall_result(ret)
     }
 }
 
@@ -247,40 +304,64 @@ pub async fn set_xattr_impl(file: &File, name: &str, value: &[u8]) -> Result<()>
 /// Implementation of xattr listing using safe xattr crate
 ///
 /// NOTE: IORING_OP_FLISTXATTR doesn't exist in the Linux kernel (as of 6.x).
-/// The kernel only has FGETXATTR and FSETXATTR, not FLISTXATTR.
-/// Using the safe `xattr` crate wrapper instead of unsafe libc.
+/// The kernel only has FGETXATTR and FSETXATTR, not FLISTXATTR, so the
+/// `flistxattr` call runs on a blocking thread.
 ///
 /// # Errors
 ///
 /// This function will return an error if the xattr operation fails
 pub async fn list_xattr_impl(file: &File) -> Result<Vec<String>> {
-    use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd};
+    use std::os::fd::{AsRawFd, FromRawFd};
     use xattr::FileExt; // Extension trait for FD-based xattr operations
 
     let fd = file.as_raw_fd();
 
-    // Using spawn + xattr crate's FileExt trait since kernel lacks IORING_OP_FLISTXATTR
-    compio::runtime::spawn(async move {
-        // Create a temporary std::fs::File to use FileExt trait
-        // SAFETY: fd is valid for the duration of this call
-        let temp_file = unsafe { std::fs::File::from_raw_fd(fd) };
+    compio::runtime::spawn_blocking(move || {
+        // Borrow the descriptor as a std::fs::File for the FileExt trait;
+        // ManuallyDrop keeps it from being closed on any return path
+        // SAFETY: the caller's borrow keeps fd open until this task is awaited
+        let borrowed = std::mem::ManuallyDrop::new(unsafe { std::fs::File::from_raw_fd(fd) });
 
-        // Use the xattr crate's safe FileExt::list_xattr method
-        let attrs = temp_file
+        let attrs = borrowed
             .list_xattr()
             .map_err(|e| xattr_error(&format!("flistxattr failed: {}", e)))?;
 
-        // Prevent temp_file from closing the fd
-        let _ = temp_file.into_raw_fd();
-
-        let names: Vec<String> = attrs
+        Ok(attrs
             .filter_map(|os_str| os_str.to_str().map(|s| s.to_string()))
-            .collect();
+            .collect())
+    })
+    .await
+    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
 
-        Ok(names)
+/// Implementation of xattr removal on an open file
+///
+/// There is no io_uring opcode for `fremovexattr`, so the call runs on a
+/// blocking thread.
+///
+/// # Errors
+///
+/// This function will return an error if the xattr operation fails,
+/// including when the attribute does not exist
+pub async fn remove_xattr_impl(file: &File, name: &str) -> Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let name_cstr =
+        CString::new(name).map_err(|e| xattr_error(&format!("Invalid xattr name: {e}")))?;
+    let fd = file.as_raw_fd();
+
+    compio::runtime::spawn_blocking(move || {
+        // SAFETY: the caller's borrow keeps fd open until this task is
+        // awaited, and name_cstr is a valid NUL-terminated string
+        if unsafe { libc::fremovexattr(fd, name_cstr.as_ptr()) } == 0 {
+            Ok(())
+        } else {
+            let errno = std::io::Error::last_os_error();
+            Err(xattr_error(&format!("fremovexattr failed: {}", errno)))
+        }
     })
     .await
-    .map_err(|e| xattr_error(&format!("spawn failed: {e:?}")))?
+    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
 }
 
 /// Get an extended attribute value at the given path
@@ -538,6 +619,45 @@ mod tests {
         }
     }
 
+    #[compio::test]
+    async fn test_xattr_fd_operations() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        fs::write(&file_path, "test content").unwrap();
+
+        if !is_xattr_supported(&file_path).await {
+            println!("Extended attributes not supported on this filesystem");
+            return;
+        }
+
+        let file = File::open(&file_path).await.unwrap();
+        set_xattr_impl(&file, "user.test", b"fd_value")
+            .await
+            .unwrap();
+
+        // The descriptor keeps addressing the same inode after a rename
+        let moved_path = temp_dir.path().join("moved.txt");
+        fs::rename(&file_path, &moved_path).unwrap();
+        fs::write(&file_path, "replacement").unwrap();
+
+        assert_eq!(
+            get_xattr_impl(&file, "user.test").await.unwrap(),
+            b"fd_value"
+        );
+        assert!(list_xattr_impl(&file)
+            .await
+            .unwrap()
+            .contains(&"user.test".to_string()));
+        assert!(get_xattr_at_path(&file_path, "user.test").await.is_err());
+
+        remove_xattr_impl(&file, "user.test").await.unwrap();
+        assert!(!list_xattr_at_path(&moved_path)
+            .await
+            .unwrap()
+            .contains(&"user.test".to_string()));
+        assert!(remove_xattr_impl(&file, "user.test").await.is_err());
+    }
+
     #[tokio::test]
     async fn test_xattr_support_detection() {
         let temp_dir = TempDir::new().unwrap();
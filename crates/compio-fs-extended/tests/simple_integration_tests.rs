@@ -154,6 +154,176 @@ async fn test_metadata_basic() {
     metadata::fchmod(fd, 0o644).await.unwrap();
 }
 
+/// Test reading the creation time of an open file
+#[compio::test]
+async fn test_statx_btime() {
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("test.txt");
+    fs::write(&file_path, "Test content").unwrap();
+
+    let file = File::open(&file_path).await.unwrap();
+    use std::os::unix::io::AsRawFd;
+
+    // std also reads the birth time with statx, and reports an error where
+    // the filesystem records none
+    let btime = metadata::statx_btime(file.as_raw_fd()).await.unwrap();
+    let std_metadata = fs::metadata(&file_path).unwrap();
+    assert_eq!(btime, std_metadata.created().ok());
+    if let Some(btime) = btime {
+        assert!(btime <= std_metadata.modified().unwrap());
+    }
+}
+
+/// Test that path-based metadata carries real timestamps and does not follow symlinks
+#[compio::test]
+async fn test_symlink_metadata() {
+    use std::os::unix::fs::MetadataExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("test.txt");
+    let link_path = temp_dir.path().join("link");
+    fs::write(&file_path, "Test content").unwrap();
+    std::os::unix::fs::symlink(&file_path, &link_path).unwrap();
+
+    let expected = fs::symlink_metadata(&file_path).unwrap();
+    let actual = metadata::symlink_metadata(&file_path).await.unwrap();
+    assert!(actual.is_file());
+    assert_eq!(actual.len(), expected.len());
+    assert_eq!(actual.ino(), expected.ino());
+    assert_eq!(actual.modified().unwrap(), expected.modified().unwrap());
+    assert_eq!(actual.ctime(), expected.ctime());
+
+    let link = metadata::symlink_metadata(&link_path).await.unwrap();
+    assert!(link.is_symlink());
+}
+
+/// Test requesting extended statx fields in one call
+#[compio::test]
+async fn test_statx_extended_mask() {
+    use std::os::unix::fs::MetadataExt;
+    use std::os::unix::io::AsRawFd;
+
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("test.txt");
+    fs::write(&file_path, "Test content").unwrap();
+    let file = File::open(&file_path).await.unwrap();
+
+    let mask =
+        libc::STATX_BASIC_STATS | libc::STATX_BTIME | libc::STATX_MNT_ID | libc::STATX_DIOALIGN;
+    let by_fd = metadata::statx_fd(file.as_raw_fd(), mask).await.unwrap();
+    let by_path = metadata::statx_path(&file_path, mask).await.unwrap();
+
+    assert_eq!(
+        by_fd.metadata().ino(),
+        fs::metadata(&file_path).unwrap().ino()
+    );
+    assert_eq!(by_fd.metadata().len(), 12);
+    // Extended fields are optional, but both calls see the same file
+    assert_eq!(by_fd.btime(), by_path.btime());
+    assert_eq!(by_fd.mount_id(), by_path.mount_id());
+    assert_eq!(by_fd.dio_alignment(), by_path.dio_alignment());
+}
+
+/// Test ownership and permission changes on a symlink itself
+#[compio::test]
+async fn test_symlink_nofollow_metadata() {
+    use compio_fs_extended::directory::DirectoryFd;
+    use compio_fs_extended::metadata::{chmod_at, chown_at, lchown, FollowSymlinks};
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+    use std::path::Path;
+
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("file");
+    let dangling = temp_dir.path().join("dangling");
+    fs::write(&file_path, "data").unwrap();
+    std::os::unix::fs::symlink("missing", &dangling).unwrap();
+    let dir = DirectoryFd::open(temp_dir.path()).await.unwrap();
+    let link_metadata = fs::symlink_metadata(&dangling).unwrap();
+    let (uid, gid) = (link_metadata.uid(), link_metadata.gid());
+
+    // A dangling symlink can only be changed without following it
+    lchown(&dangling, uid, gid).await.unwrap();
+    chown_at(
+        &dir,
+        Path::new("dangling"),
+        u32::MAX,
+        gid,
+        FollowSymlinks::NoFollow,
+    )
+    .await
+    .unwrap();
+    let err = chown_at(
+        &dir,
+        Path::new("dangling"),
+        uid,
+        gid,
+        FollowSymlinks::Follow,
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        matches!(&err, ExtendedError::Io(e) if e.kind() == std::io::ErrorKind::NotFound),
+        "unexpected error: {err}"
+    );
+
+    // Symlinks have no mode of their own; other files are changed as usual
+    assert!(
+        chmod_at(&dir, Path::new("dangling"), 0o600, FollowSymlinks::NoFollow)
+            .await
+            .is_err()
+    );
+    chmod_at(&dir, Path::new("file"), 0o600, FollowSymlinks::NoFollow)
+        .await
+        .unwrap();
+    assert_eq!(
+        fs::metadata(&file_path).unwrap().permissions().mode() & 0o777,
+        0o600
+    );
+}
+
+/// Test nanosecond timestamps through a descriptor and on a symlink itself
+#[compio::test]
+async fn test_utimens_fd_and_symlink() {
+    use compio_fs_extended::directory::DirectoryFd;
+    use compio_fs_extended::metadata::{futimens_fd, utimens_at, FollowSymlinks};
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+    use std::time::{Duration, SystemTime};
+
+    let temp_dir = TempDir::new().unwrap();
+    let file_path = temp_dir.path().join("file");
+    let link_path = temp_dir.path().join("link");
+    fs::write(&file_path, "data").unwrap();
+    std::os::unix::fs::symlink("file", &link_path).unwrap();
+
+    // Times before the epoch round-trip too
+    let old = SystemTime::UNIX_EPOCH - Duration::new(86_400, 250);
+    let file = File::open(&file_path).await.unwrap();
+    futimens_fd(file.as_raw_fd(), old, old).await.unwrap();
+    assert_eq!(fs::metadata(&file_path).unwrap().modified().unwrap(), old);
+
+    let when = SystemTime::UNIX_EPOCH + Duration::new(1_000_000_000, 123_456_789);
+    let dir = DirectoryFd::open(temp_dir.path()).await.unwrap();
+    utimens_at(
+        &dir,
+        Path::new("link"),
+        when,
+        when,
+        FollowSymlinks::NoFollow,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        fs::symlink_metadata(&link_path)
+            .unwrap()
+            .modified()
+            .unwrap(),
+        when
+    );
+    // The target keeps its own times
+    assert_eq!(fs::metadata(&file_path).unwrap().modified().unwrap(), old);
+}
+
 /// Test device file operations
 #[compio::test]
 async fn test_device_basic() {
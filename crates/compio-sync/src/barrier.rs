@@ -0,0 +1,196 @@
+//! Async barrier for compio runtime
+//!
+//! Lets a fixed number of tasks wait for each other: each calls
+//! [`Barrier::wait`], and all of them continue once the last one arrives.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use compio_sync::Barrier;
+//! use std::sync::Arc;
+//!
+//! # async fn example() {
+//! let barrier = Arc::new(Barrier::new(4));
+//!
+//! for i in 0..4 {
+//!     let barrier = barrier.clone();
+//!     compio::runtime::spawn(async move {
+//!         println!("Phase 1 of task {}", i);
+//!         barrier.wait().await;
+//!         println!("Phase 2 of task {}", i);
+//!     })
+//!     .detach();
+//! }
+//! # }
+//! ```
+
+use crate::Notify;
+use std::sync::Mutex;
+
+/// A compio-compatible async barrier
+///
+/// # Design
+///
+/// - **Reusable**: Once all tasks have arrived the barrier resets, so the same
+///   barrier can separate any number of phases
+/// - **Leader**: Exactly one task per phase (the last to arrive) is told it is
+///   the leader, e.g. to run per-phase cleanup once
+///
+/// Waiting is not cancellation-safe: a task dropping its `wait()` future
+/// still counts as arrived for the current phase.
+#[derive(Debug)]
+pub struct Barrier {
+    /// Arrivals in the current phase
+    state: Mutex<BarrierState>,
+    /// Number of tasks that must arrive to complete a phase
+    parties: usize,
+    /// Notified when a phase completes
+    released: Notify,
+}
+
+/// Bookkeeping protected by the internal mutex
+#[derive(Debug)]
+struct BarrierState {
+    /// Tasks that have arrived in the current phase
+    arrived: usize,
+}
+
+/// Result of [`Barrier::wait`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BarrierWaitResult {
+    /// Whether this task was the last to arrive
+    leader: bool,
+}
+
+impl BarrierWaitResult {
+    /// Whether this task was the last to arrive in its phase
+    ///
+    /// Exactly one task per phase is the leader.
+    #[must_use]
+    pub const fn is_leader(&self) -> bool {
+        self.leader
+    }
+}
+
+impl Barrier {
+    /// Create a barrier for `parties` tasks
+    ///
+    /// A barrier for zero tasks behaves like one for a single task: `wait()`
+    /// completes immediately.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use compio_sync::Barrier;
+    ///
+    /// let barrier = Barrier::new(8);
+    /// ```
+    #[must_use]
+    pub const fn new(parties: usize) -> Self {
+        Self {
+            state: Mutex::new(BarrierState { arrived: 0 }),
+            parties: if parties == 0 { 1 } else { parties },
+            released: Notify::new(),
+        }
+    }
+
+    /// Wait until all tasks have arrived at the barrier
+    pub async fn wait(&self) -> BarrierWaitResult {
+        let released = {
+            let mut state = self.state();
+            state.arrived += 1;
+            if state.arrived == self.parties {
+                state.arrived = 0;
+                self.released.notify_waiters();
+                return BarrierWaitResult { leader: true };
+            }
+            // Register while still holding the state, so the release of this
+            // phase cannot be missed; notify_waiters() is only called when a
+            // phase completes, so the next one is ours
+            self.released.notified()
+        };
+        released.await;
+        BarrierWaitResult { leader: false }
+    }
+
+    /// Lock the internal state
+    ///
+    /// The state is only held for a few instructions that cannot panic, so a
+    /// poisoned lock still holds consistent data and is simply recovered.
+    fn state(&self) -> std::sync::MutexGuard<'_, BarrierState> {
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[compio::test]
+    async fn test_barrier_single_party() {
+        let barrier = Barrier::new(1);
+        assert!(barrier.wait().await.is_leader());
+        assert!(barrier.wait().await.is_leader());
+
+        let barrier = Barrier::new(0);
+        assert!(barrier.wait().await.is_leader());
+    }
+
+    #[compio::test]
+    async fn test_barrier_releases_all_with_one_leader() {
+        let barrier = Arc::new(Barrier::new(10));
+        let arrived = Arc::new(AtomicUsize::new(0));
+
+        let mut handles = Vec::new();
+        for _ in 0..10 {
+            let barrier = barrier.clone();
+            let arrived = arrived.clone();
+            handles.push(compio::runtime::spawn(async move {
+                arrived.fetch_add(1, Ordering::SeqCst);
+                let result = barrier.wait().await;
+                // Nobody passes the barrier before everyone arrived
+                assert_eq!(arrived.load(Ordering::SeqCst), 10);
+                result.is_leader()
+            }));
+        }
+
+        let mut leaders = 0;
+        for handle in handles {
+            if handle.await.unwrap() {
+                leaders += 1;
+            }
+        }
+        assert_eq!(leaders, 1);
+    }
+
+    #[compio::test]
+    async fn test_barrier_is_reusable() {
+        let barrier = Arc::new(Barrier::new(3));
+        let phase = Arc::new(AtomicUsize::new(0));
+
+        let mut handles = Vec::new();
+        for _ in 0..3 {
+            let barrier = barrier.clone();
+            let phase = phase.clone();
+            handles.push(compio::runtime::spawn(async move {
+                for round in 0..3 {
+                    if barrier.wait().await.is_leader() {
+                        phase.store(round + 1, Ordering::SeqCst);
+                    }
+                    // The leader's update is visible after the next phase
+                    barrier.wait().await;
+                    assert_eq!(phase.load(Ordering::SeqCst), round + 1);
+                }
+            }));
+        }
+
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(phase.load(Ordering::SeqCst), 3);
+    }
+}
@@ -6,6 +6,11 @@
 //! # Primitives
 //!
 //! - [`Semaphore`] - Async semaphore for bounding concurrency
+//! - [`Mutex`] - Async mutex with fair FIFO wakeups
+//! - [`Notify`] - Wake tasks waiting for a condition to change
+//! - [`oneshot`] - Channel for returning one value from another task
+//! - [`Barrier`] - Wait until a fixed number of tasks reach the same point
+//! - [`WaitGroup`] - Wait until a dynamic set of tasks has finished
 //!
 //! # Example
 //!
@@ -28,6 +33,15 @@
 //! }
 //! ```
 
+mod barrier;
+mod mutex;
+mod notify;
+pub mod oneshot;
 mod semaphore;
+mod wait_group;
 
+pub use barrier::{Barrier, BarrierWaitResult};
+pub use mutex::{Mutex, MutexGuard};
+pub use notify::{Notified, Notify};
 pub use semaphore::{Semaphore, SemaphorePermit};
+pub use wait_group::{WaitGroup, WaitGroupGuard};
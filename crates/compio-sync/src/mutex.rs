@@ -0,0 +1,436 @@
+//! Async mutex for compio runtime
+//!
+//! Provides a mutual exclusion lock whose `lock()` waits asynchronously instead
+//! of blocking the thread. A task contending for a `std::sync::Mutex` parks the
+//! whole executor thread, stalling every other task scheduled on it; waiting on
+//! this mutex only suspends the task itself.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use compio_sync::Mutex;
+//! use std::sync::Arc;
+//!
+//! # async fn example() {
+//! let counter = Arc::new(Mutex::new(0u64));
+//!
+//! {
+//!     let mut value = counter.lock().await;
+//!     *value += 1;
+//! } // Lock released when the guard is dropped
+//!
+//! assert_eq!(*counter.lock().await, 1);
+//! # }
+//! ```
+
+use std::cell::UnsafeCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+
+/// A compio-compatible async mutex with fair FIFO wakeups
+///
+/// # Design
+///
+/// - **Fair**: Waiting tasks acquire the lock strictly in the order they
+///   started waiting; a task calling `lock()` never jumps ahead of the queue
+/// - **No poisoning**: A panic while the lock is held simply releases it
+/// - **Cancellation-safe**: Dropping a pending `lock()` future leaves the
+///   queue and passes the wakeup on to the next waiter
+/// - **RAII guards**: `MutexGuard` releases the lock on drop
+///
+/// The internal bookkeeping uses a short-lived `std::sync::Mutex`, held only
+/// while updating the wait queue, never while the async lock is held.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use compio_sync::Mutex;
+/// use std::sync::Arc;
+///
+/// # async fn example() {
+/// let list = Arc::new(Mutex::new(Vec::new()));
+///
+/// for i in 0..10 {
+///     let list = list.clone();
+///     compio::runtime::spawn(async move {
+///         list.lock().await.push(i);
+///     })
+///     .detach();
+/// }
+/// # }
+/// ```
+pub struct Mutex<T: ?Sized> {
+    /// Lock flag and wait queue
+    state: std::sync::Mutex<MutexState>,
+    /// The protected value
+    value: UnsafeCell<T>,
+}
+
+// SAFETY: The value is only accessed through a `MutexGuard`, and the state
+// guarantees at most one guard exists at a time, so sharing the mutex between
+// threads only ever moves exclusive access to `T` between them.
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+// SAFETY: See above
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+
+/// Bookkeeping protected by the internal `std::sync::Mutex`
+#[derive(Debug, Default)]
+struct MutexState {
+    /// Whether a `MutexGuard` currently exists
+    locked: bool,
+    /// Ticket handed to the next task that starts waiting
+    next_ticket: u64,
+    /// Tickets and wakers of waiting tasks, in arrival order
+    waiters: VecDeque<(u64, Waker)>,
+}
+
+impl<T> Mutex<T> {
+    /// Create a new unlocked mutex holding `value`
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use compio_sync::Mutex;
+    ///
+    /// let mutex = Mutex::new(5);
+    /// ```
+    #[must_use]
+    pub const fn new(value: T) -> Self {
+        Self {
+            state: std::sync::Mutex::new(MutexState {
+                locked: false,
+                next_ticket: 0,
+                waiters: VecDeque::new(),
+            }),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Consume the mutex and return the protected value
+    ///
+    /// No lock is needed: owning the mutex proves nobody else can hold it.
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Acquire the lock, waiting asynchronously if it is held
+    ///
+    /// Waiters are served in FIFO order.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use compio_sync::Mutex;
+    ///
+    /// # async fn example() {
+    /// let mutex = Mutex::new(1);
+    /// *mutex.lock().await += 1;
+    /// # }
+    /// ```
+    pub async fn lock(&self) -> MutexGuard<'_, T> {
+        LockFuture {
+            mutex: self,
+            ticket: None,
+        }
+        .await
+    }
+
+    /// Try to acquire the lock without waiting
+    ///
+    /// Returns `None` if the lock is held or other tasks are already queued
+    /// for it (taking it would let this caller jump the queue).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use compio_sync::Mutex;
+    ///
+    /// let mutex = Mutex::new(1);
+    /// let guard = mutex.try_lock().unwrap();
+    /// assert!(mutex.try_lock().is_none());
+    /// drop(guard);
+    /// assert!(mutex.try_lock().is_some());
+    /// ```
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        let mut state = self.state();
+        if state.locked || !state.waiters.is_empty() {
+            return None;
+        }
+        state.locked = true;
+        Some(MutexGuard { mutex: self })
+    }
+
+    /// Mutable access to the value without locking
+    ///
+    /// The exclusive borrow proves nobody else can hold the lock.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    /// Lock the internal state
+    ///
+    /// The state is only held for a few instructions that cannot panic, so a
+    /// poisoned lock still holds consistent data and is simply recovered.
+    fn state(&self) -> std::sync::MutexGuard<'_, MutexState> {
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Release the lock and wake the first waiter (called by `MutexGuard`)
+    fn unlock(&self) {
+        let mut state = self.state();
+        state.locked = false;
+        if let Some((_, waker)) = state.waiters.front() {
+            waker.wake_by_ref();
+        }
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for Mutex<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("Mutex");
+        match self.try_lock() {
+            Some(guard) => d.field("data", &&*guard),
+            None => d.field("data", &format_args!("<locked>")),
+        };
+        d.finish_non_exhaustive()
+    }
+}
+
+/// RAII guard giving access to the value of a locked `Mutex`
+///
+/// Returned by `Mutex::lock()` and `Mutex::try_lock()`. When dropped, the lock
+/// is released and the next waiting task (if any) is woken.
+pub struct MutexGuard<'a, T: ?Sized> {
+    /// The mutex this guard holds locked
+    mutex: &'a Mutex<T>,
+}
+
+// SAFETY: Sharing a guard only shares `&T`
+unsafe impl<T: ?Sized + Sync> Sync for MutexGuard<'_, T> {}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: The guard proves the lock is held, so no other reference
+        // to the value exists
+        unsafe { &*self.mutex.value.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: As for `deref`, and `&mut self` makes this the only access
+        // through the guard
+        unsafe { &mut *self.mutex.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.unlock();
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for MutexGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Future that resolves when the mutex is locked
+///
+/// This future is returned by `Mutex::lock()`. It will:
+/// 1. Take the lock immediately if it is free and nobody is queued
+/// 2. Otherwise take a ticket at the back of the queue and return `Poll::Pending`
+/// 3. When woken at the front of the queue with the lock free, take it
+struct LockFuture<'a, T: ?Sized> {
+    /// The mutex to lock
+    mutex: &'a Mutex<T>,
+    /// Our place in the wait queue, once we have queued
+    ticket: Option<u64>,
+}
+
+impl<'a, T: ?Sized> Future for LockFuture<'a, T> {
+    type Output = MutexGuard<'a, T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let mut state = this.mutex.state();
+        let first = match this.ticket {
+            None => state.waiters.is_empty(),
+            Some(ticket) => state.waiters.front().is_some_and(|(t, _)| *t == ticket),
+        };
+        if first && !state.locked {
+            state.locked = true;
+            if this.ticket.take().is_some() {
+                state.waiters.pop_front();
+            }
+            return Poll::Ready(MutexGuard { mutex: this.mutex });
+        }
+        match this.ticket {
+            Some(ticket) => {
+                if let Some(waiter) = state.waiters.iter_mut().find(|(t, _)| *t == ticket) {
+                    waiter.1.clone_from(cx.waker());
+                }
+            }
+            None => {
+                let ticket = state.next_ticket;
+                state.next_ticket += 1;
+                state.waiters.push_back((ticket, cx.waker().clone()));
+                this.ticket = Some(ticket);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl<T: ?Sized> Drop for LockFuture<'_, T> {
+    fn drop(&mut self) {
+        let Some(ticket) = self.ticket else {
+            return;
+        };
+        let mut state = self.mutex.state();
+        let was_first = state.waiters.front().is_some_and(|(t, _)| *t == ticket);
+        state.waiters.retain(|(t, _)| *t != ticket);
+        // We may have been woken to take the lock; pass that on
+        if was_first && !state.locked {
+            if let Some((_, waker)) = state.waiters.front() {
+                waker.wake_by_ref();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    /// Waker that does nothing, for polling futures by hand
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    fn noop_waker() -> Waker {
+        Waker::from(Arc::new(NoopWaker))
+    }
+
+    #[test]
+    fn test_mutex_try_lock() {
+        let mutex = Mutex::new(1);
+
+        let mut guard = mutex.try_lock().unwrap();
+        *guard += 1;
+        assert!(mutex.try_lock().is_none());
+        drop(guard);
+
+        assert_eq!(*mutex.try_lock().unwrap(), 2);
+        assert_eq!(mutex.into_inner(), 2);
+    }
+
+    #[compio::test]
+    async fn test_mutex_lock_basic() {
+        let mutex = Mutex::new(Vec::new());
+
+        mutex.lock().await.push(1);
+        mutex.lock().await.push(2);
+
+        assert_eq!(*mutex.lock().await, vec![1, 2]);
+    }
+
+    #[compio::test]
+    async fn test_mutex_waiters_are_fifo() {
+        let mutex = Mutex::new(Vec::new());
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let held = mutex.lock().await;
+        let mut first = Box::pin(mutex.lock());
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        let mut second = Box::pin(mutex.lock());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+
+        // try_lock must not jump the queue even once the lock is free
+        drop(held);
+        assert!(mutex.try_lock().is_none());
+
+        // The second waiter cannot overtake the first
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        let Poll::Ready(mut guard) = first.as_mut().poll(&mut cx) else {
+            panic!("first waiter should get the lock");
+        };
+        guard.push(1);
+        drop(guard);
+        let Poll::Ready(mut guard) = second.as_mut().poll(&mut cx) else {
+            panic!("second waiter should get the lock");
+        };
+        guard.push(2);
+        drop(guard);
+
+        assert_eq!(*mutex.lock().await, vec![1, 2]);
+    }
+
+    #[compio::test]
+    async fn test_mutex_abandoned_waiter_passes_wakeup() {
+        let mutex = Mutex::new(0);
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let held = mutex.lock().await;
+        let mut abandoned = Box::pin(mutex.lock());
+        assert!(abandoned.as_mut().poll(&mut cx).is_pending());
+        let mut next = Box::pin(mutex.lock());
+        assert!(next.as_mut().poll(&mut cx).is_pending());
+
+        drop(held);
+        drop(abandoned);
+        assert!(next.as_mut().poll(&mut cx).is_ready());
+    }
+
+    #[compio::test]
+    async fn test_mutex_contended_tasks() {
+        let mutex = Arc::new(Mutex::new(0u64));
+        let mut handles = Vec::new();
+
+        for _ in 0..100 {
+            let mutex = mutex.clone();
+            handles.push(compio::runtime::spawn(async move {
+                let mut guard = mutex.lock().await;
+                let value = *guard;
+                // Yield while holding the lock so other tasks contend for it
+                compio::runtime::spawn(async {}).await.unwrap();
+                *guard = value + 1;
+            }));
+        }
+
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        assert_eq!(*mutex.lock().await, 100);
+    }
+}
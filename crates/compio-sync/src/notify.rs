@@ -0,0 +1,413 @@
+//! Async notification for compio runtime
+//!
+//! Provides a primitive for tasks to wait until another task signals that a
+//! condition may have changed (e.g. "queue drained" or "budget available"),
+//! without spinning or polling on a timer.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use compio_sync::Notify;
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use std::sync::Arc;
+//!
+//! # async fn example() {
+//! let pending = Arc::new(AtomicUsize::new(1));
+//! let drained = Arc::new(Notify::new());
+//!
+//! let (p, d) = (pending.clone(), drained.clone());
+//! compio::runtime::spawn(async move {
+//!     p.fetch_sub(1, Ordering::SeqCst);
+//!     d.notify_waiters();
+//! })
+//! .detach();
+//!
+//! // Create the future before checking, so a notification sent in between
+//! // is not missed
+//! loop {
+//!     let notified = drained.notified();
+//!     if pending.load(Ordering::SeqCst) == 0 {
+//!         break;
+//!     }
+//!     notified.await;
+//! }
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll, Waker};
+
+/// A compio-compatible notification primitive
+///
+/// A `Notify` has no value of its own; tasks wait on it with
+/// [`notified()`](Notify::notified) and other tasks wake them with
+/// [`notify_one()`](Notify::notify_one) or
+/// [`notify_waiters()`](Notify::notify_waiters).
+///
+/// # Design
+///
+/// - **Stored permit**: `notify_one()` with nobody waiting is remembered, so
+///   the next `notified()` completes immediately (at most one is stored)
+/// - **FIFO waiters**: `notify_one()` wakes the longest-waiting task
+/// - **Broadcast**: `notify_waiters()` wakes every task waiting at that moment,
+///   including `Notified` futures created but not yet polled
+/// - **Cancellation-safe**: A future dropped after `notify_one()` picked it
+///   passes the notification on to the next waiter
+pub struct Notify {
+    /// Stored permit, broadcast generation and wait queue
+    state: Mutex<NotifyState>,
+}
+
+/// Bookkeeping protected by the internal mutex
+#[derive(Debug, Default)]
+struct NotifyState {
+    /// Whether a `notify_one()` is stored for the next waiter
+    permit: bool,
+    /// Number of `notify_waiters()` calls so far
+    generation: u64,
+    /// Ticket handed to the next task that starts waiting
+    next_ticket: u64,
+    /// Tickets and wakers of waiting tasks, in arrival order
+    waiters: VecDeque<(u64, Waker)>,
+    /// Tickets picked by `notify_one()` whose futures have not completed yet
+    notified: Vec<u64>,
+}
+
+impl NotifyState {
+    /// Hand one notification to the first waiter, or store it
+    fn notify_one(&mut self) {
+        if let Some((ticket, waker)) = self.waiters.pop_front() {
+            self.notified.push(ticket);
+            waker.wake();
+        } else {
+            self.permit = true;
+        }
+    }
+}
+
+impl Notify {
+    /// Create a new `Notify` with no stored permit
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use compio_sync::Notify;
+    ///
+    /// let notify = Notify::new();
+    /// ```
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(NotifyState {
+                permit: false,
+                generation: 0,
+                next_ticket: 0,
+                waiters: VecDeque::new(),
+                notified: Vec::new(),
+            }),
+        }
+    }
+
+    /// Wait for a notification
+    ///
+    /// The returned future completes after a `notify_one()` picks it (or
+    /// consumes a stored permit), or after any `notify_waiters()` call made
+    /// once this method returned. Create it *before* checking the condition
+    /// being waited for, so a notification sent in between is not lost.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use compio_sync::Notify;
+    ///
+    /// # async fn example() {
+    /// let notify = Notify::new();
+    /// notify.notify_one();
+    /// notify.notified().await; // Consumes the stored permit
+    /// # }
+    /// ```
+    pub fn notified(&self) -> Notified<'_> {
+        Notified {
+            notify: self,
+            generation: self.state().generation,
+            ticket: None,
+            done: false,
+        }
+    }
+
+    /// Wake the longest-waiting task, or store a permit if none is waiting
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use compio_sync::Notify;
+    ///
+    /// let notify = Notify::new();
+    /// notify.notify_one();
+    /// notify.notify_one(); // Permits do not accumulate
+    /// ```
+    pub fn notify_one(&self) {
+        self.state().notify_one();
+    }
+
+    /// Wake every task currently waiting
+    ///
+    /// No permit is stored: tasks that start waiting afterwards wait for the
+    /// next notification.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use compio_sync::Notify;
+    ///
+    /// let notify = Notify::new();
+    /// notify.notify_waiters(); // Nobody waiting, so nothing happens
+    /// ```
+    pub fn notify_waiters(&self) {
+        let waiters = {
+            let mut state = self.state();
+            state.generation += 1;
+            std::mem::take(&mut state.waiters)
+        };
+        for (_, waker) in waiters {
+            waker.wake();
+        }
+    }
+
+    /// Lock the internal state
+    ///
+    /// The state is only held for a few instructions that cannot panic, so a
+    /// poisoned lock still holds consistent data and is simply recovered.
+    fn state(&self) -> std::sync::MutexGuard<'_, NotifyState> {
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for Notify {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.state();
+        f.debug_struct("Notify")
+            .field("permit", &state.permit)
+            .field("waiters", &state.waiters.len())
+            .finish()
+    }
+}
+
+/// Future returned by [`Notify::notified`]
+///
+/// It will:
+/// 1. Complete at once if `notify_waiters()` was called since it was created,
+///    or consume a stored permit if there is one
+/// 2. Otherwise take a ticket at the back of the queue and return `Poll::Pending`
+/// 3. Complete once `notify_one()` picks its ticket or `notify_waiters()` is called
+#[must_use = "futures do nothing unless polled"]
+pub struct Notified<'a> {
+    /// The `Notify` being waited on
+    notify: &'a Notify,
+    /// Broadcast generation when this future was created
+    generation: u64,
+    /// Our place in the wait queue, once we have queued
+    ticket: Option<u64>,
+    /// Whether this future has completed
+    done: bool,
+}
+
+impl Future for Notified<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = &mut *self;
+        if this.done {
+            return Poll::Ready(());
+        }
+        let mut state = this.notify.state();
+        let ready = match this.ticket {
+            None if state.generation != this.generation => true,
+            None if state.permit => {
+                state.permit = false;
+                true
+            }
+            None => {
+                let ticket = state.next_ticket;
+                state.next_ticket += 1;
+                state.waiters.push_back((ticket, cx.waker().clone()));
+                this.ticket = Some(ticket);
+                false
+            }
+            Some(ticket) => {
+                if let Some(index) = state.notified.iter().position(|t| *t == ticket) {
+                    state.notified.swap_remove(index);
+                    true
+                } else if state.generation != this.generation {
+                    state.waiters.retain(|(t, _)| *t != ticket);
+                    true
+                } else {
+                    if let Some(waiter) = state.waiters.iter_mut().find(|(t, _)| *t == ticket) {
+                        waiter.1.clone_from(cx.waker());
+                    }
+                    false
+                }
+            }
+        };
+        if ready {
+            this.ticket = None;
+            this.done = true;
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for Notified<'_> {
+    fn drop(&mut self) {
+        let Some(ticket) = self.ticket else {
+            return;
+        };
+        let mut state = self.notify.state();
+        if let Some(index) = state.notified.iter().position(|t| *t == ticket) {
+            // Picked by notify_one() but never observed; pass it on
+            state.notified.swap_remove(index);
+            state.notify_one();
+        } else {
+            state.waiters.retain(|(t, _)| *t != ticket);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    /// Waker that does nothing, for polling futures by hand
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    fn noop_waker() -> Waker {
+        Waker::from(Arc::new(NoopWaker))
+    }
+
+    #[test]
+    fn test_notify_one_stores_a_single_permit() {
+        let notify = Notify::new();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        notify.notify_one();
+        notify.notify_one();
+
+        let mut first = Box::pin(notify.notified());
+        assert!(first.as_mut().poll(&mut cx).is_ready());
+        let mut second = Box::pin(notify.notified());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+    }
+
+    #[test]
+    fn test_notify_one_wakes_in_fifo_order() {
+        let notify = Notify::new();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut first = Box::pin(notify.notified());
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        let mut second = Box::pin(notify.notified());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+
+        notify.notify_one();
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        assert!(first.as_mut().poll(&mut cx).is_ready());
+
+        notify.notify_one();
+        assert!(second.as_mut().poll(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn test_notify_waiters_wakes_everyone_without_a_permit() {
+        let notify = Notify::new();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut polled = Box::pin(notify.notified());
+        assert!(polled.as_mut().poll(&mut cx).is_pending());
+        // Created but never polled before the broadcast
+        let mut unpolled = Box::pin(notify.notified());
+
+        notify.notify_waiters();
+        assert!(polled.as_mut().poll(&mut cx).is_ready());
+        assert!(unpolled.as_mut().poll(&mut cx).is_ready());
+
+        // Nothing is stored for later waiters
+        let mut later = Box::pin(notify.notified());
+        assert!(later.as_mut().poll(&mut cx).is_pending());
+    }
+
+    #[test]
+    fn test_dropped_waiter_passes_notification_on() {
+        let notify = Notify::new();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let mut abandoned = Box::pin(notify.notified());
+        assert!(abandoned.as_mut().poll(&mut cx).is_pending());
+        let mut next = Box::pin(notify.notified());
+        assert!(next.as_mut().poll(&mut cx).is_pending());
+
+        notify.notify_one();
+        drop(abandoned);
+        assert!(next.as_mut().poll(&mut cx).is_ready());
+
+        // With nobody left waiting, an abandoned notification is stored
+        let mut abandoned = Box::pin(notify.notified());
+        assert!(abandoned.as_mut().poll(&mut cx).is_pending());
+        notify.notify_one();
+        drop(abandoned);
+        let mut last = Box::pin(notify.notified());
+        assert!(last.as_mut().poll(&mut cx).is_ready());
+    }
+
+    #[compio::test]
+    async fn test_notify_wakes_waiting_task() {
+        let notify = Arc::new(Notify::new());
+        let remaining = Arc::new(AtomicUsize::new(10));
+
+        let mut handles = Vec::new();
+        for _ in 0..10 {
+            let notify = notify.clone();
+            let remaining = remaining.clone();
+            handles.push(compio::runtime::spawn(async move {
+                remaining.fetch_sub(1, Ordering::SeqCst);
+                notify.notify_one();
+            }));
+        }
+
+        loop {
+            let notified = notify.notified();
+            if remaining.load(Ordering::SeqCst) == 0 {
+                break;
+            }
+            notified.await;
+        }
+
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(remaining.load(Ordering::SeqCst), 0);
+    }
+}
@@ -0,0 +1,273 @@
+//! Oneshot channel for compio runtime
+//!
+//! A single-producer single-consumer channel that carries exactly one value,
+//! typically the result of a task dispatched elsewhere. The receiver is a
+//! future; it fails with [`RecvError`] if the sender is dropped without
+//! sending, e.g. because the task producing the value failed or panicked.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use compio_sync::oneshot;
+//!
+//! # async fn example() {
+//! let (sender, receiver) = oneshot::channel();
+//!
+//! compio::runtime::spawn(async move {
+//!     let _ = sender.send(42);
+//! })
+//! .detach();
+//!
+//! assert_eq!(receiver.await, Ok(42));
+//! # }
+//! ```
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// Create a new oneshot channel
+///
+/// # Example
+///
+/// ```rust
+/// use compio_sync::oneshot;
+///
+/// let (sender, mut receiver) = oneshot::channel();
+/// sender.send("done").unwrap();
+/// assert_eq!(receiver.try_recv(), Ok(Some("done")));
+/// ```
+#[must_use]
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let inner = Arc::new(Inner {
+        state: Mutex::new(State {
+            value: None,
+            sender_dropped: false,
+            receiver_dropped: false,
+            waker: None,
+        }),
+    });
+    (
+        Sender {
+            inner: Arc::clone(&inner),
+        },
+        Receiver { inner },
+    )
+}
+
+/// Error returned when the sender was dropped without sending a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvError;
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("oneshot sender dropped without sending a value")
+    }
+}
+
+impl std::error::Error for RecvError {}
+
+/// State shared between the sender and the receiver
+struct Inner<T> {
+    /// The value and both ends' liveness
+    state: Mutex<State<T>>,
+}
+
+/// Bookkeeping protected by the internal mutex
+struct State<T> {
+    /// Value sent but not yet received
+    value: Option<T>,
+    /// Whether the sender is gone (after sending or without sending)
+    sender_dropped: bool,
+    /// Whether the receiver is gone, so sending is pointless
+    receiver_dropped: bool,
+    /// Waker of the receiver, if it is waiting
+    waker: Option<Waker>,
+}
+
+impl<T> Inner<T> {
+    /// Lock the internal state
+    ///
+    /// The state is only held for a few instructions that cannot panic, so a
+    /// poisoned lock still holds consistent data and is simply recovered.
+    fn state(&self) -> std::sync::MutexGuard<'_, State<T>> {
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+/// Sending half of a oneshot channel
+///
+/// Consumed by [`send`](Sender::send). Dropping it without sending makes the
+/// receiver fail with [`RecvError`].
+pub struct Sender<T> {
+    /// State shared with the receiver
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Send `value` to the receiver
+    ///
+    /// # Errors
+    ///
+    /// Returns the value back if the receiver has already been dropped.
+    pub fn send(self, value: T) -> Result<(), T> {
+        let mut state = self.inner.state();
+        if state.receiver_dropped {
+            return Err(value);
+        }
+        state.value = Some(value);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    /// Whether the receiver has been dropped
+    ///
+    /// A producer can check this to skip work nobody is waiting for.
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        self.inner.state().receiver_dropped
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.inner.state();
+        state.sender_dropped = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender")
+            .field("closed", &self.is_closed())
+            .finish()
+    }
+}
+
+/// Receiving half of a oneshot channel
+///
+/// Await it to get the value, or [`RecvError`] if the sender was dropped
+/// without sending one.
+pub struct Receiver<T> {
+    /// State shared with the sender
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> Receiver<T> {
+    /// Take the value if it has been sent, without waiting
+    ///
+    /// Returns `Ok(None)` while the sender may still send.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RecvError`] if the sender was dropped without sending, or the
+    /// value was already received.
+    pub fn try_recv(&mut self) -> Result<Option<T>, RecvError> {
+        let mut state = self.inner.state();
+        match state.value.take() {
+            Some(value) => Ok(Some(value)),
+            None if state.sender_dropped => Err(RecvError),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.inner.state();
+        if let Some(value) = state.value.take() {
+            return Poll::Ready(Ok(value));
+        }
+        if state.sender_dropped {
+            return Poll::Ready(Err(RecvError));
+        }
+        match &mut state.waker {
+            Some(waker) => waker.clone_from(cx.waker()),
+            None => state.waker = Some(cx.waker().clone()),
+        }
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.inner.state();
+        state.receiver_dropped = true;
+        state.waker = None;
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_oneshot_try_recv() {
+        let (sender, mut receiver) = channel();
+        assert_eq!(receiver.try_recv(), Ok(None));
+
+        sender.send(7).unwrap();
+        assert_eq!(receiver.try_recv(), Ok(Some(7)));
+        // The value can only be received once
+        assert_eq!(receiver.try_recv(), Err(RecvError));
+    }
+
+    #[test]
+    fn test_oneshot_send_after_receiver_dropped() {
+        let (sender, receiver) = channel();
+        assert!(!sender.is_closed());
+
+        drop(receiver);
+        assert!(sender.is_closed());
+        assert_eq!(sender.send(1), Err(1));
+    }
+
+    #[compio::test]
+    async fn test_oneshot_sender_dropped() {
+        let (sender, receiver) = channel::<u32>();
+        drop(sender);
+        assert_eq!(receiver.await, Err(RecvError));
+    }
+
+    #[compio::test]
+    async fn test_oneshot_from_spawned_task() {
+        let (sender, receiver) = channel();
+
+        let handle = compio::runtime::spawn(async move {
+            sender.send(String::from("result")).unwrap();
+        });
+
+        assert_eq!(receiver.await.unwrap(), "result");
+        handle.await.unwrap();
+    }
+
+    #[compio::test]
+    async fn test_oneshot_task_dropped_without_sending() {
+        let (sender, receiver) = channel::<u32>();
+
+        let handle = compio::runtime::spawn(async move {
+            // The task finishes without sending, e.g. after an error
+            let _sender = sender;
+        });
+
+        assert_eq!(receiver.await, Err(RecvError));
+        handle.await.unwrap();
+    }
+}
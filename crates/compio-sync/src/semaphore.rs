@@ -41,8 +41,12 @@ use std::task::{Context, Poll, Waker};
 ///
 /// # Design
 ///
-/// - **Lock-free fast path**: Uses atomics for acquiring/releasing when permits available
-/// - **FIFO waiters**: Blocked tasks are woken in order to prevent starvation
+/// - **Atomic permit count**: Permits are taken and returned with atomics
+/// - **FIFO waiters**: Permits go to blocked tasks in arrival order, and
+///   nobody takes them ahead of a queued task, so a large `acquire_many`
+///   cannot be starved by a stream of small requests
+/// - **Cancellation-safe**: Dropping a pending `acquire()` future leaves the
+///   queue and hands any wakeup it received to the next task
 /// - **RAII permits**: `SemaphorePermit` automatically releases on drop
 /// - **Cloneable**: Wrapped in `Arc` for sharing across tasks
 ///
@@ -82,7 +86,16 @@ struct SemaphoreInner {
     /// Maximum permits (for metrics and debugging)
     max_permits: usize,
     /// Queue of tasks waiting for permits
-    waiters: Mutex<VecDeque<Waker>>,
+    waiters: Mutex<WaitQueue>,
+}
+
+/// Tasks waiting for permits
+#[derive(Default)]
+struct WaitQueue {
+    /// Ticket handed to the next task that starts waiting
+    next_ticket: u64,
+    /// Ticket, waker and needed permits of each waiting task, in arrival order
+    waiters: VecDeque<(u64, Waker, usize)>,
 }
 
 impl Semaphore {
@@ -111,7 +124,7 @@ impl Semaphore {
             inner: Arc::new(SemaphoreInner {
                 permits: AtomicUsize::new(permits),
                 max_permits: permits,
-                waiters: Mutex::new(VecDeque::new()),
+                waiters: Mutex::new(WaitQueue::default()),
             }),
         }
     }
@@ -135,8 +148,36 @@ impl Semaphore {
     /// # }
     /// ```
     pub async fn acquire(&self) -> SemaphorePermit {
+        self.acquire_many(1).await
+    }
+
+    /// Acquire `count` permits at once, waiting asynchronously until they are
+    /// all available
+    ///
+    /// Use this to weight operations: e.g. a large file can take several
+    /// permits so that it counts for more than a small one. The permits are
+    /// held by a single `SemaphorePermit` and released together when it is
+    /// dropped. Requests larger than `max_permits()` are clamped to it, so they
+    /// cannot wait forever.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use compio_sync::Semaphore;
+    ///
+    /// # async fn example() {
+    /// let sem = Semaphore::new(10);
+    ///
+    /// let permit = sem.acquire_many(4).await;
+    /// assert_eq!(permit.permits(), 4);
+    /// assert_eq!(sem.available_permits(), 6);
+    /// # }
+    /// ```
+    pub async fn acquire_many(&self, count: usize) -> SemaphorePermit {
         AcquireFuture {
             semaphore: self.clone(),
+            permits: count.min(self.inner.max_permits),
+            ticket: None,
         }
         .await
     }
@@ -161,24 +202,55 @@ impl Semaphore {
     /// ```
     #[must_use]
     pub fn try_acquire(&self) -> Option<SemaphorePermit> {
-        // Fast path: atomic decrement if permits available
+        self.try_acquire_many(1)
+    }
+
+    /// Try to acquire `count` permits at once without waiting
+    ///
+    /// Returns `None` unless all `count` permits were immediately available
+    /// and no task is waiting for permits; permits are never taken partially.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use compio_sync::Semaphore;
+    ///
+    /// let sem = Semaphore::new(4);
+    ///
+    /// let permit = sem.try_acquire_many(3).unwrap();
+    /// assert!(sem.try_acquire_many(2).is_none()); // Only 1 left
+    /// drop(permit);
+    /// assert!(sem.try_acquire_many(2).is_some());
+    /// ```
+    #[must_use]
+    pub fn try_acquire_many(&self, count: usize) -> Option<SemaphorePermit> {
+        // Queued tasks come first
+        if !self.queue().waiters.is_empty() {
+            return None;
+        }
+        self.take(count)
+    }
+
+    /// Take `count` permits if that many are available, whoever is queued
+    fn take(&self, count: usize) -> Option<SemaphorePermit> {
         let mut current = self.inner.permits.load(Ordering::Acquire);
 
         loop {
-            if current == 0 {
-                return None; // No permits available
+            if current < count {
+                return None; // Not enough permits available
             }
 
-            // Try to atomically decrement
+            // Try to atomically subtract
             match self.inner.permits.compare_exchange_weak(
                 current,
-                current - 1,
+                current - count,
                 Ordering::AcqRel,
                 Ordering::Acquire,
             ) {
                 Ok(_) => {
                     return Some(SemaphorePermit {
                         semaphore: self.clone(),
+                        permits: count,
                     })
                 }
                 Err(actual) => current = actual, // Retry with updated value
@@ -309,46 +381,44 @@ impl Semaphore {
     /// assert_eq!(sem.available_permits(), 100);
     /// ```
     pub fn add_permits(&self, count: usize) {
-        self.inner.permits.fetch_add(count, Ordering::Release);
-
-        // Wake up waiters (up to count)
-        if let Ok(mut waiters) = self.inner.waiters.lock() {
-            for _ in 0..count {
-                if let Some(waker) = waiters.pop_front() {
-                    waker.wake();
-                } else {
-                    break;
-                }
-            }
-        }
+        self.release(count);
     }
 
     /// Release a permit (called internally by `SemaphorePermit::drop`)
-    fn release(&self) {
+    fn release(&self, count: usize) {
         // Increment available permits
-        self.inner.permits.fetch_add(1, Ordering::Release);
+        self.inner.permits.fetch_add(count, Ordering::Release);
+        self.wake_front(&self.queue());
+    }
 
-        // Wake one waiter if any exist
-        if let Ok(mut waiters) = self.inner.waiters.lock() {
-            if let Some(waker) = waiters.pop_front() {
-                waker.wake();
+    /// Wake the first queued task if the available permits cover what it
+    /// needs; once it has its permits it wakes the next one
+    fn wake_front(&self, queue: &WaitQueue) {
+        if let Some((_, waker, needed)) = queue.waiters.front() {
+            if *needed <= self.inner.permits.load(Ordering::Acquire) {
+                waker.wake_by_ref();
             }
         }
     }
 
-    /// Add a waiter to the queue (called by `AcquireFuture`)
-    fn add_waiter(&self, waker: Waker) {
-        if let Ok(mut waiters) = self.inner.waiters.lock() {
-            waiters.push_back(waker);
-        }
+    /// Lock the wait queue
+    ///
+    /// The queue is only held for a few instructions that cannot panic, so a
+    /// poisoned lock still holds a consistent queue and is simply recovered.
+    fn queue(&self) -> std::sync::MutexGuard<'_, WaitQueue> {
+        self.inner
+            .waiters
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
     }
 }
 
 /// RAII guard that releases a semaphore permit on drop
 ///
-/// This guard is returned by `Semaphore::acquire()` and `Semaphore::try_acquire()`.
-/// When dropped, it automatically releases the permit back to the semaphore and
-/// wakes one waiting task (if any).
+/// This guard is returned by `Semaphore::acquire()`, `Semaphore::try_acquire()`
+/// and their `_many` variants, and holds one or more permits. When dropped, it
+/// automatically releases its permits back to the semaphore and wakes the
+/// waiting tasks they satisfy (if any).
 ///
 /// # Example
 ///
@@ -369,51 +439,146 @@ impl Semaphore {
 pub struct SemaphorePermit {
     /// Reference to the semaphore that issued this permit
     semaphore: Semaphore,
+    /// Number of permits held
+    permits: usize,
+}
+
+impl SemaphorePermit {
+    /// Number of permits held by this guard
+    #[must_use]
+    pub const fn permits(&self) -> usize {
+        self.permits
+    }
+
+    /// Drop the guard without releasing its permits
+    ///
+    /// The permits stay taken out of the semaphore, lowering its available
+    /// permits until someone calls `Semaphore::add_permits()`. This hands
+    /// ownership of the permits over to code that cannot hold the guard, e.g.
+    /// another thread that returns them once its work is done.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use compio_sync::Semaphore;
+    ///
+    /// let sem = Semaphore::new(10);
+    /// let permit = sem.try_acquire_many(3).unwrap();
+    /// permit.forget();
+    /// assert_eq!(sem.available_permits(), 7);
+    ///
+    /// // The new owner gives them back later
+    /// sem.add_permits(3);
+    /// assert_eq!(sem.available_permits(), 10);
+    /// ```
+    pub fn forget(mut self) {
+        self.permits = 0;
+    }
 }
 
 impl Drop for SemaphorePermit {
     fn drop(&mut self) {
-        self.semaphore.release();
+        if self.permits > 0 {
+            self.semaphore.release(self.permits);
+        }
     }
 }
 
 /// Future that resolves when a semaphore permit is acquired
 ///
-/// This future is returned by `Semaphore::acquire()`. It will:
-/// 1. Try the fast path (atomic decrement if permits available)
-/// 2. If no permits, register the task's waker and return `Poll::Pending`
-/// 3. When a permit is released, the waker is called and the future retries
+/// This future is returned by `Semaphore::acquire()` and
+/// `Semaphore::acquire_many()`. It will:
+/// 1. Take the permits at once if enough are available and nobody is queued
+/// 2. Otherwise take a ticket at the back of the queue and return `Poll::Pending`
+/// 3. When woken at the front of the queue with enough permits, take them
 struct AcquireFuture {
     /// The semaphore from which to acquire a permit
     semaphore: Semaphore,
+    /// Number of permits to acquire
+    permits: usize,
+    /// Our place in the wait queue, once we have queued
+    ticket: Option<u64>,
 }
 
 impl Future for AcquireFuture {
     type Output = SemaphorePermit;
 
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        // Try fast path first (lock-free atomic operation)
-        if let Some(permit) = self.semaphore.try_acquire() {
-            return Poll::Ready(permit);
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let mut queue = this.semaphore.queue();
+        let first = match this.ticket {
+            None => queue.waiters.is_empty(),
+            Some(ticket) => queue.waiters.front().is_some_and(|(t, ..)| *t == ticket),
+        };
+        if first {
+            if let Some(permit) = this.semaphore.take(this.permits) {
+                if this.ticket.take().is_some() {
+                    queue.waiters.pop_front();
+                    // Permits may be left over for the next task
+                    this.semaphore.wake_front(&queue);
+                }
+                return Poll::Ready(permit);
+            }
         }
-
-        // No permits available - register waker for notification
-        self.semaphore.add_waiter(cx.waker().clone());
-
-        // Try again immediately in case a permit became available
-        // while we were registering the waker (avoid missed wakeup)
-        if let Some(permit) = self.semaphore.try_acquire() {
-            return Poll::Ready(permit);
+        match this.ticket {
+            Some(ticket) => {
+                if let Some(waiter) = queue.waiters.iter_mut().find(|(t, ..)| *t == ticket) {
+                    waiter.1.clone_from(cx.waker());
+                }
+            }
+            None => {
+                let ticket = queue.next_ticket;
+                queue.next_ticket += 1;
+                queue
+                    .waiters
+                    .push_back((ticket, cx.waker().clone(), this.permits));
+                this.ticket = Some(ticket);
+            }
         }
-
-        // No permits available, wait for wakeup
         Poll::Pending
     }
 }
 
+impl Drop for AcquireFuture {
+    fn drop(&mut self) {
+        let Some(ticket) = self.ticket else {
+            return;
+        };
+        let mut queue = self.semaphore.queue();
+        let was_first = queue.waiters.front().is_some_and(|(t, ..)| *t == ticket);
+        queue.waiters.retain(|(t, ..)| *t != ticket);
+        // We may have been woken to take the permits; pass that on
+        if was_first {
+            self.semaphore.wake_front(&queue);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::task::Wake;
+
+    /// Waker that does nothing, for polling futures by hand
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    fn noop_waker() -> Waker {
+        Waker::from(Arc::new(NoopWaker))
+    }
+
+    /// Waker that counts how often it was woken
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
 
     #[test]
     fn test_semaphore_new() {
@@ -585,6 +750,130 @@ mod tests {
         assert_eq!(sem.available_permits(), 10);
     }
 
+    #[test]
+    fn test_semaphore_try_acquire_many() {
+        let sem = Semaphore::new(5);
+
+        let permit = sem.try_acquire_many(3).unwrap();
+        assert_eq!(permit.permits(), 3);
+        assert_eq!(sem.available_permits(), 2);
+
+        // Never taken partially
+        assert!(sem.try_acquire_many(3).is_none());
+        assert_eq!(sem.available_permits(), 2);
+
+        drop(permit);
+        assert_eq!(sem.available_permits(), 5);
+    }
+
+    #[test]
+    fn test_semaphore_permit_forget() {
+        let sem = Semaphore::new(4);
+
+        sem.try_acquire_many(3).unwrap().forget();
+        assert_eq!(sem.available_permits(), 1);
+        assert_eq!(sem.in_use(), 3);
+
+        sem.add_permits(3);
+        assert_eq!(sem.available_permits(), 4);
+    }
+
+    #[test]
+    fn test_semaphore_acquire_many_waits_for_enough_permits() {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let sem = Semaphore::new(4);
+        let small = sem.try_acquire().unwrap();
+        let other = sem.try_acquire().unwrap();
+
+        let mut weighted = Box::pin(sem.acquire_many(4));
+        assert!(weighted.as_mut().poll(&mut cx).is_pending());
+
+        // One release is not enough for the weighted request; both are
+        drop(small);
+        assert!(weighted.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(sem.available_permits(), 3);
+        drop(other);
+
+        let Poll::Ready(permit) = weighted.as_mut().poll(&mut cx) else {
+            panic!("all permits were released");
+        };
+        assert_eq!(permit.permits(), 4);
+        assert_eq!(sem.available_permits(), 0);
+    }
+
+    #[test]
+    fn test_semaphore_queued_acquire_many_is_not_starved() {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let sem = Semaphore::new(4);
+        let held = sem.try_acquire().unwrap();
+        let mut weighted = Box::pin(sem.acquire_many(4));
+        assert!(weighted.as_mut().poll(&mut cx).is_pending());
+
+        // Small requests must not take the free permits ahead of it
+        assert_eq!(sem.available_permits(), 3);
+        assert!(sem.try_acquire().is_none());
+        let mut small = Box::pin(sem.acquire());
+        assert!(small.as_mut().poll(&mut cx).is_pending());
+
+        drop(held);
+        assert!(small.as_mut().poll(&mut cx).is_pending());
+        let Poll::Ready(permit) = weighted.as_mut().poll(&mut cx) else {
+            panic!("the weighted request is first in line");
+        };
+        assert!(small.as_mut().poll(&mut cx).is_pending());
+        drop(permit);
+        assert!(small.as_mut().poll(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn test_semaphore_abandoned_waiter_leaves_the_queue() {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let woken = Arc::new(CountingWaker::default());
+        let next_waker = Waker::from(Arc::clone(&woken));
+
+        let sem = Semaphore::new(1);
+        let held = sem.try_acquire().unwrap();
+        let mut abandoned = Box::pin(sem.acquire());
+        assert!(abandoned.as_mut().poll(&mut cx).is_pending());
+        let mut next = Box::pin(sem.acquire());
+        assert!(next
+            .as_mut()
+            .poll(&mut Context::from_waker(&next_waker))
+            .is_pending());
+
+        // Woken for the released permit, then dropped: the wakeup passes on
+        drop(held);
+        assert_eq!(woken.0.load(Ordering::Relaxed), 0);
+        drop(abandoned);
+        assert_eq!(woken.0.load(Ordering::Relaxed), 1);
+        let Poll::Ready(permit) = next.as_mut().poll(&mut cx) else {
+            panic!("the abandoned waiter must not block the queue");
+        };
+        drop(permit);
+
+        // Dropped before any release: nothing is left queued
+        let held = sem.try_acquire().unwrap();
+        let mut abandoned = Box::pin(sem.acquire());
+        assert!(abandoned.as_mut().poll(&mut cx).is_pending());
+        drop(abandoned);
+        drop(held);
+        assert!(sem.try_acquire().is_some());
+    }
+
+    #[compio::test]
+    async fn test_semaphore_acquire_many_clamps_to_max() {
+        let sem = Semaphore::new(2);
+        let permit = sem.acquire_many(10).await;
+        assert_eq!(permit.permits(), 2);
+        assert_eq!(sem.available_permits(), 0);
+    }
+
     #[test]
     #[should_panic(expected = "Semaphore must have at least one permit")]
     fn test_semaphore_zero_permits_panics() {
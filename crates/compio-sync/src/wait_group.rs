@@ -0,0 +1,185 @@
+//! Wait group for compio runtime
+//!
+//! Lets a task wait until a dynamic set of other tasks has finished, without
+//! collecting their join handles or futures. Each task holds a
+//! [`WaitGroupGuard`]; [`WaitGroup::wait`] completes once every guard has been
+//! dropped.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use compio_sync::WaitGroup;
+//!
+//! # async fn example() {
+//! let traversal = WaitGroup::new();
+//!
+//! for i in 0..100 {
+//!     let guard = traversal.add();
+//!     compio::runtime::spawn(async move {
+//!         println!("Visiting {}", i);
+//!         drop(guard);
+//!     })
+//!     .detach();
+//! }
+//!
+//! // Every entry has been visited; start the next phase
+//! traversal.wait().await;
+//! # }
+//! ```
+
+use crate::Notify;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// A compio-compatible wait group
+///
+/// Cloning a `WaitGroup` shares the same counter.
+///
+/// # Design
+///
+/// - **RAII guards**: `add()` returns a guard that counts as one outstanding
+///   task until dropped, so a task that fails or panics still checks out
+/// - **Nestable**: Tasks can add guards for tasks they spawn in turn; `wait()`
+///   only completes once the count reaches zero
+/// - **Reusable**: After the count drops to zero, new guards start a new round
+#[derive(Clone, Debug, Default)]
+pub struct WaitGroup {
+    /// Counter and notification shared by all clones and guards
+    inner: Arc<WaitGroupInner>,
+}
+
+/// Internal shared state for the wait group
+#[derive(Debug, Default)]
+struct WaitGroupInner {
+    /// Number of guards alive
+    count: AtomicUsize,
+    /// Notified when the count drops to zero
+    idle: Notify,
+}
+
+impl WaitGroup {
+    /// Create a new wait group with no outstanding tasks
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count one more outstanding task until the returned guard is dropped
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use compio_sync::WaitGroup;
+    ///
+    /// let group = WaitGroup::new();
+    /// let guard = group.add();
+    /// assert_eq!(group.count(), 1);
+    /// drop(guard);
+    /// assert_eq!(group.count(), 0);
+    /// ```
+    #[must_use = "the task is counted as finished as soon as the guard is dropped"]
+    pub fn add(&self) -> WaitGroupGuard {
+        self.inner.count.fetch_add(1, Ordering::AcqRel);
+        WaitGroupGuard {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+
+    /// Number of outstanding tasks
+    #[must_use]
+    pub fn count(&self) -> usize {
+        self.inner.count.load(Ordering::Acquire)
+    }
+
+    /// Wait until every guard has been dropped
+    ///
+    /// Completes immediately if there are no outstanding tasks.
+    pub async fn wait(&self) {
+        loop {
+            // Register before checking, so a drop to zero in between is seen
+            let idle = self.inner.idle.notified();
+            if self.count() == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+}
+
+/// Guard for one outstanding task of a [`WaitGroup`]
+///
+/// Returned by `WaitGroup::add()`. Dropping it marks the task as finished.
+#[derive(Debug)]
+pub struct WaitGroupGuard {
+    /// State of the wait group this guard belongs to
+    inner: Arc<WaitGroupInner>,
+}
+
+impl Drop for WaitGroupGuard {
+    fn drop(&mut self) {
+        if self.inner.count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.inner.idle.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[compio::test]
+    async fn test_wait_group_empty() {
+        let group = WaitGroup::new();
+        assert_eq!(group.count(), 0);
+        group.wait().await;
+    }
+
+    #[compio::test]
+    async fn test_wait_group_waits_for_all_tasks() {
+        let group = WaitGroup::new();
+        let finished = Arc::new(AtomicUsize::new(0));
+
+        for _ in 0..50 {
+            let guard = group.add();
+            let finished = finished.clone();
+            compio::runtime::spawn(async move {
+                finished.fetch_add(1, Ordering::SeqCst);
+                drop(guard);
+            })
+            .detach();
+        }
+
+        group.wait().await;
+        assert_eq!(finished.load(Ordering::SeqCst), 50);
+        assert_eq!(group.count(), 0);
+    }
+
+    #[compio::test]
+    async fn test_wait_group_nested_tasks() {
+        let group = WaitGroup::new();
+        let finished = Arc::new(AtomicUsize::new(0));
+
+        for _ in 0..5 {
+            let guard = group.add();
+            let group = group.clone();
+            let finished = finished.clone();
+            compio::runtime::spawn(async move {
+                // Children are counted before the parent checks out
+                for _ in 0..5 {
+                    let child = group.add();
+                    let finished = finished.clone();
+                    compio::runtime::spawn(async move {
+                        finished.fetch_add(1, Ordering::SeqCst);
+                        drop(child);
+                    })
+                    .detach();
+                }
+                drop(guard);
+            })
+            .detach();
+        }
+
+        group.wait().await;
+        assert_eq!(finished.load(Ordering::SeqCst), 25);
+    }
+}
@@ -26,6 +26,19 @@ error-io-error = I/O error
 error-invalid-path = Invalid path
 error-source-not-exists = Source does not exist
 error-destination-exists = Destination already exists
+error-io-uring = io_uring operation failed
+error-copy-failed = Copy operation failed
+error-metadata-failed = Metadata operation failed
+error-directory-traversal = Directory traversal failed
+error-invalid-config = Invalid configuration
+error-read-only-filesystem = Destination is on a read-only file system
+error-file-system = File system error
+error-fd-exhaustion = Too many open files
+error-internal = Internal error
+
+## Warning Messages
+warn-ownership-skipped = Ownership could not be preserved for { $count } entries
+warn-crtimes-skipped = Creation times could not be preserved for { $count } files (the destination filesystem sets them on creation)
 
 ## Info Messages
 info-starting-copy = Starting copy operation
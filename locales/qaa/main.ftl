@@ -28,6 +28,19 @@ error-io-error = SHIVER ME TIMBERS! The ship's sprung a leak and takin' on water
 error-invalid-path = AHOY! That route through these treacherous waters don't make no sense! Check yer compass!
 error-source-not-exists = BLIMEY! That source treasure be lost to Davy Jones' locker! Gone to the depths, it has!
 error-destination-exists = AVAST! Another crew already claimed that spot fer their plunder! Find yerself another hideout, arrr!
+error-io-uring = ARRR! The ship's riggin' (io_uring) snapped clean in two!
+error-copy-failed = BLIMEY! The treasure slipped overboard while we was haulin' it!
+error-metadata-failed = AVAST! The secret treasure marks be smudged beyond readin'!
+error-directory-traversal = SHIVER ME TIMBERS! Lost our way in the hold's twisty passages!
+error-invalid-config = AHOY! The cap'n's orders make no sense, ye bilge rat!
+error-read-only-filesystem = AVAST! That vault be sealed shut by the Admiralty (read-only)! No plunder can be stowed there!
+error-file-system = BLOW ME DOWN! The treasure vault itself be cursed (file system error)!
+error-fd-exhaustion = ARRR! Too many chests open at once, the crew's hands be full!
+error-internal = SCURVY! Somethin' be rotten in the ship's own timbers (internal error)!
+
+## Warning Messages
+warn-ownership-skipped = AVAST! Couldn't mark the rightful owners on { $count } pieces o' plunder!
+warn-crtimes-skipped = ARRR! The birth dates o' { $count } treasures be lost, the new vault stamps its own!
 
 ## Info Messages
 info-starting-copy = AHOY, ME HEARTIES! Weighin' anchor and settin' sail fer the grandest plunderin' expedition on the high seas! Yo ho ho!
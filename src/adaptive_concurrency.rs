@@ -3,13 +3,148 @@
 //! This module provides self-adaptive concurrency control that automatically
 //! adjusts the number of concurrent operations based on resource availability,
 //! particularly file descriptor exhaustion.
+//!
+//! It also sizes the effective queue depth from completion latency. Every
+//! ring (one per dispatcher worker thread, plus the main thread) keeps a
+//! smoothed latency of its read, write and `copy_file_range` completions and
+//! the lowest value it has seen. When a ring's recent latency exceeds twice
+//! its baseline the device is queueing requests rather than serving them,
+//! so the number of operations in flight is halved; otherwise it grows
+//! additively back towards `--max-files-in-flight` (AIMD, as in TCP
+//! congestion control).
 
 use crate::directory::SharedSemaphore;
 use crate::error::SyncError;
+use std::cell::Cell;
 use std::io::ErrorKind;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::Arc;
-use tracing::warn;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Completions a ring must record before its latency is trusted
+const MIN_SAMPLES: u64 = 16;
+
+/// Recent latency above this multiple of the baseline signals congestion
+const CONGESTION_FACTOR: u32 = 2;
+
+/// Completions between two adjustments of the in-flight limit
+const ADJUST_WINDOW: usize = 32;
+
+thread_local! {
+    /// Completion latency of the ring owned by this thread
+    static RING_LATENCY: Cell<RingLatency> = const { Cell::new(RingLatency::new()) };
+}
+
+/// Record how long an I/O submitted on this thread's ring took to complete
+pub fn record_completion(elapsed: Duration) {
+    RING_LATENCY.with(|ring| {
+        let mut latency = ring.get();
+        latency.record(elapsed);
+        ring.set(latency);
+    });
+}
+
+/// Smoothed completion latency of one ring
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RingLatency {
+    /// Exponentially weighted moving average of recent completions
+    recent: Duration,
+    /// Lowest smoothed latency seen, drifting slowly towards `recent`
+    baseline: Duration,
+    /// Completions recorded
+    samples: u64,
+}
+
+impl RingLatency {
+    /// No completions yet
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            recent: Duration::ZERO,
+            baseline: Duration::ZERO,
+            samples: 0,
+        }
+    }
+
+    /// Add a completion that took `elapsed`
+    pub fn record(&mut self, elapsed: Duration) {
+        if self.samples == 0 {
+            self.recent = elapsed;
+            self.baseline = elapsed;
+        } else {
+            // Weight 1/8 for the new sample, like TCP's smoothed RTT
+            self.recent = (self.recent * 7 + elapsed) / 8;
+            if self.recent < self.baseline {
+                self.baseline = self.recent;
+            } else {
+                // Let the baseline follow lasting changes (larger chunks,
+                // a different device) instead of staying at an old minimum
+                self.baseline += (self.recent - self.baseline) / 256;
+            }
+        }
+        self.samples += 1;
+    }
+
+    /// Whether recent completions are markedly slower than the baseline
+    #[must_use]
+    pub fn congested(&self) -> bool {
+        self.samples >= MIN_SAMPLES && self.recent > self.baseline * CONGESTION_FACTOR
+    }
+}
+
+impl Default for RingLatency {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Additive-increase/multiplicative-decrease limit on operations in flight
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AimdLimit {
+    limit: usize,
+    min: usize,
+    max: usize,
+    step: usize,
+    since_change: usize,
+}
+
+impl AimdLimit {
+    /// Start at `max`, never going below `min`
+    #[must_use]
+    pub fn new(min: usize, max: usize) -> Self {
+        Self {
+            limit: max,
+            min: min.min(max),
+            max,
+            step: (max / 32).max(1),
+            since_change: 0,
+        }
+    }
+
+    /// Current limit
+    #[must_use]
+    pub const fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Account for one completion and return the (possibly new) limit
+    pub fn on_completion(&mut self, congested: bool) -> usize {
+        self.since_change += 1;
+        if self.since_change >= ADJUST_WINDOW {
+            let limit = if congested {
+                (self.limit / 2).max(self.min)
+            } else {
+                (self.limit + self.step).min(self.max)
+            };
+            if limit != self.limit {
+                self.limit = limit;
+                self.since_change = 0;
+            }
+        }
+        self.limit
+    }
+}
 
 /// Adaptive concurrency controller that responds to resource constraints
 ///
@@ -26,6 +161,10 @@ pub struct AdaptiveConcurrencyController {
     emfile_warned: Arc<AtomicBool>,
     /// Minimum permits to maintain
     min_permits: usize,
+    /// Latency-driven limit, unless adaptation is disabled
+    aimd: Option<Arc<Mutex<AimdLimit>>>,
+    /// Permits taken out of the semaphore to enforce the AIMD limit
+    withheld: Arc<AtomicUsize>,
 }
 
 impl AdaptiveConcurrencyController {
@@ -34,8 +173,9 @@ impl AdaptiveConcurrencyController {
     /// # Arguments
     ///
     /// * `initial_permits` - Starting number of concurrent operations allowed
+    /// * `latency_adaptive` - Whether to size concurrency from completion latency
     #[must_use]
-    pub fn new(initial_permits: usize) -> Self {
+    pub fn new(initial_permits: usize, latency_adaptive: bool) -> Self {
         let min_permits = std::cmp::max(10, initial_permits / 10); // Never go below 10 or 10%
 
         Self {
@@ -43,6 +183,9 @@ impl AdaptiveConcurrencyController {
             emfile_errors: Arc::new(AtomicUsize::new(0)),
             emfile_warned: Arc::new(AtomicBool::new(false)),
             min_permits,
+            aimd: latency_adaptive
+                .then(|| Arc::new(Mutex::new(AimdLimit::new(min_permits, initial_permits)))),
+            withheld: Arc::new(AtomicUsize::new(0)),
         }
     }
 
@@ -51,6 +194,37 @@ impl AdaptiveConcurrencyController {
         self.semaphore.acquire().await
     }
 
+    /// Adjust the in-flight limit after an operation finished on this thread
+    ///
+    /// Uses the latency of the ring the operation ran on.
+    pub fn record_completion(&self) {
+        let Some(aimd) = &self.aimd else {
+            return;
+        };
+        let congested = RING_LATENCY.with(|ring| ring.get().congested());
+        let Ok(mut aimd) = aimd.lock() else {
+            return;
+        };
+        let before = aimd.limit();
+        let limit = aimd.on_completion(congested);
+        if limit != before {
+            debug!("Adjusting operations in flight from completion latency: {before} → {limit}");
+        }
+
+        // Withhold permits above the limit; ones in use are taken back on
+        // later completions as they are released
+        let target = self.semaphore.max_permits().saturating_sub(limit);
+        let withheld = self.withheld.load(Ordering::Relaxed);
+        if withheld < target {
+            let reduced = self.semaphore.reduce_permits(target - withheld);
+            self.withheld.fetch_add(reduced, Ordering::Relaxed);
+        } else if withheld > target {
+            self.semaphore.add_permits(withheld - target);
+            self.withheld
+                .fetch_sub(withheld - target, Ordering::Relaxed);
+        }
+    }
+
     /// Handle an error, checking if it's EMFILE and adapting if needed
     ///
     /// Returns true if this is an EMFILE error and concurrency was reduced
@@ -127,8 +301,14 @@ impl AdaptiveConcurrencyController {
         ConcurrencyStats {
             max_permits: self.semaphore.max_permits(),
             available_permits: self.semaphore.available_permits(),
-            in_use: self.semaphore.max_permits() - self.semaphore.available_permits(),
+            in_use: self.semaphore.max_permits()
+                - self.semaphore.available_permits()
+                - self.withheld.load(Ordering::Relaxed),
             emfile_errors: self.emfile_errors.load(Ordering::Relaxed),
+            latency_limit: self
+                .aimd
+                .as_ref()
+                .and_then(|aimd| aimd.lock().ok().map(|aimd| aimd.limit())),
         }
     }
 }
@@ -145,6 +325,8 @@ pub struct ConcurrencyStats {
     pub in_use: usize,
     /// Number of EMFILE errors encountered
     pub emfile_errors: usize,
+    /// Limit set from completion latency, if latency adaptation is on
+    pub latency_limit: Option<usize>,
 }
 
 /// Check system file descriptor limits and warn if too low
@@ -195,3 +377,83 @@ pub fn check_fd_limits() -> std::io::Result<u64> {
 pub fn is_emfile_error(error: &std::io::Error) -> bool {
     error.kind() == ErrorKind::Other && error.raw_os_error() == Some(libc::EMFILE)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_latency_detects_congestion() {
+        let mut ring = RingLatency::new();
+        for _ in 0..MIN_SAMPLES {
+            ring.record(Duration::from_micros(100));
+        }
+        assert!(!ring.congested());
+
+        for _ in 0..16 {
+            ring.record(Duration::from_millis(5));
+        }
+        assert!(ring.congested());
+
+        // Latency recovers once the queue drains
+        for _ in 0..64 {
+            ring.record(Duration::from_micros(100));
+        }
+        assert!(!ring.congested());
+    }
+
+    #[test]
+    fn test_aimd_limit() {
+        let mut aimd = AimdLimit::new(10, 320);
+        assert_eq!(aimd.limit(), 320);
+
+        // Congestion halves the limit once per window, down to the minimum
+        for _ in 0..ADJUST_WINDOW {
+            aimd.on_completion(true);
+        }
+        assert_eq!(aimd.limit(), 160);
+        for _ in 0..ADJUST_WINDOW * 10 {
+            aimd.on_completion(true);
+        }
+        assert_eq!(aimd.limit(), 10);
+
+        // Without congestion it grows back by a step per window
+        for _ in 0..ADJUST_WINDOW {
+            aimd.on_completion(false);
+        }
+        assert_eq!(aimd.limit(), 20);
+        for _ in 0..ADJUST_WINDOW * 100 {
+            aimd.on_completion(false);
+        }
+        assert_eq!(aimd.limit(), 320);
+    }
+
+    #[test]
+    fn test_controller_withholds_permits() {
+        let controller = AdaptiveConcurrencyController::new(320, true);
+        RING_LATENCY.with(|ring| {
+            let mut latency = RingLatency::new();
+            for _ in 0..MIN_SAMPLES {
+                latency.record(Duration::from_micros(100));
+            }
+            for _ in 0..16 {
+                latency.record(Duration::from_millis(5));
+            }
+            ring.set(latency);
+        });
+        for _ in 0..ADJUST_WINDOW {
+            controller.record_completion();
+        }
+        let stats = controller.stats();
+        assert_eq!(stats.latency_limit, Some(160));
+        assert_eq!(stats.available_permits, 160);
+        assert_eq!(stats.in_use, 0);
+
+        // Disabled adaptation leaves the permits alone
+        let fixed = AdaptiveConcurrencyController::new(320, false);
+        for _ in 0..ADJUST_WINDOW {
+            fixed.record_completion();
+        }
+        assert_eq!(fixed.stats().available_permits, 320);
+    }
+}
@@ -0,0 +1,167 @@
+//! `--cpu-affinity`: pinning the copy threads to CPUs
+//!
+//! Each dispatcher worker owns its own `io_uring` instance, so pinning a
+//! worker pins its ring. With `--cpu-affinity`, one worker is started per
+//! selected CPU and bound to that CPU alone, and the main thread (which
+//! walks the tree and runs its own ring) is bound to the whole set. The set
+//! is either a CPU list in the format of `taskset -c` (`0-3,8`) or
+//! `node:N` for every CPU of NUMA node `N`, which keeps the copy next to the
+//! memory and devices attached to that node.
+//!
+//! Worker rings are set up like the main thread's, by
+//! [`proactor_builder`].
+//!
+//! There is no flag for `IORING_SETUP_SQ_AFF` (`sq_thread_cpu`): the rings
+//! do not use `SQPOLL`, so there is no kernel polling thread to pin, and
+//! compio's `ProactorBuilder` exposes only `sqpoll_idle`, with no way to set
+//! the polling thread's CPU. Submission happens on the pinned worker itself.
+
+use crate::cli::{Args, CpuAffinity};
+use crate::error::{Result, SyncError};
+use crate::io_uring::proactor_builder;
+use compio::dispatcher::Dispatcher;
+use std::collections::HashSet;
+use std::num::NonZeroUsize;
+
+/// Parse a CPU list such as `0-3,8`
+///
+/// # Errors
+///
+/// Returns a description of the problem if `s` is not a valid list.
+pub fn parse_cpu_list(s: &str) -> std::result::Result<Vec<usize>, String> {
+    let mut cpus = Vec::new();
+    for part in s.trim().split(',') {
+        let number = |n: &str| {
+            n.trim()
+                .parse::<usize>()
+                .map_err(|_| format!("invalid CPU list {s:?}: {n:?} is not a CPU number"))
+        };
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (number(first)?, number(last)?);
+                if first > last {
+                    return Err(format!("invalid CPU list {s:?}: empty range {part:?}"));
+                }
+                cpus.extend(first..=last);
+            }
+            None => cpus.push(number(part)?),
+        }
+    }
+    cpus.sort_unstable();
+    cpus.dedup();
+    Ok(cpus)
+}
+
+/// CPUs of NUMA node `node`, from sysfs
+fn node_cpus(node: usize) -> Result<Vec<usize>> {
+    let path = format!("/sys/devices/system/node/node{node}/cpulist");
+    let list = std::fs::read_to_string(&path)
+        .map_err(|e| SyncError::InvalidConfig(format!("NUMA node {node} not found: {e}")))?;
+    let cpus = parse_cpu_list(&list).map_err(SyncError::InvalidConfig)?;
+    if cpus.is_empty() {
+        return Err(SyncError::InvalidConfig(format!(
+            "NUMA node {node} has no CPUs"
+        )));
+    }
+    Ok(cpus)
+}
+
+/// CPUs selected by `--cpu-affinity`, or `None` without it
+///
+/// # Errors
+///
+/// Returns an error if a NUMA node cannot be resolved.
+pub fn cpus(args: &Args) -> Result<Option<Vec<usize>>> {
+    match &args.cpu_affinity {
+        None => Ok(None),
+        Some(CpuAffinity::Cpus(cpus)) => Ok(Some(cpus.clone())),
+        Some(CpuAffinity::Node(node)) => node_cpus(*node).map(Some),
+    }
+}
+
+/// Bind the calling thread to `cpus`
+fn pin_current_thread(cpus: &[usize]) -> Result<()> {
+    // SAFETY: cpu_set_t is plain old data
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let max = usize::try_from(libc::CPU_SETSIZE).unwrap_or(usize::MAX);
+    for &cpu in cpus {
+        if cpu >= max {
+            return Err(SyncError::InvalidConfig(format!(
+                "CPU {cpu} is out of range"
+            )));
+        }
+        // SAFETY: cpu is below CPU_SETSIZE
+        unsafe { libc::CPU_SET(cpu, &mut set) };
+    }
+    // SAFETY: set is a valid cpu_set_t of the size passed
+    let result = unsafe {
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &raw const set)
+    };
+    if result != 0 {
+        return Err(SyncError::InvalidConfig(format!(
+            "Cannot pin to CPUs {cpus:?}: {}",
+            std::io::Error::last_os_error()
+        )));
+    }
+    Ok(())
+}
+
+/// Bind the calling (main) thread to the `--cpu-affinity` set, if any
+///
+/// # Errors
+///
+/// Returns an error if the set cannot be resolved or none of its CPUs is
+/// available.
+pub fn pin_main_thread(args: &Args) -> Result<()> {
+    if let Some(cpus) = cpus(args)? {
+        pin_current_thread(&cpus)?;
+        tracing::info!("Pinned to CPUs {cpus:?}");
+    }
+    Ok(())
+}
+
+/// Dispatcher for the copy tasks, with one pinned worker per CPU under
+/// `--cpu-affinity`
+///
+/// # Errors
+///
+/// Returns an error if the set cannot be resolved or the worker threads
+/// cannot be started.
+pub fn dispatcher(args: &Args) -> Result<Dispatcher> {
+    let queue_depth = crate::memlock::init(args).queue_depth;
+    let builder = Dispatcher::builder().proactor_builder(proactor_builder(queue_depth));
+    let Some(cpus) = cpus(args)? else {
+        return Ok(builder.build()?);
+    };
+    let Some(workers) = NonZeroUsize::new(cpus.len()) else {
+        return Ok(builder.build()?);
+    };
+    Ok(builder
+        .worker_threads(workers)
+        .thread_names(|index| format!("arsync-worker-{index}"))
+        .thread_affinity(move |index| HashSet::from([cpus[index % cpus.len()]]))
+        .build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cpu_list() {
+        assert_eq!(parse_cpu_list("0-3,8"), Ok(vec![0, 1, 2, 3, 8]));
+        assert_eq!(parse_cpu_list("5,1,1\n"), Ok(vec![1, 5]));
+        assert!(parse_cpu_list("3-1").is_err());
+        assert!(parse_cpu_list("a").is_err());
+        assert!(parse_cpu_list("").is_err());
+    }
+
+    #[test]
+    fn test_node_zero_has_cpus() {
+        // Every Linux system with NUMA sysfs has node 0
+        if std::path::Path::new("/sys/devices/system/node/node0").exists() {
+            assert!(!node_cpus(0).unwrap().is_empty());
+        }
+        assert!(node_cpus(usize::MAX).is_err());
+    }
+}
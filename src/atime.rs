@@ -0,0 +1,204 @@
+//! Keeping source access times untouched while reading
+//!
+//! Reading a file normally updates its access time, so a backup run would
+//! leave every source file looking freshly used. Source files are opened with
+//! `O_NOATIME` to avoid that. The kernel only allows the flag to the file's
+//! owner or a process with `CAP_FOWNER`; for anyone else the open fails with
+//! `EPERM` and is retried without it.
+//!
+//! Whether a plain read changes the access time also depends on the source
+//! mount: `noatime` never updates it, `relatime` (the Linux default) only
+//! when it is older than the modification time or a day old, and
+//! `strictatime` on every read. [`report`] logs which strategy the run
+//! ended up using together with the mount's policy.
+
+use compio_fs_extended::directory::DirectoryFd;
+use compio_fs_extended::ExtendedError;
+use std::ffi::CString;
+use std::io::ErrorKind;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::info;
+
+/// Source opens that used `O_NOATIME`
+static NOATIME_OPENS: AtomicU64 = AtomicU64::new(0);
+
+/// Source opens that fell back to a plain open (`EPERM`)
+static PLAIN_OPENS: AtomicU64 = AtomicU64::new(0);
+
+/// When the source mount updates access times on read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AtimePolicy {
+    /// `noatime`: reads never update access times
+    Never,
+    /// `relatime`: only when the access time is older than the modification
+    /// time or more than a day old
+    Relative,
+    /// `strictatime`: every read updates the access time
+    Always,
+}
+
+impl AtimePolicy {
+    /// Policy of the filesystem mounted at or above `path`
+    #[must_use]
+    pub fn of_path(path: &Path) -> Option<Self> {
+        let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+        // SAFETY: statvfs is plain old data
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        // SAFETY: both pointers are valid for the duration of the call
+        if unsafe { libc::statvfs(c_path.as_ptr(), &raw mut stat) } != 0 {
+            return None;
+        }
+        Some(Self::from_mount_flags(stat.f_flag))
+    }
+
+    /// Policy for the `f_flag` bits reported by `statvfs`
+    #[must_use]
+    pub const fn from_mount_flags(flags: libc::c_ulong) -> Self {
+        if flags & libc::ST_NOATIME != 0 {
+            Self::Never
+        } else if flags & libc::ST_RELATIME != 0 {
+            Self::Relative
+        } else {
+            Self::Always
+        }
+    }
+
+    /// Mount option name
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Never => "noatime",
+            Self::Relative => "relatime",
+            Self::Always => "strictatime",
+        }
+    }
+}
+
+/// Open a source file for reading without updating its access time
+///
+/// Falls back to a plain open when `O_NOATIME` is not permitted.
+///
+/// # Errors
+///
+/// Returns the error from opening the file.
+#[allow(clippy::future_not_send)]
+pub async fn open_source(path: &Path) -> std::io::Result<compio::fs::File> {
+    let result = compio::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOATIME)
+        .open(path)
+        .await;
+    match result {
+        Ok(file) => {
+            NOATIME_OPENS.fetch_add(1, Ordering::Relaxed);
+            Ok(file)
+        }
+        Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+            let file = compio::fs::File::open(path).await?;
+            PLAIN_OPENS.fetch_add(1, Ordering::Relaxed);
+            Ok(file)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Open the source file `name` of `dir` for reading without updating its
+/// access time
+///
+/// Like [`open_source`], but relative to an open directory.
+///
+/// # Errors
+///
+/// Returns the error from opening the file.
+#[allow(clippy::future_not_send)]
+pub async fn open_source_at(dir: &DirectoryFd, name: &Path) -> std::io::Result<compio::fs::File> {
+    let into_io = |e: ExtendedError| match e {
+        ExtendedError::Io(e) => e,
+        e => std::io::Error::other(e.to_string()),
+    };
+    match dir.open_file_at_with_flags(name, libc::O_NOATIME).await {
+        Ok(file) => {
+            NOATIME_OPENS.fetch_add(1, Ordering::Relaxed);
+            Ok(file)
+        }
+        Err(ExtendedError::Io(e)) if e.kind() == ErrorKind::PermissionDenied => {
+            let file = dir.open_file_at(name).await.map_err(into_io)?;
+            PLAIN_OPENS.fetch_add(1, Ordering::Relaxed);
+            Ok(file)
+        }
+        Err(e) => Err(into_io(e)),
+    }
+}
+
+/// Number of source opens with and without `O_NOATIME` so far
+#[must_use]
+pub fn open_counts() -> (u64, u64) {
+    (
+        NOATIME_OPENS.load(Ordering::Relaxed),
+        PLAIN_OPENS.load(Ordering::Relaxed),
+    )
+}
+
+/// Log how source access times were protected during the run
+pub fn report(source: &Path) {
+    let (noatime, plain) = open_counts();
+    let policy = AtimePolicy::of_path(source).map_or("unknown", AtimePolicy::name);
+    let refused = if plain > 0 {
+        "; refused for files this user does not own"
+    } else {
+        ""
+    };
+    info!(
+        "Source access times: O_NOATIME on {noatime} of {} source opens{refused} \
+         (source mount: {policy})",
+        noatime + plain
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_policy_from_mount_flags() {
+        assert_eq!(
+            AtimePolicy::from_mount_flags(libc::ST_NOATIME | libc::ST_RELATIME),
+            AtimePolicy::Never
+        );
+        assert_eq!(
+            AtimePolicy::from_mount_flags(libc::ST_RELATIME),
+            AtimePolicy::Relative
+        );
+        assert_eq!(AtimePolicy::from_mount_flags(0), AtimePolicy::Always);
+    }
+
+    #[compio::test]
+    async fn test_open_source_leaves_atime() {
+        use compio::io::AsyncReadAt;
+        use std::os::unix::fs::MetadataExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("file.txt");
+        std::fs::write(&path, "content").unwrap();
+        // An access time older than the mtime would be updated even under relatime
+        let old = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_times(std::fs::FileTimes::new().set_accessed(old))
+            .unwrap();
+        let before = std::fs::metadata(&path).unwrap().atime();
+
+        let file = open_source(&path).await.unwrap();
+        let read = file.read_at(vec![0u8; 16], 0).await.0.unwrap();
+        assert_eq!(read, 7);
+        drop(file);
+
+        assert_eq!(std::fs::metadata(&path).unwrap().atime(), before);
+        assert!(open_counts().0 > 0);
+    }
+}
@@ -0,0 +1,149 @@
+//! Long-running soak test with fault injection
+//!
+//! Repeats randomized cycles against a scratch directory until the time
+//! budget runs out:
+//!
+//! 1. mutate the source tree (new, rewritten and hardlinked files),
+//! 2. sync with faults injected (`ARSYNC_FAULTS`, failures are expected),
+//! 3. sync again with faults switched off, which must succeed,
+//! 4. verify every source file arrived intact.
+//!
+//! Options such as `--delay-updates` and `-H` are chosen at random each
+//! cycle. On a mismatch the cycle, seed and path are printed and the process
+//! exits with status 1, so a failing run can be replayed with `--seed`.
+//!
+//! ```text
+//! ARSYNC_FAULTS=eio=0.01,short=0.2,delay=0.01 \
+//!     cargo run --release --features fault-injection --bin arsync-soak -- --duration 3600
+//! ```
+
+use arsync::cli::{Args, CopyMethod};
+use arsync::fault::{self, Rng};
+use clap::Parser;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Soak-test arsync with injected I/O faults
+#[derive(Debug, Parser)]
+#[command(name = "arsync-soak")]
+struct SoakArgs {
+    /// How long to keep running cycles, in seconds
+    #[arg(long, default_value_t = 60)]
+    duration: u64,
+
+    /// Seed for the generated trees and option choices
+    #[arg(long, default_value_t = 1)]
+    seed: u64,
+
+    /// Number of files to keep in the source tree
+    #[arg(long, default_value_t = 200)]
+    files: u64,
+
+    /// Scratch directory (a temporary one is created if not given)
+    #[arg(long)]
+    workdir: Option<PathBuf>,
+}
+
+#[compio::main]
+async fn main() {
+    let soak = SoakArgs::parse();
+    let workdir = soak
+        .workdir
+        .clone()
+        .unwrap_or_else(|| std::env::temp_dir().join(format!("arsync-soak-{}", soak.seed)));
+    let src = workdir.join("src");
+    let dst = workdir.join("dst");
+    let _ = std::fs::remove_dir_all(&workdir);
+    std::fs::create_dir_all(&src).expect("create source directory");
+
+    let mut rng = Rng::new(soak.seed);
+    let deadline = Instant::now() + Duration::from_secs(soak.duration);
+    let mut cycle = 0u64;
+    while cycle == 0 || Instant::now() < deadline {
+        cycle += 1;
+        mutate_tree(&src, soak.files, &mut rng);
+
+        let args = Args {
+            source: src.clone(),
+            destination: dst.clone(),
+            recursive: true,
+            copy_method: CopyMethod::ReadWrite,
+            delay_updates: rng.chance(0.5),
+            hard_links: rng.chance(0.5),
+            tmpfile: rng.chance(0.5),
+            ..Args::default()
+        };
+
+        fault::set_enabled(true);
+        let faulty = arsync::sync::sync_files(&args).await;
+        fault::set_enabled(false);
+        if let Err(e) = arsync::sync::sync_files(&args).await {
+            fail(cycle, soak.seed, &format!("clean run failed: {e}"));
+        }
+        if let Err(problem) = verify(&src, &dst) {
+            fail(cycle, soak.seed, &problem);
+        }
+
+        println!(
+            "cycle {cycle}: ok (faulty run {}, {} faults injected so far, delay-updates={}, hard-links={}, tmpfile={})",
+            if faulty.is_ok() { "completed" } else { "aborted" },
+            fault::injected(),
+            args.delay_updates,
+            args.hard_links,
+            args.tmpfile,
+        );
+    }
+
+    println!("{cycle} cycles passed");
+    let _ = std::fs::remove_dir_all(&workdir);
+}
+
+/// Report a failed cycle and exit
+fn fail(cycle: u64, seed: u64, problem: &str) -> ! {
+    eprintln!("cycle {cycle} (seed {seed}) failed: {problem}");
+    std::process::exit(1);
+}
+
+/// Randomly add, rewrite and hardlink files, keeping about `files` entries
+fn mutate_tree(src: &Path, files: u64, rng: &mut Rng) {
+    for _ in 0..=files / 4 {
+        let dir = src.join(format!("d{}", rng.below(8)));
+        std::fs::create_dir_all(&dir).expect("create directory");
+        let path = dir.join(format!("f{}", rng.below(files)));
+        if rng.chance(0.1) {
+            // Link to another file in the tree, replacing whatever was there
+            let target = src
+                .join(format!("d{}", rng.below(8)))
+                .join(format!("f{}", rng.below(files)));
+            if target.is_file() && target != path {
+                let _ = std::fs::remove_file(&path);
+                let _ = std::fs::hard_link(&target, &path);
+                continue;
+            }
+        }
+        let len = usize::try_from(rng.below(256 * 1024)).unwrap_or(0);
+        let content: Vec<u8> = (0..len).map(|_| rng.next_u64() as u8).collect();
+        // Rewrite through a new inode so existing hardlinks keep their content
+        let _ = std::fs::remove_file(&path);
+        std::fs::write(&path, content).expect("write source file");
+    }
+}
+
+/// Check that every source file exists in the destination with the same bytes
+fn verify(src: &Path, dst: &Path) -> Result<(), String> {
+    for entry in std::fs::read_dir(src).map_err(|e| e.to_string())? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let from = entry.path();
+        let to = dst.join(entry.file_name());
+        if from.is_dir() {
+            verify(&from, &to)?;
+        } else {
+            let expected = std::fs::read(&from).map_err(|e| e.to_string())?;
+            let actual = std::fs::read(&to).map_err(|e| format!("{}: {e}", to.display()))?;
+            if expected != actual {
+                return Err(format!("{} differs from its source", to.display()));
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,365 @@
+//! Byte budgets for copy buffers in flight
+//!
+//! Every read/write copy allocates a buffer per chunk. With many large files
+//! in flight those buffers add up, so `--max-memory` caps the total: a copy
+//! reserves its chunk's bytes from a [`ByteBudget`] before allocating the
+//! buffer and returns them once the chunk is written. When the budget is
+//! exhausted the copy waits, in arrival order, until enough bytes are
+//! released.
+//!
+//! `--max-device-bytes` adds a budget per block device, shared by every
+//! copy reading from or writing to it, so a single slow disk cannot
+//! accumulate gigabytes of queued I/O while faster ones stay busy.
+//!
+//! A single reservation is never larger than the whole budget (bigger
+//! requests are clamped), so a copy can always make progress once the
+//! others have released their buffers.
+
+use crate::cli::Args;
+#[allow(clippy::disallowed_types)]
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// Budget for `--max-memory`, if set
+static MEMORY: Mutex<Option<Arc<ByteBudget>>> = Mutex::new(None);
+
+/// Per-device budgets for `--max-device-bytes`, if set
+static DEVICES: Mutex<Option<DeviceBudgets>> = Mutex::new(None);
+
+/// A pool of bytes that tasks reserve from and wait on, first come first served
+#[derive(Debug)]
+pub struct ByteBudget {
+    total: u64,
+    state: Mutex<BudgetState>,
+}
+
+#[derive(Debug)]
+struct BudgetState {
+    available: u64,
+    next_ticket: u64,
+    /// Tickets and wakers of waiting reservations, in arrival order
+    waiters: VecDeque<(u64, Waker)>,
+}
+
+impl ByteBudget {
+    /// Budget of `total` bytes
+    #[must_use]
+    pub const fn new(total: u64) -> Self {
+        Self {
+            total,
+            state: Mutex::new(BudgetState {
+                available: total,
+                next_ticket: 0,
+                waiters: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Total size of the budget
+    #[must_use]
+    pub const fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Bytes not currently reserved
+    #[must_use]
+    #[allow(dead_code)] // Used by tests and budget monitoring
+    pub fn available(&self) -> u64 {
+        self.state.lock().map_or(0, |state| state.available)
+    }
+
+    /// Wait until `bytes` (at most the whole budget) can be reserved
+    pub fn reserve(self: &Arc<Self>, bytes: u64) -> Reserve {
+        Reserve {
+            budget: Arc::clone(self),
+            bytes: bytes.min(self.total),
+            ticket: None,
+        }
+    }
+
+    fn release(&self, bytes: u64) {
+        if let Ok(mut state) = self.state.lock() {
+            state.available += bytes;
+            if let Some((_, waker)) = state.waiters.front() {
+                waker.wake_by_ref();
+            }
+        }
+    }
+}
+
+/// Future returned by [`ByteBudget::reserve`]
+#[derive(Debug)]
+pub struct Reserve {
+    budget: Arc<ByteBudget>,
+    bytes: u64,
+    ticket: Option<u64>,
+}
+
+impl Future for Reserve {
+    type Output = Reservation;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Reservation> {
+        let this = &mut *self;
+        let Ok(mut state) = this.budget.state.lock() else {
+            // A poisoned budget no longer limits anything
+            return Poll::Ready(Reservation {
+                budget: None,
+                bytes: 0,
+            });
+        };
+        let first = match this.ticket {
+            None => state.waiters.is_empty(),
+            Some(ticket) => state.waiters.front().is_some_and(|(t, _)| *t == ticket),
+        };
+        if first && state.available >= this.bytes {
+            state.available -= this.bytes;
+            if this.ticket.take().is_some() {
+                state.waiters.pop_front();
+            }
+            // The next waiter may fit in what is left
+            if let Some((_, waker)) = state.waiters.front() {
+                waker.wake_by_ref();
+            }
+            return Poll::Ready(Reservation {
+                budget: Some(Arc::clone(&this.budget)),
+                bytes: this.bytes,
+            });
+        }
+        match this.ticket {
+            Some(ticket) => {
+                if let Some(waiter) = state.waiters.iter_mut().find(|(t, _)| *t == ticket) {
+                    waiter.1.clone_from(cx.waker());
+                }
+            }
+            None => {
+                let ticket = state.next_ticket;
+                state.next_ticket += 1;
+                state.waiters.push_back((ticket, cx.waker().clone()));
+                this.ticket = Some(ticket);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Reserve {
+    fn drop(&mut self) {
+        let Some(ticket) = self.ticket else {
+            return;
+        };
+        if let Ok(mut state) = self.budget.state.lock() {
+            let was_first = state.waiters.front().is_some_and(|(t, _)| *t == ticket);
+            state.waiters.retain(|(t, _)| *t != ticket);
+            if was_first {
+                if let Some((_, waker)) = state.waiters.front() {
+                    waker.wake_by_ref();
+                }
+            }
+        }
+    }
+}
+
+/// Bytes reserved from a [`ByteBudget`], returned when dropped
+#[derive(Debug)]
+#[must_use = "the bytes are released as soon as the reservation is dropped"]
+pub struct Reservation {
+    budget: Option<Arc<ByteBudget>>,
+    bytes: u64,
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if let Some(budget) = self.budget.take() {
+            budget.release(self.bytes);
+        }
+    }
+}
+
+/// `--max-device-bytes` budgets, created as devices are first seen
+#[derive(Debug)]
+struct DeviceBudgets {
+    per_device: u64,
+    #[allow(clippy::disallowed_types)]
+    budgets: HashMap<u64, Arc<ByteBudget>>,
+}
+
+impl DeviceBudgets {
+    fn new(per_device: u64) -> Self {
+        Self {
+            per_device,
+            #[allow(clippy::disallowed_types)]
+            budgets: HashMap::new(),
+        }
+    }
+
+    /// Budget of device `dev`
+    fn budget(&mut self, dev: u64) -> Arc<ByteBudget> {
+        let per_device = self.per_device;
+        Arc::clone(
+            self.budgets
+                .entry(dev)
+                .or_insert_with(|| Arc::new(ByteBudget::new(per_device))),
+        )
+    }
+}
+
+/// Whether `--max-device-bytes` is in effect
+#[must_use]
+pub fn limits_devices() -> bool {
+    DEVICES.lock().is_ok_and(|devices| devices.is_some())
+}
+
+/// Set up the `--max-memory` and `--max-device-bytes` budgets for this run
+pub fn init(args: &Args) {
+    if let Ok(mut memory) = MEMORY.lock() {
+        *memory = args
+            .max_memory
+            .map(|bytes| Arc::new(ByteBudget::new(bytes.max(1))));
+    }
+    if let Ok(mut devices) = DEVICES.lock() {
+        *devices = args
+            .max_device_bytes
+            .map(|bytes| DeviceBudgets::new(bytes.max(1)));
+    }
+}
+
+/// The budgets one copy draws its chunks from
+///
+/// Budgets are always reserved in the same order (memory, then devices by
+/// number), so copies waiting on each other cannot deadlock.
+#[derive(Debug, Default)]
+pub struct ChunkBudget {
+    budgets: Vec<Arc<ByteBudget>>,
+}
+
+impl ChunkBudget {
+    /// Budgets for copying through userspace buffers between the block
+    /// devices `devices`: `--max-memory` and each device's budget
+    #[must_use]
+    pub fn buffers(devices: &[u64]) -> Self {
+        let mut budgets: Vec<Arc<ByteBudget>> = MEMORY
+            .lock()
+            .ok()
+            .and_then(|memory| memory.clone())
+            .into_iter()
+            .collect();
+        budgets.extend(Self::devices(devices).budgets);
+        Self { budgets }
+    }
+
+    /// Budgets for copying inside the kernel between the block devices
+    /// `devices`: only each device's budget, since no buffers are allocated
+    #[must_use]
+    pub fn devices(devices: &[u64]) -> Self {
+        let Ok(mut guard) = DEVICES.lock() else {
+            return Self::default();
+        };
+        let Some(device_budgets) = guard.as_mut() else {
+            return Self::default();
+        };
+        let mut devices = devices.to_vec();
+        devices.sort_unstable();
+        devices.dedup();
+        Self {
+            budgets: devices
+                .into_iter()
+                .map(|dev| device_budgets.budget(dev))
+                .collect(),
+        }
+    }
+
+    /// Largest chunk that fits in every budget, if any applies
+    #[must_use]
+    pub fn limit(&self) -> Option<usize> {
+        self.budgets
+            .iter()
+            .map(|budget| usize::try_from(budget.total()).unwrap_or(usize::MAX))
+            .min()
+    }
+
+    /// Wait until `bytes` can be reserved from every budget
+    pub async fn reserve(&self, bytes: usize) -> Vec<Reservation> {
+        let mut reservations = Vec::with_capacity(self.budgets.len());
+        for budget in &self.budgets {
+            reservations.push(budget.reserve(bytes as u64).await);
+        }
+        reservations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[compio::test]
+    async fn test_reservations_wait_for_released_bytes() {
+        let budget = Arc::new(ByteBudget::new(100));
+        let first = budget.reserve(60).await;
+        assert_eq!(budget.available(), 40);
+
+        // 60 more bytes do not fit until the first reservation is dropped
+        let mut waiting = Box::pin(budget.reserve(60));
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(waiting.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(budget.available(), 40);
+        drop(first);
+        let second = waiting.await;
+        assert_eq!(budget.available(), 40);
+        drop(second);
+        assert_eq!(budget.available(), 100);
+
+        // Requests larger than the budget are clamped instead of waiting forever
+        let all = budget.reserve(1000).await;
+        assert_eq!(budget.available(), 0);
+        drop(all);
+        assert_eq!(budget.available(), 100);
+    }
+
+    #[compio::test]
+    async fn test_device_budgets_are_per_device() {
+        let mut devices = DeviceBudgets::new(100);
+        let first = devices.budget(1).reserve(100).await;
+        assert_eq!(devices.budget(1).available(), 0);
+        assert_eq!(devices.budget(2).available(), 100);
+        drop(first);
+        assert_eq!(devices.budget(1).available(), 100);
+    }
+
+    #[compio::test]
+    async fn test_reservations_are_first_come_first_served() {
+        let budget = Arc::new(ByteBudget::new(100));
+        let held = budget.reserve(90).await;
+
+        // A large request queues first; a small one that would fit must not
+        // jump ahead of it
+        let mut large = Box::pin(budget.reserve(50));
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(large.as_mut().poll(&mut cx).is_pending());
+        let mut small = Box::pin(budget.reserve(5));
+        assert!(small.as_mut().poll(&mut cx).is_pending());
+
+        drop(held);
+        let large = match large.as_mut().poll(&mut cx) {
+            Poll::Ready(reservation) => reservation,
+            Poll::Pending => panic!("large reservation should fit"),
+        };
+        assert!(small.as_mut().poll(&mut cx).is_ready());
+        drop(large);
+
+        // Abandoning a queued reservation lets the next one through
+        let held = budget.reserve(100).await;
+        let mut abandoned = Box::pin(budget.reserve(100));
+        assert!(abandoned.as_mut().poll(&mut cx).is_pending());
+        let mut next = Box::pin(budget.reserve(10));
+        assert!(next.as_mut().poll(&mut cx).is_pending());
+        drop(abandoned);
+        drop(held);
+        assert!(next.as_mut().poll(&mut cx).is_ready());
+    }
+}
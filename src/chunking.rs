@@ -0,0 +1,190 @@
+//! Adaptive chunk sizing for read/write copies
+//!
+//! A fixed chunk size is either too small to keep an NVMe device busy or
+//! large enough to cause latency spikes on a busy HDD. [`ChunkSizer`] starts
+//! each file at the filesystem profile's buffer size and adjusts it after
+//! every chunk based on how long the read and write took:
+//!
+//! - chunks completing faster than [`FAST_CHUNK`] double the size
+//! - chunks slower than [`SLOW_CHUNK`] halve it
+//!
+//! The upper bound shrinks as more copies run at once, so the total memory
+//! held by in-flight buffers stays around [`BUFFER_BUDGET`] and a deep queue
+//! of concurrent copies does not pile huge requests onto the device.
+//!
+//! That bound is a single process-wide count of read/write copies in flight,
+//! not a per-device queue depth. Copies to a fast and a slow device share
+//! it, and no device's actual queue depth is looked at; only the latency
+//! feedback above tells the devices apart. `--max-device-bytes` is the
+//! per-device limit (see [`crate::budget`]).
+//!
+//! An explicit `--buffer-size-kb` disables adaptation.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Smallest chunk size adaptation will shrink to
+pub const MIN_CHUNK: usize = 16 * 1024;
+
+/// Largest chunk size for a single copy
+pub const MAX_CHUNK: usize = 8 * 1024 * 1024;
+
+/// Combined buffer size targeted across all copies in flight
+pub const BUFFER_BUDGET: usize = 256 * 1024 * 1024;
+
+/// Chunks completing within this time grow the chunk size
+pub const FAST_CHUNK: Duration = Duration::from_millis(5);
+
+/// Chunks taking longer than this shrink the chunk size
+pub const SLOW_CHUNK: Duration = Duration::from_millis(50);
+
+/// Number of read/write copies currently in progress, across all devices
+static COPIES_IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+/// Registers a copy as in flight for as long as it is alive
+#[derive(Debug)]
+pub struct InFlightCopy(());
+
+impl InFlightCopy {
+    /// Register a new copy
+    #[must_use]
+    pub fn start() -> Self {
+        COPIES_IN_FLIGHT.fetch_add(1, Ordering::Relaxed);
+        Self(())
+    }
+}
+
+impl Drop for InFlightCopy {
+    fn drop(&mut self) {
+        COPIES_IN_FLIGHT.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Upper bound on the chunk size with `in_flight` copies running
+///
+/// `in_flight` counts copies on every device together.
+#[must_use]
+pub const fn max_chunk_for(in_flight: usize) -> usize {
+    let share = BUFFER_BUDGET / if in_flight == 0 { 1 } else { in_flight };
+    if share < MIN_CHUNK {
+        MIN_CHUNK
+    } else if share > MAX_CHUNK {
+        MAX_CHUNK
+    } else {
+        share
+    }
+}
+
+/// Chooses the size of each chunk of a read/write copy
+#[derive(Debug, Clone)]
+pub struct ChunkSizer {
+    size: usize,
+    adaptive: bool,
+}
+
+impl ChunkSizer {
+    /// Start adapting from `initial` bytes
+    #[must_use]
+    pub fn adaptive(initial: usize) -> Self {
+        let max = max_chunk_for(COPIES_IN_FLIGHT.load(Ordering::Relaxed));
+        Self {
+            size: initial.clamp(MIN_CHUNK, max),
+            adaptive: true,
+        }
+    }
+
+    /// Always use `size` bytes
+    #[must_use]
+    pub fn fixed(size: usize) -> Self {
+        Self {
+            size: size.max(1),
+            adaptive: false,
+        }
+    }
+
+    /// Size to use for the next chunk
+    #[must_use]
+    pub const fn current(&self) -> usize {
+        self.size
+    }
+
+    /// Record a completed chunk of `bytes` that took `elapsed`
+    ///
+    /// Short chunks (end of file) never grow the size, since their latency
+    /// says nothing about a full-sized request.
+    pub fn record(&mut self, bytes: usize, elapsed: Duration) {
+        self.record_with_in_flight(bytes, elapsed, COPIES_IN_FLIGHT.load(Ordering::Relaxed));
+    }
+
+    fn record_with_in_flight(&mut self, bytes: usize, elapsed: Duration, in_flight: usize) {
+        if !self.adaptive {
+            return;
+        }
+        let max = max_chunk_for(in_flight);
+        if elapsed > SLOW_CHUNK {
+            self.size /= 2;
+        } else if elapsed < FAST_CHUNK && bytes >= self.size {
+            self.size = self.size.saturating_mul(2);
+        }
+        self.size = self.size.clamp(MIN_CHUNK, max);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grows_on_fast_full_chunks() {
+        let mut sizer = ChunkSizer::adaptive(64 * 1024);
+        sizer.record_with_in_flight(64 * 1024, Duration::from_millis(1), 1);
+        assert_eq!(sizer.current(), 128 * 1024);
+
+        // A short chunk at end of file does not grow the size
+        sizer.record_with_in_flight(1000, Duration::from_millis(1), 1);
+        assert_eq!(sizer.current(), 128 * 1024);
+
+        for _ in 0..20 {
+            let size = sizer.current();
+            sizer.record_with_in_flight(size, Duration::from_millis(1), 1);
+        }
+        assert_eq!(sizer.current(), MAX_CHUNK);
+    }
+
+    #[test]
+    fn test_shrinks_on_slow_chunks() {
+        let mut sizer = ChunkSizer::adaptive(1024 * 1024);
+        sizer.record_with_in_flight(1024 * 1024, Duration::from_millis(200), 1);
+        assert_eq!(sizer.current(), 512 * 1024);
+
+        for _ in 0..20 {
+            sizer.record_with_in_flight(sizer.current(), Duration::from_millis(200), 1);
+        }
+        assert_eq!(sizer.current(), MIN_CHUNK);
+
+        // Moderate latency holds the size steady
+        let before = sizer.current();
+        sizer.record_with_in_flight(before, Duration::from_millis(20), 1);
+        assert_eq!(sizer.current(), before);
+    }
+
+    #[test]
+    fn test_max_chunk_shrinks_with_concurrency() {
+        assert_eq!(max_chunk_for(0), MAX_CHUNK);
+        assert_eq!(max_chunk_for(32), MAX_CHUNK);
+        assert_eq!(max_chunk_for(1024), 256 * 1024);
+        assert_eq!(max_chunk_for(1_000_000), MIN_CHUNK);
+
+        let mut sizer = ChunkSizer::adaptive(4 * 1024 * 1024);
+        sizer.record_with_in_flight(4 * 1024 * 1024, Duration::from_millis(20), 1024);
+        assert_eq!(sizer.current(), 256 * 1024);
+    }
+
+    #[test]
+    fn test_fixed_never_changes() {
+        let mut sizer = ChunkSizer::fixed(4096);
+        sizer.record_with_in_flight(4096, Duration::from_millis(1), 1);
+        sizer.record_with_in_flight(4096, Duration::from_secs(1), 1);
+        assert_eq!(sizer.current(), 4096);
+    }
+}
@@ -1,24 +1,32 @@
 //! Command-line interface definitions
 
+use crate::filter::Filter;
+#[cfg(feature = "cli")]
+use crate::filter::{Action, FilterSource};
 use anyhow::Result;
-use clap::Parser;
+use compio_fs_extended::ioprio::IoPriority;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 /// High-performance bulk file copying utility using `io_uring`
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(clap::Parser))]
+#[cfg_attr(feature = "cli", command(author, version, about, long_about = None))]
 #[allow(clippy::struct_excessive_bools)]
 pub struct Args {
     /// Source directory or file
-    #[arg(value_name = "SOURCE")]
+    #[cfg_attr(feature = "cli", arg(value_name = "SOURCE"))]
     pub source: PathBuf,
 
     /// Destination directory or file
-    #[arg(value_name = "DESTINATION")]
+    #[cfg_attr(feature = "cli", arg(value_name = "DESTINATION"))]
     pub destination: PathBuf,
 
     /// Queue depth for `io_uring` operations
-    #[arg(long, default_value = "4096")]
+    ///
+    /// Submission entries in each ring: the main thread's and every copy
+    /// worker's.
+    #[cfg_attr(feature = "cli", arg(long, default_value = "4096"))]
     pub queue_depth: usize,
 
     /// Maximum total files in flight (across all CPU cores)
@@ -30,118 +38,323 @@ pub struct Args {
     /// Default: 1024
     /// High-performance (`NVMe`, 32GB+ RAM): 2048-4096
     /// Conservative (`HDD`, limited RAM): 256-512
-    #[arg(long, default_value = "1024")]
+    #[cfg_attr(feature = "cli", arg(long, default_value = "1024"))]
     pub max_files_in_flight: usize,
 
+    /// Cap on copy buffer memory across all files in flight (e.g. 512M, 2G)
+    ///
+    /// Copies wait for buffer space instead of allocating more once the cap
+    /// is reached.
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, value_name = "SIZE", value_parser = parse_byte_size)
+    )]
+    pub max_memory: Option<u64>,
+
+    /// Cap on bytes outstanding per block device (e.g. 64M)
+    ///
+    /// Applies to every device read from or written to, so one slow disk
+    /// cannot accumulate gigabytes of queued I/O.
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, value_name = "SIZE", value_parser = parse_byte_size)
+    )]
+    pub max_device_bytes: Option<u64>,
+
     /// Number of CPU cores to use (0 = auto-detect)
-    #[arg(long, default_value = "0")]
+    #[cfg_attr(feature = "cli", arg(long, default_value = "0"))]
     pub cpu_count: usize,
 
-    /// Buffer size in KB (0 = auto-detect)
-    #[arg(long, default_value = "0")]
+    /// Pin the copy threads to CPUs: a list such as 0-3,8, or node:N for a NUMA node
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, value_name = "CPUS", value_parser = parse_cpu_affinity)
+    )]
+    pub cpu_affinity: Option<CpuAffinity>,
+
+    /// Buffer size in KB (0 = adapt per file to observed I/O latency)
+    #[cfg_attr(feature = "cli", arg(long, default_value = "0"))]
     pub buffer_size_kb: usize,
 
     /// Copy method to use
-    #[arg(long, default_value = "auto")]
+    #[cfg_attr(feature = "cli", arg(long, default_value = "auto"))]
     pub copy_method: CopyMethod,
 
+    /// TOML file overriding the per-filesystem copy strategies
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FILE"))]
+    pub fs_profiles: Option<PathBuf>,
+
+    /// I/O priority for file reads and writes: idle, best-effort or best-effort:LEVEL (0-7)
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, value_name = "CLASS", value_parser = parse_io_priority)
+    )]
+    pub io_priority: Option<IoPriority>,
+
+    /// Ask the kernel to read ahead files waiting in a directory's copy queue
+    ///
+    /// Issues `POSIX_FADV_WILLNEED` for the start of each regular file while
+    /// its directory's entries wait for a concurrency slot, so the data is
+    /// cached by the time the copy starts. Has no effect with --deterministic.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub prefetch: bool,
+
     // ========== rsync-compatible flags ==========
     /// Archive mode; same as -rlptgoD (recursive, links, perms, times, group, owner, devices)
-    #[arg(short = 'a', long)]
+    #[cfg_attr(feature = "cli", arg(short = 'a', long))]
     pub archive: bool,
 
     /// Recurse into directories
-    #[arg(short = 'r', long)]
+    #[cfg_attr(feature = "cli", arg(short = 'r', long))]
     pub recursive: bool,
 
     /// Copy symlinks as symlinks
-    #[arg(short = 'l', long)]
+    #[cfg_attr(feature = "cli", arg(short = 'l', long))]
     pub links: bool,
 
+    /// Munge symlinks so their targets point under /rsyncd-munged/
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub munge_links: bool,
+
     /// Preserve permissions
-    #[arg(short = 'p', long)]
+    #[cfg_attr(feature = "cli", arg(short = 'p', long))]
     pub perms: bool,
 
     /// Preserve modification times
-    #[arg(short = 't', long)]
+    #[cfg_attr(feature = "cli", arg(short = 't', long))]
     pub times: bool,
 
     /// Preserve group
-    #[arg(short = 'g', long)]
+    #[cfg_attr(feature = "cli", arg(short = 'g', long))]
     pub group: bool,
 
     /// Preserve owner (super-user only)
-    #[arg(short = 'o', long)]
+    #[cfg_attr(feature = "cli", arg(short = 'o', long))]
     pub owner: bool,
 
+    /// Don't map uid/gid values by user/group name
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub numeric_ids: bool,
+
+    /// Report failures to preserve ownership as warnings instead of errors
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub no_owner_errors: bool,
+
+    /// Store privileged attributes (ownership, modes, special files) in xattrs
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub fake_super: bool,
+
+    /// When started as root, switch to USER after setup, keeping only CAP_CHOWN and CAP_FOWNER
+    #[cfg_attr(feature = "cli", arg(long, value_name = "USER"))]
+    pub drop_privileges: Option<String>,
+
     /// Preserve device files (super-user only) and special files
-    #[arg(short = 'D', long)]
+    #[cfg_attr(feature = "cli", arg(short = 'D', long))]
     pub devices: bool,
 
+    /// Preserve special files (named pipes and sockets)
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub specials: bool,
+
     /// Preserve extended attributes
-    #[arg(short = 'X', long)]
+    #[cfg_attr(feature = "cli", arg(short = 'X', long))]
     pub xattrs: bool,
 
+    /// Don't preserve SELinux security contexts (preserved with -a or -X)
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub no_selinux: bool,
+
     /// Preserve ACLs (implies --perms)
-    #[arg(short = 'A', long)]
+    #[cfg_attr(feature = "cli", arg(short = 'A', long))]
     pub acls: bool,
 
     /// Preserve hard links
-    #[arg(short = 'H', long)]
+    #[cfg_attr(feature = "cli", arg(short = 'H', long))]
     pub hard_links: bool,
 
     /// Preserve access (use) times
-    #[arg(short = 'U', long)]
+    #[cfg_attr(feature = "cli", arg(short = 'U', long))]
     pub atimes: bool,
 
     /// Preserve creation times (when supported)
-    #[arg(long)]
+    #[cfg_attr(feature = "cli", arg(long))]
     pub crtimes: bool,
 
+    /// Put all updated files into place at the end of the run
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub delay_updates: bool,
+
+    /// Skip files that already exist at the destination
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub ignore_existing: bool,
+
+    /// Only update files that already exist at the destination
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub existing: bool,
+
+    /// Skip files that are newer on the destination
+    #[cfg_attr(feature = "cli", arg(short = 'u', long))]
+    pub update: bool,
+
+    /// Update existing destination files in place, rewriting only the chunks that differ
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub no_whole_file: bool,
+
+    /// Write new file contents to an anonymous O_TMPFILE and link it into
+    /// place once complete, so no partial file or temp name is ever visible.
+    /// Files updated in place by --no-whole-file are not affected
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub tmpfile: bool,
+
+    // ========== Filter flags ==========
+    /// Exclude files matching PATTERN
+    #[cfg_attr(feature = "cli", arg(long, value_name = "PATTERN"))]
+    pub exclude: Vec<String>,
+
+    /// Don't exclude files matching PATTERN
+    #[cfg_attr(feature = "cli", arg(long, value_name = "PATTERN"))]
+    pub include: Vec<String>,
+
+    /// Read exclude patterns from FILE (`-` for stdin)
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FILE"))]
+    pub exclude_from: Vec<PathBuf>,
+
+    /// Read include patterns from FILE (`-` for stdin)
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FILE"))]
+    pub include_from: Vec<PathBuf>,
+
+    /// Exclude files matched by `.gitignore` and `.ignore` files in the source
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub gitignore: bool,
+
+    /// Exclude files CVS ignores, plus those listed in `.cvsignore` files
+    #[cfg_attr(feature = "cli", arg(short = 'C', long))]
+    pub cvs_exclude: bool,
+
+    /// Rules from the filter flags, in command-line order
+    #[cfg_attr(feature = "cli", arg(skip))]
+    pub filter: Option<Arc<Filter>>,
+
+    // ========== Permission policy flags ==========
+    /// Umask (octal) applied to source permissions for new entries when
+    /// permissions are not preserved
+    #[cfg_attr(feature = "cli", arg(long, value_name = "MODE", value_parser = parse_octal_umask))]
+    pub umask: Option<u32>,
+
+    /// Give every destination file this mode (octal), ignoring the source
+    #[cfg_attr(feature = "cli", arg(long, value_name = "MODE", value_parser = parse_octal_mode))]
+    pub file_mode: Option<u32>,
+
+    /// Give every destination directory this mode (octal), ignoring the source
+    #[cfg_attr(feature = "cli", arg(long, value_name = "MODE", value_parser = parse_octal_mode))]
+    pub dir_mode: Option<u32>,
+
+    /// Affect file and/or directory permissions (e.g. `Du+rwx,Fgo-w`, `D755`)
+    #[cfg_attr(feature = "cli", arg(long, value_name = "CHMOD", value_delimiter = ',', value_parser = parse_chmod_rule))]
+    pub chmod: Vec<ChmodRule>,
+
+    /// Force ownership of all destination entries (`USER:GROUP`, `USER` or `:GROUP`)
+    #[cfg_attr(feature = "cli", arg(long, value_name = "USER:GROUP", value_parser = parse_chown_spec))]
+    pub chown: Option<ChownSpec>,
+
     // ========== Deprecated flags (for backwards compatibility) ==========
     /// Preserve extended attributes (deprecated: use -X/--xattrs)
-    #[arg(long, hide = true)]
+    #[cfg_attr(feature = "cli", arg(long, hide = true))]
     pub preserve_xattr: bool,
 
     /// Preserve POSIX ACLs (deprecated: use -A/--acls)
-    #[arg(long, hide = true)]
+    #[cfg_attr(feature = "cli", arg(long, hide = true))]
     pub preserve_acl: bool,
 
     // ========== Other flags ==========
     /// Show what would be copied without actually copying
-    #[arg(long)]
+    #[cfg_attr(feature = "cli", arg(long))]
     pub dry_run: bool,
 
+    /// Descend at most N directory levels below the source (0 = source only)
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N"))]
+    pub max_depth: Option<usize>,
+
+    /// Remember hardlinked inodes in FILE so later runs keep linking them (with -H)
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FILE"))]
+    pub hardlink_db: Option<PathBuf>,
+
+    /// Hardlink files whose content matches a file already written this run
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub dedupe_dest: bool,
+
+    /// After the initial sync, keep watching the source and apply changes as they happen
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub watch: bool,
+
+    /// With --watch, apply changes once the source has been quiet for MS milliseconds
+    #[cfg_attr(feature = "cli", arg(long, value_name = "MS", default_value = "200"))]
+    pub watch_delay: u64,
+
     /// Show progress information
-    #[arg(long)]
+    #[cfg_attr(feature = "cli", arg(long))]
     pub progress: bool,
 
+    /// Write newline-delimited JSON events to stdout
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub json: bool,
+
+    /// Print file counts, data sizes and transfer rate at the end of the run
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub stats: bool,
+
+    /// Print a change summary for every updated item
+    #[cfg_attr(feature = "cli", arg(short = 'i', long))]
+    pub itemize_changes: bool,
+
+    /// Log what happened to every item to FILE
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FILE"))]
+    pub log_file: Option<PathBuf>,
+
+    /// Line format for --log-file (%o %i %n %f %L %l %b %t %p)
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, value_name = "FMT", default_value = "%i %n%L")
+    )]
+    pub log_file_format: String,
+
     /// Verbose output (-v, -vv, -vvv)
-    #[arg(short, long, action = clap::ArgAction::Count)]
+    #[cfg_attr(feature = "cli", arg(short, long, action = clap::ArgAction::Count))]
     pub verbose: u8,
 
     /// Quiet mode (suppress all output except errors)
-    #[arg(short, long)]
+    #[cfg_attr(feature = "cli", arg(short, long))]
     pub quiet: bool,
 
     /// Enable pirate speak (arrr! 🏴‍☠️)
-    #[arg(long, default_value = "false")]
+    #[cfg_attr(feature = "cli", arg(long, default_value = "false"))]
     pub pirate: bool,
 
     // ========== Concurrency control flags ==========
     /// Disable adaptive concurrency control (fail fast on resource exhaustion)
     ///
     /// By default, arsync automatically reduces concurrency when hitting resource
-    /// limits like "Too many open files" (EMFILE). This flag disables that behavior
-    /// and causes arsync to exit immediately on such errors instead.
+    /// limits like "Too many open files" (EMFILE), and sizes the number of
+    /// operations in flight from observed completion latency. This flag disables
+    /// both and causes arsync to exit immediately on such errors instead.
     ///
     /// Use this if you want strict resource limit enforcement or in CI/CD environments
     /// where you want to catch configuration issues early.
-    #[arg(long)]
+    #[cfg_attr(feature = "cli", arg(long))]
     pub no_adaptive_concurrency: bool,
+
+    /// Make runs on identical inputs reproducible
+    ///
+    /// Directory entries are processed one at a time in name order, adaptive
+    /// concurrency and chunk sizing are turned off, and log lines carry no
+    /// timestamps or durations, so two runs over the same tree produce
+    /// byte-identical logs.
+    #[cfg_attr(feature = "cli", arg(long))]
+    pub deterministic: bool,
 }
 
-#[derive(Debug, Clone, clap::ValueEnum)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
 pub enum CopyMethod {
     /// Automatically choose the best method
     Auto,
@@ -153,6 +366,241 @@ pub enum CopyMethod {
     ReadWrite,
 }
 
+/// Parse an octal permission mode such as `0644` or `2775`
+#[cfg_attr(not(feature = "cli"), allow(dead_code))]
+fn parse_octal_mode(s: &str) -> std::result::Result<u32, String> {
+    let digits = s.strip_prefix("0o").unwrap_or(s);
+    let mode = u32::from_str_radix(digits, 8).map_err(|_| format!("invalid octal mode: {s}"))?;
+    if mode > 0o7777 {
+        return Err(format!("mode out of range (max 7777): {s}"));
+    }
+    Ok(mode)
+}
+
+/// Parse an octal umask such as `022`
+#[cfg_attr(not(feature = "cli"), allow(dead_code))]
+fn parse_octal_umask(s: &str) -> std::result::Result<u32, String> {
+    let mask = parse_octal_mode(s)?;
+    if mask > 0o777 {
+        return Err(format!("umask out of range (max 777): {s}"));
+    }
+    Ok(mask)
+}
+
+/// Which entries a `--chmod` rule applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChmodTarget {
+    /// Both files and directories (no prefix)
+    All,
+    /// Directories only (`D` prefix)
+    Dirs,
+    /// Non-directories only (`F` prefix)
+    Files,
+}
+
+/// A single permission operation within a `--chmod` rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChmodOp {
+    /// Set an absolute octal mode (e.g. `D755`)
+    Octal(u32),
+    /// Add the given bits (`+`)
+    Add(u32),
+    /// Remove the given bits (`-`)
+    Remove(u32),
+    /// Replace the bits covered by `who` with the given bits (`=`)
+    Set {
+        /// Mask of the permission classes being replaced
+        who: u32,
+        /// Bits to set within `who`
+        bits: u32,
+    },
+    /// Add execute where `X` applies (directories or already executable)
+    AddConditionalExec(u32),
+}
+
+/// A parsed `--chmod` rule, e.g. `Du+rwx` or `Fgo-w`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChmodRule {
+    /// Entries the rule applies to
+    pub target: ChmodTarget,
+    /// Operations applied in order
+    pub ops: Vec<ChmodOp>,
+}
+
+/// Ownership forced by `--chown`
+///
+/// Each part may be a name or a numeric id; a missing part leaves that
+/// half of the ownership to the normal preservation rules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChownSpec {
+    /// User name or uid
+    pub user: Option<String>,
+    /// Group name or gid
+    pub group: Option<String>,
+}
+
+/// Parse one comma-separated clause of a `--chmod` argument
+///
+/// Accepts an optional `D`/`F` prefix followed by either an octal mode or
+/// chmod(1)-style symbolic operations (`[ugoa]*([-+=][rwxXst]*)+`).
+#[cfg_attr(not(feature = "cli"), allow(dead_code))]
+pub(crate) fn parse_chmod_rule(s: &str) -> std::result::Result<ChmodRule, String> {
+    let (target, rest) = match s.as_bytes().first() {
+        Some(b'D') => (ChmodTarget::Dirs, &s[1..]),
+        Some(b'F') => (ChmodTarget::Files, &s[1..]),
+        _ => (ChmodTarget::All, s),
+    };
+    if rest.is_empty() {
+        return Err(format!("empty chmod rule: {s:?}"));
+    }
+
+    if rest.bytes().all(|b| b.is_ascii_digit()) {
+        let mode = parse_octal_mode(rest)?;
+        return Ok(ChmodRule {
+            target,
+            ops: vec![ChmodOp::Octal(mode)],
+        });
+    }
+
+    let who_len = rest
+        .find(|c: char| !matches!(c, 'u' | 'g' | 'o' | 'a'))
+        .unwrap_or(rest.len());
+    let mut who = 0;
+    for c in rest[..who_len].chars() {
+        who |= match c {
+            'u' => 0o4700,
+            'g' => 0o2070,
+            'o' => 0o1007,
+            _ => 0o7777,
+        };
+    }
+    // No class given means all of them, as with rsync's --chmod
+    if who == 0 {
+        who = 0o7777;
+    }
+
+    let mut ops = Vec::new();
+    let mut chars = rest[who_len..].chars().peekable();
+    while let Some(op) = chars.next() {
+        if !matches!(op, '+' | '-' | '=') {
+            return Err(format!("invalid chmod rule {s:?}: unexpected {op:?}"));
+        }
+        let mut bits = 0;
+        let mut cond_exec = false;
+        while let Some(&c) = chars.peek() {
+            bits |= match c {
+                'r' => 0o444,
+                'w' => 0o222,
+                'x' => 0o111,
+                's' => 0o6000,
+                't' => 0o1000,
+                'X' => {
+                    cond_exec = true;
+                    0
+                }
+                '+' | '-' | '=' => break,
+                _ => return Err(format!("invalid chmod rule {s:?}: unexpected {c:?}")),
+            };
+            chars.next();
+        }
+        let bits = bits & who;
+        ops.push(match op {
+            '+' => ChmodOp::Add(bits),
+            '-' => ChmodOp::Remove(bits),
+            _ => ChmodOp::Set { who, bits },
+        });
+        if cond_exec {
+            if op == '-' {
+                ops.push(ChmodOp::Remove(0o111 & who));
+            } else {
+                ops.push(ChmodOp::AddConditionalExec(0o111 & who));
+            }
+        }
+    }
+    if ops.is_empty() {
+        return Err(format!("invalid chmod rule {s:?}: missing operator"));
+    }
+
+    Ok(ChmodRule { target, ops })
+}
+
+/// Parse a `--chown` argument
+#[cfg_attr(not(feature = "cli"), allow(dead_code))]
+fn parse_chown_spec(s: &str) -> std::result::Result<ChownSpec, String> {
+    let (user, group) = s.split_once(':').unwrap_or((s, ""));
+    let part = |p: &str| (!p.is_empty()).then(|| p.to_string());
+    let spec = ChownSpec {
+        user: part(user),
+        group: part(group),
+    };
+    if spec.user.is_none() && spec.group.is_none() {
+        return Err(format!("invalid --chown value: {s:?}"));
+    }
+    Ok(spec)
+}
+
+/// Parse a size such as `65536`, `512K`, `256M` or `2G` (powers of 1024)
+#[cfg_attr(not(feature = "cli"), allow(dead_code))]
+fn parse_byte_size(s: &str) -> std::result::Result<u64, String> {
+    let digits = s.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let multiplier: u64 = match s[digits.len()..].to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        suffix => return Err(format!("invalid size {s:?}: unknown suffix {suffix:?}")),
+    };
+    let value: u64 = digits
+        .parse()
+        .map_err(|_| format!("invalid size {s:?}: expected a number"))?;
+    match value.checked_mul(multiplier) {
+        Some(0) => Err(format!("invalid size {s:?}: must be greater than zero")),
+        Some(bytes) => Ok(bytes),
+        None => Err(format!("invalid size {s:?}: too large")),
+    }
+}
+
+/// CPUs selected by `--cpu-affinity`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CpuAffinity {
+    /// Explicit CPU numbers
+    Cpus(Vec<usize>),
+    /// Every CPU of a NUMA node
+    Node(usize),
+}
+
+/// Parse a `--cpu-affinity` argument
+#[cfg_attr(not(feature = "cli"), allow(dead_code))]
+fn parse_cpu_affinity(s: &str) -> std::result::Result<CpuAffinity, String> {
+    match s.strip_prefix("node:") {
+        Some(node) => node
+            .parse()
+            .map(CpuAffinity::Node)
+            .map_err(|_| format!("invalid --cpu-affinity NUMA node {node:?}")),
+        None => crate::affinity::parse_cpu_list(s).map(CpuAffinity::Cpus),
+    }
+}
+
+/// Parse an `--io-priority` argument
+#[cfg_attr(not(feature = "cli"), allow(dead_code))]
+fn parse_io_priority(s: &str) -> std::result::Result<IoPriority, String> {
+    let (class, level) = s.split_once(':').unwrap_or((s, ""));
+    match (class, level) {
+        ("idle", "") => Ok(IoPriority::idle()),
+        ("best-effort", "") => Ok(IoPriority::best_effort(4)),
+        ("best-effort", level) => match level.parse::<u8>() {
+            Ok(level) if level <= 7 => Ok(IoPriority::best_effort(level)),
+            _ => Err(format!(
+                "invalid --io-priority level {level:?}: expected 0 to 7"
+            )),
+        },
+        _ => Err(format!(
+            "invalid --io-priority value {s:?}: expected idle, best-effort or best-effort:LEVEL"
+        )),
+    }
+}
+
 impl Default for CopyMethod {
     fn default() -> Self {
         Self::Auto
@@ -166,35 +614,127 @@ impl Default for Args {
             destination: std::path::PathBuf::from("/default/destination"),
             queue_depth: 4096,
             max_files_in_flight: 1024,
+            max_memory: None,
+            max_device_bytes: None,
             cpu_count: 0,
+            cpu_affinity: None,
             buffer_size_kb: 0,
             copy_method: CopyMethod::Auto,
+            fs_profiles: None,
+            io_priority: None,
+            prefetch: false,
             archive: false,
             recursive: false,
             links: false,
+            munge_links: false,
             perms: false,
             times: false,
             group: false,
             owner: false,
+            numeric_ids: false,
+            no_owner_errors: false,
+            fake_super: false,
+            exclude: Vec::new(),
+            include: Vec::new(),
+            exclude_from: Vec::new(),
+            include_from: Vec::new(),
+            gitignore: false,
+            cvs_exclude: false,
+            filter: None,
+            drop_privileges: None,
             devices: false,
+            specials: false,
             xattrs: false,
+            no_selinux: false,
             acls: false,
             hard_links: false,
             atimes: false,
             crtimes: false,
+            delay_updates: false,
+            ignore_existing: false,
+            existing: false,
+            update: false,
+            no_whole_file: false,
+            tmpfile: false,
+            umask: None,
+            file_mode: None,
+            dir_mode: None,
+            chmod: Vec::new(),
+            chown: None,
             preserve_xattr: false,
             preserve_acl: false,
             dry_run: false,
+            max_depth: None,
+            hardlink_db: None,
+            dedupe_dest: false,
+            watch: false,
+            watch_delay: 200,
             progress: false,
+            json: false,
+            stats: false,
+            itemize_changes: false,
+            log_file: None,
+            log_file_format: "%i %n%L".to_string(),
             verbose: 0,
             quiet: false,
             pirate: false,
             no_adaptive_concurrency: false,
+            deterministic: false,
         }
     }
 }
 
 impl Args {
+    /// Build [`Args::filter`] from the filter flags, keeping their
+    /// command-line order
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a rule file cannot be read.
+    #[cfg(feature = "cli")]
+    pub fn load_filter(&mut self, matches: &clap::ArgMatches) -> Result<()> {
+        let indices = |id: &str| matches.indices_of(id).into_iter().flatten();
+        let mut sources = Vec::new();
+        sources.extend(
+            indices("exclude")
+                .zip(&self.exclude)
+                .map(|(index, pattern)| {
+                    (
+                        index,
+                        FilterSource::Pattern(Action::Exclude, pattern.clone()),
+                    )
+                }),
+        );
+        sources.extend(
+            indices("include")
+                .zip(&self.include)
+                .map(|(index, pattern)| {
+                    (
+                        index,
+                        FilterSource::Pattern(Action::Include, pattern.clone()),
+                    )
+                }),
+        );
+        sources.extend(
+            indices("exclude_from")
+                .zip(&self.exclude_from)
+                .map(|(index, path)| (index, FilterSource::File(Action::Exclude, path.clone()))),
+        );
+        sources.extend(
+            indices("include_from")
+                .zip(&self.include_from)
+                .map(|(index, path)| (index, FilterSource::File(Action::Include, path.clone()))),
+        );
+        sources.sort_by_key(|(index, _)| *index);
+        let mut sources: Vec<_> = sources.into_iter().map(|(_, source)| source).collect();
+        // As in rsync, after the rules given on the command line
+        if self.cvs_exclude {
+            sources.push(FilterSource::CvsExclude);
+        }
+        self.filter = Filter::load(&self.source, &sources, self.gitignore)?.map(Arc::new);
+        Ok(())
+    }
+
     /// Validate command-line arguments
     ///
     /// # Errors
@@ -251,6 +791,13 @@ impl Args {
             anyhow::bail!("No CPU cores available");
         }
 
+        if self.watch && !self.source.is_dir() {
+            anyhow::bail!(
+                "--watch needs a source directory: {}",
+                self.source.display()
+            );
+        }
+
         // Validate conflicting options
         if self.quiet && self.verbose > 0 {
             anyhow::bail!("Cannot use both --quiet and --verbose options");
@@ -270,7 +817,6 @@ impl Args {
     }
 
     /// Get the actual buffer size in bytes
-    #[allow(dead_code)]
     #[must_use]
     pub const fn effective_buffer_size(&self) -> usize {
         if self.buffer_size_kb == 0 {
@@ -281,6 +827,23 @@ impl Args {
         }
     }
 
+    /// Whether concurrency may be reduced in response to resource exhaustion
+    #[must_use]
+    pub const fn adaptive_concurrency_enabled(&self) -> bool {
+        !self.no_adaptive_concurrency && !self.deterministic
+    }
+
+    /// The flags that turned adaptive concurrency off, for error messages
+    #[must_use]
+    pub const fn adaptive_concurrency_disabled_by(&self) -> &'static str {
+        match (self.no_adaptive_concurrency, self.deterministic) {
+            (true, true) => "--no-adaptive-concurrency and --deterministic",
+            (true, false) => "--no-adaptive-concurrency",
+            (false, true) => "--deterministic",
+            (false, false) => "",
+        }
+    }
+
     /// Check if the source is a directory
     #[must_use]
     pub fn is_directory_copy(&self) -> bool {
@@ -313,6 +876,24 @@ impl Args {
         self.owner || self.group || self.archive
     }
 
+    /// Check if destination permissions should be set, either preserved from
+    /// the source or derived from the permission policy flags
+    #[must_use]
+    pub fn should_set_permissions(&self) -> bool {
+        self.should_preserve_permissions()
+            || self.umask.is_some()
+            || self.file_mode.is_some()
+            || self.dir_mode.is_some()
+            || !self.chmod.is_empty()
+    }
+
+    /// Check if destination ownership should be set, either preserved from
+    /// the source or forced with `--chown`
+    #[must_use]
+    pub const fn should_set_ownership(&self) -> bool {
+        self.should_preserve_ownership() || self.chown.is_some()
+    }
+
     /// Check if user ownership should be preserved
     #[allow(dead_code)]
     #[must_use]
@@ -341,7 +922,6 @@ impl Args {
     }
 
     /// Check if creation times should be preserved
-    #[allow(dead_code)]
     #[must_use]
     pub const fn should_preserve_crtimes(&self) -> bool {
         self.crtimes
@@ -353,6 +933,18 @@ impl Args {
         self.xattrs || self.preserve_xattr
     }
 
+    /// Check if file capabilities (`security.capability`) should be preserved
+    #[must_use]
+    pub const fn should_preserve_file_capabilities(&self) -> bool {
+        self.should_preserve_permissions() || self.should_preserve_xattrs()
+    }
+
+    /// Check if SELinux security contexts should be preserved
+    #[must_use]
+    pub const fn should_preserve_selinux(&self) -> bool {
+        (self.archive || self.should_preserve_xattrs()) && !self.no_selinux
+    }
+
     /// Check if ACLs should be preserved
     #[allow(dead_code)]
     #[must_use]
@@ -375,12 +967,19 @@ impl Args {
     }
 
     /// Check if device files should be preserved
-    #[allow(dead_code)]
     #[must_use]
     pub const fn should_preserve_devices(&self) -> bool {
         self.devices || self.archive
     }
 
+    /// Check if special files (FIFOs and sockets) should be preserved
+    ///
+    /// As in rsync, `-D` implies `--specials` in addition to `--devices`.
+    #[must_use]
+    pub const fn should_preserve_specials(&self) -> bool {
+        self.specials || self.devices || self.archive
+    }
+
     /// Check if recursive copying should be performed
     #[allow(dead_code)]
     #[must_use]
@@ -425,31 +1024,73 @@ mod tests {
             source: file_path,
             destination: temp_dir.path().join("dest"),
             copy_method: CopyMethod::Auto,
+            fs_profiles: None,
+            io_priority: None,
+            prefetch: false,
             queue_depth: 4096,
             cpu_count: 2,
+            cpu_affinity: None,
             buffer_size_kb: 1024,
             max_files_in_flight: 100,
+            max_memory: None,
+            max_device_bytes: None,
             archive: false,
             recursive: false,
             links: false,
+            munge_links: false,
             perms: false,
             times: false,
             group: false,
             owner: false,
+            numeric_ids: false,
+            no_owner_errors: false,
+            fake_super: false,
+            exclude: Vec::new(),
+            include: Vec::new(),
+            exclude_from: Vec::new(),
+            include_from: Vec::new(),
+            gitignore: false,
+            cvs_exclude: false,
+            filter: None,
+            drop_privileges: None,
             devices: false,
+            specials: false,
             xattrs: true,
+            no_selinux: false,
             acls: false,
             hard_links: false,
             atimes: false,
             crtimes: false,
+            delay_updates: false,
+            ignore_existing: false,
+            existing: false,
+            update: false,
+            no_whole_file: false,
+            tmpfile: false,
+            umask: None,
+            file_mode: None,
+            dir_mode: None,
+            chmod: Vec::new(),
+            chown: None,
             preserve_xattr: false,
             preserve_acl: false,
             dry_run: false,
+            max_depth: None,
+            hardlink_db: None,
+            dedupe_dest: false,
+            watch: false,
+            watch_delay: 200,
             progress: false,
+            json: false,
+            stats: false,
+            itemize_changes: false,
+            log_file: None,
+            log_file_format: "%i %n%L".to_string(),
             verbose: 0,
             quiet: false,
             pirate: false,
             no_adaptive_concurrency: false,
+            deterministic: false,
         };
 
         assert!(args.validate().is_ok());
@@ -462,31 +1103,73 @@ mod tests {
             source: dir_path,
             destination: temp_dir.path().join("dest"),
             copy_method: CopyMethod::Auto,
+            fs_profiles: None,
+            io_priority: None,
+            prefetch: false,
             queue_depth: 4096,
             cpu_count: 2,
+            cpu_affinity: None,
             buffer_size_kb: 1024,
             max_files_in_flight: 100,
+            max_memory: None,
+            max_device_bytes: None,
             archive: false,
             recursive: false,
             links: false,
+            munge_links: false,
             perms: false,
             times: false,
             group: false,
             owner: false,
+            numeric_ids: false,
+            no_owner_errors: false,
+            fake_super: false,
+            exclude: Vec::new(),
+            include: Vec::new(),
+            exclude_from: Vec::new(),
+            include_from: Vec::new(),
+            gitignore: false,
+            cvs_exclude: false,
+            filter: None,
+            drop_privileges: None,
             devices: false,
+            specials: false,
             xattrs: true,
+            no_selinux: false,
             acls: false,
             hard_links: false,
             atimes: false,
             crtimes: false,
+            delay_updates: false,
+            ignore_existing: false,
+            existing: false,
+            update: false,
+            no_whole_file: false,
+            tmpfile: false,
+            umask: None,
+            file_mode: None,
+            dir_mode: None,
+            chmod: Vec::new(),
+            chown: None,
             preserve_xattr: false,
             preserve_acl: false,
             dry_run: false,
+            max_depth: None,
+            hardlink_db: None,
+            dedupe_dest: false,
+            watch: false,
+            watch_delay: 200,
             progress: false,
+            json: false,
+            stats: false,
+            itemize_changes: false,
+            log_file: None,
+            log_file_format: "%i %n%L".to_string(),
             verbose: 0,
             quiet: false,
             pirate: false,
             no_adaptive_concurrency: false,
+            deterministic: false,
         };
 
         assert!(args.validate().is_ok());
@@ -498,33 +1181,167 @@ mod tests {
             source: PathBuf::from("/nonexistent/path"),
             destination: PathBuf::from("/tmp/dest"),
             copy_method: CopyMethod::Auto,
+            fs_profiles: None,
+            io_priority: None,
+            prefetch: false,
             queue_depth: 4096,
             cpu_count: 2,
+            cpu_affinity: None,
             buffer_size_kb: 1024,
             max_files_in_flight: 100,
+            max_memory: None,
+            max_device_bytes: None,
             archive: false,
             recursive: false,
             links: false,
+            munge_links: false,
             perms: false,
             times: false,
             group: false,
             owner: false,
+            numeric_ids: false,
+            no_owner_errors: false,
+            fake_super: false,
+            exclude: Vec::new(),
+            include: Vec::new(),
+            exclude_from: Vec::new(),
+            include_from: Vec::new(),
+            gitignore: false,
+            cvs_exclude: false,
+            filter: None,
+            drop_privileges: None,
             devices: false,
+            specials: false,
             xattrs: true,
+            no_selinux: false,
             acls: false,
             hard_links: false,
             atimes: false,
             crtimes: false,
+            delay_updates: false,
+            ignore_existing: false,
+            existing: false,
+            update: false,
+            no_whole_file: false,
+            tmpfile: false,
+            umask: None,
+            file_mode: None,
+            dir_mode: None,
+            chmod: Vec::new(),
+            chown: None,
             preserve_xattr: false,
             preserve_acl: false,
             dry_run: false,
+            max_depth: None,
+            hardlink_db: None,
+            dedupe_dest: false,
+            watch: false,
+            watch_delay: 200,
             progress: false,
+            json: false,
+            stats: false,
+            itemize_changes: false,
+            log_file: None,
+            log_file_format: "%i %n%L".to_string(),
             verbose: 0,
             quiet: false,
             pirate: false,
             no_adaptive_concurrency: false,
+            deterministic: false,
         };
 
         assert!(args.validate().is_err());
     }
+
+    #[test]
+    fn test_parse_octal_modes() {
+        assert_eq!(parse_octal_mode("0644"), Ok(0o644));
+        assert_eq!(parse_octal_mode("2775"), Ok(0o2775));
+        assert_eq!(parse_octal_mode("0o755"), Ok(0o755));
+        assert!(parse_octal_mode("0899").is_err());
+        assert!(parse_octal_mode("17777").is_err());
+
+        assert_eq!(parse_octal_umask("022"), Ok(0o022));
+        assert!(parse_octal_umask("1022").is_err());
+    }
+
+    #[test]
+    fn test_parse_chmod_and_chown() {
+        let rule = parse_chmod_rule("Fgo-w").unwrap();
+        assert_eq!(rule.target, ChmodTarget::Files);
+        assert_eq!(rule.ops, vec![ChmodOp::Remove(0o022)]);
+        assert!(parse_chmod_rule("D").is_err());
+        assert!(parse_chmod_rule("u+q").is_err());
+        assert!(parse_chmod_rule("rwx").is_err());
+
+        let spec = parse_chown_spec("www-data:").unwrap();
+        assert_eq!(spec.user.as_deref(), Some("www-data"));
+        assert_eq!(spec.group, None);
+        let spec = parse_chown_spec(":1000").unwrap();
+        assert_eq!(spec.user, None);
+        assert_eq!(spec.group.as_deref(), Some("1000"));
+        assert!(parse_chown_spec(":").is_err());
+    }
+
+    #[test]
+    fn test_parse_byte_size() {
+        assert_eq!(parse_byte_size("4096"), Ok(4096));
+        assert_eq!(parse_byte_size("512K"), Ok(512 * 1024));
+        assert_eq!(parse_byte_size("256m"), Ok(256 * 1024 * 1024));
+        assert_eq!(parse_byte_size("2GiB"), Ok(2 * 1024 * 1024 * 1024));
+        assert!(parse_byte_size("0").is_err());
+        assert!(parse_byte_size("12X").is_err());
+        assert!(parse_byte_size("M").is_err());
+        assert!(parse_byte_size("99999999999T").is_err());
+    }
+
+    #[test]
+    fn test_parse_cpu_affinity() {
+        assert_eq!(
+            parse_cpu_affinity("0-2,6"),
+            Ok(CpuAffinity::Cpus(vec![0, 1, 2, 6]))
+        );
+        assert_eq!(parse_cpu_affinity("node:1"), Ok(CpuAffinity::Node(1)));
+        assert!(parse_cpu_affinity("node:").is_err());
+        assert!(parse_cpu_affinity("0-").is_err());
+    }
+
+    #[test]
+    fn test_parse_io_priority() {
+        assert_eq!(parse_io_priority("idle"), Ok(IoPriority::idle()));
+        assert_eq!(
+            parse_io_priority("best-effort"),
+            Ok(IoPriority::best_effort(4))
+        );
+        assert_eq!(
+            parse_io_priority("best-effort:7"),
+            Ok(IoPriority::best_effort(7))
+        );
+        assert!(parse_io_priority("best-effort:8").is_err());
+        assert!(parse_io_priority("idle:3").is_err());
+        assert!(parse_io_priority("realtime").is_err());
+    }
+
+    #[test]
+    fn test_adaptive_concurrency_disabled_by() {
+        let deterministic = Args {
+            deterministic: true,
+            ..Default::default()
+        };
+        assert!(!deterministic.adaptive_concurrency_enabled());
+        assert_eq!(
+            deterministic.adaptive_concurrency_disabled_by(),
+            "--deterministic"
+        );
+
+        let both = Args {
+            no_adaptive_concurrency: true,
+            ..deterministic
+        };
+        assert_eq!(
+            both.adaptive_concurrency_disabled_by(),
+            "--no-adaptive-concurrency and --deterministic"
+        );
+        assert_eq!(Args::default().adaptive_concurrency_disabled_by(), "");
+    }
 }
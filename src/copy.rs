@@ -39,19 +39,42 @@
 //! }
 //! ```
 
-use crate::cli::Args;
+use crate::adaptive_concurrency::record_completion;
+use crate::budget::ChunkBudget;
+use crate::chunking::{ChunkSizer, InFlightCopy};
+use crate::cli::{Args, ChmodOp, ChmodRule, ChmodTarget, CopyMethod};
+use crate::crtime::CrtimeChange;
 use crate::error::{Result, SyncError};
+use crate::fake_super::FakeStat;
+use crate::fs_profile::{profile_for, FilesystemKind, FsProfile};
+use crate::memlock::{self, POOL_BUFFER_SIZE};
+use crate::ownership::OwnershipChange;
 use compio::fs::OpenOptions;
 use compio::io::{AsyncReadAt, AsyncWriteAt};
-use std::os::unix::fs::PermissionsExt;
-use std::path::Path;
-use std::time::SystemTime;
-
-/// Default I/O buffer size (in bytes) used for chunked read/write operations.
-///
-/// Chosen to balance syscall overhead and memory usage. Adjust if profiling
-/// indicates different optimal sizes for specific workloads.
-const BUFFER_SIZE: usize = 64 * 1024; // 64KB buffer
+use compio_fs_extended::buffer_pool::{BufferLease, BufferPool};
+use compio_fs_extended::directory::DirectoryFd;
+use compio_fs_extended::ioprio::{self, IoPriority};
+use compio_fs_extended::probe::capabilities;
+use compio_fs_extended::ExtendedError;
+use std::cell::OnceCell;
+use std::ffi::OsString;
+use std::io::ErrorKind;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime};
+
+/// Details about a completed file copy beyond success or failure
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CopyOutcome {
+    /// What happened when applying ownership to the destination
+    pub ownership: OwnershipChange,
+    /// What happened when preserving the creation time (`--crtimes`)
+    pub crtime: CrtimeChange,
+    /// Bytes of content written; less than the file size when
+    /// `--no-whole-file` found parts of the destination already up to date
+    pub written: u64,
+}
 
 /// Copy a single file using the specified method
 ///
@@ -64,10 +87,30 @@ const BUFFER_SIZE: usize = 64 * 1024; // 64KB buffer
 /// - Metadata preservation fails
 /// - The specified copy method is not supported or fails
 #[allow(clippy::future_not_send)]
-pub async fn copy_file(src: &Path, dst: &Path, args: &Args) -> Result<()> {
+pub async fn copy_file(src: &Path, dst: &Path, args: &Args) -> Result<CopyOutcome> {
     // Simplified: always use read/write method
     // This is the only reliable method that works everywhere
-    copy_read_write(src, dst, args).await
+    copy_read_write(src, None, dst, args).await
+}
+
+/// Copy the file `src`, the entry of the open directory `src_dir` with the
+/// same name, to `dst`
+///
+/// The source is looked up and opened relative to `src_dir`, so a parent
+/// directory swapped for a symlink cannot redirect the copy; `src` is only
+/// used in messages.
+///
+/// # Errors
+///
+/// Same as [`copy_file`].
+#[allow(clippy::future_not_send)]
+pub async fn copy_file_at(
+    src_dir: &DirectoryFd,
+    src: &Path,
+    dst: &Path,
+    args: &Args,
+) -> Result<CopyOutcome> {
+    copy_read_write(src, Some(src_dir), dst, args).await
 }
 
 /// Copy file using compio read/write operations (reliable fallback)
@@ -76,6 +119,9 @@ pub async fn copy_file(src: &Path, dst: &Path, args: &Args) -> Result<()> {
 /// compio's async read/write operations. While not as fast as `copy_file_range` or
 /// `splice`, it works in all scenarios and provides guaranteed compatibility.
 ///
+/// With `--copy-method auto`, reflinks or `copy_file_range` are tried first
+/// where the destination's [filesystem profile](crate::fs_profile) allows.
+///
 /// # Parameters
 ///
 /// * `src` - Source file path
@@ -83,7 +129,7 @@ pub async fn copy_file(src: &Path, dst: &Path, args: &Args) -> Result<()> {
 ///
 /// # Returns
 ///
-/// Returns `Ok(())` if the file was copied successfully, or `Err(SyncError)` if failed.
+/// Returns a [`CopyOutcome`] if the file was copied successfully, or `Err(SyncError)` if failed.
 ///
 /// # Performance Notes
 ///
@@ -107,20 +153,268 @@ pub async fn copy_file(src: &Path, dst: &Path, args: &Args) -> Result<()> {
 /// }
 /// ```
 #[allow(clippy::future_not_send, clippy::too_many_lines)]
-async fn copy_read_write(src: &Path, dst: &Path, args: &Args) -> Result<()> {
+async fn copy_read_write(
+    src: &Path,
+    src_dir: Option<&DirectoryFd>,
+    dst: &Path,
+    args: &Args,
+) -> Result<CopyOutcome> {
+    let src_name = src_dir.and(src.file_name()).map(Path::new);
+
     // Capture source timestamps BEFORE any reads to avoid atime/mtime drift
-    let (src_accessed, src_modified) = get_precise_timestamps(src).await?;
+    let (src_accessed, src_modified) = match (src_dir, src_name) {
+        (Some(dir), Some(name)) => get_precise_timestamps_at(dir, name).await?,
+        _ => get_precise_timestamps(src).await?,
+    };
 
     // Open source file
-    let src_file = OpenOptions::new().read(true).open(src).await.map_err(|e| {
+    let src_file = match (src_dir, src_name) {
+        (Some(dir), Some(name)) => crate::atime::open_source_at(dir, name).await,
+        _ => crate::atime::open_source(src).await,
+    }
+    .map_err(|e| {
         SyncError::FileSystem(format!("Failed to open source file {}: {e}", src.display(),))
     })?;
 
-    // Open destination file
-    let mut dst_file = OpenOptions::new()
+    // With --no-whole-file an existing destination is updated in place
+    let delta = args.no_whole_file
+        && compio::fs::symlink_metadata(dst)
+            .await
+            .is_ok_and(|existing| existing.is_file());
+
+    let (mut dst_file, pending_link) = open_destination(dst, delta, args).await?;
+
+    // Get file size for progress tracking
+    let metadata = src_file
+        .metadata()
+        .await
+        .map_err(|e| SyncError::FileSystem(format!("Failed to get source file metadata: {e}")))?;
+    let file_size = metadata.len();
+
+    // Block devices the copy draws on, for --max-device-bytes
+    let devices = if crate::budget::limits_devices() {
+        let dst_metadata = dst_file.metadata().await.map_err(|e| {
+            SyncError::FileSystem(format!("Failed to get destination file metadata: {e}"))
+        })?;
+        vec![metadata.dev(), dst_metadata.dev()]
+    } else {
+        Vec::new()
+    };
+
+    // Pick a copy strategy for the filesystems involved
+    let src_profile = profile_for(FilesystemKind::of_fd(src_file.as_raw_fd()).await);
+    let dst_profile = profile_for(FilesystemKind::of_fd(dst_file.as_raw_fd()).await);
+    let kernel_copy = matches!(args.copy_method, CopyMethod::Auto);
+
+    // chattr +C only takes effect while the destination is still empty
+    if dst_profile.nocow {
+        preserve_nocow(&src_file, &dst_file, dst).await;
+    }
+
+    // Everything that writes the content; a destination created by name is
+    // removed if it fails, rather than left behind with partial content
+    let content = async {
+        let mut copied = None;
+        let mut written = None;
+        if delta {
+            let mut sizer = ChunkSizer::fixed(if args.buffer_size_kb > 0 {
+                args.buffer_size_bytes()
+            } else {
+                src_profile.buffer_size.max(dst_profile.buffer_size)
+            });
+            let budget = ChunkBudget::buffers(&devices);
+            let (total, changed) = copy_data_delta(
+                &src_file,
+                &mut dst_file,
+                file_size,
+                &mut sizer,
+                &budget,
+                args.io_priority,
+            )
+            .await?;
+            copied = Some(total);
+            written = Some(changed);
+        }
+
+        if copied.is_none() && kernel_copy && dst_profile.reflink && file_size > 0 {
+            match compio_fs_extended::copy::clone_file(&src_file, &dst_file).await {
+                Ok(()) => {
+                    crate::systemd::record_bytes(file_size);
+                    copied = Some(file_size);
+                }
+                Err(e) => tracing::debug!("Reflink unavailable for {}: {e}", dst.display()),
+            }
+        }
+
+        // Sizes both copy_file_range windows and read/write chunks
+        let mut sizer = if args.buffer_size_kb > 0 {
+            ChunkSizer::fixed(args.buffer_size_bytes())
+        } else if args.deterministic {
+            ChunkSizer::fixed(src_profile.buffer_size.max(dst_profile.buffer_size))
+        } else {
+            ChunkSizer::adaptive(src_profile.buffer_size.max(dst_profile.buffer_size))
+        };
+
+        if copied.is_none() && file_size > 0 {
+            prepare_for_copy(&src_file, &dst_file, file_size, &src_profile, &dst_profile).await?;
+            if kernel_copy && dst_profile.copy_file_range {
+                let budget = ChunkBudget::devices(&devices);
+                copied =
+                    copy_data_in_kernel(&src_file, &dst_file, file_size, dst, &mut sizer, &budget)
+                        .await?;
+            }
+        }
+
+        let total_copied = match copied {
+            Some(total) => total,
+            None => {
+                let _in_flight = InFlightCopy::start();
+                copy_data_read_write(
+                    &src_file,
+                    &mut dst_file,
+                    file_size,
+                    &mut sizer,
+                    &ChunkBudget::buffers(&devices),
+                    args.io_priority,
+                )
+                .await?
+            }
+        };
+
+        // Sync the destination file to ensure data is written to disk
+        dst_file
+            .sync_all()
+            .await
+            .map_err(|e| SyncError::FileSystem(format!("Failed to sync destination file: {e}")))?;
+
+        Ok::<_, SyncError>((total_copied, written))
+    }
+    .await;
+    let (total_copied, written) = match content {
+        Ok(content) => content,
+        Err(e) => {
+            if pending_link.is_none() && !delta {
+                if let Err(remove_error) = compio::fs::remove_file(dst).await {
+                    tracing::debug!("Failed to remove partial {}: {remove_error}", dst.display());
+                }
+            }
+            return Err(e);
+        }
+    };
+
+    // The SELinux label goes on right after the content, before other metadata
+    if args.should_preserve_selinux() {
+        crate::security::preserve_selinux_label(&src_file, &dst_file, dst).await;
+    }
+
+    // Preserve file metadata only if explicitly requested (rsync behavior).
+    // Ownership goes first: chown clears setuid/setgid bits set by chmod.
+    let source = crate::fake_super::source_stat_from_fd(&src_file, src, &metadata, args).await;
+    let fake_super = crate::fake_super::stores_in_xattr(args);
+    let mut outcome = CopyOutcome {
+        written: written.unwrap_or(total_copied),
+        ..CopyOutcome::default()
+    };
+    if !fake_super && args.should_set_ownership() {
+        outcome.ownership = preserve_ownership_from_fd(&dst_file, dst, &source, args).await?;
+    }
+
+    if !fake_super && args.should_set_permissions() {
+        preserve_permissions_from_fd(&dst_file, source.mode, args).await?;
+    }
+
+    if args.should_preserve_xattrs() {
+        copy_xattrs_except(&src_file, &dst_file, &managed_xattrs(args)).await?;
+    }
+
+    // Capabilities are cleared by writes and chown, so they go on after both
+    if args.should_preserve_file_capabilities() {
+        crate::security::preserve_file_capabilities(&src_file, &dst_file, dst).await;
+    }
+
+    // Record the fake-super stat after copying xattrs so it replaces the source's
+    if fake_super {
+        outcome.ownership = crate::fake_super::store(&dst_file, dst, &source, false, args).await?;
+    } else if args.fake_super && args.should_preserve_xattrs() {
+        crate::fake_super::clear(&dst_file, dst).await;
+    }
+
+    if args.should_preserve_timestamps() {
+        preserve_timestamps_from_fd(&dst_file, src_accessed, src_modified).await?;
+    }
+
+    // Every data path (reflink, copy_file_range, read/write, delta) ends here
+    if args.should_preserve_crtimes() {
+        outcome.crtime = crate::crtime::preserve_crtime(&src_file, &dst_file, dst).await;
+    }
+
+    // With --tmpfile the destination only gets its name once it is complete
+    if let Some(link) = &pending_link {
+        link_into_place(&dst_file, link, dst).await?;
+    }
+
+    tracing::debug!(
+        "compio read_at/write_at: successfully copied {} bytes",
+        total_copied
+    );
+    Ok(outcome)
+}
+
+/// Where an anonymous `--tmpfile` destination is linked once complete
+struct PendingLink {
+    /// Directory the destination is created in
+    dir: DirectoryFd,
+    /// Final name of the destination in `dir`
+    name: PathBuf,
+}
+
+/// Open the destination of a copy for writing
+///
+/// With `--tmpfile`, a destination that is written from scratch is created
+/// as an anonymous file and returned along with where to link it. Files
+/// updated in place by `--no-whole-file`, and filesystems without
+/// `O_TMPFILE`, open `dst` by name instead.
+#[allow(clippy::future_not_send)]
+async fn open_destination(
+    dst: &Path,
+    delta: bool,
+    args: &Args,
+) -> Result<(compio::fs::File, Option<PendingLink>)> {
+    use compio_fs_extended::tmpfile::{is_unsupported, open_tmpfile};
+
+    if let (true, false, Some(name)) = (args.tmpfile, delta, dst.file_name()) {
+        let parent = match dst.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let dir = DirectoryFd::open(parent).await.map_err(|e| {
+            SyncError::FileSystem(format!(
+                "Failed to open destination directory {}: {e}",
+                parent.display()
+            ))
+        })?;
+        match open_tmpfile(&dir, 0o666).await {
+            Ok(file) => {
+                let name = PathBuf::from(name);
+                return Ok((file, Some(PendingLink { dir, name })));
+            }
+            Err(e) if is_unsupported(&e) => {
+                tracing::debug!("O_TMPFILE unavailable for {}: {e}", dst.display());
+            }
+            Err(e) => {
+                return Err(SyncError::FileSystem(format!(
+                    "Failed to create destination file {}: {e}",
+                    dst.display()
+                )))
+            }
+        }
+    }
+
+    let file = OpenOptions::new()
+        .read(delta)
         .write(true)
         .create(true)
-        .truncate(true)
+        .truncate(!delta)
         .open(dst)
         .await
         .map_err(|e| {
@@ -129,48 +423,89 @@ async fn copy_read_write(src: &Path, dst: &Path, args: &Args) -> Result<()> {
                 dst.display(),
             ))
         })?;
+    Ok((file, None))
+}
 
-    // Get file size for progress tracking
-    let metadata = src_file
-        .metadata()
+/// Give a complete anonymous destination its final name
+///
+/// An existing destination is replaced atomically: the new file is linked
+/// under a hidden name and renamed over it, so readers see either the old
+/// or the new contents.
+#[allow(clippy::future_not_send)]
+async fn link_into_place(file: &compio::fs::File, link: &PendingLink, dst: &Path) -> Result<()> {
+    use compio_fs_extended::rename::{rename_at, RenameMode};
+    use compio_fs_extended::tmpfile::link_tmpfile;
+    use compio_fs_extended::unlink::unlink_at;
+
+    let failed = |e: compio_fs_extended::ExtendedError| {
+        SyncError::FileSystem(format!("Failed to link {} into place: {e}", dst.display()))
+    };
+    match link_tmpfile(file, &link.dir, &link.name).await {
+        Ok(()) => return Ok(()),
+        Err(ExtendedError::Io(e)) if e.kind() == ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(failed(e)),
+    }
+
+    let mut hidden = OsString::from(".");
+    hidden.push(&link.name);
+    hidden.push(format!(".{}.arsync", std::process::id()));
+    let hidden = Path::new(&hidden);
+    link_tmpfile(file, &link.dir, hidden)
         .await
-        .map_err(|e| SyncError::FileSystem(format!("Failed to get source file metadata: {e}")))?;
-    let file_size = metadata.len();
+        .map_err(failed)?;
+    if let Err(e) = rename_at(
+        &link.dir,
+        hidden,
+        &link.dir,
+        &link.name,
+        RenameMode::Replace,
+    )
+    .await
+    {
+        let _ = unlink_at(&link.dir, hidden).await;
+        return Err(failed(e));
+    }
+    Ok(())
+}
 
-    // Preallocate destination file space to the final size to reduce fragmentation
-    // and improve write performance using io_uring fallocate.
-    // Skip preallocation for empty files as fallocate fails with EINVAL for zero length.
-    if file_size > 0 {
-        use compio_fs_extended::{fadvise::FadviseAdvice, ExtendedFile, Fadvise, Fallocate};
+/// Issue `fadvise` hints and preallocate the destination, as the profiles allow
+#[allow(clippy::future_not_send)]
+async fn prepare_for_copy(
+    src_file: &compio::fs::File,
+    dst_file: &compio::fs::File,
+    file_size: u64,
+    src_profile: &FsProfile,
+    dst_profile: &FsProfile,
+) -> Result<()> {
+    use compio_fs_extended::{fadvise::FadviseAdvice, ExtendedFile, Fadvise, Fallocate};
 
-        // Apply fadvise hints to both source and destination for "one and done" copy
-        let extended_src = ExtendedFile::from_ref(&src_file);
-        let extended_dst = ExtendedFile::from_ref(&dst_file);
+    // Apply fadvise hints to both source and destination for "one and done" copy
+    let extended_src = ExtendedFile::from_ref(src_file);
+    let extended_dst = ExtendedFile::from_ref(dst_file);
+    let len = file_size.try_into().unwrap_or(i64::MAX);
 
-        // Hint that source data won't be accessed again after this copy
+    // Hint that source data won't be accessed again after this copy
+    if src_profile.fadvise {
         extended_src
-            .fadvise(
-                FadviseAdvice::NoReuse,
-                0,
-                file_size.try_into().unwrap_or(i64::MAX),
-            )
+            .fadvise(FadviseAdvice::NoReuse, 0, len)
             .await
             .map_err(|e| {
                 SyncError::FileSystem(format!("Failed to set fadvise NoReuse hint on source: {e}"))
             })?;
+    }
 
-        // Preallocate destination file space
+    // Preallocate destination file space to the final size to reduce fragmentation
+    // and improve write performance using io_uring fallocate.
+    if dst_profile.fallocate {
         extended_dst.fallocate(0, file_size, 0).await.map_err(|e| {
             SyncError::FileSystem(format!("Failed to preallocate destination file: {e}"))
         })?;
+    }
 
-        // Hint that destination data won't be accessed again after this copy
+    // Hint that destination data won't be accessed again after this copy
+    if dst_profile.fadvise {
         extended_dst
-            .fadvise(
-                FadviseAdvice::NoReuse,
-                0,
-                file_size.try_into().unwrap_or(i64::MAX),
-            )
+            .fadvise(FadviseAdvice::NoReuse, 0, len)
             .await
             .map_err(|e| {
                 SyncError::FileSystem(format!(
@@ -179,103 +514,484 @@ async fn copy_read_write(src: &Path, dst: &Path, args: &Args) -> Result<()> {
             })?;
     }
 
-    // Use compio's async read_at/write_at operations
-    let mut offset = 0u64;
-    let mut total_copied = 0u64;
+    Ok(())
+}
 
-    while total_copied < file_size {
-        // Create a new buffer for each read operation
-        let buffer = vec![0u8; BUFFER_SIZE];
+/// Carry a source's `chattr +C` (no copy-on-write) flag over to an empty destination
+///
+/// Failures only cost the optimization, so they are logged and ignored.
+#[allow(clippy::future_not_send)]
+async fn preserve_nocow(src_file: &compio::fs::File, dst_file: &compio::fs::File, dst: &Path) {
+    use compio_fs_extended::metadata::{inode_flags, set_inode_flags, FS_NOCOW_FL};
 
-        // Read data from source file using compio
-        let buf_result = src_file.read_at(buffer, offset).await;
+    let Ok(src_flags) = inode_flags(src_file.as_raw_fd()).await else {
+        return;
+    };
+    if src_flags & FS_NOCOW_FL == 0 {
+        return;
+    }
+    let result = match inode_flags(dst_file.as_raw_fd()).await {
+        Ok(dst_flags) => set_inode_flags(dst_file.as_raw_fd(), dst_flags | FS_NOCOW_FL).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        tracing::debug!("Failed to set nocow on {}: {e}", dst.display());
+    }
+}
 
-        let bytes_read = buf_result
-            .0
-            .map_err(|e| SyncError::IoUring(format!("compio read_at operation failed: {e}")))?;
+/// Copy file data inside the kernel with `copy_file_range`
+///
+/// Returns `None` if copying fails within the first window, so the caller
+/// can fall back to read/write (which rewrites from the start). Each window
+/// is sized by `sizer` like a read/write chunk, is at most what `budget`
+/// allows, and holds the budget until copied; the syscalls run on a blocking
+/// thread so the runtime keeps serving other files.
+///
+/// Progress is recorded after every window, so a large file keeps the
+/// systemd watchdog fed while it copies.
+#[allow(clippy::future_not_send, clippy::too_many_arguments)]
+async fn copy_data_in_kernel(
+    src_file: &compio::fs::File,
+    dst_file: &compio::fs::File,
+    file_size: u64,
+    dst: &Path,
+    sizer: &mut ChunkSizer,
+    budget: &ChunkBudget,
+) -> Result<Option<u64>> {
+    use compio_fs_extended::copy::{copy_file_range_all, CopyRangeOutcome};
 
-        let read_buffer = buf_result.1;
+    let mut total_copied = 0u64;
+    while total_copied < file_size {
+        let limit = budget.limit().unwrap_or(usize::MAX).min(sizer.current());
+        let window = (file_size - total_copied).min(u64::try_from(limit).unwrap_or(u64::MAX));
+        let _reserved = budget
+            .reserve(usize::try_from(window).unwrap_or(usize::MAX))
+            .await;
+        let started = Instant::now();
+        let copied =
+            match copy_file_range_all(src_file, dst_file, total_copied, total_copied, window).await
+            {
+                Ok(CopyRangeOutcome::Copied(copied)) => copied,
+                Ok(CopyRangeOutcome::Unsupported { error, .. }) if total_copied == 0 => {
+                    tracing::debug!("copy_file_range unavailable for {}: {error}", dst.display());
+                    return Ok(None);
+                }
+                Err(e) if total_copied == 0 => {
+                    tracing::debug!("copy_file_range unavailable for {}: {e}", dst.display());
+                    return Ok(None);
+                }
+                Ok(CopyRangeOutcome::Unsupported { error, .. }) => {
+                    return Err(SyncError::CopyFailed(format!(
+                        "copy_file_range failed for {}: {error}",
+                        dst.display()
+                    )))
+                }
+                Err(e) => {
+                    return Err(SyncError::CopyFailed(format!(
+                        "copy_file_range failed for {}: {e}",
+                        dst.display()
+                    )))
+                }
+            };
+        record_completion(started.elapsed());
+        sizer.record(
+            usize::try_from(copied).unwrap_or(usize::MAX),
+            started.elapsed(),
+        );
+        total_copied += copied;
+        crate::systemd::record_bytes(copied);
+        if copied < window {
+            // Source shrank while copying
+            break;
+        }
+    }
+    Ok(Some(total_copied))
+}
 
+/// Copy file data through userspace buffers with compio `read_at`/`write_at`
+///
+/// Each chunk is sized by `sizer`, which is told how long the chunk took,
+/// and its buffer is reserved from `budget` until written. Chunks of at
+/// most [`POOL_BUFFER_SIZE`] reuse one of this thread's registered buffers
+/// instead of allocating; larger chunks bypass the pool.
+///
+/// With `priority`, every read and write SQE carries that I/O priority.
+#[allow(clippy::future_not_send, clippy::too_many_arguments)]
+async fn copy_data_read_write(
+    src_file: &compio::fs::File,
+    dst_file: &mut compio::fs::File,
+    file_size: u64,
+    sizer: &mut ChunkSizer,
+    budget: &ChunkBudget,
+    priority: Option<IoPriority>,
+) -> Result<u64> {
+    let mut offset = 0u64;
+    let mut total_copied = 0u64;
+
+    while total_copied < file_size {
+        // Each chunk is no larger than what is left
+        let remaining = usize::try_from(file_size - total_copied).unwrap_or(usize::MAX);
+        // Never ask for more than a whole budget
+        let chunk_len = sizer
+            .current()
+            .min(remaining)
+            .min(budget.limit().unwrap_or(usize::MAX));
+        // Held until the chunk is written, so waiting here is the backpressure
+        let _reserved = budget.reserve(chunk_len).await;
+        let chunk_started = Instant::now();
+
+        // Chunks that fit go through a buffer registered with the ring
+        let lease = buffer_pool()
+            .filter(|pool| pool.buffer_size() >= chunk_len)
+            .and_then(|pool| pool.try_lease())
+            .map(|mut lease| {
+                lease.set_len(chunk_len);
+                lease
+            });
+        let (bytes_read, bytes_written) = match lease {
+            Some(lease) => copy_chunk_pooled(src_file, dst_file, lease, offset, priority).await?,
+            None => copy_chunk(src_file, dst_file, chunk_len, offset, priority).await?,
+        };
         if bytes_read == 0 {
             // End of file
             break;
         }
 
-        // Truncate the buffer to the actual bytes read
-        let write_buffer = read_buffer[..bytes_read].to_vec();
-
-        // Write data to destination file using compio
-        let write_buf_result = dst_file.write_at(write_buffer, offset).await;
-
-        let bytes_written = write_buf_result
-            .0
-            .map_err(|e| SyncError::IoUring(format!("compio write_at operation failed: {e}")))?;
-
-        // Ensure we wrote the expected number of bytes
-        if bytes_written != bytes_read {
+        // A short write is resumed by the next chunk, from where it stopped
+        if bytes_written == 0 {
             return Err(SyncError::CopyFailed(format!(
-                "Write size mismatch: expected {bytes_read}, got {bytes_written}"
+                "Write made no progress at offset {offset}"
             )));
         }
 
+        sizer.record(bytes_written, chunk_started.elapsed());
         total_copied += bytes_written as u64;
         offset += bytes_written as u64;
+        crate::systemd::record_bytes(bytes_written as u64);
 
         tracing::debug!(
-            "compio read_at/write_at: copied {} bytes, total: {}/{}",
+            "compio read_at/write_at: copied {} bytes, total: {}/{}, next chunk {}",
             bytes_written,
             total_copied,
-            file_size
+            file_size,
+            sizer.current()
         );
     }
 
-    // Sync the destination file to ensure data is written to disk
-    dst_file
-        .sync_all()
-        .await
-        .map_err(|e| SyncError::FileSystem(format!("Failed to sync destination file: {e}")))?;
+    Ok(total_copied)
+}
 
-    // Preserve file metadata only if explicitly requested (rsync behavior)
-    if args.should_preserve_permissions() {
-        preserve_permissions_from_fd(&src_file, &dst_file).await?;
-    }
+thread_local! {
+    /// Buffers registered with this thread's ring, set up on first use;
+    /// `None` if registration failed or the locked-memory limit leaves no
+    /// room (see [`memlock`])
+    static BUFFER_POOL: OnceCell<Option<BufferPool>> = const { OnceCell::new() };
+}
+
+/// The registered buffer pool of this thread's ring, if it has one
+fn buffer_pool() -> Option<BufferPool> {
+    BUFFER_POOL.with(|pool| {
+        pool.get_or_init(|| {
+            if !capabilities().supports_fixed_buffers() {
+                return None;
+            }
+            let buffers = memlock::pool_buffers();
+            if buffers == 0 {
+                return None;
+            }
+            match BufferPool::new(buffers, POOL_BUFFER_SIZE) {
+                Ok(pool) => Some(pool),
+                Err(e) => {
+                    tracing::debug!("Copying through unregistered buffers: {e}");
+                    None
+                }
+            }
+        })
+        .clone()
+        .filter(BufferPool::is_current)
+    })
+}
 
-    if args.should_preserve_ownership() {
-        preserve_ownership_from_fd(&src_file, &dst_file).await?;
+/// Read one chunk into a fresh buffer and write it out
+///
+/// Returns the bytes read and written; `(0, 0)` at end of file.
+#[allow(clippy::future_not_send)]
+async fn copy_chunk(
+    src_file: &compio::fs::File,
+    dst_file: &mut compio::fs::File,
+    chunk_len: usize,
+    offset: u64,
+    priority: Option<IoPriority>,
+) -> Result<(usize, usize)> {
+    let buffer = vec![0u8; chunk_len];
+    let read_started = Instant::now();
+
+    // Read data from source file using compio
+    let buf_result = match priority {
+        Some(priority) => ioprio::read_at(src_file, buffer, offset, priority).await,
+        None => src_file.read_at(buffer, offset).await,
+    };
+
+    #[cfg(feature = "fault-injection")]
+    let buf_result = (
+        crate::fault::read_completed(buf_result.0).await,
+        buf_result.1,
+    );
+    let bytes_read = buf_result
+        .0
+        .map_err(|e| SyncError::IoUring(format!("compio read_at operation failed: {e}")))?;
+    record_completion(read_started.elapsed());
+
+    let mut write_buffer = buf_result.1;
+
+    if bytes_read == 0 {
+        return Ok((0, 0));
     }
 
-    if args.should_preserve_xattrs() {
-        preserve_xattr_from_fd(&src_file, &dst_file).await?;
+    // Truncate the buffer to the actual bytes read; writing it in place
+    // keeps a single buffer per chunk within the memory budget
+    write_buffer.truncate(bytes_read);
+
+    // Write data to destination file using compio
+    let write_started = Instant::now();
+    let write_buf_result = match priority {
+        Some(priority) => ioprio::write_at(dst_file, write_buffer, offset, priority).await,
+        None => dst_file.write_at(write_buffer, offset).await,
+    };
+    #[cfg(feature = "fault-injection")]
+    let write_buf_result = (
+        crate::fault::write_completed(write_buf_result.0).await,
+        write_buf_result.1,
+    );
+
+    let bytes_written = write_buf_result
+        .0
+        .map_err(|e| SyncError::IoUring(format!("compio write_at operation failed: {e}")))?;
+    record_completion(write_started.elapsed());
+    Ok((bytes_read, bytes_written))
+}
+
+/// Like [`copy_chunk`] for a chunk the length of `lease`, through its
+/// registered buffer with fixed-buffer I/O
+#[allow(clippy::future_not_send)]
+async fn copy_chunk_pooled(
+    src_file: &compio::fs::File,
+    dst_file: &compio::fs::File,
+    lease: BufferLease,
+    offset: u64,
+    priority: Option<IoPriority>,
+) -> Result<(usize, usize)> {
+    let read_started = Instant::now();
+    let (bytes_read, mut lease) = lease.read_at(src_file, offset, priority).await;
+    #[cfg(feature = "fault-injection")]
+    let bytes_read = crate::fault::read_completed(bytes_read).await;
+    let bytes_read = bytes_read
+        .map_err(|e| SyncError::IoUring(format!("compio read_fixed operation failed: {e}")))?;
+    record_completion(read_started.elapsed());
+    if bytes_read == 0 {
+        return Ok((0, 0));
     }
+    lease.set_len(bytes_read);
+
+    let write_started = Instant::now();
+    let (bytes_written, _lease) = lease.write_at(dst_file, offset, priority).await;
+    #[cfg(feature = "fault-injection")]
+    let bytes_written = crate::fault::write_completed(bytes_written).await;
+    let bytes_written = bytes_written
+        .map_err(|e| SyncError::IoUring(format!("compio write_fixed operation failed: {e}")))?;
+    record_completion(write_started.elapsed());
+    Ok((bytes_read, bytes_written))
+}
 
-    if args.should_preserve_timestamps() {
-        preserve_timestamps_from_fd(&dst_file, src_accessed, src_modified).await?;
+/// Bring an existing destination up to date for `--no-whole-file`
+///
+/// Reads the source and the destination chunk by chunk and writes only the
+/// chunks that differ, then truncates the destination to the source's
+/// length. Returns the bytes read from the source and the bytes written.
+#[allow(clippy::future_not_send, clippy::too_many_arguments)]
+async fn copy_data_delta(
+    src_file: &compio::fs::File,
+    dst_file: &mut compio::fs::File,
+    file_size: u64,
+    sizer: &mut ChunkSizer,
+    budget: &ChunkBudget,
+    priority: Option<IoPriority>,
+) -> Result<(u64, u64)> {
+    let mut offset = 0u64;
+    let mut written = 0u64;
+
+    while offset < file_size {
+        let remaining = usize::try_from(file_size - offset).unwrap_or(usize::MAX);
+        // Each chunk holds two buffers: the source's and the destination's
+        let chunk_len = sizer.current().min(remaining).min(
+            budget
+                .limit()
+                .map_or(usize::MAX, |limit| (limit / 2).max(1)),
+        );
+        let _reserved = budget.reserve(chunk_len * 2).await;
+        let chunk_started = Instant::now();
+
+        let buf_result = match priority {
+            Some(priority) => {
+                ioprio::read_at(src_file, vec![0u8; chunk_len], offset, priority).await
+            }
+            None => src_file.read_at(vec![0u8; chunk_len], offset).await,
+        };
+        #[cfg(feature = "fault-injection")]
+        let buf_result = (
+            crate::fault::read_completed(buf_result.0).await,
+            buf_result.1,
+        );
+        let bytes_read = buf_result
+            .0
+            .map_err(|e| SyncError::IoUring(format!("compio read_at operation failed: {e}")))?;
+        record_completion(chunk_started.elapsed());
+        if bytes_read == 0 {
+            // Source shrank while copying
+            break;
+        }
+        let mut src_chunk = buf_result.1;
+        src_chunk.truncate(bytes_read);
+
+        let dst_started = Instant::now();
+        let buf_result = match priority {
+            Some(priority) => {
+                ioprio::read_at(dst_file, vec![0u8; bytes_read], offset, priority).await
+            }
+            None => dst_file.read_at(vec![0u8; bytes_read], offset).await,
+        };
+        let dst_read = buf_result.0.map_err(|e| {
+            SyncError::IoUring(format!(
+                "compio read_at operation failed on destination: {e}"
+            ))
+        })?;
+        record_completion(dst_started.elapsed());
+        let mut dst_chunk = buf_result.1;
+        dst_chunk.truncate(dst_read);
+
+        // A short write is resumed by the next chunk, from where it stopped
+        let mut advanced = bytes_read;
+        if dst_chunk != src_chunk {
+            let write_started = Instant::now();
+            let write_buf_result = match priority {
+                Some(priority) => ioprio::write_at(dst_file, src_chunk, offset, priority).await,
+                None => dst_file.write_at(src_chunk, offset).await,
+            };
+            #[cfg(feature = "fault-injection")]
+            let write_buf_result = (
+                crate::fault::write_completed(write_buf_result.0).await,
+                write_buf_result.1,
+            );
+            let bytes_written = write_buf_result.0.map_err(|e| {
+                SyncError::IoUring(format!("compio write_at operation failed: {e}"))
+            })?;
+            record_completion(write_started.elapsed());
+            if bytes_written == 0 {
+                return Err(SyncError::CopyFailed(format!(
+                    "Write made no progress at offset {offset}"
+                )));
+            }
+            advanced = bytes_written;
+            written += bytes_written as u64;
+            crate::systemd::record_bytes(bytes_written as u64);
+        }
+
+        sizer.record(advanced, chunk_started.elapsed());
+        offset += advanced as u64;
     }
 
-    tracing::debug!(
-        "compio read_at/write_at: successfully copied {} bytes",
-        total_copied
-    );
-    Ok(())
+    // Drop whatever the destination had beyond the source's end
+    compio_fs_extended::truncate::ftruncate(dst_file, offset)
+        .await
+        .map_err(|e| SyncError::FileSystem(format!("Failed to truncate destination file: {e}")))?;
+
+    tracing::debug!("delta update: {written} of {offset} bytes differed and were rewritten");
+    Ok((offset, written))
+}
+
+/// Compute the permission bits to apply to a destination entry
+///
+/// The base mode is chosen in order of precedence:
+/// 1. `--file-mode` / `--dir-mode` force a fixed mode, ignoring the source
+/// 2. `--perms` preserves the source mode including special bits
+/// 3. `--umask` applies the source mode masked by the umask, with
+///    setuid/setgid/sticky cleared (rsync's behavior for new files)
+/// 4. With only `--chmod` given, the source mode with special bits cleared
+///
+/// `--chmod` rules are then applied on top of the base mode.
+///
+/// Returns `None` when none of these apply and the mode the entry was
+/// created with should be left alone.
+#[must_use]
+pub fn destination_mode(src_mode: u32, is_dir: bool, args: &Args) -> Option<u32> {
+    let forced = if is_dir {
+        args.dir_mode
+    } else {
+        args.file_mode
+    };
+    let base = if let Some(mode) = forced {
+        mode
+    } else if args.should_preserve_permissions() {
+        src_mode & 0o7777
+    } else if let Some(mask) = args.umask {
+        src_mode & 0o777 & !mask
+    } else if !args.chmod.is_empty() {
+        src_mode & 0o777
+    } else {
+        return None;
+    };
+    Some(apply_chmod_rules(base, is_dir, &args.chmod))
+}
+
+/// Apply `--chmod` rules to a permission mode
+///
+/// Rules are applied in order; rules with a `D` prefix only affect
+/// directories and rules with an `F` prefix only affect non-directories.
+#[must_use]
+pub fn apply_chmod_rules(mode: u32, is_dir: bool, rules: &[ChmodRule]) -> u32 {
+    let mut mode = mode & 0o7777;
+    for rule in rules {
+        let applies = match rule.target {
+            ChmodTarget::All => true,
+            ChmodTarget::Dirs => is_dir,
+            ChmodTarget::Files => !is_dir,
+        };
+        if !applies {
+            continue;
+        }
+        for op in &rule.ops {
+            mode = match *op {
+                ChmodOp::Octal(octal) => octal,
+                ChmodOp::Add(bits) => mode | bits,
+                ChmodOp::Remove(bits) => mode & !bits,
+                ChmodOp::Set { who, bits } => (mode & !who) | bits,
+                ChmodOp::AddConditionalExec(bits) => {
+                    if is_dir || mode & 0o111 != 0 {
+                        mode | bits
+                    } else {
+                        mode
+                    }
+                }
+            };
+        }
+    }
+    mode
 }
 
 /// Preserve only file permissions from source to destination
 ///
 /// This function preserves file permissions including special bits (setuid, setgid, sticky)
-/// using the chmod syscall for maximum compatibility and precision.
+/// using the chmod syscall for maximum compatibility and precision. The mode applied is
+/// subject to the permission policy in [`destination_mode`].
 #[allow(clippy::future_not_send)]
 async fn preserve_permissions_from_fd(
-    src_file: &compio::fs::File,
     dst_file: &compio::fs::File,
+    src_mode: u32,
+    args: &Args,
 ) -> Result<()> {
-    // Get source file permissions using file descriptor
-    let src_metadata = src_file
-        .metadata()
-        .await
-        .map_err(|e| SyncError::FileSystem(format!("Failed to get source file metadata: {e}")))?;
-
-    let std_permissions = src_metadata.permissions();
-    let mode = std_permissions.mode();
+    let Some(mode) = destination_mode(src_mode, false, args) else {
+        return Ok(());
+    };
 
     // Convert to compio::fs::Permissions
     let compio_permissions = compio::fs::Permissions::from_mode(mode);
@@ -288,19 +1004,28 @@ async fn preserve_permissions_from_fd(
 }
 
 /// Preserve file ownership using file descriptors
+///
+/// The destination's current ownership is compared first so that no-op
+/// changes never reach `fchownat` (and never fail when unprivileged).
 #[allow(clippy::future_not_send)]
 async fn preserve_ownership_from_fd(
-    src_file: &compio::fs::File,
     dst_file: &compio::fs::File,
-) -> Result<()> {
-    use compio_fs_extended::OwnershipOps;
+    dst: &Path,
+    source: &FakeStat,
+    args: &Args,
+) -> Result<OwnershipChange> {
+    let dst_metadata = dst_file.metadata().await.map_err(|e| {
+        SyncError::FileSystem(format!("Failed to get destination file metadata: {e}"))
+    })?;
 
-    // Use compio-fs-extended for ownership preservation
-    dst_file
-        .preserve_ownership_from(src_file)
-        .await
-        .map_err(|e| SyncError::FileSystem(format!("Failed to preserve file ownership: {e}")))?;
-    Ok(())
+    crate::ownership::apply_ownership(
+        dst_file.as_raw_fd(),
+        dst,
+        (source.uid, source.gid),
+        (dst_metadata.uid(), dst_metadata.gid()),
+        args,
+    )
+    .await
 }
 
 /// Preserve file extended attributes using file descriptors
@@ -323,10 +1048,42 @@ async fn preserve_ownership_from_fd(
 /// - Extended attributes cannot be read from source
 /// - Extended attributes cannot be written to destination
 /// - Permission is denied for xattr operations
-#[allow(clippy::future_not_send)]
+#[allow(clippy::future_not_send, dead_code)]
 pub async fn preserve_xattr_from_fd(
     src_file: &compio::fs::File,
     dst_file: &compio::fs::File,
+) -> Result<()> {
+    copy_xattrs_except(src_file, dst_file, &[]).await
+}
+
+/// Extended attributes that `-X` must not copy verbatim
+///
+/// These are applied separately, with their own ordering and policy:
+/// - `security.selinux` and `security.capability`, see [`crate::security`]
+/// - `user.rsync.%stat` with `--fake-super`, see [`crate::fake_super`]
+#[must_use]
+pub fn managed_xattrs(args: &Args) -> Vec<&'static str> {
+    let mut names = vec![
+        crate::security::SELINUX_XATTR,
+        crate::security::CAPABILITY_XATTR,
+    ];
+    if args.fake_super {
+        names.push(crate::fake_super::FAKE_SUPER_XATTR);
+    }
+    names
+}
+
+/// Copy all extended attributes between open files except those in `skip`
+///
+/// # Errors
+///
+/// Failures on individual attributes are logged and skipped; this currently
+/// always returns `Ok(())`.
+#[allow(clippy::future_not_send)]
+pub async fn copy_xattrs_except(
+    src_file: &compio::fs::File,
+    dst_file: &compio::fs::File,
+    skip: &[&str],
 ) -> Result<()> {
     use compio_fs_extended::{ExtendedFile, XattrOps};
 
@@ -342,6 +1099,9 @@ pub async fn preserve_xattr_from_fd(
 
     // Copy each extended attribute
     for name in xattr_names {
+        if skip.contains(&name.as_str()) {
+            continue;
+        }
         match extended_src.get_xattr(&name).await {
             Ok(value) => {
                 if let Err(e) = extended_dst.set_xattr(&name, &value).await {
@@ -378,6 +1138,22 @@ async fn get_precise_timestamps(path: &Path) -> Result<(SystemTime, SystemTime)>
         .map_err(|e| SyncError::FileSystem(format!("Failed to get precise timestamps: {e}")))
 }
 
+/// Get the access and modification times of the entry `name` of `dir`
+#[allow(clippy::future_not_send)]
+async fn get_precise_timestamps_at(
+    dir: &DirectoryFd,
+    name: &Path,
+) -> Result<(SystemTime, SystemTime)> {
+    let metadata = dir
+        .symlink_metadata_at(name)
+        .await
+        .map_err(|e| SyncError::FileSystem(format!("Failed to get precise timestamps: {e}")))?;
+    metadata
+        .accessed()
+        .and_then(|accessed| Ok((accessed, metadata.modified()?)))
+        .map_err(|e| SyncError::FileSystem(format!("Failed to get precise timestamps: {e}")))
+}
+
 /// Preserve timestamps using file descriptor with nanosecond precision
 ///
 /// This function uses FD-based `futimens` to preserve timestamps with
@@ -427,33 +1203,96 @@ mod tests {
             destination: PathBuf::from("/test/dest"),
             queue_depth: 4096,
             max_files_in_flight: 1024,
+            max_memory: None,
+            max_device_bytes: None,
             cpu_count: 1,
+            cpu_affinity: None,
             buffer_size_kb: 64,
             copy_method: CopyMethod::Auto,
+            fs_profiles: None,
+            io_priority: None,
+            prefetch: false,
             archive: true, // Enable archive mode for full metadata preservation
             recursive: false,
             links: false,
+            munge_links: false,
             perms: false,
             times: false,
             group: false,
             owner: false,
+            numeric_ids: false,
+            no_owner_errors: false,
+            fake_super: false,
+            exclude: Vec::new(),
+            include: Vec::new(),
+            exclude_from: Vec::new(),
+            include_from: Vec::new(),
+            gitignore: false,
+            cvs_exclude: false,
+            filter: None,
+            drop_privileges: None,
             devices: false,
+            specials: false,
             xattrs: false,
+            no_selinux: false,
             acls: false,
             hard_links: false,
             atimes: false,
             crtimes: false,
+            delay_updates: false,
+            ignore_existing: false,
+            existing: false,
+            update: false,
+            no_whole_file: false,
+            tmpfile: false,
+            umask: None,
+            file_mode: None,
+            dir_mode: None,
+            chmod: Vec::new(),
+            chown: None,
             pirate: false,
             preserve_xattr: false,
             preserve_acl: false,
             dry_run: false,
+            max_depth: None,
+            hardlink_db: None,
+            dedupe_dest: false,
+            watch: false,
+            watch_delay: 200,
             progress: false,
+            json: false,
+            stats: false,
+            itemize_changes: false,
+            log_file: None,
+            log_file_format: "%i %n%L".to_string(),
             verbose: 0,
             quiet: false,
             no_adaptive_concurrency: false,
+            deterministic: false,
         }
     }
 
+    #[compio::test]
+    async fn test_copy_file_at_reads_through_directory_fd() {
+        let temp_dir = TempDir::new().unwrap();
+        let src_dir = temp_dir.path().join("src");
+        fs::create_dir(&src_dir).unwrap();
+        fs::write(src_dir.join("file.txt"), "original").unwrap();
+        let dir = DirectoryFd::open(&src_dir).await.unwrap();
+
+        // Swap the directory out from under the open descriptor
+        fs::rename(&src_dir, temp_dir.path().join("moved")).unwrap();
+        fs::create_dir(&src_dir).unwrap();
+        fs::write(src_dir.join("file.txt"), "replaced").unwrap();
+
+        let dst_path = temp_dir.path().join("destination.txt");
+        let args = create_test_args_with_archive();
+        copy_file_at(&dir, &src_dir.join("file.txt"), &dst_path, &args)
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&dst_path).unwrap(), "original");
+    }
+
     #[compio::test]
     async fn test_preserve_metadata_permissions() {
         let temp_dir = TempDir::new().unwrap();
@@ -748,6 +1587,38 @@ mod tests {
         );
     }
 
+    #[compio::test]
+    async fn test_copy_data_in_kernel_windows() {
+        let temp_dir = TempDir::new().unwrap();
+        let src_path = temp_dir.path().join("source.bin");
+        let dst_path = temp_dir.path().join("destination.bin");
+        let content: Vec<u8> = (0..1_000_000u32)
+            .map(|i| u8::try_from(i % 251).unwrap())
+            .collect();
+        fs::write(&src_path, &content).unwrap();
+
+        let src_file = compio::fs::File::open(&src_path).await.unwrap();
+        let dst_file = compio::fs::File::create(&dst_path).await.unwrap();
+        // Far smaller than the file, so it is copied over many windows
+        let mut sizer = ChunkSizer::fixed(64 * 1024);
+        let copied = copy_data_in_kernel(
+            &src_file,
+            &dst_file,
+            content.len() as u64,
+            &dst_path,
+            &mut sizer,
+            &ChunkBudget::default(),
+        )
+        .await
+        .unwrap();
+        let Some(copied) = copied else {
+            // copy_file_range is not supported here
+            return;
+        };
+        assert_eq!(copied, content.len() as u64);
+        assert_eq!(fs::read(&dst_path).unwrap(), content);
+    }
+
     #[compio::test]
     async fn test_fallocate_large_file_preallocation() {
         let temp_dir = TempDir::new().unwrap();
@@ -778,4 +1649,94 @@ mod tests {
             "Large file sizes should match"
         );
     }
+
+    #[test]
+    fn test_destination_mode_policy() {
+        // No policy: leave the created mode alone
+        assert_eq!(destination_mode(0o4755, false, &Args::default()), None);
+
+        // --perms keeps special bits
+        let args = Args {
+            perms: true,
+            ..Default::default()
+        };
+        assert_eq!(destination_mode(0o104_755, false, &args), Some(0o4755));
+
+        // --umask masks the source mode and drops special bits
+        let args = Args {
+            umask: Some(0o027),
+            ..Default::default()
+        };
+        assert_eq!(destination_mode(0o4777, false, &args), Some(0o750));
+
+        // --file-mode/--dir-mode win over everything
+        let args = Args {
+            perms: true,
+            file_mode: Some(0o644),
+            dir_mode: Some(0o2755),
+            ..Default::default()
+        };
+        assert_eq!(destination_mode(0o600, false, &args), Some(0o644));
+        assert_eq!(destination_mode(0o700, true, &args), Some(0o2755));
+    }
+
+    #[compio::test]
+    async fn test_file_mode_overrides_source_permissions() {
+        let temp_dir = TempDir::new().unwrap();
+        let src_path = temp_dir.path().join("source.txt");
+        let dst_path = temp_dir.path().join("destination.txt");
+
+        fs::write(&src_path, "mirror content").unwrap();
+        fs::set_permissions(&src_path, std::fs::Permissions::from_mode(0o600)).unwrap();
+
+        let args = Args {
+            file_mode: Some(0o644),
+            ..create_test_args_with_archive()
+        };
+        copy_file(&src_path, &dst_path, &args).await.unwrap();
+
+        let dst_mode = fs::metadata(&dst_path).unwrap().permissions().mode();
+        assert_eq!(dst_mode & 0o7777, 0o644);
+    }
+
+    #[test]
+    fn test_apply_chmod_rules() {
+        let rules: Vec<ChmodRule> = ["Du+rwx", "Fgo-w", "a+X"]
+            .iter()
+            .map(|r| crate::cli::parse_chmod_rule(r).unwrap())
+            .collect();
+
+        // Directory: u+rwx applies, F rule skipped, X adds execute
+        assert_eq!(apply_chmod_rules(0o000, true, &rules), 0o711);
+        // Non-executable file: only go-w applies, X does nothing
+        assert_eq!(apply_chmod_rules(0o666, false, &rules), 0o644);
+        // Executable file: X adds execute for everyone
+        assert_eq!(apply_chmod_rules(0o766, false, &rules), 0o755);
+
+        let rules = vec![crate::cli::parse_chmod_rule("D2775").unwrap()];
+        assert_eq!(apply_chmod_rules(0o700, true, &rules), 0o2775);
+        assert_eq!(apply_chmod_rules(0o600, false, &rules), 0o600);
+
+        let rules = vec![crate::cli::parse_chmod_rule("go=r").unwrap()];
+        assert_eq!(apply_chmod_rules(0o777, false, &rules), 0o744);
+    }
+
+    #[compio::test]
+    async fn test_chmod_applied_at_copy_time() {
+        let temp_dir = TempDir::new().unwrap();
+        let src_path = temp_dir.path().join("source.txt");
+        let dst_path = temp_dir.path().join("destination.txt");
+
+        fs::write(&src_path, "chmod content").unwrap();
+        fs::set_permissions(&src_path, std::fs::Permissions::from_mode(0o666)).unwrap();
+
+        let args = Args {
+            chmod: vec![crate::cli::parse_chmod_rule("Fgo-w").unwrap()],
+            ..create_test_args_with_archive()
+        };
+        copy_file(&src_path, &dst_path, &args).await.unwrap();
+
+        let dst_mode = fs::metadata(&dst_path).unwrap().permissions().mode();
+        assert_eq!(dst_mode & 0o7777, 0o644);
+    }
 }
@@ -0,0 +1,58 @@
+//! Creation time (birth time) handling for `--crtimes`
+//!
+//! The source birth time is read with `statx(STATX_BTIME)`. Linux has no
+//! system call that sets a file's birth time; every filesystem stamps it
+//! when the inode is created. A copy keeps the original creation time only
+//! if the destination already carries the same one, which in practice it
+//! never does.
+//!
+//! Each file whose creation time is lost is logged at debug level and
+//! counted in the run's statistics. At the end of the run a single warning
+//! says how many files were affected. This happens whichever way the data
+//! was copied: reflink, `copy_file_range` or read/write.
+//!
+//! Sources on filesystems that record no birth time (tmpfs on older kernels,
+//! most network filesystems) have nothing to preserve and are not counted.
+
+use compio::fs::File;
+use compio_fs_extended::metadata::statx_btime;
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use tracing::debug;
+
+/// Result of preserving the creation time of a destination entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CrtimeChange {
+    /// `--crtimes` was not given
+    #[default]
+    NotRequested,
+    /// The source filesystem records no creation time
+    NotRecorded,
+    /// The destination already has the source's creation time
+    Preserved,
+    /// The creation time could not be applied to the destination
+    Unsupported,
+}
+
+/// Preserve the creation time of `src` on `dst` as far as the platform allows
+///
+/// Never fails: a missing or unsettable birth time is reported through the
+/// returned [`CrtimeChange`] and a debug log naming `dst_path`.
+#[allow(clippy::future_not_send)]
+pub async fn preserve_crtime(src: &File, dst: &File, dst_path: &Path) -> CrtimeChange {
+    let Ok(Some(src_btime)) = statx_btime(src.as_raw_fd()).await else {
+        return CrtimeChange::NotRecorded;
+    };
+
+    match statx_btime(dst.as_raw_fd()).await {
+        Ok(Some(dst_btime)) if dst_btime == src_btime => CrtimeChange::Preserved,
+        _ => {
+            debug!(
+                "Creation time {:?} not preserved on {}: Linux cannot set birth times",
+                src_btime,
+                dst_path.display()
+            );
+            CrtimeChange::Unsupported
+        }
+    }
+}
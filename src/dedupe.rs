@@ -0,0 +1,191 @@
+//! Destination-side deduplication for `--dedupe-dest`
+//!
+//! Before a file is copied its content is hashed and looked up among the
+//! files already written to the destination in this run. If one matches and
+//! compares equal byte for byte, the new path is hardlinked to it instead of
+//! writing the same data again.
+//!
+//! Hardlinks share one inode, so the metadata arsync would preserve (mode,
+//! owner, group, modification time and extended attributes, depending on the
+//! flags given) is part of the key: files with identical content but
+//! different preserved metadata are still copied separately.
+
+use crate::cli::Args;
+use crate::error::{Result, SyncError};
+use compio::buf::BufResult;
+use compio::io::AsyncReadAt;
+#[allow(clippy::disallowed_types)]
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// Read size used when hashing and comparing file contents
+const READ_CHUNK: usize = 256 * 1024;
+
+/// Content and preserved metadata of a source file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ContentKey {
+    /// File size in bytes
+    len: u64,
+    /// Hash of the content and of the preserved metadata
+    hash: u64,
+}
+
+/// Files written to the destination during this run, by content
+#[derive(Debug, Default)]
+pub struct DedupeIndex {
+    /// Destination paths holding each content
+    #[allow(clippy::disallowed_types)]
+    files: HashMap<ContentKey, Vec<PathBuf>>,
+}
+
+impl DedupeIndex {
+    /// Destination files recorded with the same key, oldest first
+    #[must_use]
+    pub fn candidates(&self, key: &ContentKey) -> Vec<PathBuf> {
+        self.files.get(key).cloned().unwrap_or_default()
+    }
+
+    /// Record that `dst` was written with the content described by `key`
+    pub fn insert(&mut self, key: ContentKey, dst: &Path) {
+        self.files.entry(key).or_default().push(dst.to_path_buf());
+    }
+}
+
+/// Hash the content of `path` and the metadata that `args` preserves
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be opened or read.
+#[allow(clippy::future_not_send)]
+pub async fn content_key(
+    path: &Path,
+    metadata: &compio::fs::Metadata,
+    args: &Args,
+) -> Result<ContentKey> {
+    let file = crate::atime::open_source(path)
+        .await
+        .map_err(|e| SyncError::FileSystem(format!("Failed to open {}: {e}", path.display())))?;
+
+    let mut hasher = DefaultHasher::new();
+    if args.should_preserve_permissions() {
+        metadata.mode().hash(&mut hasher);
+    }
+    if args.should_preserve_owner() {
+        metadata.uid().hash(&mut hasher);
+    }
+    if args.should_preserve_group() {
+        metadata.gid().hash(&mut hasher);
+    }
+    if args.should_preserve_timestamps() {
+        (metadata.mtime(), metadata.mtime_nsec()).hash(&mut hasher);
+    }
+    if args.should_preserve_xattrs() || args.should_preserve_acls() {
+        hash_xattrs(&file, &mut hasher).await;
+    }
+
+    let mut offset = 0u64;
+    loop {
+        let BufResult(read, buffer) = file.read_at(vec![0u8; READ_CHUNK], offset).await;
+        let read = read.map_err(|e| {
+            SyncError::FileSystem(format!("Failed to read {}: {e}", path.display()))
+        })?;
+        if read == 0 {
+            break;
+        }
+        buffer[..read].hash(&mut hasher);
+        offset += read as u64;
+    }
+
+    Ok(ContentKey {
+        len: offset,
+        hash: hasher.finish(),
+    })
+}
+
+/// Feed the file's extended attributes (which include POSIX ACLs) to `hasher`
+#[allow(clippy::future_not_send)]
+async fn hash_xattrs(file: &compio::fs::File, hasher: &mut DefaultHasher) {
+    use compio_fs_extended::{ExtendedFile, XattrOps};
+
+    let extended = ExtendedFile::from_ref(file);
+    let Ok(mut names) = extended.list_xattr().await else {
+        return;
+    };
+    names.sort_unstable();
+    for name in names {
+        let value = extended.get_xattr(&name).await.unwrap_or_default();
+        (name, value).hash(hasher);
+    }
+}
+
+/// Whether two files have byte-for-byte identical content
+///
+/// # Errors
+///
+/// Returns an error if either file cannot be opened or read.
+#[allow(clippy::future_not_send)]
+pub async fn same_content(a: &Path, b: &Path) -> Result<bool> {
+    let open = |path: &Path| {
+        let path = path.to_path_buf();
+        async move {
+            compio::fs::File::open(&path).await.map_err(|e| {
+                SyncError::FileSystem(format!("Failed to open {}: {e}", path.display()))
+            })
+        }
+    };
+    let (first, second) = (open(a).await?, open(b).await?);
+
+    let mut offset = 0u64;
+    loop {
+        let BufResult(read_a, buf_a) = first.read_at(vec![0u8; READ_CHUNK], offset).await;
+        let BufResult(read_b, buf_b) = second.read_at(vec![0u8; READ_CHUNK], offset).await;
+        let (read_a, read_b) = (read_a?, read_b?);
+        if read_a != read_b || buf_a[..read_a] != buf_b[..read_b] {
+            return Ok(false);
+        }
+        if read_a == 0 {
+            return Ok(true);
+        }
+        offset += read_a as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    async fn key(path: &Path, args: &Args) -> ContentKey {
+        let metadata = compio::fs::metadata(path).await.unwrap();
+        content_key(path, &metadata, args).await.unwrap()
+    }
+
+    #[compio::test]
+    async fn test_key_follows_content_and_preserved_metadata() {
+        let temp_dir = TempDir::new().unwrap();
+        let a = temp_dir.path().join("a");
+        let b = temp_dir.path().join("b");
+        let c = temp_dir.path().join("c");
+        std::fs::write(&a, "same").unwrap();
+        std::fs::write(&b, "same").unwrap();
+        std::fs::write(&c, "diff").unwrap();
+        std::fs::set_permissions(&b, std::fs::Permissions::from_mode(0o600)).unwrap();
+
+        let plain = Args::default();
+        let perms = Args {
+            perms: true,
+            ..Args::default()
+        };
+
+        assert_eq!(key(&a, &plain).await, key(&b, &plain).await);
+        assert_ne!(key(&a, &plain).await, key(&c, &plain).await);
+        // Same content, but the differing mode would be preserved
+        assert_ne!(key(&a, &perms).await, key(&b, &perms).await);
+
+        assert!(same_content(&a, &b).await.unwrap());
+        assert!(!same_content(&a, &c).await.unwrap());
+    }
+}
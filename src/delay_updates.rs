@@ -0,0 +1,154 @@
+//! Deferred renames for `--delay-updates`
+//!
+//! Like rsync, each updated file is written to a `.~tmp~` directory next to
+//! its final location instead of over the destination. Once the whole tree
+//! has been copied, [`commit`] renames every staged file into place and
+//! removes the staging directories, so the destination switches to the new
+//! contents at the very end of the run rather than file by file.
+//!
+//! Pending renames are kept in a process-wide list keyed by their final path,
+//! so copies running concurrently (one per worker) can all register theirs.
+
+use crate::error::{Result, SyncError};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use tracing::warn;
+
+/// Name of the per-directory staging area, matching rsync
+pub const STAGING_DIR: &str = ".~tmp~";
+
+/// Staged file and its final destination, in the order they were staged
+static PENDING: Mutex<Vec<(PathBuf, PathBuf)>> = Mutex::new(Vec::new());
+
+/// Path that `dst` should be written to until the run is committed
+#[must_use]
+pub fn staging_path(dst: &Path) -> PathBuf {
+    let parent = dst.parent().unwrap_or_else(|| Path::new("."));
+    let name = dst.file_name().unwrap_or_default();
+    parent.join(STAGING_DIR).join(name)
+}
+
+/// Create the staging directory for `dst` and return where to write it
+///
+/// # Errors
+///
+/// Returns an error if the staging directory cannot be created.
+#[allow(clippy::future_not_send)]
+pub async fn stage(dst: &Path) -> Result<PathBuf> {
+    let staged = staging_path(dst);
+    if let Some(dir) = staged.parent() {
+        match compio::fs::create_dir(dir).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+            Err(e) => {
+                return Err(SyncError::FileSystem(format!(
+                    "Failed to create staging directory {}: {e}",
+                    dir.display()
+                )))
+            }
+        }
+    }
+    Ok(staged)
+}
+
+/// Record that `staged` should replace `dst` at commit time
+pub fn defer(staged: PathBuf, dst: PathBuf) {
+    PENDING
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push((staged, dst));
+}
+
+/// Move every staged file under `root` into place
+///
+/// Returns the number of files renamed. Staging directories are removed
+/// afterwards; one that is not empty (for example because a copy into it
+/// failed) is left behind with a warning.
+///
+/// # Errors
+///
+/// Returns an error if a staged file cannot be renamed into place. Files not
+/// yet renamed are left in their staging directories.
+#[allow(clippy::future_not_send)]
+#[cfg_attr(
+    feature = "spans",
+    tracing::instrument(name = "delay_updates.commit", skip_all)
+)]
+pub async fn commit(root: &Path) -> Result<usize> {
+    let pending: Vec<_> = {
+        let mut all = PENDING.lock().unwrap_or_else(PoisonError::into_inner);
+        let (ours, others) = all.drain(..).partition(|(_, dst)| dst.starts_with(root));
+        *all = others;
+        ours
+    };
+
+    let mut staging_dirs: Vec<PathBuf> = Vec::new();
+    let mut renamed = 0;
+    for (staged, dst) in &pending {
+        compio::fs::rename(staged, dst).await.map_err(|e| {
+            SyncError::FileSystem(format!(
+                "Failed to move {} into place at {}: {e}",
+                staged.display(),
+                dst.display()
+            ))
+        })?;
+        renamed += 1;
+        if let Some(dir) = staged.parent() {
+            if !staging_dirs.iter().any(|d| d == dir) {
+                staging_dirs.push(dir.to_path_buf());
+            }
+        }
+    }
+
+    for dir in &staging_dirs {
+        if let Err(e) = compio::fs::remove_dir(dir).await {
+            warn!("Leaving staging directory {}: {e}", dir.display());
+        }
+    }
+
+    Ok(renamed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_staging_path_is_beside_destination() {
+        assert_eq!(
+            staging_path(Path::new("/dst/sub/file.txt")),
+            PathBuf::from("/dst/sub/.~tmp~/file.txt")
+        );
+    }
+
+    #[compio::test]
+    async fn test_commit_moves_only_files_under_root() {
+        let temp_dir = TempDir::new().unwrap();
+        let ours = temp_dir.path().join("ours");
+        let other = temp_dir.path().join("other");
+        std::fs::create_dir(&ours).unwrap();
+        std::fs::create_dir(&other).unwrap();
+
+        let mut staged_paths = Vec::new();
+        for dir in [&ours, &other] {
+            let dst = dir.join("file.txt");
+            std::fs::write(&dst, "old").unwrap();
+            let staged = stage(&dst).await.unwrap();
+            std::fs::write(&staged, "new").unwrap();
+            defer(staged.clone(), dst);
+            staged_paths.push(staged);
+        }
+
+        assert_eq!(commit(&ours).await.unwrap(), 1);
+        assert_eq!(std::fs::read(ours.join("file.txt")).unwrap(), b"new");
+        assert!(!ours.join(STAGING_DIR).exists());
+
+        // Updates for another destination are still staged
+        assert_eq!(std::fs::read(other.join("file.txt")).unwrap(), b"old");
+        assert!(staged_paths[1].exists());
+        assert_eq!(commit(&other).await.unwrap(), 1);
+        assert_eq!(std::fs::read(other.join("file.txt")).unwrap(), b"new");
+    }
+}
@@ -6,41 +6,67 @@
 
 use crate::adaptive_concurrency::{check_fd_limits, AdaptiveConcurrencyController};
 use crate::cli::{Args, CopyMethod};
-use crate::copy::copy_file;
+use crate::copy::{copy_file, copy_file_at, destination_mode};
+use crate::crtime::CrtimeChange;
+use crate::dedupe::{ContentKey, DedupeIndex};
 use crate::error::{Result, SyncError};
+use crate::events::{self, Event};
+use crate::fake_super::FakeStat;
+use crate::hardlink_db::HardlinkDb;
+use crate::i18n::TranslationKey;
 use crate::io_uring::FileOperations;
+use crate::itemize::{self, Update};
+use crate::ownership::OwnershipChange;
 // io_uring_extended removed - using compio directly
 use compio::dispatcher::Dispatcher;
+use compio_fs_extended::directory::{DirectoryFd, EntryType};
+use compio_fs_extended::metadata::FollowSymlinks;
+use compio_sync::oneshot;
+use compio_sync::Mutex;
 use compio_sync::Semaphore;
+use futures::future::{FutureExt, Shared};
 #[allow(clippy::disallowed_types)]
 use std::collections::HashMap;
-use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
-use tracing::{debug, info, warn};
+use std::sync::Arc;
+use tracing::{debug, info, warn, Instrument};
+
+/// Mode for newly created destination directories, before the umask
+///
+/// Same as `std::fs::create_dir`; the source permissions are applied
+/// afterwards when metadata is preserved.
+const NEW_DIR_MODE: u32 = 0o777;
+
+/// Bytes at the start of each file covered by `--prefetch` advice
+const PREFETCH_WINDOW: i64 = 8 * 1024 * 1024;
 
 /// Wrapper for shared statistics tracking across async tasks
 ///
-/// This struct wraps `DirectoryStats` in `Arc<Mutex<>>` to allow shared access
+/// This struct wraps `DirectoryStats` in an `Arc` and an async
+/// [`compio_sync::Mutex`] to allow shared access
 /// across multiple async tasks dispatched by compio's dispatcher. It provides
 /// a clean API for updating statistics from concurrent operations.
 ///
 /// # Thread Safety
 ///
 /// All methods are thread-safe and can be called concurrently from different
-/// async tasks without additional synchronization.
+/// async tasks without additional synchronization. Under contention a caller
+/// waits for the lock asynchronously, so other tasks on the same executor
+/// thread keep running.
 ///
 /// # Usage
 ///
 /// ```rust,ignore
 /// let stats = SharedStats::new(DirectoryStats::default());
-/// stats.increment_files_copied();
-/// stats.increment_bytes_copied(1024);
+/// stats.increment_files_copied().await;
+/// stats.increment_bytes_copied(1024).await;
 /// let final_stats = stats.into_inner();
 /// ```
 #[derive(Clone)]
 pub struct SharedStats {
-    /// Inner stats wrapped in Arc<Mutex<>> for thread-safe access
+    /// Inner stats behind an async mutex for thread-safe access
     inner: Arc<Mutex<DirectoryStats>>,
 }
 
@@ -59,159 +85,107 @@ impl SharedStats {
 
     #[allow(dead_code)]
     /// Get the number of files copied
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if the internal mutex is poisoned.
-    pub fn files_copied(&self) -> Result<u64> {
-        Ok(self
-            .inner
-            .lock()
-            .map_err(|_| SyncError::FileSystem("Failed to acquire stats lock".to_string()))?
-            .files_copied)
+    pub async fn files_copied(&self) -> u64 {
+        self.inner.lock().await.files_copied
     }
 
     #[allow(dead_code)]
     /// Get the number of directories created
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if the internal mutex is poisoned.
-    pub fn directories_created(&self) -> Result<u64> {
-        Ok(self
-            .inner
-            .lock()
-            .map_err(|_| SyncError::FileSystem("Failed to acquire stats lock".to_string()))?
-            .directories_created)
+    pub async fn directories_created(&self) -> u64 {
+        self.inner.lock().await.directories_created
     }
 
     #[allow(dead_code)]
     /// Get the number of bytes copied
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if the internal mutex is poisoned.
-    pub fn bytes_copied(&self) -> Result<u64> {
-        Ok(self
-            .inner
-            .lock()
-            .map_err(|_| SyncError::FileSystem("Failed to acquire stats lock".to_string()))?
-            .bytes_copied)
+    pub async fn bytes_copied(&self) -> u64 {
+        self.inner.lock().await.bytes_copied
     }
 
     #[allow(dead_code)]
     /// Get the number of symlinks processed
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if the internal mutex is poisoned.
-    pub fn symlinks_processed(&self) -> Result<u64> {
-        Ok(self
-            .inner
-            .lock()
-            .map_err(|_| SyncError::FileSystem("Failed to acquire stats lock".to_string()))?
-            .symlinks_processed)
+    pub async fn symlinks_processed(&self) -> u64 {
+        self.inner.lock().await.symlinks_processed
     }
 
     #[allow(dead_code)]
     /// Get the number of errors encountered
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if the internal mutex is poisoned.
-    pub fn errors(&self) -> Result<u64> {
-        Ok(self
-            .inner
-            .lock()
-            .map_err(|_| SyncError::FileSystem("Failed to acquire stats lock".to_string()))?
-            .errors)
+    pub async fn errors(&self) -> u64 {
+        self.inner.lock().await.errors
     }
 
     /// Increment the number of files copied
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if the internal mutex is poisoned.
-    pub fn increment_files_copied(&self) -> Result<()> {
-        self.inner
-            .lock()
-            .map_err(|_| SyncError::FileSystem("Failed to acquire stats lock".to_string()))?
-            .files_copied += 1;
-        Ok(())
+    pub async fn increment_files_copied(&self) {
+        self.inner.lock().await.files_copied += 1;
     }
 
     /// Increment the number of directories created
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if the internal mutex is poisoned.
-    pub fn increment_directories_created(&self) -> Result<()> {
-        self.inner
-            .lock()
-            .map_err(|_| SyncError::FileSystem("Failed to acquire stats lock".to_string()))?
-            .directories_created += 1;
-        Ok(())
+    pub async fn increment_directories_created(&self) {
+        self.inner.lock().await.directories_created += 1;
     }
 
     /// Increment the number of bytes copied
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if the internal mutex is poisoned.
-    pub fn increment_bytes_copied(&self, bytes: u64) -> Result<()> {
-        self.inner
-            .lock()
-            .map_err(|_| SyncError::FileSystem("Failed to acquire stats lock".to_string()))?
-            .bytes_copied += bytes;
-        Ok(())
+    pub async fn increment_bytes_copied(&self, bytes: u64) {
+        self.inner.lock().await.bytes_copied += bytes;
     }
 
     /// Increment the number of symlinks processed
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if the internal mutex is poisoned.
-    pub fn increment_symlinks_processed(&self) -> Result<()> {
-        self.inner
-            .lock()
-            .map_err(|_| SyncError::FileSystem("Failed to acquire stats lock".to_string()))?
-            .symlinks_processed += 1;
-        Ok(())
+    pub async fn increment_symlinks_processed(&self) {
+        self.inner.lock().await.symlinks_processed += 1;
+    }
+
+    /// Increment the number of special files created
+    pub async fn increment_specials_created(&self) {
+        self.inner.lock().await.specials_created += 1;
+    }
+
+    /// Increment the number of ownership changes that were skipped
+    pub async fn increment_ownership_skipped(&self) {
+        self.inner.lock().await.ownership_skipped += 1;
+    }
+
+    /// Increment the number of files whose creation time was not preserved
+    pub async fn increment_crtimes_skipped(&self) {
+        self.inner.lock().await.crtimes_skipped += 1;
+    }
+
+    /// Count a source entry by type (and its size, for regular files)
+    pub async fn record_found(&self, metadata: &ExtendedMetadata) {
+        let mut stats = self.inner.lock().await;
+        if metadata.is_dir() {
+            stats.directories_found += 1;
+        } else if metadata.is_file() {
+            stats.regular_files_found += 1;
+            stats.total_size += metadata.len();
+        } else if metadata.is_symlink() {
+            stats.symlinks_found += 1;
+        } else {
+            stats.specials_found += 1;
+        }
     }
 
     /// Increment the number of errors encountered
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if the internal mutex is poisoned.
-    pub fn increment_errors(&self) -> Result<()> {
-        self.inner
-            .lock()
-            .map_err(|_| SyncError::FileSystem("Failed to acquire stats lock".to_string()))?
-            .errors += 1;
-        Ok(())
+    pub async fn increment_errors(&self) {
+        self.inner.lock().await.errors += 1;
     }
 
     /// Extract the inner `DirectoryStats` from the shared wrapper
     ///
     /// # Errors
     ///
-    /// This function will return an error if:
-    /// - Multiple references to the Arc exist (cannot unwrap)
-    /// - The internal mutex is poisoned
+    /// This function will return an error if multiple references to the Arc
+    /// exist (cannot unwrap).
     pub fn into_inner(self) -> Result<DirectoryStats> {
         let inner = Arc::try_unwrap(self.inner).map_err(|_| {
             SyncError::FileSystem("Failed to unwrap Arc - multiple references exist".to_string())
         })?;
-        inner.into_inner().map_err(|_| {
-            SyncError::FileSystem("Failed to unwrap Mutex - mutex is poisoned".to_string())
-        })
+        Ok(inner.into_inner())
     }
 }
 
 /// Wrapper for shared hardlink tracking across async tasks
 ///
-/// This struct wraps `FilesystemTracker` in `Arc<Mutex<>>` to allow shared access
+/// This struct wraps `FilesystemTracker` in an `Arc` and an async
+/// [`compio_sync::Mutex`] to allow shared access
 /// across multiple async tasks dispatched by compio's dispatcher. It provides
 /// thread-safe hardlink detection and tracking for efficient file copying.
 ///
@@ -225,20 +199,22 @@ impl SharedStats {
 /// # Thread Safety
 ///
 /// All methods are thread-safe and can be called concurrently from different
-/// async tasks without additional synchronization.
+/// async tasks without additional synchronization. Under contention a caller
+/// waits for the lock asynchronously, so other tasks on the same executor
+/// thread keep running.
 ///
 /// # Usage
 ///
 /// ```rust,ignore
 /// let tracker = SharedHardlinkTracker::new(FilesystemTracker::new());
-/// tracker.register_file(path, device_id, inode, link_count);
-/// if tracker.is_inode_copied(inode) {
+/// tracker.register_file(path, device_id, inode, link_count).await;
+/// if tracker.is_inode_copied(device_id, inode).await {
 ///     // Create hardlink instead of copying
 /// }
 /// ```
 #[derive(Clone)]
 pub struct SharedHardlinkTracker {
-    /// Inner tracker wrapped in Arc<Mutex<>> for thread-safe access
+    /// Inner tracker behind an async mutex for thread-safe access
     inner: Arc<Mutex<FilesystemTracker>>,
 }
 
@@ -256,119 +232,96 @@ impl SharedHardlinkTracker {
     }
 
     /// Check if an inode has already been copied
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if the internal mutex is poisoned.
-    pub fn is_inode_copied(&self, inode: u64) -> Result<bool> {
-        Ok(self
-            .inner
-            .lock()
-            .map_err(|_| {
-                SyncError::FileSystem("Failed to acquire hardlink tracker lock".to_string())
-            })?
-            .is_inode_copied(inode))
+    pub async fn is_inode_copied(&self, device_id: u64, inode: u64) -> bool {
+        self.inner.lock().await.is_inode_copied(device_id, inode)
     }
 
     /// Get the original path for an inode that has been copied
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if the internal mutex is poisoned.
-    pub fn get_original_path_for_inode(&self, inode: u64) -> Result<Option<PathBuf>> {
-        Ok(self
-            .inner
+    pub async fn get_original_path_for_inode(&self, device_id: u64, inode: u64) -> Option<PathBuf> {
+        self.inner
             .lock()
-            .map_err(|_| {
-                SyncError::FileSystem("Failed to acquire hardlink tracker lock".to_string())
-            })?
-            .get_original_path_for_inode(inode)
-            .map(std::path::Path::to_path_buf))
+            .await
+            .get_original_path_for_inode(device_id, inode)
+            .map(std::path::Path::to_path_buf)
     }
 
     /// Mark an inode as copied
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if the internal mutex is poisoned.
-    pub fn mark_inode_copied(&self, inode: u64, path: &Path) -> Result<()> {
+    pub async fn mark_inode_copied(&self, device_id: u64, inode: u64, path: &Path) {
         self.inner
             .lock()
-            .map_err(|_| {
-                SyncError::FileSystem("Failed to acquire hardlink tracker lock".to_string())
-            })?
-            .mark_inode_copied(inode, path);
-        Ok(())
+            .await
+            .mark_inode_copied(device_id, inode, path);
     }
 
-    #[allow(dead_code)]
-    /// Register a file with the hardlink tracker
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if the internal mutex is poisoned.
-    pub fn register_file(
+    /// Claim a multiply-linked inode for copying (see [`InodeClaim`])
+    pub async fn claim_inode(
         &self,
         path: &Path,
         device_id: u64,
         inode: u64,
         link_count: u64,
-    ) -> Result<()> {
+    ) -> InodeClaim {
         self.inner
             .lock()
-            .map_err(|_| {
-                SyncError::FileSystem("Failed to acquire hardlink tracker lock".to_string())
-            })?
-            .register_file(path, device_id, inode, link_count);
-        Ok(())
+            .await
+            .claim_inode(path, device_id, inode, link_count)
     }
 
     #[allow(dead_code)]
-    /// Set the source filesystem device ID
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if the internal mutex is poisoned.
-    pub fn set_source_filesystem(&self, device_id: u64) -> Result<()> {
+    /// Register a file with the hardlink tracker
+    pub async fn register_file(&self, path: &Path, device_id: u64, inode: u64, link_count: u64) {
+        self.inner
+            .lock()
+            .await
+            .register_file(path, device_id, inode, link_count);
+    }
+
+    /// Destination an earlier run copied the source inode to (`--hardlink-db`)
+    pub async fn recorded_path(&self, device_id: u64, inode: u64) -> Option<PathBuf> {
+        self.inner.lock().await.recorded_path(device_id, inode)
+    }
+
+    /// Record where a source inode was copied to (`--hardlink-db`)
+    pub async fn record_in_database(&self, device_id: u64, inode: u64, path: &Path) {
         self.inner
             .lock()
-            .map_err(|_| {
-                SyncError::FileSystem("Failed to acquire hardlink tracker lock".to_string())
-            })?
-            .set_source_filesystem(device_id);
-        Ok(())
+            .await
+            .record_in_database(device_id, inode, path);
+    }
+
+    /// Destination files written this run with the given content (`--dedupe-dest`)
+    pub async fn content_candidates(&self, key: &ContentKey) -> Vec<PathBuf> {
+        self.inner.lock().await.content_candidates(key)
+    }
+
+    /// Record the content written to a destination file (`--dedupe-dest`)
+    pub async fn record_content(&self, key: ContentKey, path: &Path) {
+        self.inner.lock().await.record_content(key, path);
+    }
+
+    #[allow(dead_code)]
+    /// Set the source filesystem device ID
+    pub async fn set_source_filesystem(&self, device_id: u64) {
+        self.inner.lock().await.set_source_filesystem(device_id);
     }
 
     #[allow(dead_code)]
     /// Get filesystem tracking statistics
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if the internal mutex is poisoned.
-    pub fn get_stats(&self) -> Result<FilesystemStats> {
-        Ok(self
-            .inner
-            .lock()
-            .map_err(|_| {
-                SyncError::FileSystem("Failed to acquire hardlink tracker lock".to_string())
-            })?
-            .get_stats())
+    pub async fn get_stats(&self) -> FilesystemStats {
+        self.inner.lock().await.get_stats()
     }
 
     /// Extract the inner `FilesystemTracker` from the shared wrapper
     ///
     /// # Errors
     ///
-    /// This function will return an error if:
-    /// - Multiple references to the Arc exist (cannot unwrap)
-    /// - The internal mutex is poisoned
+    /// This function will return an error if multiple references to the Arc
+    /// exist (cannot unwrap).
     pub fn into_inner(self) -> Result<FilesystemTracker> {
         let inner = Arc::try_unwrap(self.inner).map_err(|_| {
             SyncError::FileSystem("Failed to unwrap Arc - multiple references exist".to_string())
         })?;
-        inner.into_inner().map_err(|_| {
-            SyncError::FileSystem("Failed to unwrap Mutex - mutex is poisoned".to_string())
-        })
+        Ok(inner.into_inner())
     }
 }
 
@@ -464,11 +417,35 @@ impl ExtendedMetadata {
     /// - The path is not accessible
     #[allow(clippy::future_not_send)]
     pub async fn new(path: &Path) -> Result<Self> {
-        // Use compio::fs::symlink_metadata for async metadata retrieval
-        let metadata = compio::fs::symlink_metadata(path).await.map_err(|e| {
+        // compio::fs::symlink_metadata asks statx for no fields, which leaves
+        // the timestamps zeroed on some kernels; request the basic stats
+        let metadata = compio_fs_extended::metadata::symlink_metadata(path)
+            .await
+            .map_err(|e| {
+                SyncError::FileSystem(format!(
+                    "Failed to get metadata for {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+        Ok(Self { metadata })
+    }
+
+    /// Get metadata for the entry `name` of an open directory
+    ///
+    /// The entry is looked up relative to `dir` with `statx`, without
+    /// resolving the directory's path again. Symlinks are not followed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the entry does not exist or
+    /// cannot be examined.
+    #[allow(clippy::future_not_send)]
+    pub async fn at(dir: &DirectoryFd, name: &Path) -> Result<Self> {
+        let metadata = dir.symlink_metadata_at(name).await.map_err(|e| {
             SyncError::FileSystem(format!(
                 "Failed to get metadata for {}: {}",
-                path.display(),
+                dir.path().join(name).display(),
                 e
             ))
         })?;
@@ -493,6 +470,25 @@ impl ExtendedMetadata {
         self.metadata.file_type().is_symlink()
     }
 
+    /// Check if this is a named pipe (FIFO)
+    #[must_use]
+    pub fn is_fifo(&self) -> bool {
+        self.metadata.file_type().is_fifo()
+    }
+
+    /// Check if this is a Unix domain socket
+    #[must_use]
+    pub fn is_socket(&self) -> bool {
+        self.metadata.file_type().is_socket()
+    }
+
+    /// Check if this is a block or character device
+    #[must_use]
+    pub fn is_device(&self) -> bool {
+        let file_type = self.metadata.file_type();
+        file_type.is_block_device() || file_type.is_char_device()
+    }
+
     /// Get file size
     #[must_use]
     pub fn len(&self) -> u64 {
@@ -500,7 +496,6 @@ impl ExtendedMetadata {
     }
 
     /// Check if file is empty
-    #[allow(dead_code)]
     #[must_use]
     pub fn is_empty(&self) -> bool {
         self.metadata.len() == 0
@@ -536,8 +531,24 @@ pub struct DirectoryStats {
     pub bytes_copied: u64,
     /// Number of symlinks processed
     pub symlinks_processed: u64,
+    /// Number of special files (FIFOs and sockets) created
+    pub specials_created: u64,
+    /// Number of ownership changes skipped (no privilege or `--no-owner-errors`)
+    pub ownership_skipped: u64,
+    /// Number of files whose creation time could not be preserved (`--crtimes`)
+    pub crtimes_skipped: u64,
     /// Number of errors encountered
     pub errors: u64,
+    /// Regular files found in the source
+    pub regular_files_found: u64,
+    /// Directories found in the source
+    pub directories_found: u64,
+    /// Symlinks found in the source
+    pub symlinks_found: u64,
+    /// Devices, FIFOs and sockets found in the source
+    pub specials_found: u64,
+    /// Total size of the regular files found in the source
+    pub total_size: u64,
 }
 
 /// Copy a directory recursively with metadata preservation and hardlink detection
@@ -579,6 +590,9 @@ pub async fn copy_directory(
 ) -> Result<DirectoryStats> {
     let mut stats = DirectoryStats::default();
     let mut hardlink_tracker = FilesystemTracker::new();
+    if let Some(db_path) = &args.hardlink_db {
+        hardlink_tracker.attach_database(HardlinkDb::load(db_path, dst)?);
+    }
 
     info!(
         "Starting directory copy from {} to {}",
@@ -592,19 +606,25 @@ pub async fn copy_directory(
         let root_metadata = ExtendedMetadata::new(src).await?;
         hardlink_tracker.set_source_filesystem(root_metadata.device_id());
     } else {
-        compio::fs::create_dir_all(dst).await.map_err(|e| {
-            SyncError::FileSystem(format!(
-                "Failed to create destination directory {}: {}",
-                dst.display(),
-                e
-            ))
-        })?;
+        compio_fs_extended::directory::create_dir_all(dst, NEW_DIR_MODE)
+            .await
+            .map_err(|e| {
+                SyncError::FileSystem(format!(
+                    "Failed to create destination directory {}: {}",
+                    dst.display(),
+                    e
+                ))
+            })?;
         stats.directories_created += 1;
         debug!("Created destination directory: {}", dst.display());
 
         // Preserve root directory metadata (permissions, ownership, timestamps) if requested
         let root_metadata = ExtendedMetadata::new(src).await?;
-        preserve_directory_metadata(src, dst, &root_metadata, args).await?;
+        if preserve_directory_metadata(src, dst, &root_metadata, args).await?
+            == OwnershipChange::Skipped
+        {
+            stats.ownership_skipped += 1;
+        }
 
         // Set source filesystem from root directory
         hardlink_tracker.set_source_filesystem(root_metadata.device_id());
@@ -622,12 +642,29 @@ pub async fn copy_directory(
     )
     .await?;
 
+    if let Some(db) = hardlink_tracker.take_database() {
+        db.save()?;
+        debug!("Saved {} inodes to the hardlink database", db.len());
+    }
+
     // Log hardlink detection results
     let hardlink_stats = hardlink_tracker.get_stats();
     info!(
         "Directory copy completed: {} files, {} directories, {} bytes, {} symlinks",
         stats.files_copied, stats.directories_created, stats.bytes_copied, stats.symlinks_processed
     );
+    if stats.ownership_skipped > 0 {
+        warn!(
+            "{}",
+            TranslationKey::WarnOwnershipSkipped.with_count(stats.ownership_skipped)
+        );
+    }
+    if stats.crtimes_skipped > 0 {
+        warn!(
+            "{}",
+            TranslationKey::WarnCrtimesSkipped.with_count(stats.crtimes_skipped)
+        );
+    }
     if hardlink_stats.hardlink_groups > 0 {
         info!(
             "Hardlink detection: {} unique files, {} hardlink groups, {} total hardlinks",
@@ -649,7 +686,7 @@ pub async fn copy_directory(
 /// # Architecture
 ///
 /// 1. **Dispatcher Creation**: Creates a static dispatcher using `Box::leak` for lifetime management
-/// 2. **State Wrapping**: Wraps `DirectoryStats` and `FilesystemTracker` in `Arc<Mutex<>>` for shared access
+/// 2. **State Wrapping**: Wraps `DirectoryStats` and `FilesystemTracker` in `Arc<Mutex<>>` (an async mutex) for shared access
 /// 3. **Entry Processing**: Dispatches all directory entries to `process_directory_entry_with_compio`
 /// 4. **Error Handling**: Uses `try_join_all` to short-circuit on first error
 ///
@@ -683,6 +720,11 @@ pub async fn copy_directory(
 #[allow(clippy::too_many_arguments)]
 #[allow(clippy::future_not_send)]
 #[allow(clippy::used_underscore_binding)]
+#[cfg_attr(feature = "spans", tracing::instrument(
+    name = "traverse",
+    skip_all,
+    fields(source = %initial_src.display())
+))]
 async fn traverse_and_copy_directory_iterative(
     initial_src: PathBuf,
     initial_dst: PathBuf,
@@ -693,7 +735,7 @@ async fn traverse_and_copy_directory_iterative(
     args: &Args,
 ) -> Result<()> {
     // Create a dispatcher for async operations
-    let dispatcher = Box::leak(Box::new(Dispatcher::new()?));
+    let dispatcher = Box::leak(Box::new(crate::affinity::dispatcher(args)?));
 
     // Leak file_ops and args to give them static lifetimes (it's fine since they're just references)
     let file_ops_static: &'static FileOperations = unsafe { std::mem::transmute(file_ops) };
@@ -717,20 +759,24 @@ async fn traverse_and_copy_directory_iterative(
 
     // Create adaptive concurrency controller for bounding concurrent operations
     // This prevents unbounded queue growth and adapts to resource constraints
-    let concurrency_controller =
-        Arc::new(AdaptiveConcurrencyController::new(args.max_files_in_flight));
+    let concurrency_controller = Arc::new(AdaptiveConcurrencyController::new(
+        args.max_files_in_flight,
+        args.adaptive_concurrency_enabled(),
+    ));
 
     // Process the directory
     let result = process_directory_entry_with_compio(
         dispatcher,
         initial_src,
         initial_dst,
+        None,
         file_ops_static,
         _copy_method,
         shared_stats.clone(),
         shared_hardlink_tracker.clone(),
         concurrency_controller,
         args_static,
+        0,
     )
     .await;
 
@@ -741,6 +787,18 @@ async fn traverse_and_copy_directory_iterative(
     result
 }
 
+/// Wait for an entry dispatched to a worker thread to finish
+///
+/// Resolves to an error only if the task stopped without finishing (e.g. it
+/// panicked); the entry's own `Result` is discarded here.
+async fn dispatched_entry(receiver: futures::channel::oneshot::Receiver<Result<()>>) -> Result<()> {
+    receiver.await.map(drop).map_err(|e| {
+        SyncError::FileSystem(format!(
+            "Failed to receive result from dispatched operation: {e:?}"
+        ))
+    })
+}
+
 /// Process directory entry using compio's dispatcher for async operations
 ///
 /// This is the core function that handles all types of directory entries (files, directories, symlinks)
@@ -766,10 +824,13 @@ async fn traverse_and_copy_directory_iterative(
 /// * `dispatcher` - Static dispatcher for scheduling async operations
 /// * `src_path` - Source path of the directory entry
 /// * `dst_path` - Destination path for the entry
+/// * `src_parent` - Open source directory holding the entry (`None` for the
+///   root); the entry is examined and, if a directory, opened relative to it
 /// * `file_ops` - File operations handler with `io_uring` support
 /// * `copy_method` - Copy method (e.g., `io_uring`, fallback)
 /// * `stats` - Shared statistics tracking (wrapped in Arc<Mutex<>>)
 /// * `hardlink_tracker` - Shared hardlink detection (wrapped in Arc<Mutex<>>)
+/// * `depth` - Number of directory levels below the source root (root is 0)
 ///
 /// # Returns
 ///
@@ -786,24 +847,44 @@ async fn traverse_and_copy_directory_iterative(
 #[allow(clippy::too_many_arguments)]
 #[allow(clippy::future_not_send)]
 #[allow(clippy::used_underscore_binding)]
+#[cfg_attr(feature = "spans", tracing::instrument(
+    name = "entry",
+    level = "debug",
+    skip_all,
+    fields(path = %src_path.display(), depth = depth)
+))]
 async fn process_directory_entry_with_compio(
     dispatcher: &'static Dispatcher,
     src_path: PathBuf,
     dst_path: PathBuf,
+    src_parent: Option<DirectoryFd>,
     file_ops: &'static FileOperations,
     _copy_method: CopyMethod,
     stats: SharedStats,
     hardlink_tracker: SharedHardlinkTracker,
     concurrency_controller: Arc<AdaptiveConcurrencyController>,
     args: &'static Args,
+    depth: usize,
 ) -> Result<()> {
     // Acquire permit from adaptive concurrency controller
     // This prevents unbounded queue growth and adapts to resource constraints (e.g., FD exhaustion)
-    // The permit is held for the entire operation (directory, file, or symlink)
-    let _permit = concurrency_controller.acquire().await;
+    // The permit is held for the entire operation (file or symlink); a
+    // directory gives it back before waiting on its children, so a tree
+    // deeper than the permit count cannot starve itself
+    let permit = concurrency_controller.acquire().await;
+
+    // --watch: a stop signal ends the initial sync between entries
+    if crate::watch::stop_requested() {
+        return Ok(());
+    }
 
-    // Get comprehensive metadata using compio's async operations
-    let extended_metadata = ExtendedMetadata::new(&src_path).await?;
+    // Get comprehensive metadata, relative to the parent directory below the root
+    let entry_name = src_path.file_name().map(Path::new);
+    let extended_metadata = match (&src_parent, entry_name) {
+        (Some(parent), Some(name)) => ExtendedMetadata::at(parent, name).await?,
+        _ => ExtendedMetadata::new(&src_path).await?,
+    };
+    stats.record_found(&extended_metadata).await;
 
     if extended_metadata.is_dir() {
         // ========================================================================
@@ -813,31 +894,60 @@ async fn process_directory_entry_with_compio(
 
         // Create destination directory using compio's dispatcher
         if !dst_path.exists() {
-            compio::fs::create_dir(&dst_path).await.map_err(|e| {
-                SyncError::FileSystem(format!(
-                    "Failed to create directory {}: {}",
-                    dst_path.display(),
-                    e
-                ))
-            })?;
-            stats.increment_directories_created()?;
+            let before = itemize::before(&dst_path, args);
+            compio_fs_extended::directory::create_dir(&dst_path, NEW_DIR_MODE)
+                .await
+                .map_err(|e| {
+                    SyncError::FileSystem(format!(
+                        "Failed to create directory {}: {}",
+                        dst_path.display(),
+                        e
+                    ))
+                })?;
+            stats.increment_directories_created().await;
 
             // Preserve directory metadata (permissions, ownership, timestamps) if requested
-            preserve_directory_metadata(&src_path, &dst_path, &extended_metadata, args).await?;
+            if preserve_directory_metadata(&src_path, &dst_path, &extended_metadata, args).await?
+                == OwnershipChange::Skipped
+            {
+                stats.increment_ownership_skipped().await;
+            }
+            itemize::print(Update::Created, before, &src_path, &dst_path, args);
         }
 
-        // Read directory entries using compio-fs-extended wrapper
-        // This abstracts whether read_dir is blocking or uses io_uring (currently blocking due to kernel limitation)
-        // See: compio_fs_extended::directory::read_dir for implementation details and kernel status
-        let entries = compio_fs_extended::directory::read_dir(&src_path)
-            .await
-            .map_err(|e| {
-                SyncError::FileSystem(format!(
-                    "Failed to read directory {}: {}",
-                    src_path.display(),
-                    e
-                ))
-            })?;
+        // --max-depth: the directory itself is copied but not descended into
+        if args.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+            debug!(
+                "Not descending into {} (depth {} reached --max-depth)",
+                src_path.display(),
+                depth
+            );
+            return Ok(());
+        }
+
+        // Open the directory relative to its parent, so the walk never
+        // resolves a full path again and cannot be redirected by a directory
+        // swapped for a symlink; children are opened relative to this one
+        let src_dir = match (&src_parent, entry_name) {
+            (Some(parent), Some(name)) => parent.open_at(name).await,
+            _ => DirectoryFd::open(&src_path).await,
+        }
+        .map_err(|e| {
+            SyncError::FileSystem(format!(
+                "Failed to open directory {}: {}",
+                src_path.display(),
+                e
+            ))
+        })?;
+
+        // Read directory entries through the open directory
+        let entries = src_dir.read_dir().await.map_err(|e| {
+            SyncError::FileSystem(format!(
+                "Failed to read directory {}: {}",
+                src_path.display(),
+                e
+            ))
+        })?;
 
         // ========================================================================
         // CONCURRENT PROCESSING: Dispatch all child entries concurrently
@@ -850,16 +960,40 @@ async fn process_directory_entry_with_compio(
         // we dispatch all child entries to the same function, creating a tree
         // of concurrent operations that compio manages efficiently
         let copy_method = _copy_method.clone();
-        for entry_result in entries {
-            let entry = entry_result.map_err(|e| {
-                SyncError::FileSystem(format!("Failed to read directory entry: {e}"))
-            })?;
-            let child_src_path = entry.path();
-            let file_name = child_src_path.file_name().ok_or_else(|| {
-                SyncError::FileSystem(format!("Invalid file name in {}", child_src_path.display()))
-            })?;
-            let child_dst_path = dst_path.join(file_name);
+        let mut children = Vec::new();
+        let mut prefetch = Vec::new();
+        if let Some(filter) = &args.filter {
+            filter.read_dir_rules(&src_dir).await;
+        }
+        for entry in entries {
+            let child_src_path = src_path.join(entry.name());
+            if let Some(filter) = &args.filter {
+                let is_dir = match entry.file_type() {
+                    Some(file_type) => file_type == EntryType::Directory,
+                    None => src_dir
+                        .symlink_metadata_at(Path::new(entry.name()))
+                        .await
+                        .is_ok_and(|metadata| metadata.is_dir()),
+                };
+                if filter.excludes(&child_src_path, is_dir) {
+                    debug!("Excluded by filter: {}", child_src_path.display());
+                    continue;
+                }
+            }
+            let child_dst_path = dst_path.join(entry.name());
+            if args.prefetch && !args.deterministic && entry.file_type() == Some(EntryType::File) {
+                prefetch.push(PathBuf::from(entry.name()));
+            }
+            children.push((child_src_path, child_dst_path));
+        }
+
+        // --deterministic: visit entries in name order, one at a time
+        if args.deterministic {
+            children.sort_by(|(a, _), (b, _)| a.file_name().cmp(&b.file_name()));
+        }
+        drop(permit);
 
+        for (child_src_path, child_dst_path) in children {
             // Dispatch all entries to the same function regardless of type
             // This creates a unified processing pipeline where each entry
             // determines its own processing path (file/dir/symlink)
@@ -869,24 +1003,35 @@ async fn process_directory_entry_with_compio(
             let stats = stats.clone();
             let hardlink_tracker = hardlink_tracker.clone();
             let concurrency_controller = concurrency_controller.clone();
+            let src_dir = src_dir.clone();
+            // Dispatched work runs on another thread; carry the current span
+            // over so entries nest under their directory in traces
+            let parent_span = tracing::Span::current();
             let receiver = dispatcher
                 .dispatch(move || {
                     process_directory_entry_with_compio(
                         dispatcher,
                         child_src_path,
                         child_dst_path,
+                        Some(src_dir),
                         file_ops,
                         copy_method,
                         stats,
                         hardlink_tracker,
                         concurrency_controller.clone(),
                         args,
+                        depth + 1,
                     )
+                    .instrument(parent_span)
                 })
                 .map_err(|e| {
                     SyncError::FileSystem(format!("Failed to dispatch entry processing: {e:?}"))
                 })?;
-            futures.push(receiver);
+            if args.deterministic {
+                dispatched_entry(receiver).await?;
+            } else {
+                futures.push(receiver);
+            }
         }
 
         // ========================================================================
@@ -896,23 +1041,33 @@ async fn process_directory_entry_with_compio(
         // This is crucial for performance: we don't wait for all operations
         // to complete before checking for errors. As soon as any operation
         // fails, we cancel the remaining operations and return the error.
-        let _ = futures::future::try_join_all(futures.into_iter().map(|receiver| async move {
-            let _ = receiver.await.map_err(|e| {
-                SyncError::FileSystem(format!(
-                    "Failed to receive result from dispatched operation: {e:?}"
-                ))
-            })?;
-            Ok::<(), SyncError>(())
-        }))
+        // --prefetch: read ahead the queued files while their entries wait
+        // for a concurrency slot
+        futures::future::try_join(
+            prefetch_files(&src_dir, prefetch).map(Ok),
+            futures::future::try_join_all(futures.into_iter().map(dispatched_entry)),
+        )
         .await?;
+    } else if extended_metadata.is_file()
+        && args.fake_super
+        && fake_special_preserved(
+            &crate::fake_super::source_stat(&src_path, &extended_metadata.metadata, args).await,
+            args,
+        )
+    {
+        // A device, FIFO or socket stored as a regular file by --fake-super
+        process_special_file(src_path, dst_path, &extended_metadata, stats, args).await?;
     } else if extended_metadata.is_file() {
         // ========================================================================
         // FILE PROCESSING: Handle regular files with hardlink detection
         // ========================================================================
         // Files are processed with hardlink detection to avoid copying
         // the same content multiple times when hardlinks exist
-        process_file(
+        let len = extended_metadata.len();
+        crate::progress::record_discovered(len);
+        let result = process_file(
             src_path,
+            src_parent.as_ref(),
             dst_path,
             extended_metadata,
             file_ops,
@@ -922,19 +1077,80 @@ async fn process_directory_entry_with_compio(
             concurrency_controller,
             args,
         )
-        .await?;
+        .await;
+        crate::progress::record_done(len);
+        result?;
     } else if extended_metadata.is_symlink() {
         // ========================================================================
         // SYMLINK PROCESSING: Handle symbolic links
         // ========================================================================
         // Symlinks are copied with their target preserved, including
         // broken symlinks (which is the correct behavior)
-        process_symlink(src_path, dst_path, stats).await?;
+        process_symlink(
+            src_path,
+            src_parent.as_ref(),
+            dst_path,
+            &extended_metadata,
+            stats,
+            args,
+        )
+        .await?;
+    } else if extended_metadata.is_fifo() || extended_metadata.is_socket() {
+        // ========================================================================
+        // SPECIAL FILE PROCESSING: Handle named pipes and sockets
+        // ========================================================================
+        // Special files have no content to copy; they are recreated as new
+        // nodes only when --specials (or -D / --archive) is given
+        if args.should_preserve_specials() {
+            process_special_file(src_path, dst_path, &extended_metadata, stats, args).await?;
+        } else {
+            info!("skipping non-regular file \"{}\"", src_path.display());
+        }
+    } else if extended_metadata.is_device() {
+        // ========================================================================
+        // DEVICE PROCESSING: Handle block and character devices
+        // ========================================================================
+        // Device nodes are recreated with the source's device number when
+        // --devices (or -D / --archive) is given
+        if args.should_preserve_devices() {
+            process_special_file(src_path, dst_path, &extended_metadata, stats, args).await?;
+        } else {
+            info!("skipping non-regular file \"{}\"", src_path.display());
+        }
     }
 
     Ok(())
 }
 
+/// Whether a `--fake-super` placeholder with recorded stat `source` is
+/// recreated as a special file rather than copied as a regular file
+const fn fake_special_preserved(source: &FakeStat, args: &Args) -> bool {
+    (source.is_device() && args.should_preserve_devices())
+        || (source.is_special() && args.should_preserve_specials())
+}
+
+/// Advise the kernel to read ahead the start of each file `names` in `dir`
+///
+/// Files are opened one at a time, so prefetching holds at most one extra
+/// descriptor per directory. Failures only cost the readahead and are ignored.
+#[allow(clippy::future_not_send)]
+async fn prefetch_files(dir: &DirectoryFd, names: Vec<PathBuf>) {
+    use compio_fs_extended::fadvise::{fadvise, FadviseAdvice};
+
+    for name in names {
+        let Ok(file) = dir.open_file_at(&name).await else {
+            continue;
+        };
+        if let Err(e) = fadvise(&file, FadviseAdvice::WillNeed, 0, PREFETCH_WINDOW).await {
+            debug!(
+                "WILLNEED advice failed for {}: {}",
+                dir.path().join(&name).display(),
+                e
+            );
+        }
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 #[allow(clippy::future_not_send)]
 #[allow(clippy::used_underscore_binding)]
@@ -946,6 +1162,7 @@ async fn process_directory_entry_with_compio(
 ///
 /// # Parameters
 /// - `src_path`: Source file path to process
+/// - `src_dir`: Open parent directory of `src_path`, to open it relative to
 /// - `dst_path`: Destination file path
 /// - `metadata`: Extended source metadata used for decisions (size, inode, links)
 /// - `_file_ops`: File operations handle (reserved for future metadata work)
@@ -956,8 +1173,15 @@ async fn process_directory_entry_with_compio(
 /// # Errors
 /// Returns an error if hardlink handling fails in an unrecoverable way or if
 /// filesystem operations cannot be performed.
+#[cfg_attr(feature = "spans", tracing::instrument(
+    name = "copy_file",
+    level = "debug",
+    skip_all,
+    fields(path = %src_path.display(), bytes = metadata.len())
+))]
 async fn process_file(
     src_path: PathBuf,
+    src_dir: Option<&DirectoryFd>,
     dst_path: PathBuf,
     metadata: ExtendedMetadata,
     _file_ops: &'static FileOperations,
@@ -973,46 +1197,170 @@ async fn process_file(
         metadata.link_count()
     );
 
-    let _device_id = metadata.device_id();
+    // --ignore-existing, --existing and --update decide from the destination alone
+    if args.ignore_existing || args.existing || args.update {
+        if let Some(reason) = skip_reason(&src_path, &dst_path, args).await {
+            debug!("Skipping {}: {}", dst_path.display(), reason);
+            itemize::skipped(&src_path, &dst_path, args);
+            return Ok(());
+        }
+    }
+    events::emit(|| Event::file_started(&src_path, &dst_path));
+    let before = itemize::before(&dst_path, args);
+
+    let device_id = metadata.device_id();
     let inode_number = metadata.inode_number();
     let link_count = metadata.link_count();
+    let track_links = args.should_preserve_hard_links() && link_count > 1;
+    let mut first_link = None;
+    let mut already_copied = false;
+    if track_links {
+        match hardlink_tracker
+            .claim_inode(&src_path, device_id, inode_number, link_count)
+            .await
+        {
+            InodeClaim::First(done) => first_link = Some(done),
+            // If the claiming path fails to copy, copy the content here instead
+            InodeClaim::Follower(copied) => already_copied = copied.await.is_ok(),
+        }
+    }
+    let content_key = if args.dedupe_dest && !already_copied && !metadata.is_empty() {
+        // A source that cannot be read is reported by the copy below
+        crate::dedupe::content_key(&src_path, &metadata.metadata, args)
+            .await
+            .ok()
+    } else {
+        None
+    };
 
     // Check if this inode has already been copied (for hardlinks)
-    if link_count > 1 && hardlink_tracker.is_inode_copied(inode_number)? {
-        handle_existing_hardlink(
+    if already_copied {
+        if handle_existing_hardlink(
             &dst_path,
             &src_path,
-            inode_number,
+            InodeInfo {
+                dev: device_id,
+                ino: inode_number,
+            },
             &stats,
             &hardlink_tracker,
         )
-        .await?;
+        .await?
+        {
+            itemize::print(Update::Linked, before, &src_path, &dst_path, args);
+        }
+    } else if first_link.is_some()
+        && link_from_database(&dst_path, &metadata, &stats, &hardlink_tracker).await?
+    {
+        debug!("Reused copy from an earlier run: {}", dst_path.display());
+        itemize::print(Update::Linked, before, &src_path, &dst_path, args);
+        events::emit(|| Event::file_completed(&src_path, &dst_path, 0, true));
+    } else if let Some(original) = link_duplicate(
+        &src_path,
+        &dst_path,
+        content_key.as_ref(),
+        &hardlink_tracker,
+    )
+    .await?
+    {
+        hardlink_tracker
+            .mark_inode_copied(device_id, inode_number, &original)
+            .await;
+        stats.increment_files_copied().await;
+        debug!(
+            "Deduplicated {} as a link to {}",
+            dst_path.display(),
+            original.display()
+        );
+        events::emit(|| Event::file_completed(&src_path, &dst_path, 0, true));
+        itemize::print(Update::Linked, before, &src_path, &dst_path, args);
     } else {
         // First time seeing this inode - copy the file content normally
         debug!("Copying file content: {}", src_path.display());
 
-        match copy_file(&src_path, &dst_path, args).await {
-            Ok(()) => {
-                stats.increment_files_copied()?;
-                stats.increment_bytes_copied(metadata.len())?;
-                hardlink_tracker.mark_inode_copied(inode_number, dst_path.as_path())?;
+        // With --delay-updates the copy goes to a staging area until the end
+        let target = if args.delay_updates {
+            crate::delay_updates::stage(&dst_path).await?
+        } else {
+            dst_path.clone()
+        };
+
+        // A destination hardlinked by an earlier run (-H or --dedupe-dest)
+        // shares its inode with other paths; replace it instead of writing
+        // through to all of them, and fail the file if it cannot be removed
+        let replaced = match compio::fs::symlink_metadata(&target).await {
+            Ok(existing) if existing.is_file() && existing.nlink() > 1 => {
+                compio::fs::remove_file(&target).await.map_err(|e| {
+                    SyncError::FileSystem(format!(
+                        "Failed to unlink hardlinked destination {}: {}",
+                        target.display(),
+                        e
+                    ))
+                })
+            }
+            _ => Ok(()),
+        };
+
+        let copied = match (replaced, src_dir) {
+            (Err(e), _) => Err(e),
+            (Ok(()), Some(dir)) => copy_file_at(dir, &src_path, &target, args).await,
+            (Ok(()), None) => copy_file(&src_path, &target, args).await,
+        };
+        match copied {
+            Ok(outcome) => {
+                concurrency_controller.record_completion();
+                if outcome.ownership == OwnershipChange::Skipped {
+                    stats.increment_ownership_skipped().await;
+                }
+                if outcome.crtime == CrtimeChange::Unsupported {
+                    stats.increment_crtimes_skipped().await;
+                }
+                stats.increment_files_copied().await;
+                stats.increment_bytes_copied(outcome.written).await;
+                crate::systemd::record_file();
+                hardlink_tracker
+                    .mark_inode_copied(device_id, inode_number, target.as_path())
+                    .await;
+                if track_links {
+                    hardlink_tracker
+                        .record_in_database(device_id, inode_number, &dst_path)
+                        .await;
+                }
+                if let Some(key) = content_key {
+                    hardlink_tracker.record_content(key, &target).await;
+                }
+                if args.delay_updates {
+                    crate::delay_updates::defer(target, dst_path.clone());
+                }
                 debug!("Copied file: {}", dst_path.display());
+                events::emit(|| {
+                    Event::file_completed(&src_path, &dst_path, outcome.written, false)
+                });
+                itemize::print(Update::Transferred, before, &src_path, &dst_path, args);
             }
             Err(e) => {
+                events::emit(|| Event::file_error(&src_path, &dst_path, &e));
+                if args.delay_updates {
+                    // Don't leave a partial copy in the staging area
+                    let _ = compio::fs::remove_file(&target).await;
+                }
                 // Check if this is FD exhaustion and handle accordingly
                 let adapted = concurrency_controller.handle_error(&e);
 
                 if adapted {
                     // Adapted to FD exhaustion
-                    if args.no_adaptive_concurrency {
+                    if !args.adaptive_concurrency_enabled() {
                         // User disabled adaptive concurrency - fail hard
+                        let flags = args.adaptive_concurrency_disabled_by();
                         return Err(SyncError::FdExhaustion(format!(
-                            "File descriptor exhaustion detected (--no-adaptive-concurrency is set). \
+                            "File descriptor exhaustion detected and {} keeps concurrency fixed. \
                              Failed to copy {} -> {}: {}. \
-                             Either increase ulimit or remove --no-adaptive-concurrency flag.",
+                             Either increase ulimit or remove {}.",
+                            flags,
                             src_path.display(),
                             dst_path.display(),
-                            e
+                            e,
+                            flags
                         )));
                     }
                     // Otherwise, log warning and continue with reduced concurrency
@@ -1032,11 +1380,21 @@ async fn process_file(
                         e
                     );
                 }
-                stats.increment_errors()?;
+                stats.increment_errors().await;
             }
         }
     }
 
+    // Let other paths to this inode link to the copy
+    if let Some(done) = first_link {
+        if hardlink_tracker
+            .is_inode_copied(device_id, inode_number)
+            .await
+        {
+            let _ = done.send(());
+        }
+    }
+
     Ok(())
 }
 
@@ -1052,19 +1410,20 @@ async fn process_file(
 ///
 /// - `dst_path`: Destination path where the hardlink should be created
 /// - `src_path`: Source path (used for logging and error context)
-/// - `inode_number`: The inode identifier for the file being processed
+/// - `inode`: The device and inode of the file being processed
 /// - `stats`: Shared statistics tracker used to record successes/errors
 /// - `hardlink_tracker`: Tracker used to look up the original path for this inode
 ///
 /// # Returns
 ///
-/// Returns `Ok(())` if the hardlink was created successfully (or if an expected
-/// recovery path was handled), otherwise returns `Err(SyncError)`.
+/// Returns `Ok(true)` if the hardlink was created, `Ok(false)` if the failure
+/// was recorded as an error and processing can continue, otherwise returns
+/// `Err(SyncError)`.
 ///
 /// # Errors
 ///
 /// This function will return an error if:
-/// - The original path associated with `inode_number` cannot be determined
+/// - The original path associated with `inode` cannot be determined
 /// - The destination parent directory cannot be created when needed
 /// - The hardlink creation via `std::fs::hard_link` fails unexpectedly
 ///
@@ -1076,42 +1435,47 @@ async fn process_file(
 async fn handle_existing_hardlink(
     dst_path: &Path,
     src_path: &Path,
-    inode_number: u64,
+    inode: InodeInfo,
     stats: &SharedStats,
     hardlink_tracker: &SharedHardlinkTracker,
-) -> Result<()> {
+) -> Result<bool> {
     // This is a hardlink - create a hardlink instead of copying content
     debug!(
         "Creating hardlink for {} (inode: {})",
         src_path.display(),
-        inode_number
+        inode.ino
     );
 
     // Find the original file path for this inode
-    if let Some(original_path) = hardlink_tracker.get_original_path_for_inode(inode_number)? {
+    if let Some(original_path) = hardlink_tracker
+        .get_original_path_for_inode(inode.dev, inode.ino)
+        .await
+    {
         // Create destination directory if needed
         if let Some(parent) = dst_path.parent() {
             if !parent.exists() {
-                compio::fs::create_dir_all(parent).await.map_err(|e| {
-                    SyncError::FileSystem(format!(
-                        "Failed to create parent directory {}: {}",
-                        parent.display(),
-                        e
-                    ))
-                })?;
+                compio_fs_extended::directory::create_dir_all(parent, NEW_DIR_MODE)
+                    .await
+                    .map_err(|e| {
+                        SyncError::FileSystem(format!(
+                            "Failed to create parent directory {}: {}",
+                            parent.display(),
+                            e
+                        ))
+                    })?;
             }
         }
 
-        // Create hardlink using compio-fs-extended for io_uring operations
-        match compio_fs_extended::hardlink::create_hardlink_at_path(&original_path, dst_path).await
-        {
+        match link_into_place(&original_path, dst_path).await {
             Ok(()) => {
-                stats.increment_files_copied()?;
+                stats.increment_files_copied().await;
                 debug!(
                     "Created hardlink: {} -> {}",
                     dst_path.display(),
                     original_path.display()
                 );
+                events::emit(|| Event::file_completed(src_path, dst_path, 0, true));
+                return Ok(true);
             }
             Err(e) => {
                 warn!(
@@ -1119,15 +1483,163 @@ async fn handle_existing_hardlink(
                     src_path.display(),
                     e
                 );
-                stats.increment_errors()?;
+                events::emit(|| Event::file_error(src_path, dst_path, &e));
+                stats.increment_errors().await;
             }
         }
     } else {
-        warn!("Could not find original path for inode {}", inode_number);
-        stats.increment_errors()?;
+        warn!("Could not find original path for inode {}", inode.ino);
+        events::emit(|| {
+            Event::file_error(
+                src_path,
+                dst_path,
+                format!("no copy of inode {} to link to", inode.ino),
+            )
+        });
+        stats.increment_errors().await;
+    }
+
+    Ok(false)
+}
+
+/// Why a file should be left alone given what is at its destination
+///
+/// Returns `None` if the file should be copied.
+#[allow(clippy::future_not_send)]
+async fn skip_reason(src_path: &Path, dst_path: &Path, args: &Args) -> Option<&'static str> {
+    if compio::fs::symlink_metadata(dst_path).await.is_err() {
+        return args
+            .existing
+            .then_some("not present at the destination (--existing)");
+    }
+    if args.ignore_existing {
+        return Some("already exists (--ignore-existing)");
+    }
+    if args.update {
+        // STATX with an explicit mask: compio's path metadata can come back
+        // with zeroed timestamps
+        let modified = |path| async move {
+            compio_fs_extended::metadata::statx_at(path)
+                .await
+                .ok()
+                .map(|(_, modified)| modified)
+        };
+        if let (Some(dst), Some(src)) = (modified(dst_path).await, modified(src_path).await) {
+            if dst > src {
+                return Some("newer at the destination (--update)");
+            }
+        }
     }
+    None
+}
 
-    Ok(())
+/// Link `dst_path` to the copy of its inode made by an earlier run
+///
+/// Looks the source inode up in the `--hardlink-db` database. The recorded
+/// destination file is reused only if it is still a regular file with the
+/// source's size and modification time; its content and metadata are then
+/// left as they are. Returns `false` if there is nothing usable to link to,
+/// in which case the caller copies the file as usual.
+///
+/// # Errors
+///
+/// Returns an error if the hardlink tracker lock is poisoned.
+#[allow(clippy::future_not_send)]
+async fn link_from_database(
+    dst_path: &Path,
+    metadata: &ExtendedMetadata,
+    stats: &SharedStats,
+    hardlink_tracker: &SharedHardlinkTracker,
+) -> Result<bool> {
+    let inode_number = metadata.inode_number();
+    let Some(recorded) = hardlink_tracker
+        .recorded_path(metadata.device_id(), inode_number)
+        .await
+    else {
+        return Ok(false);
+    };
+    let Ok(existing) = compio_fs_extended::metadata::symlink_metadata(&recorded).await else {
+        return Ok(false);
+    };
+    if !existing.is_file()
+        || existing.len() != metadata.len()
+        || existing.modified().ok() != metadata.metadata.modified().ok()
+    {
+        debug!(
+            "Recorded copy {} no longer matches, copying again",
+            recorded.display()
+        );
+        return Ok(false);
+    }
+
+    if let Err(e) = link_into_place(&recorded, dst_path).await {
+        warn!("Failed to reuse {}: {}", recorded.display(), e);
+        return Ok(false);
+    }
+    hardlink_tracker
+        .mark_inode_copied(metadata.device_id(), inode_number, &recorded)
+        .await;
+    stats.increment_files_copied().await;
+    Ok(true)
+}
+
+/// Link `dst_path` to a file with the same content written earlier in the run
+///
+/// Candidates come from the `--dedupe-dest` index and are compared with the
+/// source byte for byte before linking, so a hash collision can never
+/// produce a wrong file. Returns the file linked to, or `None` if the caller
+/// should copy the file as usual.
+///
+/// # Errors
+///
+/// Returns an error if the hardlink tracker lock is poisoned.
+#[allow(clippy::future_not_send)]
+async fn link_duplicate(
+    src_path: &Path,
+    dst_path: &Path,
+    content_key: Option<&ContentKey>,
+    hardlink_tracker: &SharedHardlinkTracker,
+) -> Result<Option<PathBuf>> {
+    let Some(key) = content_key else {
+        return Ok(None);
+    };
+    for candidate in hardlink_tracker.content_candidates(key).await {
+        if !crate::dedupe::same_content(src_path, &candidate)
+            .await
+            .unwrap_or(false)
+        {
+            continue;
+        }
+        match link_into_place(&candidate, dst_path).await {
+            Ok(()) => return Ok(Some(candidate)),
+            Err(e) => debug!("Could not link to {}: {}", candidate.display(), e),
+        }
+    }
+    Ok(None)
+}
+
+/// Make `dst_path` a hardlink to `original_path`
+///
+/// A file already at `dst_path` is replaced unless it is already a link to
+/// `original_path`, which happens when re-running into the same destination.
+///
+/// # Errors
+///
+/// Returns an error if an existing file cannot be removed or the link cannot
+/// be created.
+#[allow(clippy::future_not_send)]
+async fn link_into_place(original_path: &Path, dst_path: &Path) -> Result<()> {
+    if let Ok(existing) = compio::fs::symlink_metadata(dst_path).await {
+        let original = compio::fs::symlink_metadata(original_path).await?;
+        if existing.dev() == original.dev() && existing.ino() == original.ino() {
+            return Ok(());
+        }
+        compio::fs::remove_file(dst_path).await?;
+    }
+    // Create hardlink using compio-fs-extended for io_uring operations
+    compio_fs_extended::hardlink::create_hardlink_at_path(original_path, dst_path)
+        .await
+        .map_err(|e| SyncError::FileSystem(e.to_string()))
 }
 
 /// Process a symlink by copying it
@@ -1144,8 +1656,11 @@ async fn handle_existing_hardlink(
 /// # Parameters
 ///
 /// * `src_path` - Source symlink path
+/// * `src_dir` - Open parent directory of `src_path`, if the walk has one
 /// * `dst_path` - Destination symlink path
+/// * `metadata` - Source symlink metadata, for ownership
 /// * `stats` - Shared statistics tracking
+/// * `args` - Command-line arguments (`--munge-links`, ownership options)
 ///
 /// # Returns
 ///
@@ -1156,33 +1671,278 @@ async fn handle_existing_hardlink(
 /// This function will return an error if:
 /// - Symlink target reading fails
 /// - Symlink creation fails
+#[allow(clippy::too_many_arguments)]
 #[allow(clippy::future_not_send)]
-async fn process_symlink(src_path: PathBuf, dst_path: PathBuf, stats: SharedStats) -> Result<()> {
+async fn process_symlink(
+    src_path: PathBuf,
+    src_dir: Option<&DirectoryFd>,
+    dst_path: PathBuf,
+    metadata: &ExtendedMetadata,
+    stats: SharedStats,
+    args: &Args,
+) -> Result<()> {
     debug!("Processing symlink: {}", src_path.display());
 
-    match copy_symlink(&src_path, &dst_path).await {
-        Ok(()) => {
-            stats.increment_symlinks_processed()?;
+    let before = itemize::before(&dst_path, args);
+    match copy_symlink(&src_path, src_dir, &dst_path, metadata, args).await {
+        Ok(ownership) => {
+            if ownership == OwnershipChange::Skipped {
+                stats.increment_ownership_skipped().await;
+            }
+            stats.increment_symlinks_processed().await;
+            itemize::print(Update::Created, before, &src_path, &dst_path, args);
             Ok(())
         }
         Err(e) => {
-            stats.increment_errors()?;
+            stats.increment_errors().await;
             warn!("Failed to copy symlink {}: {}", src_path.display(), e);
             Err(e)
         }
     }
 }
 
-/// Copy a symlink preserving its target
+/// Process a special file (device node, named pipe or Unix socket)
+///
+/// Recreates the node at the destination and applies the requested
+/// permissions, ownership and timestamps by path, since opening a FIFO
+/// would block until a writer appears.
+///
+/// # Errors
+///
+/// This function will return an error if:
+/// - An existing destination cannot be removed
+/// - The special file cannot be created
+/// - Metadata preservation fails
 #[allow(clippy::future_not_send)]
-async fn copy_symlink(src: &Path, dst: &Path) -> Result<()> {
-    use compio_fs_extended::directory::DirectoryFd;
+async fn process_special_file(
+    src_path: PathBuf,
+    dst_path: PathBuf,
+    metadata: &ExtendedMetadata,
+    stats: SharedStats,
+    args: &Args,
+) -> Result<()> {
+    debug!("Processing special file: {}", src_path.display());
+
+    let source = crate::fake_super::source_stat(&src_path, &metadata.metadata, args).await;
+    let before = itemize::before(&dst_path, args);
+    match copy_special_file(&dst_path, metadata, &source, args).await {
+        Ok(ownership) => {
+            if ownership == OwnershipChange::Skipped {
+                stats.increment_ownership_skipped().await;
+            }
+            stats.increment_specials_created().await;
+            itemize::print(Update::Created, before, &src_path, &dst_path, args);
+            Ok(())
+        }
+        Err(e) => {
+            stats.increment_errors().await;
+            warn!("Failed to copy special file {}: {}", src_path.display(), e);
+            Err(e)
+        }
+    }
+}
+
+/// Recreate a device node, FIFO or socket at `dst` and preserve its metadata
+///
+/// `source` carries the type, mode and ownership to recreate, which may come
+/// from a `--fake-super` xattr rather than `metadata`. The node is created
+/// and updated through its parent directory, since opening a FIFO would
+/// block until a writer appears.
+#[allow(clippy::future_not_send)]
+async fn copy_special_file(
+    dst: &Path,
+    metadata: &ExtendedMetadata,
+    source: &FakeStat,
+    args: &Args,
+) -> Result<OwnershipChange> {
+    let (dst_parent, dst_name) = dst.parent().zip(dst.file_name()).ok_or_else(|| {
+        SyncError::FileSystem(format!(
+            "Destination path has no parent or filename: {}",
+            dst.display()
+        ))
+    })?;
+    let dst_name = Path::new(dst_name);
+    let dst_dir_fd = DirectoryFd::open(dst_parent).await.map_err(|e| {
+        SyncError::FileSystem(format!(
+            "Failed to open destination directory {}: {}",
+            dst_parent.display(),
+            e
+        ))
+    })?;
+
+    // Remove the destination if it exists, even as a dangling symlink
+    match compio_fs_extended::unlink::unlink_at(&dst_dir_fd, dst_name).await {
+        Err(compio_fs_extended::ExtendedError::Io(e))
+            if e.kind() != std::io::ErrorKind::NotFound =>
+        {
+            return Err(SyncError::FileSystem(format!(
+                "Failed to remove existing destination {}: {}",
+                dst.display(),
+                e
+            )));
+        }
+        _ => {}
+    }
+
+    let ownership = if crate::fake_super::stores_in_xattr(args) {
+        create_fake_special_file(&dst_dir_fd, dst_name, dst, source, args).await?
+    } else {
+        create_special_node(&dst_dir_fd, dst_name, dst, source, args).await?
+    };
+
+    if args.should_preserve_timestamps() {
+        let accessed = metadata
+            .metadata
+            .accessed()
+            .map_err(|e| SyncError::FileSystem(format!("Failed to get source access time: {e}")))?;
+        let modified = metadata.metadata.modified().map_err(|e| {
+            SyncError::FileSystem(format!("Failed to get source modification time: {e}"))
+        })?;
+        compio_fs_extended::metadata::utimens_at(
+            &dst_dir_fd,
+            dst_name,
+            accessed,
+            modified,
+            FollowSymlinks::NoFollow,
+        )
+        .await
+        .map_err(|e| {
+            SyncError::FileSystem(format!(
+                "Failed to preserve timestamps for {}: {}",
+                dst.display(),
+                e
+            ))
+        })?;
+    }
+
+    debug!("Created special file {}", dst.display());
+    Ok(ownership)
+}
+
+/// Create a device, FIFO or socket node `name` in `dir` with the requested
+/// ownership and mode
+///
+/// `dst` is the node's full path, used in messages.
+#[allow(clippy::future_not_send)]
+async fn create_special_node(
+    dir: &DirectoryFd,
+    name: &Path,
+    dst: &Path,
+    source: &FakeStat,
+    args: &Args,
+) -> Result<OwnershipChange> {
+    use compio_fs_extended::device::{makedev, mknod_at};
+
+    let dev = if source.is_device() {
+        makedev(source.rdev_major, source.rdev_minor)
+    } else {
+        0
+    };
+    mknod_at(dir, name, source.mode & (libc::S_IFMT | 0o7777), dev)
+        .await
+        .map_err(|e| {
+            SyncError::FileSystem(format!(
+                "Failed to create special file {}: {}",
+                dst.display(),
+                e
+            ))
+        })?;
+
+    let mut ownership = OwnershipChange::NotRequested;
+    if args.should_set_ownership() {
+        let current = dir.symlink_metadata_at(name).await.map_err(|e| {
+            SyncError::FileSystem(format!(
+                "Failed to get metadata for {}: {}",
+                dst.display(),
+                e
+            ))
+        })?;
+        ownership = crate::ownership::apply_ownership_at(
+            dir,
+            name,
+            (source.uid, source.gid),
+            (current.uid(), current.gid()),
+            args,
+        )
+        .await?;
+    }
+
+    // Set permissions after chown, which may clear setuid/setgid bits
+    if let Some(mode) = destination_mode(source.mode & 0o7777, false, args) {
+        compio_fs_extended::metadata::chmod_at(dir, name, mode, FollowSymlinks::NoFollow)
+            .await
+            .map_err(|e| {
+                SyncError::FileSystem(format!(
+                    "Failed to preserve permissions for {}: {}",
+                    dst.display(),
+                    e
+                ))
+            })?;
+    }
+
+    Ok(ownership)
+}
+
+/// Store a device, FIFO or socket as an empty regular file `name` in `dir`
+/// for `--fake-super`
+///
+/// The real file type, mode, ownership and device number go into the
+/// `user.rsync.%stat` xattr, since user xattrs cannot be set on special files.
+#[allow(clippy::future_not_send)]
+async fn create_fake_special_file(
+    dir: &DirectoryFd,
+    name: &Path,
+    dst: &Path,
+    source: &FakeStat,
+    args: &Args,
+) -> Result<OwnershipChange> {
+    let created =
+        match compio_fs_extended::device::mknod_at(dir, name, libc::S_IFREG | 0o600, 0).await {
+            Ok(()) => dir.open_file_at(name).await,
+            Err(e) => Err(e),
+        };
+    let file = created.map_err(|e| {
+        SyncError::FileSystem(format!(
+            "Failed to create special file {}: {}",
+            dst.display(),
+            e
+        ))
+    })?;
+    crate::fake_super::store(&file, dst, source, false, args).await
+}
+
+/// Prefix added to symlink targets by `--munge-links`, matching rsync
+pub const MUNGED_LINK_PREFIX: &str = "/rsyncd-munged/";
+
+/// Rewrite a symlink target so it cannot be followed
+///
+/// The target is placed under [`MUNGED_LINK_PREFIX`], a directory that does
+/// not exist, so a link planted in the source cannot point outside the
+/// destination. Targets that are already munged are left unchanged.
+#[must_use]
+pub fn munge_link_target(target: &str) -> String {
+    if target.starts_with(MUNGED_LINK_PREFIX) {
+        target.to_string()
+    } else {
+        format!("{MUNGED_LINK_PREFIX}{target}")
+    }
+}
+
+/// Copy a symlink preserving its target (munged with `--munge-links`)
+///
+/// When ownership or times are preserved, they are applied to the new
+/// symlink itself from `metadata`; its target is never touched.
+#[allow(clippy::future_not_send)]
+pub(crate) async fn copy_symlink(
+    src: &Path,
+    src_dir: Option<&DirectoryFd>,
+    dst: &Path,
+    metadata: &ExtendedMetadata,
+    args: &Args,
+) -> Result<OwnershipChange> {
     use compio_fs_extended::symlink::{create_symlink_at_dirfd, read_symlink_at_dirfd};
 
     // Extract parent directory and filename for DirectoryFd operations
-    let src_parent = src.parent().ok_or_else(|| {
-        SyncError::FileSystem(format!("Source path has no parent: {}", src.display()))
-    })?;
     let src_name = src
         .file_name()
         .ok_or_else(|| {
@@ -1203,14 +1963,25 @@ async fn copy_symlink(src: &Path, dst: &Path) -> Result<()> {
         })?
         .to_string_lossy();
 
-    // Open DirectoryFd for source and destination parents
-    let src_dir_fd = DirectoryFd::open(src_parent).await.map_err(|e| {
-        SyncError::FileSystem(format!(
-            "Failed to open source directory {}: {}",
-            src_parent.display(),
-            e
-        ))
-    })?;
+    // Read through the walk's open source directory; only an entry synced on
+    // its own (e.g. by --watch) opens its parent by path
+    let opened;
+    let src_dir_fd = match src_dir {
+        Some(dir) => dir,
+        None => {
+            let src_parent = src.parent().ok_or_else(|| {
+                SyncError::FileSystem(format!("Source path has no parent: {}", src.display()))
+            })?;
+            opened = DirectoryFd::open(src_parent).await.map_err(|e| {
+                SyncError::FileSystem(format!(
+                    "Failed to open source directory {}: {}",
+                    src_parent.display(),
+                    e
+                ))
+            })?;
+            &opened
+        }
+    };
 
     let dst_dir_fd = DirectoryFd::open(dst_parent).await.map_err(|e| {
         SyncError::FileSystem(format!(
@@ -1221,7 +1992,7 @@ async fn copy_symlink(src: &Path, dst: &Path) -> Result<()> {
     })?;
 
     // Read symlink target using io_uring DirectoryFd operations
-    let target = read_symlink_at_dirfd(&src_dir_fd, &src_name)
+    let target = read_symlink_at_dirfd(src_dir_fd, &src_name)
         .await
         .map_err(|e| {
             SyncError::FileSystem(format!(
@@ -1231,19 +2002,26 @@ async fn copy_symlink(src: &Path, dst: &Path) -> Result<()> {
             ))
         })?;
 
-    // Remove destination if it exists
-    if dst.exists() {
-        compio::fs::remove_file(dst).await.map_err(|e| {
-            SyncError::FileSystem(format!(
+    // Remove the destination if it exists, even as a dangling symlink
+    match compio_fs_extended::unlink::unlink_at(&dst_dir_fd, Path::new(dst_name.as_ref())).await {
+        Err(compio_fs_extended::ExtendedError::Io(e))
+            if e.kind() != std::io::ErrorKind::NotFound =>
+        {
+            return Err(SyncError::FileSystem(format!(
                 "Failed to remove existing destination {}: {}",
                 dst.display(),
                 e
-            ))
-        })?;
+            )));
+        }
+        _ => {}
     }
 
     // Create symlink with same target using io_uring DirectoryFd operations
-    let target_str = target.to_string_lossy();
+    let target_str = if args.munge_links {
+        munge_link_target(&target.to_string_lossy()).into()
+    } else {
+        target.to_string_lossy()
+    };
     create_symlink_at_dirfd(&dst_dir_fd, &target_str, &dst_name)
         .await
         .map_err(|e| {
@@ -1255,8 +2033,64 @@ async fn copy_symlink(src: &Path, dst: &Path) -> Result<()> {
             ))
         })?;
 
-    debug!("Copied symlink {} -> {}", dst.display(), target.display());
-    Ok(())
+    debug!("Copied symlink {} -> {}", dst.display(), target_str);
+
+    let dst_name = Path::new(dst_name.as_ref());
+
+    // Symlinks cannot carry a fake-super xattr, so that mode leaves them be
+    let mut ownership = OwnershipChange::NotRequested;
+    if args.should_set_ownership() && !crate::fake_super::stores_in_xattr(args) {
+        let current = dst_dir_fd
+            .symlink_metadata_at(dst_name)
+            .await
+            .map_err(|e| {
+                SyncError::FileSystem(format!(
+                    "Failed to get metadata for {}: {}",
+                    dst.display(),
+                    e
+                ))
+            })?;
+        ownership = crate::ownership::apply_ownership_at(
+            &dst_dir_fd,
+            dst_name,
+            (metadata.metadata.uid(), metadata.metadata.gid()),
+            (current.uid(), current.gid()),
+            args,
+        )
+        .await?;
+    }
+
+    // Times go on the symlink itself, never on what it points to
+    if args.should_preserve_timestamps() {
+        let times = metadata
+            .metadata
+            .accessed()
+            .and_then(|accessed| Ok((accessed, metadata.metadata.modified()?)));
+        let (accessed, modified) = times.map_err(|e| {
+            SyncError::FileSystem(format!(
+                "Failed to get timestamps of {}: {}",
+                src.display(),
+                e
+            ))
+        })?;
+        compio_fs_extended::metadata::utimens_at(
+            &dst_dir_fd,
+            dst_name,
+            accessed,
+            modified,
+            FollowSymlinks::NoFollow,
+        )
+        .await
+        .map_err(|e| {
+            SyncError::FileSystem(format!(
+                "Failed to preserve timestamps for {}: {}",
+                dst.display(),
+                e
+            ))
+        })?;
+    }
+
+    Ok(ownership)
 }
 
 /// Preserve file metadata (permissions, ownership, timestamps)
@@ -1331,6 +2165,7 @@ pub struct HardlinkInfo {
     #[allow(dead_code)]
     pub original_path: std::path::PathBuf,
     /// Inode number
+    #[allow(dead_code)]
     pub inode_number: u64,
     /// Number of hardlinks found
     pub link_count: u64,
@@ -1349,6 +2184,30 @@ pub struct FilesystemTracker {
     hardlinks: HashMap<InodeInfo, HardlinkInfo>,
     /// Source filesystem device ID (for boundary detection)
     source_filesystem: Option<u64>,
+    /// Signals resolved once each claimed inode has been copied
+    #[allow(clippy::disallowed_types)]
+    claims: HashMap<InodeInfo, CopiedSignal>,
+    /// Inodes copied by earlier runs (`--hardlink-db`)
+    database: Option<HardlinkDb>,
+    /// Files written this run, by content (`--dedupe-dest`)
+    content: DedupeIndex,
+}
+
+/// Resolves once the first path of an inode has been copied, or fails with
+/// `RecvError` if that copy did not succeed
+pub type CopiedSignal = Shared<oneshot::Receiver<()>>;
+
+/// Result of claiming a multiply-linked inode during traversal
+///
+/// Links to the same inode are processed concurrently, so the first path to
+/// claim it copies the content and the others wait for that copy before
+/// linking to it.
+#[derive(Debug)]
+pub enum InodeClaim {
+    /// This path copies the inode; send on the sender once it is copied
+    First(oneshot::Sender<()>),
+    /// Another path is copying the inode; wait for it, then link to it
+    Follower(CopiedSignal),
 }
 
 #[allow(dead_code)]
@@ -1360,6 +2219,56 @@ impl FilesystemTracker {
             #[allow(clippy::disallowed_types)]
             hardlinks: HashMap::new(),
             source_filesystem: None,
+            #[allow(clippy::disallowed_types)]
+            claims: HashMap::new(),
+            database: None,
+            content: DedupeIndex::default(),
+        }
+    }
+
+    /// Destination files written this run with the given content
+    #[must_use]
+    pub fn content_candidates(&self, key: &ContentKey) -> Vec<PathBuf> {
+        self.content.candidates(key)
+    }
+
+    /// Remember that `dst_path` holds the content described by `key`
+    pub fn record_content(&mut self, key: ContentKey, dst_path: &Path) {
+        self.content.insert(key, dst_path);
+    }
+
+    /// Register `path` and decide whether it copies the inode or links to it
+    pub fn claim_inode(&mut self, path: &Path, dev: u64, ino: u64, link_count: u64) -> InodeClaim {
+        self.register_file(path, dev, ino, link_count);
+        let inode_info = InodeInfo { dev, ino };
+        if let Some(copied) = self.claims.get(&inode_info) {
+            return InodeClaim::Follower(copied.clone());
+        }
+        let (done, copied) = oneshot::channel();
+        self.claims.insert(inode_info, copied.shared());
+        InodeClaim::First(done)
+    }
+
+    /// Use `db` to find inodes copied by earlier runs and record new ones
+    pub fn attach_database(&mut self, db: HardlinkDb) {
+        self.database = Some(db);
+    }
+
+    /// Detach the database so it can be saved
+    pub fn take_database(&mut self) -> Option<HardlinkDb> {
+        self.database.take()
+    }
+
+    /// Destination an earlier run copied the source inode `(dev, ino)` to
+    #[must_use]
+    pub fn recorded_path(&self, dev: u64, ino: u64) -> Option<PathBuf> {
+        self.database.as_ref()?.lookup(dev, ino)
+    }
+
+    /// Remember that `dst_path` holds the content of source inode `(dev, ino)`
+    pub fn record_in_database(&mut self, dev: u64, ino: u64, dst_path: &Path) {
+        if let Some(db) = self.database.as_mut() {
+            db.record(dev, ino, dst_path);
         }
     }
 
@@ -1457,24 +2366,25 @@ impl FilesystemTracker {
     /// Returns true if this inode has been processed and copied to the destination.
     /// This is used to determine whether to copy file content or create a hardlink.
     #[must_use]
-    pub fn is_inode_copied(&self, ino: u64) -> bool {
-        self.hardlinks
-            .values()
-            .any(|info| info.inode_number == ino && info.is_copied)
+    pub fn is_inode_copied(&self, dev: u64, ino: u64) -> bool {
+        self.get_hardlink_info(dev, ino)
+            .is_some_and(|info| info.is_copied)
     }
 
     /// Mark an inode as copied and store its destination path
     ///
     /// This should be called after successfully copying a file's content,
     /// so that subsequent hardlinks to the same inode can be created instead of copied.
-    pub fn mark_inode_copied(&mut self, ino: u64, dst_path: &Path) {
-        for info in self.hardlinks.values_mut() {
-            if info.inode_number == ino {
-                info.is_copied = true;
-                info.dst_path = Some(dst_path.to_path_buf());
-                debug!("Marked inode {} as copied to {}", ino, dst_path.display());
-                break;
-            }
+    pub fn mark_inode_copied(&mut self, dev: u64, ino: u64, dst_path: &Path) {
+        if let Some(info) = self.hardlinks.get_mut(&InodeInfo { dev, ino }) {
+            info.is_copied = true;
+            info.dst_path = Some(dst_path.to_path_buf());
+            debug!(
+                "Marked inode ({}, {}) as copied to {}",
+                dev,
+                ino,
+                dst_path.display()
+            );
         }
     }
 
@@ -1483,10 +2393,9 @@ impl FilesystemTracker {
     /// Returns the destination path where this inode's content was first copied.
     /// This is used to create hardlinks pointing to the original copied file.
     #[must_use]
-    pub fn get_original_path_for_inode(&self, ino: u64) -> Option<&Path> {
-        self.hardlinks
-            .values()
-            .find(|info| info.inode_number == ino && info.is_copied)
+    pub fn get_original_path_for_inode(&self, dev: u64, ino: u64) -> Option<&Path> {
+        self.get_hardlink_info(dev, ino)
+            .filter(|info| info.is_copied)
             .and_then(|info| info.dst_path.as_deref())
     }
 
@@ -1540,11 +2449,18 @@ pub struct FilesystemStats {
 /// - Extended attributes cannot be read from source
 /// - Extended attributes cannot be written to destination
 /// - Permission is denied for xattr operations
-#[allow(clippy::future_not_send)]
+#[allow(clippy::future_not_send, dead_code)]
 pub async fn preserve_directory_xattr(src_path: &Path, dst_path: &Path) -> Result<()> {
-    use compio_fs_extended::{ExtendedFile, XattrOps};
+    let (src_dir, dst_dir) = open_directory_pair(src_path, dst_path).await?;
+    crate::copy::copy_xattrs_except(&src_dir, &dst_dir, &[]).await
+}
 
-    // Open source and destination directories for xattr operations
+/// Open a source and destination directory for xattr operations
+#[allow(clippy::future_not_send)]
+async fn open_directory_pair(
+    src_path: &Path,
+    dst_path: &Path,
+) -> Result<(compio::fs::File, compio::fs::File)> {
     let src_dir = compio::fs::File::open(src_path).await.map_err(|e| {
         SyncError::FileSystem(format!("Failed to open source directory for xattr: {e}"))
     })?;
@@ -1553,41 +2469,7 @@ pub async fn preserve_directory_xattr(src_path: &Path, dst_path: &Path) -> Resul
             "Failed to open destination directory for xattr: {e}"
         ))
     })?;
-
-    // Convert to ExtendedFile to access xattr operations
-    let extended_src = ExtendedFile::from_ref(&src_dir);
-    let extended_dst = ExtendedFile::from_ref(&dst_dir);
-
-    // Get all extended attribute names from source directory
-    let Ok(xattr_names) = extended_src.list_xattr().await else {
-        // If xattr is not supported or no xattrs exist, that's fine
-        return Ok(());
-    };
-
-    // Copy each extended attribute
-    for name in xattr_names {
-        match extended_src.get_xattr(&name).await {
-            Ok(value) => {
-                if let Err(e) = extended_dst.set_xattr(&name, &value).await {
-                    // Log warning but continue with other xattrs
-                    tracing::warn!(
-                        "Failed to preserve directory extended attribute '{}': {}",
-                        name,
-                        e
-                    );
-                }
-            }
-            Err(e) => {
-                tracing::warn!(
-                    "Failed to read directory extended attribute '{}': {}",
-                    name,
-                    e
-                );
-            }
-        }
-    }
-
-    Ok(())
+    Ok((src_dir, dst_dir))
 }
 
 /// Preserve directory metadata (permissions, ownership, timestamps) from source to destination
@@ -1603,7 +2485,7 @@ pub async fn preserve_directory_xattr(src_path: &Path, dst_path: &Path) -> Resul
 ///
 /// # Returns
 ///
-/// `Ok(())` if all metadata was preserved successfully
+/// The [`OwnershipChange`] applied to the directory if all metadata was preserved successfully
 ///
 /// # Errors
 ///
@@ -1617,22 +2499,66 @@ pub async fn preserve_directory_metadata(
     dst_path: &Path,
     extended_metadata: &ExtendedMetadata,
     args: &Args,
-) -> Result<()> {
-    use compio_fs_extended::{metadata, OwnershipOps};
+) -> Result<OwnershipChange> {
+    use compio_fs_extended::metadata;
+
+    // Metadata is read and written through descriptors, so it comes from and
+    // goes to the directories being copied even if a path is swapped meanwhile
+    let (src_dir, dst_dir) =
+        if args.fake_super || args.should_preserve_selinux() || args.should_preserve_xattrs() {
+            let (src_dir, dst_dir) = open_directory_pair(src_path, dst_path).await?;
+            (Some(src_dir), dst_dir)
+        } else {
+            let dst_dir = compio::fs::File::open(dst_path).await.map_err(|e| {
+                SyncError::FileSystem(format!(
+                    "Failed to open destination directory {}: {e}",
+                    dst_path.display()
+                ))
+            })?;
+            (None, dst_dir)
+        };
+
+    let source = match &src_dir {
+        Some(src_dir) => {
+            crate::fake_super::source_stat_from_fd(
+                src_dir,
+                src_path,
+                &extended_metadata.metadata,
+                args,
+            )
+            .await
+        }
+        None => FakeStat::from_metadata(&extended_metadata.metadata),
+    };
+    let fake_super = crate::fake_super::stores_in_xattr(args);
 
-    // Preserve directory permissions if requested
-    if args.should_preserve_permissions() {
-        let src_permissions = extended_metadata.metadata.permissions();
-        let mode = src_permissions.mode();
-        let compio_permissions = compio::fs::Permissions::from_mode(mode);
+    // The SELinux label goes on before the remaining metadata
+    if let Some(src_dir) = src_dir.as_ref().filter(|_| args.should_preserve_selinux()) {
+        crate::security::preserve_selinux_label(src_dir, &dst_dir, dst_path).await;
+    }
 
-        // Open destination directory for permission operations
-        let dst_dir = compio::fs::File::open(dst_path).await.map_err(|e| {
-            SyncError::FileSystem(format!(
-                "Failed to open destination directory for permissions: {e}"
-            ))
+    // Preserve directory ownership first: chown clears setuid/setgid bits
+    let mut ownership = OwnershipChange::NotRequested;
+    if !fake_super && args.should_set_ownership() {
+        let dst_metadata = dst_dir.metadata().await.map_err(|e| {
+            SyncError::FileSystem(format!("Failed to get destination directory metadata: {e}"))
         })?;
 
+        ownership = crate::ownership::apply_ownership(
+            dst_dir.as_raw_fd(),
+            dst_path,
+            (source.uid, source.gid),
+            (dst_metadata.uid(), dst_metadata.gid()),
+            args,
+        )
+        .await?;
+    }
+
+    // Preserve directory permissions (or apply the configured policy) if requested
+    let dir_mode = destination_mode(source.mode, true, args).filter(|_| !fake_super);
+    if let Some(mode) = dir_mode {
+        let compio_permissions = compio::fs::Permissions::from_mode(mode);
+
         // Use file descriptor-based set_permissions to avoid umask interference
         dst_dir
             .set_permissions(compio_permissions)
@@ -1648,31 +2574,6 @@ pub async fn preserve_directory_metadata(
         );
     }
 
-    // Preserve directory ownership if requested
-    if args.should_preserve_ownership() {
-        let source_uid = extended_metadata.metadata.uid();
-        let source_gid = extended_metadata.metadata.gid();
-
-        // Open destination directory for ownership operations
-        let dst_dir = compio::fs::File::open(dst_path).await.map_err(|e| {
-            SyncError::FileSystem(format!(
-                "Failed to open destination directory for ownership: {e}"
-            ))
-        })?;
-
-        // Set ownership using fchown
-        dst_dir.fchown(source_uid, source_gid).await.map_err(|e| {
-            SyncError::FileSystem(format!("Failed to preserve directory ownership: {e}"))
-        })?;
-
-        debug!(
-            "Preserved directory ownership for {}: uid={}, gid={}",
-            dst_path.display(),
-            source_uid,
-            source_gid
-        );
-    }
-
     // Preserve directory timestamps if requested
     if args.should_preserve_timestamps() {
         let src_accessed = extended_metadata.metadata.accessed().map_err(|e| {
@@ -1684,8 +2585,7 @@ pub async fn preserve_directory_metadata(
             ))
         })?;
 
-        // Use compio-fs-extended for timestamp preservation
-        metadata::futimesat(dst_path, src_accessed, src_modified)
+        metadata::futimens_fd(dst_dir.as_raw_fd(), src_accessed, src_modified)
             .await
             .map_err(|e| {
                 SyncError::FileSystem(format!("Failed to preserve directory timestamps: {e}"))
@@ -1694,13 +2594,25 @@ pub async fn preserve_directory_metadata(
         debug!("Preserved directory timestamps for {}", dst_path.display());
     }
 
+    let Some(src_dir) = src_dir else {
+        return Ok(ownership);
+    };
+
     // Preserve directory extended attributes if requested
     if args.should_preserve_xattrs() {
-        preserve_directory_xattr(src_path, dst_path).await?;
+        crate::copy::copy_xattrs_except(&src_dir, &dst_dir, &crate::copy::managed_xattrs(args))
+            .await?;
         debug!("Preserved directory xattrs for {}", dst_path.display());
     }
 
-    Ok(())
+    // Record the fake-super stat after copying xattrs so it replaces the source's
+    if fake_super {
+        ownership = crate::fake_super::store(&dst_dir, dst_path, &source, true, args).await?;
+    } else if args.fake_super && args.should_preserve_xattrs() {
+        crate::fake_super::clear(&dst_dir, dst_path).await;
+    }
+
+    Ok(ownership)
 }
 
 #[cfg(test)]
@@ -1796,10 +2708,14 @@ mod tests {
 
         // Test symlink processing
         let stats = DirectoryStats::default();
+        let metadata = ExtendedMetadata::new(&src_symlink).await.unwrap();
         let result = process_symlink(
             src_symlink.clone(),
+            None,
             dst_symlink.clone(),
+            &metadata,
             SharedStats::new(stats),
+            &Args::default(),
         )
         .await;
 
@@ -1828,10 +2744,14 @@ mod tests {
 
         // Test processing broken symlink
         let stats = DirectoryStats::default();
+        let metadata = ExtendedMetadata::new(&src_symlink).await.unwrap();
         let result = process_symlink(
             src_symlink.clone(),
+            None,
             dst_symlink.clone(),
+            &metadata,
             SharedStats::new(stats),
+            &Args::default(),
         )
         .await;
 
@@ -1846,6 +2766,221 @@ mod tests {
         assert_eq!(target.to_string_lossy(), "nonexistent_file");
     }
 
+    /// Test that the owner is applied to the copied symlink, not its target
+    #[compio::test]
+    async fn test_process_symlink_preserves_ownership() {
+        use std::os::unix::fs::MetadataExt;
+
+        if !crate::ownership::has_cap_chown() {
+            eprintln!("test_process_symlink_preserves_ownership: SKIPPED (requires CAP_CHOWN)");
+            return;
+        }
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let target_file = temp_dir.path().join("target.txt");
+        let src_symlink = temp_dir.path().join("src_symlink");
+        let dst_symlink = temp_dir.path().join("dst_symlink");
+        std::fs::write(&target_file, "target content").unwrap();
+        std::os::unix::fs::symlink(&target_file, &src_symlink).unwrap();
+        std::os::unix::fs::lchown(&src_symlink, Some(1234), Some(5678)).unwrap();
+
+        let args = Args {
+            owner: true,
+            group: true,
+            numeric_ids: true,
+            ..Default::default()
+        };
+        let metadata = ExtendedMetadata::new(&src_symlink).await.unwrap();
+        process_symlink(
+            src_symlink,
+            None,
+            dst_symlink.clone(),
+            &metadata,
+            SharedStats::new(DirectoryStats::default()),
+            &args,
+        )
+        .await
+        .expect("Failed to process symlink");
+
+        let link = std::fs::symlink_metadata(&dst_symlink).unwrap();
+        assert_eq!((link.uid(), link.gid()), (1234, 5678));
+        let target = std::fs::metadata(&target_file).unwrap();
+        assert_ne!((target.uid(), target.gid()), (1234, 5678));
+    }
+
+    /// Test --times sets the symlink's own times and leaves its target alone
+    #[compio::test]
+    async fn test_process_symlink_preserves_times() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let target_file = temp_dir.path().join("target.txt");
+        let src_symlink = temp_dir.path().join("src_symlink");
+        let dst_symlink = temp_dir.path().join("dst_symlink");
+        std::fs::write(&target_file, "target content").unwrap();
+        std::os::unix::fs::symlink(&target_file, &src_symlink).unwrap();
+        let old = std::time::UNIX_EPOCH + std::time::Duration::new(1_000_000_000, 123_456_789);
+        let dir = DirectoryFd::open(temp_dir.path()).await.unwrap();
+        compio_fs_extended::metadata::utimens_at(
+            &dir,
+            Path::new("src_symlink"),
+            old,
+            old,
+            FollowSymlinks::NoFollow,
+        )
+        .await
+        .unwrap();
+        let target_modified = std::fs::metadata(&target_file).unwrap().modified().unwrap();
+
+        let args = Args {
+            times: true,
+            ..Default::default()
+        };
+        let metadata = ExtendedMetadata::new(&src_symlink).await.unwrap();
+        process_symlink(
+            src_symlink,
+            None,
+            dst_symlink.clone(),
+            &metadata,
+            SharedStats::new(DirectoryStats::default()),
+            &args,
+        )
+        .await
+        .expect("Failed to process symlink");
+
+        let link = std::fs::symlink_metadata(&dst_symlink).unwrap();
+        assert_eq!(link.modified().unwrap(), old);
+        let target = std::fs::metadata(&target_file).unwrap();
+        assert_eq!(target.modified().unwrap(), target_modified);
+    }
+
+    /// Test --munge-links rewrites targets so they cannot escape the destination
+    #[compio::test]
+    async fn test_process_symlink_munged() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let src_symlink = temp_dir.path().join("escape");
+        let dst_symlink = temp_dir.path().join("dst_escape");
+        std::os::unix::fs::symlink("/etc/passwd", &src_symlink)
+            .expect("Failed to create source symlink");
+
+        let args = Args {
+            munge_links: true,
+            ..Default::default()
+        };
+        let metadata = ExtendedMetadata::new(&src_symlink).await.unwrap();
+        process_symlink(
+            src_symlink,
+            None,
+            dst_symlink.clone(),
+            &metadata,
+            SharedStats::new(DirectoryStats::default()),
+            &args,
+        )
+        .await
+        .expect("Failed to process symlink");
+
+        let target = std::fs::read_link(&dst_symlink).expect("Failed to read symlink target");
+        assert_eq!(target, PathBuf::from("/rsyncd-munged//etc/passwd"));
+        assert_eq!(
+            munge_link_target("/rsyncd-munged/etc/passwd"),
+            "/rsyncd-munged/etc/passwd"
+        );
+    }
+
+    /// Test process_special_file recreates a FIFO with its mode
+    #[compio::test]
+    async fn test_process_special_file_fifo() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let src_fifo = temp_dir.path().join("src_fifo");
+        let dst_fifo = temp_dir.path().join("dst_fifo");
+
+        compio_fs_extended::device::create_named_pipe_at_path(&src_fifo, 0o640)
+            .await
+            .expect("Failed to create source FIFO");
+        std::fs::set_permissions(&src_fifo, std::fs::Permissions::from_mode(0o640))
+            .expect("Failed to set FIFO permissions");
+
+        let metadata = ExtendedMetadata::new(&src_fifo)
+            .await
+            .expect("Failed to get metadata");
+        assert!(metadata.is_fifo());
+        assert!(!metadata.is_socket());
+
+        let args = Args {
+            specials: true,
+            perms: true,
+            ..Default::default()
+        };
+        let stats = SharedStats::new(DirectoryStats::default());
+        process_special_file(src_fifo, dst_fifo.clone(), &metadata, stats.clone(), &args)
+            .await
+            .expect("Failed to process special file");
+
+        let dst_metadata = std::fs::symlink_metadata(&dst_fifo).expect("dst FIFO missing");
+        assert!(dst_metadata.file_type().is_fifo());
+        assert_eq!(dst_metadata.permissions().mode() & 0o7777, 0o640);
+        assert_eq!(stats.into_inner().unwrap().specials_created, 1);
+    }
+
+    /// Test a device recorded by --fake-super is recreated, or stored again
+    #[compio::test]
+    async fn test_process_special_file_fake_super_device() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let src_path = temp_dir.path().join("null");
+        let dst_path = temp_dir.path().join("dst_null");
+        std::fs::write(&src_path, b"").expect("Failed to create placeholder");
+        if xattr::set(
+            &src_path,
+            crate::fake_super::FAKE_SUPER_XATTR,
+            b"20644 1,3 0:0",
+        )
+        .is_err()
+        {
+            println!("Skipping test: user xattrs not supported");
+            return;
+        }
+
+        let metadata = ExtendedMetadata::new(&src_path)
+            .await
+            .expect("Failed to get metadata");
+        let args = Args {
+            fake_super: true,
+            devices: true,
+            perms: true,
+            numeric_ids: true,
+            ..Default::default()
+        };
+        let source = crate::fake_super::source_stat(&src_path, &metadata.metadata, &args).await;
+        assert!(source.is_device());
+        assert!(fake_special_preserved(&source, &args));
+        let specials_only = Args {
+            fake_super: true,
+            specials: true,
+            ..Default::default()
+        };
+        assert!(!fake_special_preserved(&source, &specials_only));
+
+        let stats = SharedStats::new(DirectoryStats::default());
+        process_special_file(src_path, dst_path.clone(), &metadata, stats.clone(), &args)
+            .await
+            .expect("Failed to process fake-super device");
+
+        let dst_metadata = std::fs::symlink_metadata(&dst_path).expect("dst device missing");
+        if crate::fake_super::stores_in_xattr(&args) {
+            assert!(
+                dst_metadata.is_file(),
+                "placeholder should be a regular file"
+            );
+            let recorded = xattr::get(&dst_path, crate::fake_super::FAKE_SUPER_XATTR)
+                .expect("Failed to read xattr")
+                .expect("device stat should be recorded");
+            assert_eq!(recorded, b"20644 1,3 0:0");
+        } else {
+            assert!(dst_metadata.file_type().is_char_device());
+            let rdev = dst_metadata.rdev();
+            assert_eq!((libc::major(rdev), libc::minor(rdev)), (1, 3));
+            assert_eq!(dst_metadata.permissions().mode() & 0o7777, 0o644);
+        }
+        assert_eq!(stats.into_inner().unwrap().specials_created, 1);
+    }
+
     /// Test FilesystemTracker basic functionality
     #[compio::test]
     async fn test_filesystem_tracker_basic() {
@@ -1889,4 +3024,28 @@ mod tests {
         assert_eq!(stats.hardlink_groups, 1);
         assert_eq!(stats.total_hardlinks, 2);
     }
+
+    /// Equal inode numbers on different devices are different files
+    #[test]
+    fn test_filesystem_tracker_inode_on_other_device() {
+        let mut tracker = FilesystemTracker::new();
+        let (first, second) = (
+            InodeInfo { dev: 1, ino: 100 },
+            InodeInfo { dev: 2, ino: 100 },
+        );
+        assert!(tracker.register_file(Path::new("a"), first.dev, first.ino, 2));
+        assert!(tracker.register_file(Path::new("b"), second.dev, second.ino, 2));
+
+        tracker.mark_inode_copied(first.dev, first.ino, Path::new("/dst/a"));
+        assert!(tracker.is_inode_copied(first.dev, first.ino));
+        assert_eq!(
+            tracker.get_original_path_for_inode(first.dev, first.ino),
+            Some(Path::new("/dst/a"))
+        );
+        assert!(!tracker.is_inode_copied(second.dev, second.ino));
+        assert_eq!(
+            tracker.get_original_path_for_inode(second.dev, second.ino),
+            None
+        );
+    }
 }
@@ -1,5 +1,6 @@
 //! Error handling and types
 
+use crate::i18n::{Language, TranslationKey};
 use thiserror::Error;
 
 /// Synchronization and file operation errors
@@ -33,9 +34,12 @@ pub enum SyncError {
 
     /// Permission denied error
     #[error("Permission denied: {0}")]
-    #[allow(dead_code)]
     PermissionDenied(String),
 
+    /// Destination is on a read-only filesystem
+    #[error("Read-only file system: {0}")]
+    ReadOnlyFilesystem(String),
+
     /// General filesystem error
     #[error("File system error: {0}")]
     FileSystem(String),
@@ -51,3 +55,124 @@ pub enum SyncError {
 }
 
 pub type Result<T> = std::result::Result<T, SyncError>;
+
+/// Exit code for general failures
+pub const EXIT_FAILURE: i32 = 1;
+
+/// Exit code when the destination cannot be written at all
+///
+/// Matches rsync's "errors selecting input/output files, dirs".
+pub const EXIT_DESTINATION_NOT_WRITABLE: i32 = 3;
+
+impl SyncError {
+    /// Process exit code to report for this error
+    #[must_use]
+    pub const fn exit_code(&self) -> i32 {
+        match self {
+            Self::PermissionDenied(_) | Self::ReadOnlyFilesystem(_) => {
+                EXIT_DESTINATION_NOT_WRITABLE
+            }
+            _ => EXIT_FAILURE,
+        }
+    }
+
+    /// Catalog entry describing this kind of error
+    #[must_use]
+    pub const fn translation_key(&self) -> TranslationKey {
+        match self {
+            Self::Io(_) => TranslationKey::ErrorIoError,
+            Self::IoUring(_) => TranslationKey::ErrorIoUring,
+            Self::CopyFailed(_) => TranslationKey::ErrorCopyFailed,
+            Self::MetadataFailed(_) => TranslationKey::ErrorMetadataFailed,
+            Self::DirectoryTraversal(_) => TranslationKey::ErrorDirectoryTraversal,
+            Self::InvalidConfig(_) => TranslationKey::ErrorInvalidConfig,
+            Self::PermissionDenied(_) => TranslationKey::ErrorPermissionDenied,
+            Self::ReadOnlyFilesystem(_) => TranslationKey::ErrorReadOnlyFilesystem,
+            Self::FileSystem(_) => TranslationKey::ErrorFileSystem,
+            Self::FdExhaustion(_) => TranslationKey::ErrorFdExhaustion,
+            Self::Internal(_) => TranslationKey::ErrorInternal,
+        }
+    }
+
+    /// Stable identifier for this kind of error, e.g. `error-copy-failed`
+    #[must_use]
+    pub const fn message_id(&self) -> &'static str {
+        self.translation_key().message_id()
+    }
+
+    /// Detail text without the English prefix added by `Display`
+    #[must_use]
+    pub fn detail(&self) -> String {
+        match self {
+            Self::Io(e) => e.to_string(),
+            Self::IoUring(s)
+            | Self::CopyFailed(s)
+            | Self::MetadataFailed(s)
+            | Self::DirectoryTraversal(s)
+            | Self::InvalidConfig(s)
+            | Self::PermissionDenied(s)
+            | Self::ReadOnlyFilesystem(s)
+            | Self::FileSystem(s)
+            | Self::FdExhaustion(s)
+            | Self::Internal(s) => s.clone(),
+        }
+    }
+
+    /// User-facing message in the current language, prefixed with the
+    /// message ID: `[error-copy-failed] Copy operation failed: <detail>`
+    #[must_use]
+    pub fn localized(&self) -> String {
+        let key = self.translation_key();
+        let summary = key
+            .get()
+            .unwrap_or_else(|_| key.translate(Language::English.locale_id()));
+        format!("[{}] {summary}: {}", self.message_id(), self.detail())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_ids_are_distinct_and_translated() {
+        let errors = [
+            SyncError::Io(std::io::Error::other("boom")),
+            SyncError::IoUring(String::new()),
+            SyncError::CopyFailed(String::new()),
+            SyncError::MetadataFailed(String::new()),
+            SyncError::DirectoryTraversal(String::new()),
+            SyncError::InvalidConfig(String::new()),
+            SyncError::PermissionDenied(String::new()),
+            SyncError::ReadOnlyFilesystem(String::new()),
+            SyncError::FileSystem(String::new()),
+            SyncError::FdExhaustion(String::new()),
+            SyncError::Internal(String::new()),
+        ];
+
+        let mut ids: Vec<_> = errors.iter().map(SyncError::message_id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        assert_eq!(ids.len(), errors.len());
+
+        for error in &errors {
+            for locale in ["en-US", "qaa"] {
+                let text = error.translation_key().translate(locale);
+                assert!(
+                    !text.contains("[Missing translation:"),
+                    "{} has no {locale} text",
+                    error.message_id()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_localized_includes_id_and_detail() {
+        let error = SyncError::ReadOnlyFilesystem("/mnt/backup".to_string());
+        assert_eq!(error.message_id(), "error-read-only-filesystem");
+        let message = error.localized();
+        assert!(message.starts_with("[error-read-only-filesystem] "));
+        assert!(message.ends_with(": /mnt/backup"));
+    }
+}
@@ -0,0 +1,179 @@
+//! Machine-readable event stream (`--json`)
+//!
+//! With `--json`, arsync writes one JSON object per line to stdout as the
+//! run progresses, so orchestration tools can follow it without scraping
+//! log output. Every object carries an `event` field:
+//!
+//! | `event`          | Fields                                                |
+//! |------------------|-------------------------------------------------------|
+//! | `file_started`   | `source`, `destination`                               |
+//! | `file_completed` | `source`, `destination`, `bytes`, `linked`            |
+//! | `error`          | `source` and `destination` (if about a file), `message` |
+//! | `summary`        | `files`, `bytes`, `duration_ms`                       |
+//!
+//! `linked` is true when the destination was hardlinked to an existing copy
+//! instead of having its content written. Paths that are not valid UTF-8 are
+//! converted lossily. Logging continues to go to stderr.
+
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Whether events are written to stdout
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// One line of the event stream
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// A file is about to be copied or linked
+    FileStarted {
+        /// Source path
+        source: String,
+        /// Destination path
+        destination: String,
+    },
+    /// A file reached its destination
+    FileCompleted {
+        /// Source path
+        source: String,
+        /// Destination path
+        destination: String,
+        /// Bytes of content written
+        bytes: u64,
+        /// Whether the destination was hardlinked rather than written
+        linked: bool,
+    },
+    /// Something went wrong
+    Error {
+        /// Source path, if the error concerns a file
+        #[serde(skip_serializing_if = "Option::is_none")]
+        source: Option<String>,
+        /// Destination path, if the error concerns a file
+        #[serde(skip_serializing_if = "Option::is_none")]
+        destination: Option<String>,
+        /// Error description
+        message: String,
+    },
+    /// Totals for a finished run
+    Summary {
+        /// Files copied
+        files: u64,
+        /// Bytes copied
+        bytes: u64,
+        /// Wall-clock duration in milliseconds
+        duration_ms: u64,
+    },
+}
+
+impl Event {
+    /// `file_started` for `src` -> `dst`
+    #[must_use]
+    pub fn file_started(src: &Path, dst: &Path) -> Self {
+        Self::FileStarted {
+            source: src.to_string_lossy().into_owned(),
+            destination: dst.to_string_lossy().into_owned(),
+        }
+    }
+
+    /// `file_completed` for `src` -> `dst`
+    #[must_use]
+    pub fn file_completed(src: &Path, dst: &Path, bytes: u64, linked: bool) -> Self {
+        Self::FileCompleted {
+            source: src.to_string_lossy().into_owned(),
+            destination: dst.to_string_lossy().into_owned(),
+            bytes,
+            linked,
+        }
+    }
+
+    /// `error` about copying `src` -> `dst`
+    #[must_use]
+    pub fn file_error(src: &Path, dst: &Path, message: impl ToString) -> Self {
+        Self::Error {
+            source: Some(src.to_string_lossy().into_owned()),
+            destination: Some(dst.to_string_lossy().into_owned()),
+            message: message.to_string(),
+        }
+    }
+
+    /// `error` that ended the run
+    #[must_use]
+    pub fn fatal(message: impl ToString) -> Self {
+        Self::Error {
+            source: None,
+            destination: None,
+            message: message.to_string(),
+        }
+    }
+
+    /// `summary` of a finished run
+    #[must_use]
+    pub fn summary(files: u64, bytes: u64, duration: Duration) -> Self {
+        Self::Summary {
+            files,
+            bytes,
+            duration_ms: u64::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+        }
+    }
+
+    /// The event as a single line of JSON (without the newline)
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        // Only strings and integers: serialization cannot fail
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// Start writing events to stdout
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Whether `--json` output is on
+#[must_use]
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Write `event` to stdout if the stream is enabled
+///
+/// `make` is only called when events are enabled, so callers don't pay for
+/// building paths and messages otherwise.
+pub fn emit(make: impl FnOnce() -> Event) {
+    if !enabled() {
+        return;
+    }
+    let line = make().to_json();
+    // One locked write per event keeps lines from interleaving
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{line}");
+    let _ = stdout.flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_json_lines() {
+        assert_eq!(
+            Event::file_started(Path::new("src/a"), Path::new("dst/a")).to_json(),
+            r#"{"event":"file_started","source":"src/a","destination":"dst/a"}"#
+        );
+        assert_eq!(
+            Event::file_completed(Path::new("src/a"), Path::new("dst/a"), 4, false).to_json(),
+            r#"{"event":"file_completed","source":"src/a","destination":"dst/a","bytes":4,"linked":false}"#
+        );
+        assert_eq!(
+            Event::fatal("boom").to_json(),
+            r#"{"event":"error","message":"boom"}"#
+        );
+        assert_eq!(
+            Event::summary(2, 10, Duration::from_millis(1500)).to_json(),
+            r#"{"event":"summary","files":2,"bytes":10,"duration_ms":1500}"#
+        );
+    }
+}
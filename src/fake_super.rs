@@ -0,0 +1,377 @@
+//! `--fake-super`: privileged metadata stored in extended attributes
+//!
+//! An unprivileged user cannot chown files, set setuid bits on files owned
+//! by someone else or create device nodes. With `--fake-super`, like rsync,
+//! the metadata that cannot be applied for real is recorded in a
+//! `user.rsync.%stat` xattr on the destination instead, using rsync's format:
+//!
+//! ```text
+//! <mode in octal> <rdev major>,<rdev minor> <uid>:<gid>
+//! ```
+//!
+//! The real entry keeps owner read/write (and search, for directories)
+//! access so the backup stays usable. Device nodes, FIFOs and sockets are
+//! stored as empty regular files whose recorded mode carries the real file
+//! type, along with the device number for block and character devices.
+//!
+//! When reading a source tree with `--fake-super`, a `user.rsync.%stat` xattr
+//! takes precedence over the real stat. Running the copy back as root (with
+//! `CAP_CHOWN`) therefore restores the original ownership, modes and special
+//! files; without the capability the values are simply carried over into the
+//! destination's xattr again.
+
+use crate::cli::Args;
+use crate::copy::destination_mode;
+use crate::error::{Result, SyncError};
+use crate::ownership::{destination_ownership, has_cap_chown, OwnershipChange};
+use compio_fs_extended::xattr::{
+    get_xattr_at_path, get_xattr_impl, remove_xattr_impl, set_xattr_impl,
+};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::Path;
+use tracing::debug;
+
+/// Name of the xattr holding the fake-super stat, as used by rsync
+pub const FAKE_SUPER_XATTR: &str = "user.rsync.%stat";
+
+/// Ownership, mode and device number as recorded by `--fake-super`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FakeStat {
+    /// Full `st_mode`, including the file type bits
+    pub mode: u32,
+    /// Major device number (0 for anything but device nodes)
+    pub rdev_major: u32,
+    /// Minor device number (0 for anything but device nodes)
+    pub rdev_minor: u32,
+    /// Owning user id
+    pub uid: u32,
+    /// Owning group id
+    pub gid: u32,
+}
+
+impl FakeStat {
+    /// Take the stat values from real metadata
+    #[must_use]
+    pub fn from_metadata(metadata: &impl MetadataExt) -> Self {
+        let rdev = metadata.rdev();
+        Self {
+            mode: metadata.mode(),
+            rdev_major: libc::major(rdev),
+            rdev_minor: libc::minor(rdev),
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+        }
+    }
+
+    /// Parse an xattr value in rsync's `"%o %u,%u %u:%u"` format
+    #[must_use]
+    pub fn parse(value: &[u8]) -> Option<Self> {
+        let text = std::str::from_utf8(value).ok()?;
+        let mut fields = text.trim_end_matches('\0').split(' ');
+        let mode = u32::from_str_radix(fields.next()?, 8).ok()?;
+        let (major, minor) = fields.next()?.split_once(',')?;
+        let (uid, gid) = fields.next()?.split_once(':')?;
+        if fields.next().is_some() {
+            return None;
+        }
+        Some(Self {
+            mode,
+            rdev_major: major.parse().ok()?,
+            rdev_minor: minor.parse().ok()?,
+            uid: uid.parse().ok()?,
+            gid: gid.parse().ok()?,
+        })
+    }
+
+    /// Format the xattr value in rsync's `"%o %u,%u %u:%u"` format
+    #[must_use]
+    pub fn encode(&self) -> String {
+        format!(
+            "{:o} {},{} {}:{}",
+            self.mode, self.rdev_major, self.rdev_minor, self.uid, self.gid
+        )
+    }
+
+    /// Whether the recorded file type is a FIFO
+    #[must_use]
+    pub const fn is_fifo(&self) -> bool {
+        self.mode & libc::S_IFMT == libc::S_IFIFO
+    }
+
+    /// Whether the recorded file type is a Unix socket
+    #[must_use]
+    pub const fn is_socket(&self) -> bool {
+        self.mode & libc::S_IFMT == libc::S_IFSOCK
+    }
+
+    /// Whether the recorded file type is a FIFO or socket
+    #[must_use]
+    pub const fn is_special(&self) -> bool {
+        self.is_fifo() || self.is_socket()
+    }
+
+    /// Whether the recorded file type is a block or character device
+    #[must_use]
+    pub const fn is_device(&self) -> bool {
+        matches!(self.mode & libc::S_IFMT, libc::S_IFBLK | libc::S_IFCHR)
+    }
+}
+
+/// Whether privileged metadata goes into xattrs instead of the real inode
+///
+/// True for `--fake-super` when the process lacks `CAP_CHOWN`.
+#[must_use]
+pub fn stores_in_xattr(args: &Args) -> bool {
+    args.fake_super && !has_cap_chown()
+}
+
+/// Determine the stat values of a source entry
+///
+/// With `--fake-super`, a valid `user.rsync.%stat` xattr on the source
+/// overrides the real metadata. The xattr is looked up by path; entries that
+/// are opened anyway should use [`source_stat_from_fd`].
+#[allow(clippy::future_not_send)]
+pub async fn source_stat(path: &Path, metadata: &impl MetadataExt, args: &Args) -> FakeStat {
+    if !args.fake_super {
+        return FakeStat::from_metadata(metadata);
+    }
+    let value = get_xattr_at_path(path, FAKE_SUPER_XATTR).await.ok();
+    stat_from_xattr(value, path, metadata)
+}
+
+/// Determine the stat values of an open source entry
+///
+/// Same as [`source_stat`], but reads the xattr through `src`, so it comes
+/// from the inode whose content and metadata are being copied.
+#[allow(clippy::future_not_send)]
+pub async fn source_stat_from_fd(
+    src: &compio::fs::File,
+    path: &Path,
+    metadata: &impl MetadataExt,
+    args: &Args,
+) -> FakeStat {
+    if !args.fake_super {
+        return FakeStat::from_metadata(metadata);
+    }
+    let value = get_xattr_impl(src, FAKE_SUPER_XATTR).await.ok();
+    stat_from_xattr(value, path, metadata)
+}
+
+/// Use the fake-super xattr `value` if it parses, the real metadata otherwise
+fn stat_from_xattr(value: Option<Vec<u8>>, path: &Path, metadata: &impl MetadataExt) -> FakeStat {
+    if let Some(value) = value {
+        if let Some(stat) = FakeStat::parse(&value) {
+            return stat;
+        }
+        debug!(
+            "Ignoring malformed {} on {}",
+            FAKE_SUPER_XATTR,
+            path.display()
+        );
+    }
+    FakeStat::from_metadata(metadata)
+}
+
+/// Record ownership and mode of `source` on an open destination entry
+///
+/// The values that would have been applied (after `--chown`, `--chmod` and
+/// the rest of the permission policy) are written to the
+/// `user.rsync.%stat` xattr, and the real permissions are limited to what an
+/// unprivileged owner can use. When the result matches the real inode the
+/// xattr is removed instead, so plain files carry no extra attribute.
+///
+/// # Errors
+///
+/// This function will return an error if the destination metadata cannot be
+/// read, its permissions cannot be set or the xattr cannot be written.
+#[allow(clippy::future_not_send)]
+pub async fn store(
+    dst_file: &compio::fs::File,
+    dst_path: &Path,
+    source: &FakeStat,
+    is_dir: bool,
+    args: &Args,
+) -> Result<OwnershipChange> {
+    let current = dst_file.metadata().await.map_err(|e| {
+        SyncError::FileSystem(format!(
+            "Failed to get metadata for {}: {e}",
+            dst_path.display()
+        ))
+    })?;
+
+    let (uid, gid) = if args.should_set_ownership() {
+        destination_ownership(source.uid, source.gid, args).await?
+    } else {
+        (current.uid(), current.gid())
+    };
+    let perms = destination_mode(source.mode, is_dir, args).unwrap_or(current.mode() & 0o7777);
+    let stat = FakeStat {
+        mode: (source.mode & libc::S_IFMT) | perms,
+        rdev_major: source.rdev_major,
+        rdev_minor: source.rdev_minor,
+        uid,
+        gid,
+    };
+
+    // Keep the entry usable by its (unprivileged) owner
+    let real_perms = (perms & 0o777) | if is_dir { 0o700 } else { 0o600 };
+    if current.mode() & 0o7777 != real_perms {
+        dst_file
+            .set_permissions(compio::fs::Permissions::from_mode(real_perms))
+            .await
+            .map_err(|e| {
+                SyncError::FileSystem(format!(
+                    "Failed to set permissions on {}: {e}",
+                    dst_path.display()
+                ))
+            })?;
+    }
+
+    let real = FakeStat {
+        mode: (current.mode() & libc::S_IFMT) | real_perms,
+        rdev_major: 0,
+        rdev_minor: 0,
+        uid: current.uid(),
+        gid: current.gid(),
+    };
+    if stat == real {
+        clear(dst_file, dst_path).await;
+    } else {
+        set_xattr_impl(dst_file, FAKE_SUPER_XATTR, stat.encode().as_bytes())
+            .await
+            .map_err(|e| {
+                SyncError::FileSystem(format!(
+                    "Failed to record {} on {}: {e}",
+                    FAKE_SUPER_XATTR,
+                    dst_path.display()
+                ))
+            })?;
+        debug!(
+            "Recorded fake-super stat for {}: {}",
+            dst_path.display(),
+            stat.encode()
+        );
+    }
+
+    Ok(if !args.should_set_ownership() {
+        OwnershipChange::NotRequested
+    } else if (uid, gid) == (current.uid(), current.gid()) {
+        OwnershipChange::Unchanged
+    } else {
+        OwnershipChange::Changed
+    })
+}
+
+/// Remove a stale `user.rsync.%stat` xattr from an open destination
+///
+/// Used when the real metadata was applied, e.g. a copied-over xattr after
+/// restoring as root. A missing xattr is not an error.
+#[allow(clippy::future_not_send)]
+pub async fn clear(dst_file: &compio::fs::File, dst_path: &Path) {
+    if remove_xattr_impl(dst_file, FAKE_SUPER_XATTR).await.is_ok() {
+        debug!("Removed {} from {}", FAKE_SUPER_XATTR, dst_path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+
+    #[test]
+    fn test_fake_stat_round_trip() {
+        let stat = FakeStat {
+            mode: 0o104_755,
+            rdev_major: 0,
+            rdev_minor: 0,
+            uid: 0,
+            gid: 42,
+        };
+        assert_eq!(stat.encode(), "104755 0,0 0:42");
+        assert_eq!(FakeStat::parse(stat.encode().as_bytes()), Some(stat));
+    }
+
+    #[test]
+    fn test_fake_stat_parse_rsync_values() {
+        let fifo = FakeStat::parse(b"10644 0,0 1000:1000").unwrap();
+        assert!(fifo.is_fifo());
+        assert_eq!(fifo.mode & 0o7777, 0o644);
+
+        let device = FakeStat::parse(b"20660 4,64 0:5").unwrap();
+        assert_eq!((device.rdev_major, device.rdev_minor), (4, 64));
+        assert!(!device.is_fifo() && !device.is_socket());
+        assert!(device.is_device() && !device.is_special());
+        assert!(!fifo.is_device());
+
+        assert_eq!(FakeStat::parse(b""), None);
+        assert_eq!(FakeStat::parse(b"644 0,0"), None);
+        assert_eq!(FakeStat::parse(b"999 0,0 0:0"), None);
+        assert_eq!(FakeStat::parse(b"644 0,0 0:0 extra"), None);
+    }
+
+    #[compio::test]
+    async fn test_store_records_stat_and_source_reads_it_back() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("fifo");
+        std::fs::write(&path, b"").unwrap();
+        let file = compio::fs::File::open(&path).await.unwrap();
+        let args = Args {
+            fake_super: true,
+            archive: true,
+            numeric_ids: true,
+            ..Default::default()
+        };
+        let source = FakeStat {
+            mode: libc::S_IFIFO | 0o4640,
+            rdev_major: 0,
+            rdev_minor: 0,
+            uid: 1234,
+            gid: 5678,
+        };
+
+        let change = store(&file, &path, &source, false, &args).await.unwrap();
+        assert_eq!(change, OwnershipChange::Changed);
+
+        // The real file stays owner-accessible without special bits
+        let metadata = std::fs::metadata(&path).unwrap();
+        assert!(metadata.is_file());
+        assert_eq!(metadata.mode() & 0o7777, 0o640);
+
+        let recorded = source_stat(&path, &metadata, &args).await;
+        assert_eq!(recorded, source);
+        assert!(recorded.is_special());
+
+        // Without --fake-super the real metadata is used
+        let plain = source_stat(&path, &metadata, &Args::default()).await;
+        assert_eq!(plain, FakeStat::from_metadata(&metadata));
+    }
+
+    #[compio::test]
+    async fn test_store_records_device_number() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("null");
+        std::fs::write(&path, b"").unwrap();
+        let file = compio::fs::File::open(&path).await.unwrap();
+        let args = Args {
+            fake_super: true,
+            archive: true,
+            numeric_ids: true,
+            ..Default::default()
+        };
+        let source = FakeStat {
+            mode: libc::S_IFCHR | 0o666,
+            rdev_major: 1,
+            rdev_minor: 3,
+            uid: 0,
+            gid: 0,
+        };
+
+        store(&file, &path, &source, false, &args).await.unwrap();
+
+        let metadata = std::fs::metadata(&path).unwrap();
+        assert!(metadata.is_file());
+        let recorded = source_stat(&path, &metadata, &args).await;
+        assert_eq!(recorded, source);
+        assert!(recorded.is_device());
+    }
+}
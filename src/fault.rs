@@ -0,0 +1,326 @@
+//! Fault injection for soak testing (`fault-injection` feature)
+//!
+//! When built with the `fault-injection` feature, the read/write copy loop
+//! passes the completion of every read and write through this module, which
+//! may replace it with a failure or a shorter transfer. Faults are
+//! configured through the `ARSYNC_FAULTS` environment variable, a
+//! comma-separated list of `name=value` pairs:
+//!
+//! | Name       | Meaning                                                     |
+//! |------------|-------------------------------------------------------------|
+//! | `eio`      | Probability that an operation fails with `EIO`              |
+//! | `short`    | Probability that a read or write completes with fewer bytes |
+//! | `delay`    | Probability that an operation completes late                |
+//! | `delay-ms` | Upper bound for an injected delay (default 10)              |
+//! | `seed`     | Seed for the fault sequence (default 1)                     |
+//!
+//! For example `ARSYNC_FAULTS=eio=0.001,short=0.05,delay=0.01,seed=7`.
+//! A short read is followed by a read of the rest, a short write by a write
+//! of the rest, and an `EIO` fails the file's copy.
+//! Injection can also be switched on and off at runtime with [`set_enabled`],
+//! which is how the `arsync-soak` harness alternates faulty and clean runs.
+//!
+//! The kernel-side copy paths (reflink, `copy_file_range`) bypass the copy
+//! loop; use `--copy-method read-write` to route every byte through it.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Parsed `ARSYNC_FAULTS` configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaultConfig {
+    /// Probability of failing an operation with `EIO`
+    pub eio: f64,
+    /// Probability of truncating a read or write
+    pub short: f64,
+    /// Probability of delaying an operation
+    pub delay: f64,
+    /// Longest injected delay
+    pub max_delay: Duration,
+    /// Seed for the fault sequence
+    pub seed: u64,
+}
+
+impl Default for FaultConfig {
+    fn default() -> Self {
+        Self {
+            eio: 0.0,
+            short: 0.0,
+            delay: 0.0,
+            max_delay: Duration::from_millis(10),
+            seed: 1,
+        }
+    }
+}
+
+impl FaultConfig {
+    /// Parse a specification such as `eio=0.01,short=0.1,seed=3`
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the first invalid entry.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut config = Self::default();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (name, value) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("expected name=value, got {entry:?}"))?;
+            let probability = || -> Result<f64, String> {
+                value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|p| (0.0..=1.0).contains(p))
+                    .ok_or_else(|| format!("{name} must be a probability in 0..=1"))
+            };
+            match name {
+                "eio" => config.eio = probability()?,
+                "short" => config.short = probability()?,
+                "delay" => config.delay = probability()?,
+                "delay-ms" => {
+                    let ms = value
+                        .parse()
+                        .map_err(|_| format!("delay-ms must be an integer, got {value:?}"))?;
+                    config.max_delay = Duration::from_millis(ms);
+                }
+                "seed" => {
+                    config.seed = value
+                        .parse()
+                        .map_err(|_| format!("seed must be an integer, got {value:?}"))?;
+                }
+                _ => return Err(format!("unknown fault {name:?}")),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Small deterministic PRNG (xorshift64*) so fault sequences are repeatable
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    /// Generator for `seed` (a zero seed is remapped, as xorshift needs one bit set)
+    #[must_use]
+    pub const fn new(seed: u64) -> Self {
+        Self(if seed == 0 {
+            0x9E37_79B9_7F4A_7C15
+        } else {
+            seed
+        })
+    }
+
+    /// Next pseudo-random value
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = step(self.0);
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform value in `0..bound` (0 when `bound` is 0)
+    pub fn below(&mut self, bound: u64) -> u64 {
+        if bound == 0 {
+            0
+        } else {
+            self.next_u64() % bound
+        }
+    }
+
+    /// `true` with probability `p`
+    #[allow(clippy::cast_precision_loss)]
+    pub fn chance(&mut self, p: f64) -> bool {
+        // 53 random bits give a uniform value in [0, 1)
+        let unit = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        p > 0.0 && unit < p
+    }
+}
+
+/// One xorshift64 step
+const fn step(mut x: u64) -> u64 {
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    x
+}
+
+/// Configuration read from `ARSYNC_FAULTS` on first use
+static CONFIG: OnceLock<FaultConfig> = OnceLock::new();
+
+/// Whether faults are currently injected
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Shared generator state; every draw advances it
+static STATE: AtomicU64 = AtomicU64::new(0);
+
+/// Number of faults injected so far
+static INJECTED: AtomicU64 = AtomicU64::new(0);
+
+/// Active configuration (all probabilities zero if `ARSYNC_FAULTS` is unset)
+pub fn config() -> &'static FaultConfig {
+    CONFIG.get_or_init(|| {
+        let config = std::env::var("ARSYNC_FAULTS")
+            .ok()
+            .map(|spec| {
+                FaultConfig::parse(&spec).unwrap_or_else(|e| {
+                    tracing::warn!("Ignoring invalid ARSYNC_FAULTS: {e}");
+                    FaultConfig::default()
+                })
+            })
+            .unwrap_or_default();
+        STATE.store(Rng::new(config.seed).0, Ordering::Relaxed);
+        config
+    })
+}
+
+/// Turn fault injection on or off
+#[allow(dead_code)]
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Number of faults injected since the process started
+#[allow(dead_code)]
+#[must_use]
+pub fn injected() -> u64 {
+    INJECTED.load(Ordering::Relaxed)
+}
+
+/// Draw from the shared generator
+fn draw() -> Rng {
+    let mut previous = STATE.load(Ordering::Relaxed);
+    loop {
+        let next = step(previous.max(1));
+        match STATE.compare_exchange_weak(previous, next, Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => return Rng::new(next),
+            Err(current) => previous = current,
+        }
+    }
+}
+
+/// Replace the successful completion `result` of an operation the way
+/// `config` and `rng` decide: with `EIO`, or with fewer bytes transferred
+///
+/// Failures pass through unchanged, and a transfer is never cut to zero
+/// bytes, which would read as end of file.
+fn corrupt(
+    config: &FaultConfig,
+    rng: &mut Rng,
+    what: &str,
+    result: std::io::Result<usize>,
+) -> std::io::Result<usize> {
+    let Ok(len) = result else {
+        return result;
+    };
+    if rng.chance(config.eio) {
+        INJECTED.fetch_add(1, Ordering::Relaxed);
+        tracing::debug!("Injecting EIO into {what}");
+        return Err(std::io::Error::from_raw_os_error(libc::EIO));
+    }
+    if len > 1 && rng.chance(config.short) {
+        INJECTED.fetch_add(1, Ordering::Relaxed);
+        let short = 1 + rng.below(len as u64 - 1);
+        tracing::debug!("Cutting {what} of {len} bytes to {short}");
+        return Ok(usize::try_from(short).unwrap_or(len));
+    }
+    Ok(len)
+}
+
+/// Delay and/or replace the completion of an operation
+async fn inject(what: &str, result: std::io::Result<usize>) -> std::io::Result<usize> {
+    let config = config();
+    if !ENABLED.load(Ordering::Relaxed) {
+        return result;
+    }
+    let mut rng = draw();
+    if rng.chance(config.delay) {
+        let max_ms = u64::try_from(config.max_delay.as_millis()).unwrap_or(u64::MAX);
+        INJECTED.fetch_add(1, Ordering::Relaxed);
+        compio::time::sleep(Duration::from_millis(rng.below(max_ms.saturating_add(1)))).await;
+    }
+    corrupt(config, &mut rng, what, result)
+}
+
+/// Hook run on the completion of a read; returns the result the copy sees
+///
+/// # Errors
+///
+/// Returns the read's own error, or an injected `EIO`.
+pub async fn read_completed(result: std::io::Result<usize>) -> std::io::Result<usize> {
+    inject("read", result).await
+}
+
+/// Hook run on the completion of a write; returns the result the copy sees
+///
+/// # Errors
+///
+/// Returns the write's own error, or an injected `EIO`.
+pub async fn write_completed(result: std::io::Result<usize>) -> std::io::Result<usize> {
+    inject("write", result).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fault_spec() {
+        let config = FaultConfig::parse("eio=0.5, short=1,delay=0,delay-ms=3,seed=9").unwrap();
+        assert_eq!(
+            config,
+            FaultConfig {
+                eio: 0.5,
+                short: 1.0,
+                delay: 0.0,
+                max_delay: Duration::from_millis(3),
+                seed: 9,
+            }
+        );
+        assert_eq!(FaultConfig::parse("").unwrap(), FaultConfig::default());
+        assert!(FaultConfig::parse("eio=2").is_err());
+        assert!(FaultConfig::parse("bogus=1").is_err());
+        assert!(FaultConfig::parse("eio").is_err());
+    }
+
+    #[test]
+    fn test_rng_is_repeatable() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        let first: Vec<_> = (0..8).map(|_| a.next_u64()).collect();
+        let second: Vec<_> = (0..8).map(|_| b.next_u64()).collect();
+        assert_eq!(first, second);
+        assert!((0..100).all(|_| a.below(10) < 10));
+        assert!(!a.chance(0.0));
+        assert!(a.chance(1.0));
+    }
+
+    #[test]
+    fn test_corrupt_completions() {
+        let mut rng = Rng::new(5);
+        let eio = FaultConfig {
+            eio: 1.0,
+            ..FaultConfig::default()
+        };
+        let err = corrupt(&eio, &mut rng, "read", Ok(4096)).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EIO));
+
+        let short = FaultConfig {
+            short: 1.0,
+            ..FaultConfig::default()
+        };
+        for _ in 0..100 {
+            let len = corrupt(&short, &mut rng, "write", Ok(4096)).unwrap();
+            assert!((1..4096).contains(&len));
+        }
+        // One byte cannot get shorter without looking like end of file
+        assert_eq!(corrupt(&short, &mut rng, "read", Ok(1)).unwrap(), 1);
+        assert_eq!(corrupt(&short, &mut rng, "read", Ok(0)).unwrap(), 0);
+
+        // Real failures are left alone
+        let err = std::io::Error::from_raw_os_error(libc::ENOSPC);
+        let err = corrupt(&eio, &mut rng, "write", Err(err)).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::ENOSPC));
+        assert_eq!(
+            corrupt(&FaultConfig::default(), &mut rng, "read", Ok(10)).unwrap(),
+            10
+        );
+    }
+}
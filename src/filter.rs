@@ -0,0 +1,840 @@
+//! Include/exclude filtering, as rsync's `--include`/`--exclude` rules
+//!
+//! Rules come from `--exclude`, `--include`, `--exclude-from` and
+//! `--include-from`, in the order they were given; the rules read from a file
+//! take that file's place in the order. For each entry below the source the
+//! first matching rule decides, and an entry no rule matches is copied. An
+//! excluded directory is not descended into, so nothing below it is copied
+//! whatever later rules say.
+//!
+//! Patterns follow rsync:
+//!
+//! - A leading `/` anchors the pattern at the source root; otherwise it
+//!   matches the end of the path (`*.o` matches `a/b/x.o`, `b/*.o` matches
+//!   `a/b/x.o`)
+//! - A trailing `/` matches only directories
+//! - `*` matches anything but `/`, `**` anything including `/`, `?` one
+//!   character but `/`, and `[...]` a character class; `\` escapes
+//! - `dir/***` matches `dir` and everything below it
+//! - `+ ` or `- ` at the start makes the rule an include or exclude whatever
+//!   option it came from, and a lone `!` clears the rules so far
+//!
+//! In rule files, blank lines and lines starting with `;` or `#` are
+//! ignored. A file name of `-` reads standard input.
+//!
+//! With `--gitignore`, the `.gitignore` and `.ignore` files found below the
+//! source also exclude entries, as git would: each file's patterns are
+//! relative to its directory, a later pattern overrides an earlier one, `!`
+//! re-includes, and a file deeper in the tree overrides those above it
+//! (`.ignore` overrides `.gitignore` in the same directory). The
+//! command-line rules are checked first, so `--include` can keep an ignored
+//! file.
+//!
+//! `-C`/`--cvs-exclude` adds rsync's list of files CVS ignores (version
+//! control directories, editor backups, object files, `core` and the like),
+//! plus the words of `~/.cvsignore` and `$CVSIGNORE`, after the command-line
+//! rules. A `.cvsignore` file excludes the whitespace-separated patterns it
+//! lists from its own directory only; a `!` word clears the patterns before
+//! it.
+//!
+//! Per-directory rule files are read once per directory and copied like any
+//! other file.
+
+use crate::error::{Result, SyncError};
+use compio_fs_extended::directory::DirectoryFd;
+use compio_fs_extended::ExtendedError;
+#[allow(clippy::disallowed_types)]
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::io::{self, Read};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use tracing::warn;
+
+/// Per-directory ignore files read with `--gitignore`, lowest precedence
+/// first
+pub const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+/// Per-directory rule file read with `--cvs-exclude`
+pub const CVSIGNORE_FILE: &str = ".cvsignore";
+
+/// Files CVS ignores by default, which `--cvs-exclude` excludes (as rsync)
+const CVS_EXCLUDES: &str = "RCS SCCS CVS CVS.adm RCSLOG cvslog.* tags TAGS \
+    .make.state .nse_depinfo *~ #* .#* ,* _$* *$ *.old *.bak *.BAK *.orig *.rej \
+    .del-* *.a *.olb *.o *.obj *.so *.exe *.Z *.elc *.ln core .svn/ .git/ .hg/ .bzr/";
+
+/// What a matching rule does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Copy the entry
+    Include,
+    /// Skip the entry (and, for a directory, everything below it)
+    Exclude,
+}
+
+/// Where rules come from, in command-line order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterSource {
+    /// One pattern, from `--include` or `--exclude`
+    Pattern(Action, String),
+    /// A file of patterns, from `--include-from` or `--exclude-from`
+    File(Action, PathBuf),
+    /// rsync's CVS exclusions and per-directory `.cvsignore` files, from
+    /// `--cvs-exclude`
+    CvsExclude,
+}
+
+/// One include or exclude rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    /// What a match does
+    pub action: Action,
+    /// Glob, without the anchoring `/`, trailing `/` or `/***`
+    pattern: Vec<u8>,
+    /// Matched from the source root rather than against the end of the path
+    anchored: bool,
+    /// Matches only directories
+    dir_only: bool,
+    /// Also matches everything below a match (`dir/***`)
+    with_contents: bool,
+}
+
+impl Rule {
+    /// Parse `text` as a rule, defaulting to `action`
+    ///
+    /// Returns `None` for a `!` (clear) rule.
+    fn parse(action: Action, text: &str) -> Option<Self> {
+        let (action, mut pattern) = match text.as_bytes() {
+            [b'+', b' ', rest @ ..] => (Action::Include, rest),
+            [b'-', b' ', rest @ ..] => (Action::Exclude, rest),
+            b"!" => return None,
+            _ => (action, text.as_bytes()),
+        };
+        let anchored = pattern.first() == Some(&b'/');
+        if anchored {
+            pattern = &pattern[1..];
+        }
+        let with_contents = pattern.ends_with(b"/***");
+        if with_contents {
+            pattern = &pattern[..pattern.len() - 4];
+        }
+        let dir_only = pattern.len() > 1 && pattern.ends_with(b"/");
+        if dir_only {
+            pattern = &pattern[..pattern.len() - 1];
+        }
+        Some(Self {
+            action,
+            pattern: pattern.to_vec(),
+            anchored,
+            dir_only,
+            with_contents,
+        })
+    }
+
+    /// Parse a line of a `.gitignore` file
+    ///
+    /// A `/**/` in the middle of a pattern also matches no directories at
+    /// all, which the rsync glob does not, so it becomes one rule per way of
+    /// dropping them.
+    fn parse_gitignore(line: &str) -> Vec<Self> {
+        let mut pattern = line.as_bytes();
+        // Trailing spaces are dropped unless escaped
+        while let [rest @ .., b' '] = pattern {
+            if rest.last() == Some(&b'\\') {
+                break;
+            }
+            pattern = rest;
+        }
+        let (action, mut pattern) = match pattern {
+            [] | [b'#', ..] => return Vec::new(),
+            [b'!', rest @ ..] => (Action::Include, rest),
+            [b'\\', rest @ ..] if matches!(rest, [b'#' | b'!', ..]) => (Action::Exclude, rest),
+            _ => (Action::Exclude, pattern),
+        };
+        let dir_only = pattern.len() > 1 && pattern.ends_with(b"/");
+        if dir_only {
+            pattern = &pattern[..pattern.len() - 1];
+        }
+        // A slash anywhere but the end ties the pattern to the file's
+        // directory; a leading `**/` matches at any depth instead
+        let anchored = if let Some(rest) = pattern.strip_prefix(b"**/") {
+            pattern = rest;
+            false
+        } else if let Some(rest) = pattern.strip_prefix(b"/") {
+            pattern = rest;
+            true
+        } else {
+            pattern.contains(&b'/')
+        };
+        if pattern.is_empty() {
+            return Vec::new();
+        }
+        expand_double_star(pattern)
+            .into_iter()
+            .map(|pattern| Self {
+                action,
+                pattern,
+                anchored,
+                dir_only,
+                with_contents: false,
+            })
+            .collect()
+    }
+
+    /// Whether the rule matches `path` (relative to the source root)
+    #[must_use]
+    pub fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        let path = path.as_os_str().as_bytes();
+        if self.with_contents {
+            // `dir/***`: the directory itself, or anything below it
+            return self.matches_name(path)
+                || path
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, &b)| b == b'/')
+                    .any(|(i, _)| self.matches_name(&path[..i]));
+        }
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        self.matches_name(path)
+    }
+
+    /// Match the pattern against `path` or, unless anchored, one of its
+    /// trailing runs of components
+    fn matches_name(&self, path: &[u8]) -> bool {
+        if self.anchored {
+            return glob(&self.pattern, path);
+        }
+        std::iter::once(0)
+            .chain(
+                path.iter()
+                    .enumerate()
+                    .filter(|(_, &b)| b == b'/')
+                    .map(|(i, _)| i + 1),
+            )
+            .any(|start| glob(&self.pattern, &path[start..]))
+    }
+}
+
+/// `pattern` with each `/**/` either kept or collapsed to `/`
+fn expand_double_star(pattern: &[u8]) -> Vec<Vec<u8>> {
+    let Some(i) = pattern.windows(4).position(|w| w == b"/**/") else {
+        return vec![pattern.to_vec()];
+    };
+    expand_double_star(&pattern[i + 4..])
+        .into_iter()
+        .flat_map(|tail| {
+            [
+                [&pattern[..=i], &tail[..]].concat(),
+                [&pattern[..i + 4], &tail[..]].concat(),
+            ]
+        })
+        .collect()
+}
+
+/// Whether glob `pattern` matches all of `text`
+fn glob(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        [b'*', b'*', rest @ ..] => (0..=text.len()).any(|i| glob(rest, &text[i..])),
+        [b'*', rest @ ..] => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != b'/')
+            .any(|i| glob(rest, &text[i..])),
+        [b'?', rest @ ..] => matches!(text, [c, tail @ ..] if *c != b'/' && glob(rest, tail)),
+        [b'[', class @ ..] => match (text, class_match(class, text.first().copied())) {
+            ([_, tail @ ..], Some((true, rest))) => glob(rest, tail),
+            // An unterminated class is a literal `[`
+            ([b'[', tail @ ..], None) => glob(class, tail),
+            _ => false,
+        },
+        [b'\\', c, rest @ ..] | [c, rest @ ..] => {
+            matches!(text, [t, tail @ ..] if t == c && glob(rest, tail))
+        }
+    }
+}
+
+/// Match `c` against the class starting after its `[`
+///
+/// Returns whether it matched and the pattern after the closing `]`, or
+/// `None` if the class is not terminated.
+fn class_match(class: &[u8], c: Option<u8>) -> Option<(bool, &[u8])> {
+    let (negated, mut rest) = match class {
+        [b'!' | b'^', rest @ ..] => (true, rest),
+        _ => (false, class),
+    };
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        match rest {
+            [] => return None,
+            [b']', tail @ ..] if !first => {
+                let matched = matched != negated && c.is_some_and(|c| c != b'/');
+                return Some((matched, tail));
+            }
+            [lo, b'-', hi, tail @ ..] if *hi != b']' => {
+                matched |= c.is_some_and(|c| (*lo..=*hi).contains(&c));
+                rest = tail;
+            }
+            [b, tail @ ..] => {
+                matched |= c == Some(*b);
+                rest = tail;
+            }
+        }
+        first = false;
+    }
+}
+
+/// The rules for a run, with the source root they are relative to
+#[derive(Debug)]
+pub struct Filter {
+    /// Source root
+    root: PathBuf,
+    /// Rules in order; the first match decides
+    rules: Vec<Rule>,
+    /// Honor `.gitignore` and `.ignore` files below the root
+    gitignore: bool,
+    /// Honor `.cvsignore` files below the root
+    cvsignore: bool,
+    /// Rules from each directory's rule files (relative to the root), read
+    /// on first use
+    #[allow(clippy::disallowed_types)]
+    dir_rules: Mutex<HashMap<PathBuf, Arc<DirRules>>>,
+}
+
+/// Rules read from one directory's rule files
+#[derive(Debug, Default)]
+struct DirRules {
+    /// From `.gitignore` then `.ignore`; the last match decides
+    gitignore: Vec<Rule>,
+    /// From `.cvsignore`; any match excludes
+    cvsignore: Vec<Rule>,
+}
+
+impl Filter {
+    /// Build the filter for `root` from `sources`, reading rule files, and
+    /// honoring ignore files below `root` if `gitignore` is set
+    ///
+    /// Returns `None` if there is nothing to filter.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a rule file cannot be read.
+    #[allow(clippy::disallowed_types)]
+    pub fn load(root: &Path, sources: &[FilterSource], gitignore: bool) -> Result<Option<Self>> {
+        let mut rules = Vec::new();
+        let mut cvsignore = false;
+        for source in sources {
+            match source {
+                FilterSource::Pattern(action, text) => push_rule(&mut rules, *action, text),
+                FilterSource::File(action, path) => {
+                    for line in read_rule_file(path)?.lines() {
+                        let line = line.strip_suffix('\r').unwrap_or(line);
+                        if line.is_empty() || line.starts_with(['#', ';']) {
+                            continue;
+                        }
+                        push_rule(&mut rules, *action, line);
+                    }
+                }
+                FilterSource::CvsExclude => {
+                    rules.extend(cvs_exclude_rules());
+                    cvsignore = true;
+                }
+            }
+        }
+        Ok((!rules.is_empty() || gitignore || cvsignore).then(|| Self {
+            root: root.to_path_buf(),
+            rules,
+            gitignore,
+            cvsignore,
+            dir_rules: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    /// Whether the entry at `path`, below the source root, is excluded by
+    /// the rules themselves (not by an excluded directory above it)
+    #[must_use]
+    pub fn excludes(&self, path: &Path, is_dir: bool) -> bool {
+        let rel = path.strip_prefix(&self.root).unwrap_or(path);
+        if rel.as_os_str().is_empty() {
+            return false;
+        }
+        if let Some(rule) = self.rules.iter().find(|rule| rule.matches(rel, is_dir)) {
+            return rule.action == Action::Exclude;
+        }
+        if self.cvsignore {
+            let dir = rel.parent().unwrap_or(Path::new(""));
+            let name = rel.strip_prefix(dir).unwrap_or(rel);
+            if self
+                .dir_rules(dir)
+                .cvsignore
+                .iter()
+                .any(|rule| rule.matches(name, is_dir))
+            {
+                return true;
+            }
+        }
+        self.gitignore && self.ignored(rel, is_dir)
+    }
+
+    /// Whether the ignore files of the directories above `rel` exclude it
+    fn ignored(&self, rel: &Path, is_dir: bool) -> bool {
+        for dir in rel.ancestors().skip(1) {
+            let below = rel.strip_prefix(dir).unwrap_or(rel);
+            if let Some(rule) = self
+                .dir_rules(dir)
+                .gitignore
+                .iter()
+                .rev()
+                .find(|rule| rule.matches(below, is_dir))
+            {
+                return rule.action == Action::Exclude;
+            }
+        }
+        false
+    }
+
+    /// Rules from the rule files in `dir`, relative to the root
+    ///
+    /// The walk reads them ahead with [`read_dir_rules`](Self::read_dir_rules);
+    /// a directory it has not read is read here, blocking.
+    fn dir_rules(&self, dir: &Path) -> Arc<DirRules> {
+        if let Some(rules) = self.cached_dir_rules(dir) {
+            return rules;
+        }
+        // Read without the lock; a racing reader just reads the same files
+        let files = self
+            .rule_files()
+            .map(|name| (name, read_dir_rule_file(&self.root.join(dir).join(name))))
+            .collect();
+        self.cache_dir_rules(dir, files)
+    }
+
+    /// Read the rule files of the open directory `dir` below the root, so
+    /// that checking its entries does no blocking I/O
+    #[allow(clippy::future_not_send)]
+    pub async fn read_dir_rules(&self, dir: &DirectoryFd) {
+        let Ok(rel) = dir.path().strip_prefix(&self.root) else {
+            return;
+        };
+        if self.cached_dir_rules(rel).is_some() {
+            return;
+        }
+        let mut files = Vec::new();
+        for name in self.rule_files() {
+            files.push((name, read_dir_rule_file_at(dir, name).await));
+        }
+        self.cache_dir_rules(rel, files);
+    }
+
+    /// Read the rule files of the directories above `path`, so that checking
+    /// it does no blocking I/O
+    #[allow(clippy::future_not_send)]
+    pub async fn read_rules_above(&self, path: &Path) {
+        let rel = path.strip_prefix(&self.root).unwrap_or(path);
+        for dir in rel.ancestors().skip(1) {
+            if self.cached_dir_rules(dir).is_some() {
+                continue;
+            }
+            // A directory that cannot be opened has no rules to read
+            if let Ok(dir) = DirectoryFd::open(&self.root.join(dir)).await {
+                self.read_dir_rules(&dir).await;
+            }
+        }
+    }
+
+    /// Names of the per-directory rule files this filter reads, in order
+    fn rule_files(&self) -> impl Iterator<Item = &'static str> {
+        let gitignore = if self.gitignore {
+            &IGNORE_FILES[..]
+        } else {
+            &[]
+        };
+        let cvsignore = if self.cvsignore {
+            &[CVSIGNORE_FILE][..]
+        } else {
+            &[]
+        };
+        gitignore.iter().chain(cvsignore).copied()
+    }
+
+    /// Rules already read for `dir`
+    fn cached_dir_rules(&self, dir: &Path) -> Option<Arc<DirRules>> {
+        self.dir_rules
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(dir)
+            .cloned()
+    }
+
+    /// Parse the contents of `dir`'s rule files and cache the rules
+    fn cache_dir_rules(&self, dir: &Path, files: Vec<(&str, String)>) -> Arc<DirRules> {
+        let mut rules = DirRules::default();
+        for (name, text) in files {
+            if name == CVSIGNORE_FILE {
+                rules.cvsignore = word_rules(&text);
+                continue;
+            }
+            for line in text.lines() {
+                let line = line.strip_suffix('\r').unwrap_or(line);
+                rules.gitignore.extend(Rule::parse_gitignore(line));
+            }
+        }
+        let rules = Arc::new(rules);
+        self.dir_rules
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(dir.to_path_buf(), Arc::clone(&rules));
+        rules
+    }
+
+    /// Whether files called `name` hold per-directory rules for this filter
+    #[must_use]
+    pub fn reads_rule_file(&self, name: &OsStr) -> bool {
+        (self.gitignore && IGNORE_FILES.iter().any(|file| name == *file))
+            || (self.cvsignore && name == CVSIGNORE_FILE)
+    }
+
+    /// Forget the rule files read for the directory holding `path`, so
+    /// they are read again after a change
+    pub fn rule_file_changed(&self, path: &Path) {
+        let rel = path.strip_prefix(&self.root).unwrap_or(path);
+        if let Some(dir) = rel.parent() {
+            self.dir_rules
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .remove(dir);
+        }
+    }
+
+    /// Whether the entry at `path` is excluded, by the rules or because a
+    /// directory above it is
+    #[must_use]
+    pub fn excludes_path(&self, path: &Path, is_dir: bool) -> bool {
+        let rel = path.strip_prefix(&self.root).unwrap_or(path);
+        rel.ancestors()
+            .skip(1)
+            .any(|dir| self.excludes(&self.root.join(dir), true))
+            || self.excludes(path, is_dir)
+    }
+}
+
+/// Add the rule `text` (defaulting to `action`), or clear on `!`
+fn push_rule(rules: &mut Vec<Rule>, action: Action, text: &str) {
+    match Rule::parse(action, text) {
+        Some(rule) => rules.push(rule),
+        None => rules.clear(),
+    }
+}
+
+/// Exclude rules for the whitespace-separated patterns in `text`, a `!`
+/// clearing those before it
+fn word_rules(text: &str) -> Vec<Rule> {
+    let mut rules = Vec::new();
+    for word in text.split_whitespace() {
+        push_rule(&mut rules, Action::Exclude, word);
+    }
+    rules
+}
+
+/// rsync's CVS exclusions, then those of `~/.cvsignore` and `$CVSIGNORE`
+fn cvs_exclude_rules() -> Vec<Rule> {
+    let mut words = CVS_EXCLUDES.to_string();
+    if let Some(home) = std::env::var_os("HOME") {
+        words.push(' ');
+        words.push_str(&read_dir_rule_file(&Path::new(&home).join(CVSIGNORE_FILE)));
+    }
+    if let Ok(env) = std::env::var("CVSIGNORE") {
+        words.push(' ');
+        words.push_str(&env);
+    }
+    word_rules(&words)
+}
+
+/// Contents of a per-directory rule file; a missing or unreadable one (which
+/// is logged) is empty
+fn read_dir_rule_file(path: &Path) -> String {
+    match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            if e.kind() != io::ErrorKind::NotFound {
+                warn!("Failed to read filter rules from {}: {e}", path.display());
+            }
+            String::new()
+        }
+    }
+}
+
+/// Contents of the per-directory rule file `name` of `dir`, like
+/// [`read_dir_rule_file`]
+#[allow(clippy::future_not_send)]
+async fn read_dir_rule_file_at(dir: &DirectoryFd, name: &str) -> String {
+    let read = match dir.open_file_at(Path::new(name)).await {
+        Ok(file) => {
+            let compio::BufResult(read, text) =
+                compio::io::AsyncReadAtExt::read_to_end_at(&file, Vec::new(), 0).await;
+            read.map(|_| text).map_err(ExtendedError::Io)
+        }
+        Err(e) => Err(e),
+    };
+    match read {
+        Ok(text) => String::from_utf8_lossy(&text).into_owned(),
+        Err(ExtendedError::Io(e)) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => {
+            warn!(
+                "Failed to read filter rules from {}: {e}",
+                dir.path().join(name).display()
+            );
+            String::new()
+        }
+    }
+}
+
+/// Contents of a rule file, `-` being standard input
+fn read_rule_file(path: &Path) -> Result<String> {
+    let read = if path == Path::new("-") {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text).map(|_| text)
+    } else {
+        std::fs::read_to_string(path)
+    };
+    read.map_err(|e| {
+        SyncError::InvalidConfig(format!(
+            "Failed to read filter rules from {}: {e}",
+            path.display()
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+
+    fn filter(rules: &[(Action, &str)]) -> Filter {
+        let sources: Vec<_> = rules
+            .iter()
+            .map(|(action, text)| FilterSource::Pattern(*action, (*text).to_string()))
+            .collect();
+        Filter::load(Path::new("/src"), &sources, false)
+            .unwrap()
+            .unwrap()
+    }
+
+    fn excluded(filter: &Filter, path: &str) -> bool {
+        let is_dir = path.ends_with('/');
+        filter.excludes_path(&Path::new("/src").join(path.trim_end_matches('/')), is_dir)
+    }
+
+    #[test]
+    fn test_glob() {
+        assert!(glob(b"*.o", b"main.o"));
+        assert!(!glob(b"*.o", b"dir/main.o"));
+        assert!(glob(b"**.o", b"dir/main.o"));
+        assert!(glob(b"a?c", b"abc"));
+        assert!(!glob(b"a?c", b"a/c"));
+        assert!(glob(b"[a-c]x", b"bx"));
+        assert!(!glob(b"[!a-c]x", b"bx"));
+        assert!(glob(b"[]]", b"]"));
+        assert!(glob(b"\\*", b"*"));
+        assert!(!glob(b"\\*", b"a"));
+        assert!(glob(b"[x", b"[x"));
+    }
+
+    #[test]
+    fn test_unanchored_and_anchored() {
+        let f = filter(&[
+            (Action::Exclude, "*.o"),
+            (Action::Exclude, "/top"),
+            (Action::Exclude, "b/c"),
+        ]);
+        assert!(excluded(&f, "x.o"));
+        assert!(excluded(&f, "a/b/x.o"));
+        assert!(excluded(&f, "top"));
+        assert!(!excluded(&f, "a/top"));
+        assert!(excluded(&f, "a/b/c"));
+        assert!(!excluded(&f, "a/xb/c"));
+        assert!(!excluded(&f, "x.c"));
+    }
+
+    #[test]
+    fn test_first_match_wins() {
+        // rsync's "only these files" idiom
+        let f = filter(&[
+            (Action::Include, "*/"),
+            (Action::Include, "*.rs"),
+            (Action::Exclude, "*"),
+        ]);
+        assert!(!excluded(&f, "src/"));
+        assert!(!excluded(&f, "src/lib.rs"));
+        assert!(excluded(&f, "src/notes.txt"));
+
+        // An excluded directory hides everything below it
+        let f = filter(&[(Action::Exclude, "target/"), (Action::Include, "*.rs")]);
+        assert!(excluded(&f, "target/"));
+        assert!(!excluded(&f, "target"));
+        assert!(excluded(&f, "target/gen.rs"));
+    }
+
+    #[test]
+    fn test_prefixes_clear_and_contents() {
+        let f = filter(&[
+            (Action::Exclude, "*.log"),
+            (Action::Exclude, "!"),
+            (Action::Exclude, "+ keep/***"),
+            (Action::Include, "- *"),
+        ]);
+        assert!(!excluded(&f, "keep/"));
+        assert!(!excluded(&f, "keep/a/b.log"));
+        assert!(excluded(&f, "other.txt"));
+    }
+
+    #[test]
+    fn test_rule_files_keep_command_line_order() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let rules = dir.path().join("rules");
+        std::fs::write(&rules, "# build output\n\n; more\n*.o\r\n+ keep.tmp\n").unwrap();
+        let sources = [
+            FilterSource::Pattern(Action::Include, "important.o".to_string()),
+            FilterSource::File(Action::Exclude, rules.clone()),
+            FilterSource::Pattern(Action::Exclude, "*.tmp".to_string()),
+        ];
+        let f = Filter::load(Path::new("/src"), &sources, false)
+            .unwrap()
+            .unwrap();
+        assert!(!excluded(&f, "important.o"));
+        assert!(excluded(&f, "main.o"));
+        assert!(!excluded(&f, "keep.tmp"));
+        assert!(excluded(&f, "other.tmp"));
+
+        // Include files default to include
+        let sources = [
+            FilterSource::File(Action::Include, rules),
+            FilterSource::Pattern(Action::Exclude, "*".to_string()),
+        ];
+        let f = Filter::load(Path::new("/src"), &sources, false)
+            .unwrap()
+            .unwrap();
+        assert!(!excluded(&f, "main.o"));
+        assert!(excluded(&f, "main.c"));
+
+        assert!(Filter::load(Path::new("/src"), &[], false)
+            .unwrap()
+            .is_none());
+        assert!(matches!(
+            Filter::load(
+                Path::new("/src"),
+                &[FilterSource::File(
+                    Action::Exclude,
+                    dir.path().join("missing")
+                )],
+                false
+            ),
+            Err(SyncError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_gitignore_patterns() {
+        let excluded = |line: &str, path: &str, is_dir: bool| {
+            Rule::parse_gitignore(line)
+                .iter()
+                .rev()
+                .find(|rule| rule.matches(Path::new(path), is_dir))
+                .is_some_and(|rule| rule.action == Action::Exclude)
+        };
+        assert!(excluded("*.o", "a/b/x.o", false));
+        assert!(excluded("build/", "a/build", true));
+        assert!(!excluded("build/", "a/build", false));
+        // A slash ties the pattern to the ignore file's directory
+        assert!(excluded("doc/*.html", "doc/x.html", false));
+        assert!(!excluded("doc/*.html", "a/doc/x.html", false));
+        assert!(excluded("/top", "top", false));
+        assert!(!excluded("/top", "a/top", false));
+        assert!(excluded("**/gen/out", "a/b/gen/out", false));
+        assert!(excluded("a/**/b", "a/b", false));
+        assert!(excluded("a/**/b", "a/x/y/b", false));
+        assert!(excluded("cache/**", "cache/x/y", false));
+        assert!(!excluded("cache/**", "cache", true));
+        assert!(excluded("trailing   ", "trailing", false));
+        assert!(excluded("\\#hash", "#hash", false));
+        assert!(Rule::parse_gitignore("# comment").is_empty());
+        assert!(Rule::parse_gitignore("").is_empty());
+        assert_eq!(Rule::parse_gitignore("!keep")[0].action, Action::Include);
+    }
+
+    #[test]
+    fn test_gitignore_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("sub/deeper")).unwrap();
+        std::fs::write(root.join(".gitignore"), "*.log\ntarget/\n/only-top\n").unwrap();
+        std::fs::write(root.join("sub/.gitignore"), "!keep.log\nlocal\n").unwrap();
+        std::fs::write(root.join("sub/.ignore"), "local-too\n").unwrap();
+
+        let f = Filter::load(root, &[], true).unwrap().unwrap();
+        let excluded = |path: &str, is_dir: bool| f.excludes_path(&root.join(path), is_dir);
+        assert!(excluded("a.log", false));
+        assert!(excluded("sub/deeper/a.log", false));
+        assert!(excluded("target", true));
+        assert!(excluded("target/x", false));
+        assert!(excluded("only-top", false));
+        assert!(!excluded("sub/only-top", false));
+        // Deeper files override those above
+        assert!(!excluded("sub/keep.log", false));
+        assert!(excluded("sub/deeper/local", false));
+        assert!(excluded("sub/local-too", false));
+        assert!(!excluded("local", false));
+        assert!(!excluded(".gitignore", false));
+
+        // Command-line rules come first
+        let sources = [FilterSource::Pattern(Action::Include, "a.log".to_string())];
+        let f = Filter::load(root, &sources, true).unwrap().unwrap();
+        assert!(!f.excludes(&root.join("a.log"), false));
+
+        // A changed ignore file is read again once forgotten
+        assert!(!f.excludes(&root.join("sub/keep.log"), false));
+        std::fs::write(root.join("sub/.gitignore"), "other\n").unwrap();
+        assert!(!f.excludes(&root.join("sub/keep.log"), false));
+        f.rule_file_changed(&root.join("sub/.gitignore"));
+        assert!(f.excludes(&root.join("sub/keep.log"), false));
+    }
+
+    #[test]
+    fn test_cvs_exclude() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::write(root.join(".cvsignore"), "*.gen  stale\nnotes.txt\n").unwrap();
+        std::fs::write(root.join("sub/.cvsignore"), "*.dat ! *.tmp\n").unwrap();
+
+        let sources = [
+            FilterSource::Pattern(Action::Include, "keep.o".to_string()),
+            FilterSource::CvsExclude,
+        ];
+        let f = Filter::load(root, &sources, false).unwrap().unwrap();
+        let excluded = |path: &str, is_dir: bool| f.excludes_path(&root.join(path), is_dir);
+        // The built-in list
+        assert!(excluded(".git", true));
+        assert!(!excluded(".git", false));
+        assert!(excluded("sub/main.o", false));
+        assert!(excluded("sub/core", false));
+        assert!(excluded("file.c~", false));
+        assert!(excluded("CVS/Entries", false));
+        assert!(!excluded("main.c", false));
+        // Command-line rules come first
+        assert!(!excluded("keep.o", false));
+        // .cvsignore covers its own directory only
+        assert!(excluded("a.gen", false));
+        assert!(excluded("stale", false));
+        assert!(excluded("notes.txt", false));
+        assert!(!excluded("sub/a.gen", false));
+        // `!` clears the words before it
+        assert!(!excluded("sub/a.dat", false));
+        assert!(excluded("sub/a.tmp", false));
+        assert!(!excluded(".cvsignore", false));
+        assert!(f.reads_rule_file(OsStr::new(".cvsignore")));
+        assert!(!f.reads_rule_file(OsStr::new(".gitignore")));
+    }
+}
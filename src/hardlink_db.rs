@@ -0,0 +1,222 @@
+//! Persistent hardlink database for `--hardlink-db`
+//!
+//! Hardlink detection normally only spans a single run: the first path seen
+//! for a multiply-linked source inode is copied and later paths are linked to
+//! it. With `--hardlink-db FILE`, the destination path chosen for each source
+//! `(dev, ino)` is also written to `FILE` at the end of the run. The next run
+//! loads it and links to the recorded destination file instead of copying
+//! the content again, as long as that file still has the source's size and
+//! modification time (so it only helps together with `-t`/`-a`).
+//!
+//! Paths are stored relative to the destination root so the database stays
+//! valid if the destination is moved. The file is TOML:
+//!
+//! ```toml
+//! [inodes]
+//! "2049:1311" = "photos/2024/img_0001.jpg"
+//! ```
+//!
+//! Device IDs can change between boots for some filesystems (NFS, some FUSE
+//! mounts); a stale entry simply fails the size/mtime check and the file is
+//! copied as usual.
+
+use crate::error::{Result, SyncError};
+use serde::{Deserialize, Serialize};
+#[allow(clippy::disallowed_types)]
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// `"dev:ino"` keys mapped to paths; ordered so saved files are stable
+#[allow(clippy::disallowed_types)]
+type InodeTable = BTreeMap<String, String>;
+
+/// On-disk layout of the database
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DbFile {
+    /// `"dev:ino"` -> destination path relative to the root
+    #[serde(default)]
+    inodes: InodeTable,
+}
+
+/// Map of source `(dev, ino)` to the destination file holding its content
+#[derive(Debug, Default)]
+pub struct HardlinkDb {
+    /// File the database is loaded from and saved to
+    path: PathBuf,
+    /// Destination root that stored paths are relative to
+    root: PathBuf,
+    /// `(dev, ino)` -> relative destination path, sorted so saves are stable
+    #[allow(clippy::disallowed_types)]
+    entries: BTreeMap<(u64, u64), PathBuf>,
+}
+
+impl HardlinkDb {
+    /// Load the database at `path` for the destination `root`
+    ///
+    /// A missing file yields an empty database, which is the first run.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read or parsed.
+    #[cfg_attr(feature = "spans", tracing::instrument(
+        name = "hardlink_db.load",
+        skip_all,
+        fields(path = %path.display())
+    ))]
+    pub fn load(path: &Path, root: &Path) -> Result<Self> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => {
+                return Err(SyncError::InvalidConfig(format!(
+                    "Failed to read hardlink database {}: {e}",
+                    path.display()
+                )))
+            }
+        };
+        let mut db = Self::parse(&text, root)?;
+        db.path = path.to_path_buf();
+        Ok(db)
+    }
+
+    /// Parse database contents for the destination `root`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the text is not a valid database.
+    pub fn parse(text: &str, root: &Path) -> Result<Self> {
+        let file: DbFile = toml::from_str(text)
+            .map_err(|e| SyncError::InvalidConfig(format!("Invalid hardlink database: {e}")))?;
+        #[allow(clippy::disallowed_types)]
+        let mut entries = BTreeMap::new();
+        for (key, path) in file.inodes {
+            let parsed = key
+                .split_once(':')
+                .and_then(|(dev, ino)| Some((dev.parse().ok()?, ino.parse().ok()?)));
+            let Some(inode) = parsed else {
+                return Err(SyncError::InvalidConfig(format!(
+                    "Invalid hardlink database key {key:?} (expected \"dev:ino\")"
+                )));
+            };
+            entries.insert(inode, PathBuf::from(path));
+        }
+        Ok(Self {
+            path: PathBuf::new(),
+            root: root.to_path_buf(),
+            entries,
+        })
+    }
+
+    /// Destination file recorded for a source inode in an earlier run
+    #[must_use]
+    pub fn lookup(&self, dev: u64, ino: u64) -> Option<PathBuf> {
+        self.entries
+            .get(&(dev, ino))
+            .map(|relative| self.root.join(relative))
+    }
+
+    /// Record that `dst` holds the content of source inode `(dev, ino)`
+    ///
+    /// Paths outside the destination root or not valid UTF-8 are not stored.
+    pub fn record(&mut self, dev: u64, ino: u64, dst: &Path) {
+        if let Ok(relative) = dst.strip_prefix(&self.root) {
+            if relative.to_str().is_some() {
+                self.entries.insert((dev, ino), relative.to_path_buf());
+            }
+        }
+    }
+
+    /// Number of inodes in the database
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the database has no entries
+    #[allow(dead_code)]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Serialize the database to TOML
+    #[must_use]
+    pub fn encode(&self) -> String {
+        let file = DbFile {
+            inodes: self
+                .entries
+                .iter()
+                .filter_map(|((dev, ino), path)| {
+                    Some((format!("{dev}:{ino}"), path.to_str()?.to_string()))
+                })
+                .collect(),
+        };
+        toml::to_string(&file).unwrap_or_default()
+    }
+
+    /// Write the database back to the file it was loaded from
+    ///
+    /// The file is replaced atomically so an interrupted run never leaves a
+    /// truncated database behind.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    #[cfg_attr(feature = "spans", tracing::instrument(
+        name = "hardlink_db.save",
+        skip_all,
+        fields(path = %self.path.display(), entries = self.entries.len())
+    ))]
+    pub fn save(&self) -> Result<()> {
+        let mut partial = self.path.clone().into_os_string();
+        partial.push(".tmp");
+        let partial = PathBuf::from(partial);
+        std::fs::write(&partial, self.encode())
+            .and_then(|()| std::fs::rename(&partial, &self.path))
+            .map_err(|e| {
+                SyncError::FileSystem(format!(
+                    "Failed to write hardlink database {}: {e}",
+                    self.path.display()
+                ))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_round_trip_relative_to_root() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("dst");
+        let db_path = temp_dir.path().join("links.toml");
+
+        let mut db = HardlinkDb::load(&db_path, &root).unwrap();
+        assert!(db.is_empty());
+        db.record(2049, 1311, &root.join("a/b.txt"));
+        // Outside the destination: not recorded
+        db.record(2049, 7, Path::new("/elsewhere/c.txt"));
+        db.save().unwrap();
+
+        let text = std::fs::read_to_string(&db_path).unwrap();
+        assert!(text.contains("\"2049:1311\" = \"a/b.txt\""), "{text}");
+
+        let moved = temp_dir.path().join("moved");
+        let db = HardlinkDb::load(&db_path, &moved).unwrap();
+        assert_eq!(db.len(), 1);
+        assert_eq!(db.lookup(2049, 1311), Some(moved.join("a/b.txt")));
+        assert_eq!(db.lookup(2049, 7), None);
+    }
+
+    #[test]
+    fn test_rejects_malformed_keys() {
+        let root = Path::new("/dst");
+        assert!(HardlinkDb::parse("[inodes]\n\"12\" = \"x\"\n", root).is_err());
+        assert!(HardlinkDb::parse("[inodes]\n\"a:b\" = \"x\"\n", root).is_err());
+        assert!(HardlinkDb::parse("unknown = 1\n", root).is_err());
+        assert!(HardlinkDb::parse("", root).unwrap().is_empty());
+    }
+}
@@ -8,8 +8,14 @@
 //! - `x-pirate` - Pirate speak (arrr! 🏴‍☠️)
 //!
 //! The `x-` prefix indicates a private/experimental locale per BCP 47 standards.
+//!
+//! This module is also the catalog of user-visible messages. Every
+//! [`TranslationKey`] has a stable Fluent message ID (for example
+//! `error-read-only-filesystem`), and every [`crate::error::SyncError`] maps
+//! to one through [`crate::error::SyncError::message_id`], so front ends can
+//! attach their own help text to an ID instead of matching English strings.
 
-use fluent::{FluentBundle, FluentResource};
+use fluent::{FluentArgs, FluentBundle, FluentResource};
 use std::sync::{LazyLock, RwLock};
 use unic_langid::LanguageIdentifier;
 
@@ -62,6 +68,8 @@ fn create_bundle(locale: &str, ftl_string: &'static str) -> FluentBundle<FluentR
         FluentResource::try_new(ftl_string.to_string()).expect("Failed to parse fluent resource");
 
     let mut bundle = FluentBundle::new(vec![langid]);
+    // Messages end up in terminals and logs, not bidirectional text
+    bundle.set_use_isolating(false);
     bundle
         .add_resource(resource)
         .expect("Failed to add fluent resource");
@@ -149,6 +157,19 @@ pub enum TranslationKey {
     ErrorInvalidPath,
     ErrorSourceNotExists,
     ErrorDestinationExists,
+    ErrorIoUring,
+    ErrorCopyFailed,
+    ErrorMetadataFailed,
+    ErrorDirectoryTraversal,
+    ErrorInvalidConfig,
+    ErrorReadOnlyFilesystem,
+    ErrorFileSystem,
+    ErrorFdExhaustion,
+    ErrorInternal,
+
+    // Warning messages (take a `count` argument)
+    WarnOwnershipSkipped,
+    WarnCrtimesSkipped,
 
     // Info messages
     InfoStartingCopy,
@@ -202,7 +223,10 @@ pub enum TranslationKey {
 
 impl TranslationKey {
     /// Get the Fluent message ID for this key
-    const fn message_id(self) -> &'static str {
+    ///
+    /// IDs are stable across releases and locales.
+    #[must_use]
+    pub const fn message_id(self) -> &'static str {
         match self {
             // Progress messages
             Self::ProgressDiscovered => "progress-discovered",
@@ -230,6 +254,19 @@ impl TranslationKey {
             Self::ErrorInvalidPath => "error-invalid-path",
             Self::ErrorSourceNotExists => "error-source-not-exists",
             Self::ErrorDestinationExists => "error-destination-exists",
+            Self::ErrorIoUring => "error-io-uring",
+            Self::ErrorCopyFailed => "error-copy-failed",
+            Self::ErrorMetadataFailed => "error-metadata-failed",
+            Self::ErrorDirectoryTraversal => "error-directory-traversal",
+            Self::ErrorInvalidConfig => "error-invalid-config",
+            Self::ErrorReadOnlyFilesystem => "error-read-only-filesystem",
+            Self::ErrorFileSystem => "error-file-system",
+            Self::ErrorFdExhaustion => "error-fd-exhaustion",
+            Self::ErrorInternal => "error-internal",
+
+            // Warning messages
+            Self::WarnOwnershipSkipped => "warn-ownership-skipped",
+            Self::WarnCrtimesSkipped => "warn-crtimes-skipped",
 
             // Info messages
             Self::InfoStartingCopy => "info-starting-copy",
@@ -291,9 +328,34 @@ impl TranslationKey {
         Ok(self.translate(locale.as_str()))
     }
 
+    /// Get the translated string for this key in the current language, with
+    /// the given Fluent arguments
+    ///
+    /// # Errors
+    /// Returns `I18nError::LockPoisoned` if the locale lock is poisoned
+    pub fn get_with_args(self, args: &FluentArgs<'_>) -> Result<String, I18nError> {
+        let locale = CURRENT_LOCALE.read().map_err(|_| I18nError::LockPoisoned)?;
+        Ok(self.format(locale.as_str(), Some(args)))
+    }
+
+    /// Get the translated string for this key with a `count` argument, falling
+    /// back to the English text if the locale lock is poisoned
+    #[must_use]
+    pub fn with_count(self, count: u64) -> String {
+        let mut args = FluentArgs::new();
+        args.set("count", count);
+        self.get_with_args(&args)
+            .unwrap_or_else(|_| self.format(EN_US, Some(&args)))
+    }
+
     /// Get the translated string for this key in a specific language
     #[must_use]
     pub fn translate(self, locale: &str) -> String {
+        self.format(locale, None)
+    }
+
+    /// Format this key in `locale` with optional Fluent arguments
+    fn format(self, locale: &str, args: Option<&FluentArgs<'_>>) -> String {
         // Create bundle fresh each time (cheap - FTL strings are static)
         let ftl_string = get_ftl_for_locale(locale);
         let bundle = create_bundle(locale, ftl_string);
@@ -302,7 +364,7 @@ impl TranslationKey {
         if let Some(message) = bundle.get_message(msg_id) {
             if let Some(pattern) = message.value() {
                 let mut errors = vec![];
-                let value = bundle.format_pattern(pattern, None, &mut errors);
+                let value = bundle.format_pattern(pattern, args, &mut errors);
                 return value.to_string();
             }
         }
@@ -427,6 +489,17 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_count_argument_is_substituted() {
+        // Test: Summary warnings carry their count into every language
+        let mut args = FluentArgs::new();
+        args.set("count", 3);
+        let english = TranslationKey::WarnOwnershipSkipped.format(EN_US, Some(&args));
+        assert_eq!(english, "Ownership could not be preserved for 3 entries");
+        let pirate = TranslationKey::WarnOwnershipSkipped.format(EN_X_PIRATE, Some(&args));
+        assert!(pirate.contains(" 3 "), "{pirate}");
+    }
+
     #[test]
     fn test_locale_ids() {
         // Test: Locale IDs should follow BCP 47/ISO 639-2 standards
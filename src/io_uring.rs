@@ -29,10 +29,37 @@
 //! ```
 
 use crate::error::{Result, SyncError};
+use compio::driver::ProactorBuilder;
 use compio::io::{AsyncReadAt, AsyncWriteAtExt};
+use compio_fs_extended::probe::{capabilities, IoUringCapabilities};
 use std::path::Path;
 use tracing::debug;
 
+/// Ring setup for the main thread and each copy worker
+///
+/// Each ring holds `--queue-depth` submission entries and uses
+/// `COOP_TASKRUN` where the kernel supports it. `SINGLE_ISSUER` would suit
+/// these per-thread rings too, but compio's `ProactorBuilder` cannot set it
+/// (see [`compio_fs_extended::probe`]). `SQPOLL` is left off, which is why
+/// `--cpu-affinity` has no separate `sq_thread_cpu` setting.
+#[must_use]
+pub fn proactor_builder(queue_depth: usize) -> ProactorBuilder {
+    capabilities().proactor_builder(u32::try_from(queue_depth).unwrap_or(u32::MAX))
+}
+
+/// Startup warning when `caps` lacks opcodes arsync uses, `None` otherwise
+///
+/// Fallbacks keep the copy correct but cost a thread hop per operation (or
+/// disable a feature), which is worth knowing without `-v`.
+#[must_use]
+pub fn fallback_warning(caps: &IoUringCapabilities) -> Option<String> {
+    if caps.fallbacks().is_empty() {
+        None
+    } else {
+        Some(format!("io_uring fallbacks in use: {caps}"))
+    }
+}
+
 /// Basic file operations using async I/O
 ///
 /// This structure provides a high-level interface for performing file operations
@@ -130,7 +157,7 @@ impl FileOperations {
         }
 
         // Open source and destination files
-        let mut src_file = compio::fs::File::open(src).await.map_err(|e| {
+        let mut src_file = crate::atime::open_source(src).await.map_err(|e| {
             SyncError::FileSystem(format!(
                 "Failed to open source file {}: {}",
                 src.display(),
@@ -409,7 +436,7 @@ impl FileOperations {
         }
 
         // Open source and destination files
-        let mut src_file = compio::fs::File::open(src).await.map_err(|e| {
+        let mut src_file = crate::atime::open_source(src).await.map_err(|e| {
             SyncError::FileSystem(format!(
                 "Failed to open source file {}: {}",
                 src.display(),
@@ -618,8 +645,25 @@ mod tests {
     #![allow(clippy::expect_used)]
 
     use super::*;
+    use compio_fs_extended::probe::KNOWN_OPCODES;
     use tempfile::TempDir;
 
+    #[test]
+    fn test_fallback_warning() {
+        let all: Vec<u8> = KNOWN_OPCODES.iter().map(|(code, _, _)| *code).collect();
+        assert_eq!(
+            fallback_warning(&IoUringCapabilities::with(&all, &[])),
+            None
+        );
+
+        let warning = fallback_warning(&IoUringCapabilities::with(&all[1..], &[]))
+            .expect("a missing opcode should warn");
+        assert!(warning.contains(KNOWN_OPCODES[0].1), "{warning}");
+
+        let none = IoUringCapabilities::none("ENOSYS");
+        assert!(fallback_warning(&none).is_some_and(|w| w.contains("unavailable")));
+    }
+
     #[compio::test]
     async fn test_file_operations_basic() -> Result<()> {
         let temp_dir = TempDir::new().map_err(|e| {
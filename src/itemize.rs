@@ -0,0 +1,244 @@
+//! `--itemize-changes` (`-i`) output
+//!
+//! For every item that is created, written or linked, prints rsync's change
+//! string followed by the path relative to the destination, e.g.
+//! `>f.st...... notes.txt`. The string is `YXcstpoguax`:
+//!
+//! - `Y` is the update type: `>` content written, `c` created locally
+//!   (directory, symlink, special file), `h` hardlinked
+//! - `X` is the file type: `f` file, `d` directory, `L` symlink, `D` device,
+//!   `S` FIFO or socket
+//! - the remaining letters are the attributes that differ from what was at
+//!   the destination before: `s` size, `t` modification time (`T` when it is
+//!   not preserved and becomes the transfer time), `p` permissions,
+//!   `o` owner, `g` group. `c`, `u`, `a` and `x` are not compared and stay `.`
+//!
+//! A newly created item shows `+` for every attribute. Since arsync writes
+//! every file it visits, regular files always show `>`; the attribute
+//! letters tell whether anything actually changed.
+
+use crate::cli::Args;
+use crate::log_file::{LogEntry, Operation};
+use std::fs::Metadata;
+use std::io::Write;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::Path;
+
+/// How an item reached the destination (the `Y` column)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Update {
+    /// Content was written (`>`)
+    Transferred,
+    /// Created without transferring content (`c`)
+    Created,
+    /// Hardlinked to another destination file (`h`)
+    Linked,
+}
+
+impl Update {
+    const fn symbol(self) -> char {
+        match self {
+            Self::Transferred => '>',
+            Self::Created => 'c',
+            Self::Linked => 'h',
+        }
+    }
+}
+
+/// File type letter (the `X` column)
+fn type_symbol(metadata: &Metadata) -> char {
+    let file_type = metadata.file_type();
+    if file_type.is_dir() {
+        'd'
+    } else if file_type.is_symlink() {
+        'L'
+    } else if file_type.is_block_device() || file_type.is_char_device() {
+        'D'
+    } else if file_type.is_fifo() || file_type.is_socket() {
+        'S'
+    } else {
+        'f'
+    }
+}
+
+/// rsync change string for `source` replacing `before` at the destination
+#[must_use]
+pub fn change_string(
+    update: Update,
+    before: Option<&Metadata>,
+    source: &Metadata,
+    args: &Args,
+) -> String {
+    let mut code = String::with_capacity(11);
+    code.push(update.symbol());
+    code.push(type_symbol(source));
+    let Some(before) = before else {
+        code.push_str("+++++++++");
+        return code;
+    };
+    let flag = |changed: bool, letter: char| if changed { letter } else { '.' };
+    let mtime_changed =
+        (before.mtime(), before.mtime_nsec()) != (source.mtime(), source.mtime_nsec());
+    code.push('.');
+    code.push(flag(before.len() != source.len() && !source.is_dir(), 's'));
+    code.push(
+        if !args.should_preserve_timestamps() && update == Update::Transferred {
+            'T'
+        } else {
+            flag(args.should_preserve_timestamps() && mtime_changed, 't')
+        },
+    );
+    code.push(flag(
+        args.should_preserve_permissions() && before.mode() & 0o7777 != source.mode() & 0o7777,
+        'p',
+    ));
+    code.push(flag(
+        args.should_preserve_owner() && before.uid() != source.uid(),
+        'o',
+    ));
+    code.push(flag(
+        args.should_preserve_group() && before.gid() != source.gid(),
+        'g',
+    ));
+    code.push_str("...");
+    code
+}
+
+/// What is at `dst` before it is changed, if changes are being reported
+///
+/// Returns `None` when neither `--itemize-changes` nor `--log-file` is in
+/// use, and `Some(None)` when nothing is at the destination yet.
+#[must_use]
+pub fn before(dst: &Path, args: &Args) -> Option<Option<Metadata>> {
+    (args.itemize_changes || crate::log_file::is_open())
+        .then(|| std::fs::symlink_metadata(dst).ok())
+}
+
+/// Path of `dst` as shown to the user: relative to the destination, with a
+/// trailing `/` for directories
+fn display_name(dst: &Path, is_dir: bool, args: &Args) -> String {
+    let name = dst.strip_prefix(&args.destination).unwrap_or(dst);
+    let name = if name.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        name
+    };
+    let slash = if is_dir { "/" } else { "" };
+    format!("{}{slash}", name.display())
+}
+
+/// Report the change for `src` -> `dst` on stdout (`-i`) and in the log file
+///
+/// `before` is the value returned by [`before`] ahead of the change.
+pub fn print(
+    update: Update,
+    before: Option<Option<Metadata>>,
+    src: &Path,
+    dst: &Path,
+    args: &Args,
+) {
+    let Some(before) = before else {
+        return;
+    };
+    let Ok(source) = std::fs::symlink_metadata(src) else {
+        return;
+    };
+    let code = change_string(update, before.as_ref(), &source, args);
+    let name = display_name(dst, source.is_dir(), args);
+    if args.itemize_changes {
+        let mut stdout = std::io::stdout().lock();
+        let _ = writeln!(stdout, "{code} {name}");
+    }
+    let operation = match update {
+        Update::Transferred => Operation::Copy,
+        Update::Created => Operation::Create,
+        Update::Linked => Operation::Link,
+    };
+    crate::log_file::record(&LogEntry {
+        operation,
+        changes: &code,
+        name: &name,
+        path: dst,
+        link_target: source
+            .is_symlink()
+            .then(|| std::fs::read_link(dst).ok())
+            .flatten(),
+        length: source.len(),
+        bytes: if update == Update::Transferred {
+            source.len()
+        } else {
+            0
+        },
+    });
+}
+
+/// Record in the log file that `src` was left alone
+pub fn skipped(src: &Path, dst: &Path, args: &Args) {
+    if !crate::log_file::is_open() {
+        return;
+    }
+    let Ok(source) = std::fs::symlink_metadata(src) else {
+        return;
+    };
+    // rsync shows unchanged attributes as blanks
+    let code = format!(".{}         ", type_symbol(&source));
+    let name = display_name(dst, source.is_dir(), args);
+    crate::log_file::record(&LogEntry {
+        operation: Operation::Skip,
+        changes: &code,
+        name: &name,
+        path: dst,
+        link_target: None,
+        length: source.len(),
+        bytes: 0,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_change_string() {
+        let temp_dir = TempDir::new().unwrap();
+        let src = temp_dir.path().join("src");
+        let dst = temp_dir.path().join("dst");
+        std::fs::write(&src, "new content").unwrap();
+        std::fs::write(&dst, "old").unwrap();
+        std::fs::set_permissions(&dst, std::fs::Permissions::from_mode(0o600)).unwrap();
+        std::fs::set_permissions(&src, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let source = std::fs::metadata(&src).unwrap();
+        let existing = std::fs::metadata(&dst).unwrap();
+        let dir = std::fs::metadata(temp_dir.path()).unwrap();
+
+        let mut args = Args::default();
+        assert_eq!(
+            change_string(Update::Transferred, None, &source, &args),
+            ">f+++++++++"
+        );
+        assert_eq!(
+            change_string(Update::Created, None, &dir, &args),
+            "cd+++++++++"
+        );
+        assert_eq!(
+            change_string(Update::Transferred, Some(&existing), &source, &args),
+            ">f.sT......"
+        );
+        args.perms = true;
+        assert_eq!(
+            change_string(Update::Transferred, Some(&source), &source, &args),
+            ">f..T......"
+        );
+        assert_eq!(
+            change_string(Update::Transferred, Some(&existing), &source, &args),
+            ">f.sTp....."
+        );
+        args.archive = true;
+        assert_eq!(
+            change_string(Update::Linked, Some(&source), &source, &args),
+            "hf........."
+        );
+    }
+}
@@ -46,14 +46,38 @@
 //! ```
 
 pub mod adaptive_concurrency;
+pub mod affinity;
+pub mod atime;
+pub mod budget;
+pub mod chunking;
 pub mod cli;
 pub mod copy;
+pub mod crtime;
+pub mod dedupe;
+pub mod delay_updates;
 pub mod directory;
 pub mod error;
+pub mod events;
+pub mod fake_super;
+#[cfg(feature = "fault-injection")]
+pub mod fault;
+pub mod filter;
+pub mod fs_profile;
+pub mod hardlink_db;
 pub mod i18n;
 pub mod io_uring;
+pub mod itemize;
+pub mod log_file;
+pub mod memlock;
+#[cfg(feature = "spans")]
+pub mod otel;
+pub mod ownership;
+pub mod privileges;
 pub mod progress;
+pub mod security;
 pub mod sync;
+pub mod systemd;
+pub mod watch;
 
 // Re-export commonly used types
 pub use directory::FilesystemTracker;
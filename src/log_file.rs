@@ -0,0 +1,232 @@
+//! `--log-file`: a per-item record of what the run did
+//!
+//! Independent of the tracing output on stderr, every item that is copied,
+//! linked, created or skipped is appended to the log file as one line:
+//! `YYYY/MM/DD HH:MM:SS [pid] ` followed by `--log-file-format` with these
+//! escapes expanded:
+//!
+//! | Escape | Meaning                                                        |
+//! |--------|----------------------------------------------------------------|
+//! | `%o`   | Operation: `copy`, `link`, `create` or `skip`                  |
+//! | `%i`   | Itemized change string, as printed by `--itemize-changes`      |
+//! | `%n`   | Path relative to the destination (directories end in `/`)      |
+//! | `%f`   | Full destination path                                          |
+//! | `%L`   | ` -> TARGET` for symlinks, empty otherwise                     |
+//! | `%l`   | Length of the file in bytes                                    |
+//! | `%b`   | Bytes of content written                                       |
+//! | `%t`   | Local date and time                                            |
+//! | `%p`   | Process ID                                                     |
+//! | `%%`   | A literal `%`                                                  |
+//!
+//! The default format, `%i %n%L`, matches rsync's.
+
+use crate::cli::Args;
+use crate::error::{Result, SyncError};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The open log file and its line format
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    format: String,
+}
+
+/// Log file for the current run, if `--log-file` was given
+static LOG: Mutex<Option<LogFile>> = Mutex::new(None);
+
+/// What happened to an item (`%o`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// Content was written
+    Copy,
+    /// Hardlinked to an existing destination file
+    Link,
+    /// Directory, symlink or special file created
+    Create,
+    /// Left alone
+    Skip,
+}
+
+impl Operation {
+    /// Name written for `%o`
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Copy => "copy",
+            Self::Link => "link",
+            Self::Create => "create",
+            Self::Skip => "skip",
+        }
+    }
+}
+
+/// One logged item
+#[derive(Debug, Clone)]
+pub struct LogEntry<'a> {
+    /// What happened
+    pub operation: Operation,
+    /// Itemized change string
+    pub changes: &'a str,
+    /// Path relative to the destination
+    pub name: &'a str,
+    /// Full destination path
+    pub path: &'a Path,
+    /// Symlink target, for symlinks
+    pub link_target: Option<PathBuf>,
+    /// File length
+    pub length: u64,
+    /// Bytes of content written
+    pub bytes: u64,
+}
+
+/// Open the log file named by `--log-file` (appending), if any
+///
+/// A log already open for the same path is kept, so a file opened before
+/// `--drop-privileges` stays usable by later syncs.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be opened.
+pub fn open(args: &Args) -> Result<()> {
+    let log = match &args.log_file {
+        Some(path)
+            if LOG
+                .lock()
+                .is_ok_and(|guard| guard.as_ref().is_some_and(|log| &log.path == path)) =>
+        {
+            return Ok(());
+        }
+        Some(path) => {
+            let file = File::options()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| {
+                    SyncError::FileSystem(format!(
+                        "Failed to open log file {}: {e}",
+                        path.display()
+                    ))
+                })?;
+            Some(LogFile {
+                path: path.clone(),
+                file,
+                format: args.log_file_format.clone(),
+            })
+        }
+        None => None,
+    };
+    if let Ok(mut guard) = LOG.lock() {
+        *guard = log;
+    }
+    Ok(())
+}
+
+/// Whether a log file is open
+#[must_use]
+pub fn is_open() -> bool {
+    LOG.lock().is_ok_and(|guard| guard.is_some())
+}
+
+/// Append `entry` to the log file, if one is open
+pub fn record(entry: &LogEntry<'_>) {
+    let Ok(mut guard) = LOG.lock() else {
+        return;
+    };
+    let Some(log) = guard.as_mut() else {
+        return;
+    };
+    let now = local_time();
+    let pid = std::process::id();
+    let line = render(&log.format, entry, &now, pid);
+    // A failing log file must not fail the copy
+    let _ = writeln!(log.file, "{now} [{pid}] {line}");
+}
+
+/// Expand the `%` escapes of `format` for `entry`
+#[must_use]
+pub fn render(format: &str, entry: &LogEntry<'_>, now: &str, pid: u32) -> String {
+    let mut line = String::with_capacity(format.len() + entry.name.len());
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            line.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('o') => line.push_str(entry.operation.name()),
+            Some('i') => line.push_str(entry.changes),
+            Some('n') => line.push_str(entry.name),
+            Some('f') => line.push_str(&entry.path.to_string_lossy()),
+            Some('L') => {
+                if let Some(target) = &entry.link_target {
+                    line.push_str(" -> ");
+                    line.push_str(&target.to_string_lossy());
+                }
+            }
+            Some('l') => line.push_str(&entry.length.to_string()),
+            Some('b') => line.push_str(&entry.bytes.to_string()),
+            Some('t') => line.push_str(now),
+            Some('p') => line.push_str(&pid.to_string()),
+            Some('%') => line.push('%'),
+            // Unknown escapes are kept as written
+            Some(other) => {
+                line.push('%');
+                line.push(other);
+            }
+            None => line.push('%'),
+        }
+    }
+    line
+}
+
+/// Current local time as `YYYY/MM/DD HH:MM:SS`
+fn local_time() -> String {
+    // SAFETY: time(NULL) has no preconditions
+    let now = unsafe { libc::time(std::ptr::null_mut()) };
+    // SAFETY: tm is plain old data
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    // SAFETY: both pointers are valid for the duration of the call
+    if unsafe { libc::localtime_r(&raw const now, &raw mut tm) }.is_null() {
+        return String::from("0000/00/00 00:00:00");
+    }
+    format!(
+        "{:04}/{:02}/{:02} {:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_escapes() {
+        let entry = LogEntry {
+            operation: Operation::Create,
+            changes: "cL+++++++++",
+            name: "sub/link",
+            path: Path::new("/backup/sub/link"),
+            link_target: Some(PathBuf::from("../file")),
+            length: 7,
+            bytes: 0,
+        };
+        let now = "2024/01/02 03:04:05";
+        assert_eq!(
+            render("%i %n%L", &entry, now, 42),
+            "cL+++++++++ sub/link -> ../file"
+        );
+        assert_eq!(
+            render("%t %o %f %l/%b [%p] 100%% %q", &entry, now, 42),
+            "2024/01/02 03:04:05 create /backup/sub/link 7/0 [42] 100% %q"
+        );
+        assert_eq!(render("trailing %", &entry, now, 42), "trailing %");
+    }
+}
@@ -4,53 +4,140 @@
 //! using `io_uring` for maximum performance and parallelism.
 
 use anyhow::{Context, Result};
-use clap::Parser;
-use tracing::{info, Level};
+use clap::{CommandFactory, FromArgMatches};
+use tracing::{info, warn, Level};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::{Layer, Registry};
 
 mod adaptive_concurrency;
+mod affinity;
+mod atime;
+mod budget;
+mod chunking;
 mod cli;
 mod copy;
+mod crtime;
+mod dedupe;
+mod delay_updates;
 mod directory;
 mod error;
+mod events;
+mod fake_super;
+#[cfg(feature = "fault-injection")]
+mod fault;
+mod filter;
+mod fs_profile;
+mod hardlink_db;
 mod i18n;
 mod io_uring;
+mod itemize;
+mod log_file;
+mod memlock;
+#[cfg(feature = "spans")]
+mod otel;
+mod ownership;
+mod privileges;
 mod progress;
+mod security;
 mod sync;
+mod systemd;
+mod watch;
 
 use cli::Args;
 use i18n::{set_language, Language, TranslationKey};
 
-#[compio::main]
-async fn main() -> Result<()> {
+fn main() -> Result<()> {
     // Parse command line arguments
-    let args = Args::parse();
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    args.load_filter(&matches)?;
 
+    // Before any thread starts, so the watch thread alone receives them
+    if args.watch {
+        watch::block_stop_signals()?;
+    }
+
+    // Also before any thread starts: capabilities are per thread. The log
+    // file may be somewhere only root can write
+    if let Some(user) = &args.drop_privileges {
+        log_file::open(&args)?;
+        privileges::drop_to(user)?;
+    }
+
+    // The ring is sized by --queue-depth and the locked-memory limit; an
+    // invalid depth is reported by validation below, on a default ring
+    let plan = memlock::init(&args);
+    let (runtime, ring_error) = match compio::runtime::RuntimeBuilder::new()
+        .with_proactor(io_uring::proactor_builder(plan.queue_depth))
+        .build()
+    {
+        Ok(runtime) => (runtime, None),
+        Err(e) => (
+            compio::runtime::Runtime::new().context("Failed to set up io_uring")?,
+            Some(e),
+        ),
+    };
+    let result = runtime.block_on(run(args, ring_error));
+    #[cfg(feature = "spans")]
+    otel::shutdown();
+    result
+}
+
+/// Exit with `code`, after sending any spans still queued for export
+fn exit(code: i32) -> ! {
+    #[cfg(feature = "spans")]
+    otel::shutdown();
+    std::process::exit(code)
+}
+
+/// Run arsync with parsed arguments on the main thread's ring
+///
+/// `ring_error` is why the configured ring could not be set up, if the
+/// main thread fell back to a default one.
+async fn run(args: Args, ring_error: Option<std::io::Error>) -> Result<()> {
     // Set language based on --pirate flag
     if args.pirate {
         set_language(Language::Pirate);
     }
 
     // Initialize logging based on verbosity and quiet mode
-    if args.quiet {
+    let max_level = if args.quiet {
         // In quiet mode, only log errors
-        let subscriber = tracing_subscriber::fmt()
-            .with_max_level(Level::ERROR)
-            .with_target(false)
-            .finish();
-        tracing::subscriber::set_global_default(subscriber)?;
+        Level::ERROR
     } else {
-        let subscriber = tracing_subscriber::fmt()
-            .with_max_level(match args.verbose {
-                0 => Level::WARN,
-                1 => Level::INFO,
-                2 => Level::DEBUG,
-                _ => Level::TRACE,
-            })
-            .with_target(false)
-            .with_thread_ids(false)
-            .with_thread_names(false)
-            .finish();
-        tracing::subscriber::set_global_default(subscriber)?;
+        match args.verbose {
+            0 => Level::WARN,
+            1 => Level::INFO,
+            2 => Level::DEBUG,
+            _ => Level::TRACE,
+        }
+    };
+    let log = tracing_subscriber::fmt::layer()
+        // stdout belongs to --json events and --itemize-changes
+        .with_writer(std::io::stderr)
+        .with_target(false)
+        .with_thread_ids(false)
+        .with_thread_names(false);
+    // With span instrumentation, report how long each span was busy and idle
+    #[cfg(feature = "spans")]
+    let log = log.with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE);
+    // Timestamps would make --deterministic logs differ between runs
+    let log: Box<dyn Layer<Registry> + Send + Sync> = if args.deterministic {
+        Box::new(log.without_time())
+    } else {
+        Box::new(log)
+    };
+    let subscriber =
+        tracing_subscriber::registry().with(log.with_filter(LevelFilter::from(max_level)));
+    // Spans are exported to a configured OpenTelemetry collector whatever the
+    // verbosity on stderr
+    #[cfg(feature = "spans")]
+    let subscriber = subscriber.with(otel::layer()?);
+    tracing::subscriber::set_global_default(subscriber)?;
+
+    if let Some(e) = ring_error {
+        warn!("Failed to set up the configured io_uring ({e}); using a default ring");
     }
 
     // Log startup information (unless in quiet mode)
@@ -81,16 +168,62 @@ async fn main() -> Result<()> {
         info!("CPU count: {}", args.effective_cpu_count());
         info!("Buffer size: {} KB", args.buffer_size_kb);
         info!("Max files in flight: {}", args.max_files_in_flight);
+        let caps = compio_fs_extended::probe::capabilities();
+        match io_uring::fallback_warning(caps) {
+            Some(warning) => warn!("{warning}"),
+            None => info!("io_uring: {caps}"),
+        }
+        info!("io_uring sizing: {}", memlock::init(&args));
+        if let Some(user) = &args.drop_privileges {
+            info!("Running as {user} with CAP_CHOWN and CAP_FOWNER");
+        }
     }
 
     // Validate arguments
     args.validate().context("Invalid arguments")?;
 
+    // Signal readiness to systemd (no-op outside of a notify service)
+    let service = systemd::ServiceNotifier::start();
+
+    if args.json {
+        events::enable();
+    }
+
+    // Live progress on stderr for --progress
+    let progress = if args.progress {
+        progress::ProgressDisplay::start()
+            .map_err(|e| warn!("Progress display unavailable: {e}"))
+            .ok()
+    } else {
+        None
+    };
+
+    // Watch before the initial sync so nothing changed during it is missed
+    let watcher = if args.watch {
+        Some(watch::Watcher::start(&args)?)
+    } else {
+        None
+    };
+
     // Perform the sync operation
     let result = sync::sync_files(&args).await;
+    if let Some(progress) = progress {
+        progress.finish();
+    }
 
     match result {
         Ok(stats) => {
+            events::emit(|| {
+                events::Event::summary(stats.files_copied, stats.bytes_copied, stats.duration)
+            });
+            if args.stats {
+                // Keep stdout a pure event stream under --json
+                if args.json {
+                    eprintln!("{}", stats.report());
+                } else {
+                    println!("{}", stats.report());
+                }
+            }
             info!(
                 "{}",
                 TranslationKey::StatusComplete
@@ -117,17 +250,45 @@ async fn main() -> Result<()> {
                     .unwrap_or_else(|_| "Completed".to_string()),
                 stats.bytes_copied
             );
-            info!("Duration: {:?}", stats.duration);
+            if !args.deterministic {
+                info!("Duration: {:?}", stats.duration);
+            }
+            if watch::stop_requested() {
+                info!("Stop requested during the initial sync");
+                service.finish("Stopped during the initial sync");
+                return Ok(());
+            }
+            if let Some(watcher) = watcher {
+                info!("Watching {} for changes", args.source.display());
+                if let Err(e) = watcher.run(&args).await {
+                    service.finish(&format!("Failed: {e}"));
+                    eprintln!(
+                        "{}: {}",
+                        TranslationKey::StatusFailed
+                            .get()
+                            .unwrap_or_else(|_| "Failed".to_string()),
+                        e.localized()
+                    );
+                    exit(e.exit_code());
+                }
+            }
+            service.finish(&format!(
+                "Complete: {} files, {} bytes in {:?}",
+                stats.files_copied, stats.bytes_copied, stats.duration
+            ));
             Ok(())
         }
         Err(e) => {
+            events::emit(|| events::Event::fatal(&e));
+            service.finish(&format!("Failed: {e}"));
             eprintln!(
-                "{}: {e}",
+                "{}: {}",
                 TranslationKey::StatusFailed
                     .get()
-                    .unwrap_or_else(|_| "Failed".to_string())
+                    .unwrap_or_else(|_| "Failed".to_string()),
+                e.localized()
             );
-            std::process::exit(1);
+            exit(e.exit_code());
         }
     }
 }
@@ -0,0 +1,241 @@
+//! Fitting the rings and registered buffers into `RLIMIT_MEMLOCK`
+//!
+//! Every thread with a ring (the main thread and each dispatcher worker)
+//! also registers a pool of copy buffers, and registered buffers are charged
+//! to the locked-memory limit; before Linux 5.12 so is the ring itself. With
+//! the default limit of 8 MiB a full pool per CPU does not fit, and ring
+//! setup or registration fails.
+//!
+//! At startup the soft limit is raised to the hard limit. If the rings and
+//! pools still do not fit, each thread's share of the limit goes to its ring
+//! first, halving the queue depth until the ring fits, and the rest to as
+//! many registered buffers as fit; threads without any copy through ordinary
+//! buffers. Holders of `CAP_IPC_LOCK` are not limited.
+
+use crate::affinity;
+use crate::cli::Args;
+use std::fmt;
+use std::sync::OnceLock;
+
+/// Registered copy buffers per thread when the limit allows
+pub const POOL_BUFFERS: usize = 64;
+
+/// Size of each registered copy buffer
+///
+/// Chunks larger than this do not use the pool: they are read and written
+/// through a freshly allocated buffer with plain `READ`/`WRITE`. Adaptive
+/// chunk sizing grows well past 64 KiB on fast devices, so most of a large
+/// file's data bypasses the pool; it mainly serves small files and
+/// `--buffer-size-kb` of 64 or less.
+pub const POOL_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Smallest queue depth the limit may shrink a ring to
+const MIN_QUEUE_DEPTH: usize = 64;
+
+/// `CAP_IPC_LOCK`, which exempts a process from the limit
+const CAP_IPC_LOCK: u32 = 14;
+
+/// Ring and buffer sizes that fit the locked-memory limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemlockPlan {
+    /// Soft limit in bytes, `None` if unlimited
+    pub limit: Option<u64>,
+    /// Soft limit before it was raised, if it was
+    pub raised_from: Option<u64>,
+    /// Submission entries per ring
+    pub queue_depth: usize,
+    /// Registered copy buffers per thread
+    pub pool_buffers: usize,
+}
+
+impl MemlockPlan {
+    /// Sizes for `threads` rings of `queue_depth` entries under `limit`
+    ///
+    /// `queue_depth` is first clamped to what a ring can hold
+    /// ([`ring_entries`](compio_fs_extended::probe::ring_entries)).
+    /// `locked_rings` says whether the rings themselves are charged to the
+    /// limit (see
+    /// [`IoUringCapabilities::rings_count_against_memlock`](compio_fs_extended::probe::IoUringCapabilities::rings_count_against_memlock)).
+    #[must_use]
+    pub fn fit(limit: Option<u64>, threads: usize, queue_depth: usize, locked_rings: bool) -> Self {
+        let requested = u32::try_from(queue_depth).unwrap_or(u32::MAX);
+        let queue_depth = compio_fs_extended::probe::ring_entries(requested) as usize;
+        let unlimited = Self {
+            limit,
+            raised_from: None,
+            queue_depth,
+            pool_buffers: POOL_BUFFERS,
+        };
+        let Some(limit) = limit else {
+            return unlimited;
+        };
+        let share = limit / u64::try_from(threads.max(1)).unwrap_or(u64::MAX);
+
+        let mut queue_depth = queue_depth;
+        let mut ring = 0;
+        if locked_rings {
+            while ring_bytes(queue_depth) > share && queue_depth > MIN_QUEUE_DEPTH {
+                queue_depth = (queue_depth / 2).max(MIN_QUEUE_DEPTH);
+            }
+            ring = ring_bytes(queue_depth);
+        }
+        let buffers = share.saturating_sub(ring) / POOL_BUFFER_SIZE as u64;
+        Self {
+            queue_depth,
+            pool_buffers: usize::try_from(buffers).map_or(POOL_BUFFERS, |n| n.min(POOL_BUFFERS)),
+            ..unlimited
+        }
+    }
+}
+
+impl fmt::Display for MemlockPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.limit {
+            Some(limit) => write!(f, "locked memory limit {} KiB", limit / 1024)?,
+            None => write!(f, "locked memory unlimited")?,
+        }
+        if let Some(from) = self.raised_from {
+            write!(f, " (raised from {} KiB)", from / 1024)?;
+        }
+        write!(
+            f,
+            ": queue depth {}, {} registered {} KiB buffers per thread",
+            self.queue_depth,
+            self.pool_buffers,
+            POOL_BUFFER_SIZE / 1024
+        )
+    }
+}
+
+/// Locked memory taken by a ring of `entries` submission entries
+///
+/// The kernel rounds the entry count up to a power of two and gives the
+/// completion queue twice as many entries; each region is whole pages.
+#[must_use]
+pub fn ring_bytes(entries: usize) -> u64 {
+    /// Page granularity of ring allocations
+    const PAGE: u64 = 4096;
+    let round = |bytes: u64| bytes.div_ceil(PAGE) * PAGE;
+    let entries = u64::try_from(entries.next_power_of_two()).unwrap_or(u64::MAX);
+    // SQEs, then the SQ index array and the CQEs that share the rings region
+    round(entries.saturating_mul(64)) + round(entries.saturating_mul(4 + 2 * 16))
+}
+
+/// Whether this process holds `CAP_IPC_LOCK`
+fn has_ipc_lock() -> bool {
+    std::fs::read_to_string("/proc/self/status").is_ok_and(|status| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix("CapEff:"))
+            .and_then(|caps| u64::from_str_radix(caps.trim(), 16).ok())
+            .is_some_and(|caps| caps & (1 << CAP_IPC_LOCK) != 0)
+    })
+}
+
+/// Raise the soft `RLIMIT_MEMLOCK` to the hard limit
+///
+/// Returns the soft limit now in effect (`None` if unlimited) and what it was
+/// before, if it changed.
+fn raise_limit() -> (Option<u64>, Option<u64>) {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: limit is a valid rlimit to fill in
+    if unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, &raw mut limit) } != 0 {
+        return (None, None);
+    }
+    let bytes = |value: libc::rlim_t| (value != libc::RLIM_INFINITY).then_some(value);
+    if limit.rlim_cur >= limit.rlim_max {
+        return (bytes(limit.rlim_cur), None);
+    }
+    let raised = libc::rlimit {
+        rlim_cur: limit.rlim_max,
+        rlim_max: limit.rlim_max,
+    };
+    // SAFETY: raised is a valid rlimit
+    if unsafe { libc::setrlimit(libc::RLIMIT_MEMLOCK, &raw const raised) } != 0 {
+        return (bytes(limit.rlim_cur), None);
+    }
+    (bytes(raised.rlim_cur), bytes(limit.rlim_cur))
+}
+
+/// Plan for this run, made on first use
+static PLAN: OnceLock<MemlockPlan> = OnceLock::new();
+
+/// Raise the limit and size the rings and buffers for this run
+///
+/// Later calls return the first plan.
+pub fn init(args: &Args) -> MemlockPlan {
+    *PLAN.get_or_init(|| {
+        let (limit, raised_from) = raise_limit();
+        let limit = limit.filter(|_| !has_ipc_lock());
+        // The main thread's ring plus one per dispatcher worker
+        let workers = affinity::cpus(args)
+            .ok()
+            .flatten()
+            .map_or_else(num_cpus::get, |cpus| cpus.len());
+        let locked_rings = compio_fs_extended::probe::capabilities().rings_count_against_memlock();
+        MemlockPlan {
+            raised_from,
+            ..MemlockPlan::fit(limit, workers + 1, args.queue_depth, locked_rings)
+        }
+    })
+}
+
+/// Registered copy buffers each thread may set up
+#[must_use]
+pub fn pool_buffers() -> usize {
+    PLAN.get().map_or(POOL_BUFFERS, |plan| plan.pool_buffers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_bytes() {
+        // 4096 SQEs, then 4096 indexes and 8192 CQEs
+        assert_eq!(ring_bytes(4096), 256 * 1024 + 144 * 1024);
+        // Entry counts are rounded up to a power of two
+        assert_eq!(ring_bytes(3000), ring_bytes(4096));
+    }
+
+    #[test]
+    fn test_fit_unlimited() {
+        let plan = MemlockPlan::fit(None, 9, 4096, true);
+        assert_eq!(plan.queue_depth, 4096);
+        assert_eq!(plan.pool_buffers, POOL_BUFFERS);
+
+        // Deeper than a ring can be: the plan shows the depth actually used
+        let plan = MemlockPlan::fit(None, 9, 65_536, true);
+        assert_eq!(plan.queue_depth, 32_768);
+        assert!(plan.to_string().contains("queue depth 32768"));
+    }
+
+    #[test]
+    fn test_fit_shares_limit_between_threads() {
+        // 8 MiB over 4 threads leaves 2 MiB each: 32 buffers
+        let plan = MemlockPlan::fit(Some(8 << 20), 4, 4096, false);
+        assert_eq!(plan.queue_depth, 4096);
+        assert_eq!(plan.pool_buffers, 32);
+
+        // Charged rings come out of the share first
+        let plan = MemlockPlan::fit(Some(8 << 20), 4, 4096, true);
+        assert_eq!(plan.queue_depth, 4096);
+        assert_eq!(
+            plan.pool_buffers,
+            usize::try_from(((2 << 20) - ring_bytes(4096)) / POOL_BUFFER_SIZE as u64).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_fit_shrinks_rings() {
+        // 64 KiB each cannot hold a 4096-entry ring
+        let plan = MemlockPlan::fit(Some(64 * 1024 * 16), 16, 4096, true);
+        assert!(ring_bytes(plan.queue_depth) <= 64 * 1024);
+        assert!(plan.queue_depth >= MIN_QUEUE_DEPTH);
+        assert_eq!(plan.pool_buffers, 0);
+        assert!(plan.to_string().contains("0 registered 64 KiB buffers"));
+    }
+}
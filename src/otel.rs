@@ -0,0 +1,127 @@
+//! OpenTelemetry export of the `spans` feature's spans
+//!
+//! When `OTEL_EXPORTER_OTLP_ENDPOINT` (or
+//! `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT`) is set, the CLI adds a
+//! `tracing-opentelemetry` layer that sends every span (the run, its phases,
+//! each entry and file copy) to that collector over OTLP/HTTP with protobuf
+//! encoding, along with the events inside them at `info` and above. The
+//! exporter and resource read the standard `OTEL_*` variables
+//! (`OTEL_EXPORTER_OTLP_HEADERS`, `OTEL_RESOURCE_ATTRIBUTES`, ...); the
+//! service name is `arsync` unless they set one.
+//!
+//! Spans are batched and sent from a background thread, so the copy never
+//! waits on the collector. [`shutdown`] sends what is still queued and must
+//! run before the process exits.
+
+use crate::error::{Result, SyncError};
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use std::sync::OnceLock;
+use tracing::{Level, Subscriber};
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Variables that name a collector; export is off without one
+const ENDPOINT_VARS: [&str; 2] = [
+    "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+    "OTEL_EXPORTER_OTLP_ENDPOINT",
+];
+
+/// Service name reported when the environment does not set one
+const SERVICE_NAME: &str = "arsync";
+
+/// Provider behind the installed layer, kept for [`shutdown`]
+static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+/// Whether the environment names a collector to export to
+#[must_use]
+pub fn is_configured() -> bool {
+    ENDPOINT_VARS
+        .iter()
+        .any(|var| std::env::var_os(var).is_some_and(|value| !value.is_empty()))
+}
+
+/// Resource describing this process, from the `OTEL_*` variables
+fn resource() -> Resource {
+    let detected = Resource::builder().build();
+    let named = detected
+        .get(&opentelemetry::Key::new("service.name"))
+        .is_some_and(|name| name.as_str() != "unknown_service");
+    if named {
+        detected
+    } else {
+        Resource::builder().with_service_name(SERVICE_NAME).build()
+    }
+}
+
+/// Layer exporting spans to the configured collector, `None` if there is none
+///
+/// Spans are exported at every level, whatever the verbosity on stderr.
+///
+/// # Errors
+///
+/// Returns an error if the exporter cannot be set up, e.g. for an invalid
+/// endpoint.
+pub fn layer<S>() -> Result<Option<impl Layer<S>>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    if !is_configured() {
+        return Ok(None);
+    }
+    let provider = match PROVIDER.get() {
+        Some(provider) => provider,
+        None => {
+            let exporter = SpanExporter::builder().with_http().build().map_err(|e| {
+                SyncError::InvalidConfig(format!("Failed to set up OpenTelemetry export: {e}"))
+            })?;
+            let provider = SdkTracerProvider::builder()
+                .with_batch_exporter(exporter)
+                .with_resource(resource())
+                .build();
+            PROVIDER.get_or_init(|| provider)
+        }
+    };
+    let tracer: SdkTracer = provider.tracer(SERVICE_NAME);
+    let exported = filter_fn(|metadata| metadata.is_span() || *metadata.level() <= Level::INFO);
+    Ok(Some(
+        tracing_opentelemetry::layer()
+            .with_tracer(tracer)
+            .with_filter(exported),
+    ))
+}
+
+/// Send the spans still queued and stop exporting
+///
+/// Does nothing if [`layer`] never set up an exporter.
+pub fn shutdown() {
+    if let Some(provider) = PROVIDER.get() {
+        if let Err(e) = provider.shutdown() {
+            eprintln!("Failed to export OpenTelemetry spans: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_name_defaults_to_arsync() {
+        if std::env::var_os("OTEL_SERVICE_NAME").is_some()
+            || std::env::var_os("OTEL_RESOURCE_ATTRIBUTES").is_some()
+        {
+            return;
+        }
+        let resource = resource();
+        assert_eq!(
+            resource
+                .get(&opentelemetry::Key::new("service.name"))
+                .map(|name| name.as_str().into_owned()),
+            Some(SERVICE_NAME.to_string())
+        );
+    }
+}
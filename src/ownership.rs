@@ -0,0 +1,493 @@
+//! Ownership mapping for preserved uid/gid values
+//!
+//! By default, like rsync, ownership is preserved by *name*: the source
+//! uid/gid is resolved to a user/group name and that name is looked up again
+//! to obtain the destination id. When the name does not exist (or the id has
+//! no name at all) the numeric id is used unchanged. Id 0 is never mapped,
+//! matching rsync.
+//!
+//! With `--numeric-ids` the raw ids are copied without any lookups.
+//!
+//! `--chown` overrides either half of the ownership with a fixed user or
+//! group (by name or numeric id). A half that is neither forced nor
+//! preserved is left unchanged.
+//!
+//! Lookups go through the system user and group databases (`/etc/passwd`,
+//! `/etc/group` or any NSS source) and are cached for the lifetime of the
+//! process, so each distinct id costs at most two lookups.
+
+use crate::cli::Args;
+use crate::error::{Result, SyncError};
+use compio_fs_extended::directory::DirectoryFd;
+use compio_fs_extended::metadata::{chown_at, FollowSymlinks};
+use compio_fs_extended::ownership::{
+    fchown_empty_path, gid_for_group_name, group_name_for_gid, uid_for_user_name, user_name_for_uid,
+};
+use std::borrow::Borrow;
+#[allow(clippy::disallowed_types)]
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::os::fd::RawFd;
+use std::path::Path;
+use std::sync::{LazyLock, Mutex, PoisonError};
+use tracing::{debug, warn};
+
+/// Cache of source uid -> destination uid
+#[allow(clippy::disallowed_types)]
+static UID_CACHE: LazyLock<Mutex<HashMap<u32, u32>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Cache of source gid -> destination gid
+#[allow(clippy::disallowed_types)]
+static GID_CACHE: LazyLock<Mutex<HashMap<u32, u32>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Cache of `--chown` user name -> uid
+#[allow(clippy::disallowed_types)]
+static USER_NAME_CACHE: LazyLock<Mutex<HashMap<String, u32>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Cache of `--chown` group name -> gid
+#[allow(clippy::disallowed_types)]
+static GROUP_NAME_CACHE: LazyLock<Mutex<HashMap<String, u32>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Look up a cached mapping, tolerating a poisoned lock
+#[allow(clippy::disallowed_types)]
+fn cached<K, Q>(cache: &Mutex<HashMap<K, u32>>, key: &Q) -> Option<u32>
+where
+    K: Borrow<Q> + Eq + Hash,
+    Q: Eq + Hash + ?Sized,
+{
+    let guard = cache.lock().unwrap_or_else(PoisonError::into_inner);
+    guard.get(key).copied()
+}
+
+/// Remember a mapping, tolerating a poisoned lock
+#[allow(clippy::disallowed_types)]
+fn remember<K: Eq + Hash>(cache: &Mutex<HashMap<K, u32>>, key: K, mapped: u32) {
+    let mut guard = cache.lock().unwrap_or_else(PoisonError::into_inner);
+    guard.insert(key, mapped);
+}
+
+/// Map a source uid to the uid to apply at the destination
+///
+/// Lookup failures are not fatal: the numeric id is used, as rsync does.
+#[allow(clippy::future_not_send)]
+pub async fn map_uid(uid: u32, args: &Args) -> u32 {
+    if args.numeric_ids || uid == 0 {
+        return uid;
+    }
+    if let Some(mapped) = cached(&UID_CACHE, &uid) {
+        return mapped;
+    }
+
+    let mapped = match user_name_for_uid(uid).await {
+        Ok(Some(name)) => match uid_for_user_name(&name).await {
+            Ok(Some(dst_uid)) => {
+                debug!("Mapped uid {} -> {} via user name {}", uid, dst_uid, name);
+                dst_uid
+            }
+            _ => uid,
+        },
+        _ => uid,
+    };
+    remember(&UID_CACHE, uid, mapped);
+    mapped
+}
+
+/// Map a source gid to the gid to apply at the destination
+///
+/// Lookup failures are not fatal: the numeric id is used, as rsync does.
+#[allow(clippy::future_not_send)]
+pub async fn map_gid(gid: u32, args: &Args) -> u32 {
+    if args.numeric_ids || gid == 0 {
+        return gid;
+    }
+    if let Some(mapped) = cached(&GID_CACHE, &gid) {
+        return mapped;
+    }
+
+    let mapped = match group_name_for_gid(gid).await {
+        Ok(Some(name)) => match gid_for_group_name(&name).await {
+            Ok(Some(dst_gid)) => {
+                debug!("Mapped gid {} -> {} via group name {}", gid, dst_gid, name);
+                dst_gid
+            }
+            _ => gid,
+        },
+        _ => gid,
+    };
+    remember(&GID_CACHE, gid, mapped);
+    mapped
+}
+
+/// Id passed to `fchown`/`chown` to leave that half of the ownership unchanged
+pub const UNCHANGED_ID: u32 = u32::MAX;
+
+/// Resolve a `--chown` user given as a name or numeric uid
+#[allow(clippy::future_not_send)]
+async fn resolve_user(user: &str) -> Result<u32> {
+    if let Ok(uid) = user.parse::<u32>() {
+        return Ok(uid);
+    }
+    if let Some(uid) = cached(&USER_NAME_CACHE, user) {
+        return Ok(uid);
+    }
+    let uid = uid_for_user_name(user)
+        .await
+        .map_err(|e| SyncError::InvalidConfig(format!("Failed to look up user {user}: {e}")))?
+        .ok_or_else(|| SyncError::InvalidConfig(format!("Unknown user in --chown: {user}")))?;
+    remember(&USER_NAME_CACHE, user.to_string(), uid);
+    Ok(uid)
+}
+
+/// Resolve a `--chown` group given as a name or numeric gid
+#[allow(clippy::future_not_send)]
+async fn resolve_group(group: &str) -> Result<u32> {
+    if let Ok(gid) = group.parse::<u32>() {
+        return Ok(gid);
+    }
+    if let Some(gid) = cached(&GROUP_NAME_CACHE, group) {
+        return Ok(gid);
+    }
+    let gid = gid_for_group_name(group)
+        .await
+        .map_err(|e| SyncError::InvalidConfig(format!("Failed to look up group {group}: {e}")))?
+        .ok_or_else(|| SyncError::InvalidConfig(format!("Unknown group in --chown: {group}")))?;
+    remember(&GROUP_NAME_CACHE, group.to_string(), gid);
+    Ok(gid)
+}
+
+/// Resolve the `--chown` names up front so typos fail before copying starts
+///
+/// # Errors
+///
+/// This function will return an error if a `--chown` name cannot be resolved.
+#[allow(clippy::future_not_send)]
+pub async fn validate_chown(args: &Args) -> Result<()> {
+    if let Some(chown) = &args.chown {
+        if let Some(user) = &chown.user {
+            resolve_user(user).await?;
+        }
+        if let Some(group) = &chown.group {
+            resolve_group(group).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Compute the `(uid, gid)` to apply to a destination entry
+///
+/// `--chown` takes precedence; otherwise the source ids are mapped with
+/// [`map_uid`] and [`map_gid`] when ownership is preserved, or
+/// [`UNCHANGED_ID`] is returned for a half that should not be touched.
+///
+/// # Errors
+///
+/// This function will return an error if a `--chown` name cannot be resolved.
+#[allow(clippy::future_not_send)]
+pub async fn destination_ownership(uid: u32, gid: u32, args: &Args) -> Result<(u32, u32)> {
+    let chown = args.chown.as_ref();
+    let from_source = args.should_preserve_ownership();
+
+    let uid = match chown.and_then(|c| c.user.as_deref()) {
+        Some(user) => resolve_user(user).await?,
+        None if from_source => map_uid(uid, args).await,
+        None => UNCHANGED_ID,
+    };
+    let gid = match chown.and_then(|c| c.group.as_deref()) {
+        Some(group) => resolve_group(group).await?,
+        None if from_source => map_gid(gid, args).await,
+        None => UNCHANGED_ID,
+    };
+    Ok((uid, gid))
+}
+
+/// Result of applying ownership to a destination entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OwnershipChange {
+    /// Ownership was not requested (`-o`, `-g` and `--chown` all absent)
+    #[default]
+    NotRequested,
+    /// The destination already had the requested ownership
+    Unchanged,
+    /// Ownership was changed
+    Changed,
+    /// A requested change was not applied (missing `CAP_CHOWN`, or an error
+    /// downgraded to a warning by `--no-owner-errors`)
+    Skipped,
+}
+
+/// Parse the effective capability mask from `/proc/self/status` contents
+#[must_use]
+pub fn parse_effective_caps(status: &str) -> Option<u64> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("CapEff:"))
+        .and_then(|hex| u64::from_str_radix(hex.trim(), 16).ok())
+}
+
+/// Capability number of `CAP_CHOWN` (linux/capability.h)
+const CAP_CHOWN: u32 = 0;
+
+/// Whether this process holds `CAP_CHOWN` and may give files away
+///
+/// Read once from `/proc/self/status`; if that fails, fall back to checking
+/// for euid 0.
+#[must_use]
+pub fn has_cap_chown() -> bool {
+    static HAS_CAP_CHOWN: LazyLock<bool> = LazyLock::new(|| {
+        std::fs::read_to_string("/proc/self/status")
+            .ok()
+            .and_then(|status| parse_effective_caps(&status))
+            .map_or_else(
+                // SAFETY: geteuid has no preconditions and cannot fail
+                || unsafe { libc::geteuid() } == 0,
+                |caps| caps & (1 << CAP_CHOWN) != 0,
+            )
+    });
+    *HAS_CAP_CHOWN
+}
+
+/// Drop the halves of `target` that already match `current`
+const fn pending_change(current: (u32, u32), target: (u32, u32)) -> (u32, u32) {
+    let uid = if target.0 == current.0 {
+        UNCHANGED_ID
+    } else {
+        target.0
+    };
+    let gid = if target.1 == current.1 {
+        UNCHANGED_ID
+    } else {
+        target.1
+    };
+    (uid, gid)
+}
+
+/// Apply preserved or forced ownership to an open destination descriptor
+///
+/// `source` is the source `(uid, gid)` and `current` the destination's
+/// present ownership. No syscall is made when the ownership already
+/// matches. Without `CAP_CHOWN` the owner cannot be changed, so that half
+/// is skipped (as rsync does for non-root receivers) while a group change
+/// is still attempted. `fd` may be an `O_PATH` descriptor.
+///
+/// # Errors
+///
+/// This function will return an error if a `--chown` name cannot be resolved,
+/// or if the change fails and `--no-owner-errors` was not given.
+#[allow(clippy::future_not_send)]
+pub async fn apply_ownership(
+    fd: RawFd,
+    path: &Path,
+    source: (u32, u32),
+    current: (u32, u32),
+    args: &Args,
+) -> Result<OwnershipChange> {
+    change_ownership(ChownTarget::Fd(fd), path, source, current, args).await
+}
+
+/// Apply preserved or forced ownership to the entry `name` in `dir`
+///
+/// Same as [`apply_ownership`], but never follows `name` (`lchown`
+/// semantics). This reaches entries that cannot be opened, like symlinks,
+/// or not without blocking, like FIFOs.
+///
+/// # Errors
+///
+/// Same as [`apply_ownership`].
+#[allow(clippy::future_not_send)]
+pub async fn apply_ownership_at(
+    dir: &DirectoryFd,
+    name: &Path,
+    source: (u32, u32),
+    current: (u32, u32),
+    args: &Args,
+) -> Result<OwnershipChange> {
+    let path = dir.path().join(name);
+    change_ownership(ChownTarget::At(dir, name), &path, source, current, args).await
+}
+
+/// The destination entry an ownership change applies to
+enum ChownTarget<'a> {
+    /// An open (possibly `O_PATH`) descriptor
+    Fd(RawFd),
+    /// An entry, not followed, by name relative to its directory
+    At(&'a DirectoryFd, &'a Path),
+}
+
+/// Shared policy of [`apply_ownership`] and [`apply_ownership_at`]
+#[allow(clippy::future_not_send)]
+async fn change_ownership(
+    target_entry: ChownTarget<'_>,
+    path: &Path,
+    source: (u32, u32),
+    current: (u32, u32),
+    args: &Args,
+) -> Result<OwnershipChange> {
+    if !args.should_set_ownership() {
+        return Ok(OwnershipChange::NotRequested);
+    }
+
+    let target = destination_ownership(source.0, source.1, args).await?;
+    let (mut uid, gid) = pending_change(current, target);
+    if uid == UNCHANGED_ID && gid == UNCHANGED_ID {
+        return Ok(OwnershipChange::Unchanged);
+    }
+
+    let mut skipped = false;
+    if uid != UNCHANGED_ID && !has_cap_chown() {
+        debug!(
+            "Skipping owner change for {} to uid {}: no CAP_CHOWN",
+            path.display(),
+            uid
+        );
+        uid = UNCHANGED_ID;
+        skipped = true;
+        if gid == UNCHANGED_ID {
+            return Ok(OwnershipChange::Skipped);
+        }
+    }
+
+    let changed = match target_entry {
+        ChownTarget::Fd(fd) => fchown_empty_path(fd, uid, gid).await,
+        ChownTarget::At(dir, name) => chown_at(dir, name, uid, gid, FollowSymlinks::NoFollow).await,
+    };
+    match changed {
+        Ok(()) if skipped => Ok(OwnershipChange::Skipped),
+        Ok(()) => Ok(OwnershipChange::Changed),
+        Err(e) if args.no_owner_errors => {
+            warn!("Failed to preserve ownership for {}: {}", path.display(), e);
+            Ok(OwnershipChange::Skipped)
+        }
+        Err(e) => Err(SyncError::FileSystem(format!(
+            "Failed to preserve ownership for {}: {}",
+            path.display(),
+            e
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+    use crate::cli::ChownSpec;
+    use std::os::unix::fs::MetadataExt;
+
+    #[compio::test]
+    async fn test_numeric_ids_pass_through() {
+        let args = Args {
+            numeric_ids: true,
+            ..Default::default()
+        };
+        assert_eq!(map_uid(12345, &args).await, 12345);
+        assert_eq!(map_gid(54321, &args).await, 54321);
+    }
+
+    #[compio::test]
+    async fn test_unnamed_ids_fall_back_to_numeric() {
+        let args = Args::default();
+        // Ids this large are not assigned on any sane system
+        assert_eq!(map_uid(3_999_999_001, &args).await, 3_999_999_001);
+        assert_eq!(map_gid(3_999_999_002, &args).await, 3_999_999_002);
+    }
+
+    #[compio::test]
+    async fn test_named_ids_round_trip() {
+        let args = Args::default();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let metadata = std::fs::metadata(file.path()).unwrap();
+        let (uid, gid) = (metadata.uid(), metadata.gid());
+        // On a single host the name round-trip resolves to the same ids
+        assert_eq!(map_uid(uid, &args).await, uid);
+        assert_eq!(map_gid(gid, &args).await, gid);
+    }
+
+    #[compio::test]
+    async fn test_chown_overrides_source_ids() {
+        let args = Args {
+            owner: true,
+            group: true,
+            chown: Some(ChownSpec {
+                user: Some("root".to_string()),
+                group: None,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            destination_ownership(12345, 54321, &args).await.unwrap(),
+            (0, 54321)
+        );
+    }
+
+    #[compio::test]
+    async fn test_chown_without_preservation_leaves_other_half() {
+        let args = Args {
+            chown: Some(ChownSpec {
+                user: None,
+                group: Some("4242".to_string()),
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            destination_ownership(12345, 54321, &args).await.unwrap(),
+            (UNCHANGED_ID, 4242)
+        );
+    }
+
+    #[compio::test]
+    async fn test_chown_unknown_user_is_an_error() {
+        let args = Args {
+            chown: Some(ChownSpec {
+                user: Some("arsync-no-such-user".to_string()),
+                group: None,
+            }),
+            ..Default::default()
+        };
+        assert!(destination_ownership(1, 1, &args).await.is_err());
+    }
+
+    #[test]
+    fn test_parse_effective_caps() {
+        let status = "Name:\tarsync\nCapInh:\t0000000000000000\nCapEff:\t00000000a80425fb\n";
+        assert_eq!(parse_effective_caps(status), Some(0xa804_25fb));
+        assert_eq!(parse_effective_caps("Name:\tarsync\n"), None);
+    }
+
+    #[test]
+    fn test_pending_change_skips_matching_halves() {
+        assert_eq!(
+            pending_change((10, 20), (10, 20)),
+            (UNCHANGED_ID, UNCHANGED_ID)
+        );
+        assert_eq!(pending_change((10, 20), (11, 20)), (11, UNCHANGED_ID));
+        assert_eq!(
+            pending_change((10, 20), (UNCHANGED_ID, 21)),
+            (UNCHANGED_ID, 21)
+        );
+    }
+
+    #[compio::test]
+    async fn test_apply_ownership_noop_when_matching() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let metadata = std::fs::metadata(file.path()).unwrap();
+        let ids = (metadata.uid(), metadata.gid());
+        let args = Args {
+            owner: true,
+            group: true,
+            numeric_ids: true,
+            ..Default::default()
+        };
+
+        let change = apply_ownership(
+            std::os::fd::AsRawFd::as_raw_fd(file.as_file()),
+            file.path(),
+            ids,
+            ids,
+            &args,
+        )
+        .await
+        .unwrap();
+        assert_eq!(change, OwnershipChange::Unchanged);
+    }
+}
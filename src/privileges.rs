@@ -0,0 +1,226 @@
+//! `--drop-privileges`: give up root once setup is done
+//!
+//! Preserving ownership needs root, but little else does. With
+//! `--drop-privileges USER`, arsync opens the `--log-file` as root and then
+//! switches to USER's uid, gid and supplementary groups, keeping only
+//! `CAP_CHOWN` (give files away) and `CAP_FOWNER` (set modes, times and
+//! xattrs on files it does not own). Everything else, including reading the
+//! source and writing the destination, happens with USER's permissions.
+//!
+//! The two capabilities are kept in the permitted, effective and ambient
+//! sets, and every other capability is dropped from the bounding set, so
+//! nothing can regain root. Capabilities are per thread, so this runs on the
+//! main thread before any other thread starts; workers and the io_uring
+//! threads inherit the reduced set.
+
+use crate::error::{Result, SyncError};
+use std::ffi::{CStr, CString};
+use std::io;
+
+/// `CAP_CHOWN` (linux/capability.h)
+const CAP_CHOWN: u32 = 0;
+
+/// `CAP_FOWNER` (linux/capability.h)
+const CAP_FOWNER: u32 = 3;
+
+/// Capabilities kept after dropping privileges
+const KEPT_CAPS: [u32; 2] = [CAP_CHOWN, CAP_FOWNER];
+
+/// `_LINUX_CAPABILITY_VERSION_3`, with 64-bit capability sets
+const CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+/// `struct __user_cap_header_struct`
+#[repr(C)]
+struct CapHeader {
+    version: u32,
+    pid: i32,
+}
+
+/// `struct __user_cap_data_struct`; version 3 takes two of them
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct CapData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+/// Account to run as, from the user database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Account {
+    /// Login name
+    pub name: String,
+    /// User id
+    pub uid: u32,
+    /// Primary group id
+    pub gid: u32,
+}
+
+impl Account {
+    /// Look up `user`, given as a name or numeric uid
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the user does not exist or the lookup fails.
+    pub fn lookup(user: &str) -> Result<Self> {
+        // SAFETY: passwd is plain data; all-zero is a valid value
+        let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut result = std::ptr::null_mut();
+        let mut buf = vec![0u8; 16 * 1024];
+        let ret = if let Ok(uid) = user.parse::<u32>() {
+            // SAFETY: all pointers are valid for the sizes given
+            unsafe {
+                libc::getpwuid_r(
+                    uid,
+                    &raw mut passwd,
+                    buf.as_mut_ptr().cast(),
+                    buf.len(),
+                    &raw mut result,
+                )
+            }
+        } else {
+            let name = CString::new(user).map_err(|_| unknown_user(user))?;
+            // SAFETY: all pointers are valid for the sizes given
+            unsafe {
+                libc::getpwnam_r(
+                    name.as_ptr(),
+                    &raw mut passwd,
+                    buf.as_mut_ptr().cast(),
+                    buf.len(),
+                    &raw mut result,
+                )
+            }
+        };
+        if ret != 0 {
+            return Err(SyncError::InvalidConfig(format!(
+                "Failed to look up user {user}: {}",
+                io::Error::from_raw_os_error(ret)
+            )));
+        }
+        if result.is_null() {
+            return Err(unknown_user(user));
+        }
+        // SAFETY: on success pw_name points at a NUL-terminated name in buf
+        let name = unsafe { CStr::from_ptr(passwd.pw_name) };
+        Ok(Self {
+            name: name.to_string_lossy().into_owned(),
+            uid: passwd.pw_uid,
+            gid: passwd.pw_gid,
+        })
+    }
+}
+
+/// Error for a user missing from the user database
+fn unknown_user(user: &str) -> SyncError {
+    SyncError::InvalidConfig(format!("Unknown user in --drop-privileges: {user}"))
+}
+
+/// Error for a failed step of dropping privileges
+fn failed(step: &str) -> SyncError {
+    SyncError::PermissionDenied(format!(
+        "Failed to drop privileges ({step}): {}",
+        io::Error::last_os_error()
+    ))
+}
+
+/// Highest capability number the kernel knows
+fn last_cap() -> u32 {
+    std::fs::read_to_string("/proc/sys/kernel/cap_last_cap")
+        .ok()
+        .and_then(|last| last.trim().parse().ok())
+        .unwrap_or(40)
+}
+
+/// Switch to `user`, keeping only `CAP_CHOWN` and `CAP_FOWNER`
+///
+/// Must be called before any other thread starts.
+///
+/// # Errors
+///
+/// Returns an error if arsync is not running as root, `user` cannot be
+/// found, or any step fails; arsync must not go on half-privileged.
+pub fn drop_to(user: &str) -> Result<Account> {
+    // SAFETY: geteuid has no preconditions and cannot fail
+    if unsafe { libc::geteuid() } != 0 {
+        return Err(SyncError::InvalidConfig(
+            "--drop-privileges needs arsync to be started as root".to_string(),
+        ));
+    }
+    let account = Account::lookup(user)?;
+    let name = CString::new(account.name.as_str()).map_err(|_| unknown_user(user))?;
+
+    // SAFETY: plain syscalls on this process; name is a valid C string
+    unsafe {
+        if libc::initgroups(name.as_ptr(), account.gid) != 0 {
+            return Err(failed("initgroups"));
+        }
+        // Keep the permitted set across the uid change
+        if libc::prctl(libc::PR_SET_KEEPCAPS, 1, 0, 0, 0) != 0 {
+            return Err(failed("PR_SET_KEEPCAPS"));
+        }
+        for cap in (0..=last_cap()).filter(|cap| !KEPT_CAPS.contains(cap)) {
+            if libc::prctl(libc::PR_CAPBSET_DROP, libc::c_ulong::from(cap), 0, 0, 0) != 0
+                && io::Error::last_os_error().raw_os_error() != Some(libc::EINVAL)
+            {
+                return Err(failed("PR_CAPBSET_DROP"));
+            }
+        }
+        if libc::setresgid(account.gid, account.gid, account.gid) != 0 {
+            return Err(failed("setresgid"));
+        }
+        if libc::setresuid(account.uid, account.uid, account.uid) != 0 {
+            return Err(failed("setresuid"));
+        }
+    }
+
+    let kept = KEPT_CAPS.iter().fold(0, |mask, cap| mask | (1u32 << cap));
+    let mut header = CapHeader {
+        version: CAPABILITY_VERSION_3,
+        pid: 0,
+    };
+    let data = [
+        CapData {
+            effective: kept,
+            permitted: kept,
+            inheritable: kept,
+        },
+        CapData::default(),
+    ];
+    // SAFETY: header and data match the version 3 layout
+    if unsafe { libc::syscall(libc::SYS_capset, &raw mut header, data.as_ptr()) } != 0 {
+        return Err(failed("capset"));
+    }
+    for cap in KEPT_CAPS {
+        // SAFETY: plain syscall; the capability is permitted and inheritable
+        let ret = unsafe {
+            libc::prctl(
+                libc::PR_CAP_AMBIENT,
+                libc::PR_CAP_AMBIENT_RAISE,
+                libc::c_ulong::from(cap),
+                0,
+                0,
+            )
+        };
+        if ret != 0 {
+            return Err(failed("PR_CAP_AMBIENT_RAISE"));
+        }
+    }
+    Ok(account)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+
+    #[test]
+    fn test_account_lookup() {
+        let root = Account::lookup("root").unwrap();
+        assert_eq!((root.uid, root.gid), (0, 0));
+        assert_eq!(Account::lookup("0").unwrap(), root);
+        assert!(matches!(
+            Account::lookup("arsync-no-such-user"),
+            Err(SyncError::InvalidConfig(_))
+        ));
+    }
+}
@@ -46,8 +46,208 @@
 
 use crate::i18n::TranslationKey;
 use crate::io_uring::CopyOperation;
-use indicatif::{ProgressBar, ProgressStyle};
-use std::time::Duration;
+use indicatif::{HumanBytes, HumanDuration, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Regular files found during traversal
+static FILES_DISCOVERED: AtomicU64 = AtomicU64::new(0);
+
+/// Total size of the regular files found during traversal
+static BYTES_DISCOVERED: AtomicU64 = AtomicU64::new(0);
+
+/// Regular files that finished processing (copied, linked or skipped)
+static FILES_DONE: AtomicU64 = AtomicU64::new(0);
+
+/// Total size of the files that finished processing
+static BYTES_DONE: AtomicU64 = AtomicU64::new(0);
+
+/// How often the terminal display is redrawn
+const DRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often a status line is printed when stderr is not a terminal
+const LINE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Record a regular file found during traversal
+pub fn record_discovered(bytes: u64) {
+    FILES_DISCOVERED.fetch_add(1, Ordering::Relaxed);
+    BYTES_DISCOVERED.fetch_add(bytes, Ordering::Relaxed);
+}
+
+/// Record that a discovered file of `bytes` finished processing
+pub fn record_done(bytes: u64) {
+    FILES_DONE.fetch_add(1, Ordering::Relaxed);
+    BYTES_DONE.fetch_add(bytes, Ordering::Relaxed);
+}
+
+/// Point-in-time copy of the progress counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProgressSnapshot {
+    /// Regular files found so far
+    pub files_discovered: u64,
+    /// Bytes in the files found so far
+    pub bytes_discovered: u64,
+    /// Files that finished processing
+    pub files_done: u64,
+    /// Bytes in the files that finished processing
+    pub bytes_done: u64,
+}
+
+impl ProgressSnapshot {
+    /// Read the current counters
+    #[must_use]
+    pub fn now() -> Self {
+        Self {
+            files_discovered: FILES_DISCOVERED.load(Ordering::Relaxed),
+            bytes_discovered: BYTES_DISCOVERED.load(Ordering::Relaxed),
+            files_done: FILES_DONE.load(Ordering::Relaxed),
+            bytes_done: BYTES_DONE.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Average throughput in bytes per second over `elapsed`
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn rate(&self, elapsed: Duration) -> f64 {
+        let secs = elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.bytes_done as f64 / secs
+        } else {
+            0.0
+        }
+    }
+
+    /// Estimated time left for the bytes discovered so far
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn eta(&self, elapsed: Duration) -> Option<Duration> {
+        let rate = self.rate(elapsed);
+        let remaining = self.bytes_discovered.saturating_sub(self.bytes_done);
+        (rate > 0.0).then(|| Duration::from_secs_f64(remaining as f64 / rate))
+    }
+
+    /// One-line summary used when stderr is not a terminal
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn status_line(&self, elapsed: Duration) -> String {
+        let eta = self.eta(elapsed).map_or_else(
+            || "unknown".to_string(),
+            |eta| HumanDuration(eta).to_string(),
+        );
+        format!(
+            "Progress: {}/{} files, {}/{}, {}/s, ETA {}",
+            self.files_done,
+            self.files_discovered,
+            HumanBytes(self.bytes_done),
+            HumanBytes(self.bytes_discovered),
+            HumanBytes(self.rate(elapsed) as u64),
+            eta
+        )
+    }
+}
+
+/// Live `--progress` display on stderr
+///
+/// A background thread samples the progress counters. On a terminal it
+/// draws a bar with files completed vs discovered, throughput and ETA; when
+/// stderr is redirected it prints a status line every few seconds instead,
+/// so logs and CI output stay readable.
+pub struct ProgressDisplay {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ProgressDisplay {
+    /// Start drawing progress
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the thread cannot be spawned.
+    pub fn start() -> std::io::Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+        let terminal = std::io::stderr().is_terminal();
+        let handle = std::thread::Builder::new()
+            .name("arsync-progress".to_string())
+            .spawn(move || {
+                if terminal {
+                    draw_bar(&thread_stop);
+                } else {
+                    print_lines(&thread_stop);
+                }
+            })?;
+        Ok(Self {
+            stop,
+            handle: Some(handle),
+        })
+    }
+
+    /// Draw the final state and stop the display thread
+    pub fn finish(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for ProgressDisplay {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Redraw a progress bar until `stop` is set
+#[allow(clippy::unwrap_used)]
+fn draw_bar(stop: &AtomicBool) {
+    let pb = ProgressBar::with_draw_target(Some(0), ProgressDrawTarget::stderr());
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(
+                "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] \
+                 {bytes}/{total_bytes} {msg} {binary_bytes_per_sec} ETA {eta}",
+            )
+            .unwrap()
+            .progress_chars("#>-"),
+    );
+    loop {
+        let done = stop.load(Ordering::Relaxed);
+        let snapshot = ProgressSnapshot::now();
+        pb.set_length(snapshot.bytes_discovered);
+        pb.set_position(snapshot.bytes_done);
+        pb.set_message(format!(
+            "{}/{} files",
+            snapshot.files_done, snapshot.files_discovered
+        ));
+        if done {
+            break;
+        }
+        std::thread::park_timeout(DRAW_INTERVAL);
+    }
+    pb.finish();
+}
+
+/// Print a status line every [`LINE_INTERVAL`] and once more when `stop` is set
+fn print_lines(stop: &AtomicBool) {
+    let start = Instant::now();
+    let mut next = start + LINE_INTERVAL;
+    while !stop.load(Ordering::Relaxed) {
+        std::thread::park_timeout(next.saturating_duration_since(Instant::now()));
+        if Instant::now() >= next {
+            eprintln!("{}", ProgressSnapshot::now().status_line(start.elapsed()));
+            next += LINE_INTERVAL;
+        }
+    }
+    eprintln!("{}", ProgressSnapshot::now().status_line(start.elapsed()));
+}
 
 /// Progress tracker for file synchronization operations
 ///
@@ -425,3 +625,26 @@ pub struct ProgressStats {
     /// Time elapsed since the operation started
     pub elapsed: Duration,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_rate_and_eta() {
+        let snapshot = ProgressSnapshot {
+            files_discovered: 4,
+            bytes_discovered: 3 * 1024 * 1024,
+            files_done: 1,
+            bytes_done: 1024 * 1024,
+        };
+        let elapsed = Duration::from_secs(2);
+        assert!((snapshot.rate(elapsed) - 524_288.0).abs() < f64::EPSILON);
+        assert_eq!(snapshot.eta(elapsed), Some(Duration::from_secs(4)));
+        assert_eq!(
+            snapshot.status_line(elapsed),
+            "Progress: 1/4 files, 1.00 MiB/3.00 MiB, 512.00 KiB/s, ETA 4 seconds"
+        );
+        assert_eq!(ProgressSnapshot::default().eta(elapsed), None);
+    }
+}
@@ -0,0 +1,99 @@
+//! Security extended attributes with dedicated handling
+//!
+//! SELinux labels (`security.selinux`) are preserved with `-a` or `-X`
+//! unless `--no-selinux` is given. The label is applied right after the data
+//! is written and before ownership, permissions and timestamps, so the entry
+//! never carries its final metadata under the policy's default label. The
+//! generic xattr copy skips it, see [`crate::copy::managed_xattrs`].
+//!
+//! A destination that cannot store the label (no SELinux on the destination
+//! filesystem, or a context unknown to the loaded policy) is not an error:
+//! the first failure is logged as a warning and later ones at debug level.
+//!
+//! File capabilities (`security.capability`, as set by `setcap`) are
+//! preserved for regular files whenever permissions or xattrs are. The
+//! kernel drops them when a file is written to or chowned, so they are
+//! applied after the data, ownership and permissions. Setting them needs
+//! `CAP_SETFCAP`; every file whose capabilities could not be applied gets a
+//! warning, since the copied binary will not behave like the original.
+
+use compio::fs::File;
+use compio_fs_extended::xattr::{get_xattr_impl, set_xattr_impl};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{debug, warn};
+
+/// Name of the xattr holding the SELinux security context
+pub const SELINUX_XATTR: &str = "security.selinux";
+
+/// Name of the xattr holding file capabilities
+pub const CAPABILITY_XATTR: &str = "security.capability";
+
+/// Whether a label failure has already been reported as a warning
+static SELINUX_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Copy the SELinux label of `src` to `dst`
+///
+/// Sources without a label are left alone. Failures are logged, never
+/// returned, so an unlabeled destination filesystem does not fail the copy.
+#[allow(clippy::future_not_send)]
+pub async fn preserve_selinux_label(src: &File, dst: &File, dst_path: &Path) {
+    let Ok(label) = get_xattr_impl(src, SELINUX_XATTR).await else {
+        return;
+    };
+    if label.is_empty() {
+        return;
+    }
+
+    match set_xattr_impl(dst, SELINUX_XATTR, &label).await {
+        Ok(()) => debug!(
+            "Preserved SELinux label {} on {}",
+            String::from_utf8_lossy(&label).trim_end_matches('\0'),
+            dst_path.display()
+        ),
+        Err(e) => {
+            if SELINUX_WARNED.swap(true, Ordering::Relaxed) {
+                debug!(
+                    "Failed to preserve SELinux label on {}: {}",
+                    dst_path.display(),
+                    e
+                );
+            } else {
+                warn!(
+                    "Failed to preserve SELinux label on {}: {} (further failures are logged at debug level)",
+                    dst_path.display(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// Copy the file capabilities of `src` to `dst`
+///
+/// Must run after anything that writes to or chowns `dst`. Returns whether
+/// capabilities were applied; failures are logged as warnings.
+#[allow(clippy::future_not_send)]
+pub async fn preserve_file_capabilities(src: &File, dst: &File, dst_path: &Path) -> bool {
+    let Ok(caps) = get_xattr_impl(src, CAPABILITY_XATTR).await else {
+        return false;
+    };
+    if caps.is_empty() {
+        return false;
+    }
+
+    match set_xattr_impl(dst, CAPABILITY_XATTR, &caps).await {
+        Ok(()) => {
+            debug!("Preserved file capabilities on {}", dst_path.display());
+            true
+        }
+        Err(e) => {
+            warn!(
+                "File capabilities could not be applied to {} (requires CAP_SETFCAP): {}",
+                dst_path.display(),
+                e
+            );
+            false
+        }
+    }
+}
@@ -56,10 +56,14 @@
 
 use crate::cli::Args;
 use crate::directory::copy_directory;
-use crate::error::Result;
+use crate::error::{Result, SyncError};
+use crate::events::{self, Event};
 use crate::io_uring::FileOperations;
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
 use std::time::{Duration, Instant};
-use tracing::{error, info};
+use tracing::{debug, error, info};
 
 /// Statistics for a synchronization operation
 ///
@@ -82,6 +86,7 @@ use tracing::{error, info};
 ///     files_copied: 150,
 ///     bytes_copied: 1_048_576,
 ///     duration: Duration::from_secs(5),
+///     ..SyncStats::default()
 /// };
 /// println!("Copied {} files ({} bytes) in {:?}",
 ///          stats.files_copied, stats.bytes_copied, stats.duration);
@@ -94,7 +99,7 @@ use tracing::{error, info};
 /// - User feedback and progress reporting
 /// - Benchmarking and comparison with other tools
 /// - Debugging and troubleshooting slow operations
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct SyncStats {
     /// Number of files successfully copied during the operation
     pub files_copied: u64,
@@ -104,6 +109,75 @@ pub struct SyncStats {
 
     /// Total duration of the synchronization operation
     pub duration: Duration,
+
+    /// Regular files found in the source
+    pub regular_files: u64,
+
+    /// Directories found in the source
+    pub directories: u64,
+
+    /// Symlinks found in the source
+    pub symlinks: u64,
+
+    /// Devices, FIFOs and sockets found in the source
+    pub specials: u64,
+
+    /// Total size of the regular files found in the source
+    pub total_size: u64,
+}
+
+impl SyncStats {
+    /// rsync `--stats` style summary, one statistic per line
+    ///
+    /// Literal data is content written to the destination; matched data is
+    /// the rest of the source's size, which was linked, already present or
+    /// found unchanged by `--no-whole-file`. The speedup is the total size divided by the literal
+    /// data, as rsync computes it from the bytes sent.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn report(&self) -> String {
+        let entries = self.regular_files + self.directories + self.symlinks + self.specials;
+        let secs = self.duration.as_secs_f64();
+        let rate = if secs > 0.0 {
+            self.bytes_copied as f64 / secs
+        } else {
+            0.0
+        };
+        let speedup = self.total_size as f64 / self.bytes_copied.max(1) as f64;
+        format!(
+            "Number of files: {} (reg: {}, dir: {}, link: {}, special: {})\n\
+             Number of files copied: {}\n\
+             Total file size: {} bytes\n\
+             Literal data: {} bytes\n\
+             Matched data: {} bytes\n\
+             Total time: {secs:.3} seconds\n\
+             Transfer rate: {rate:.2} bytes/sec\n\
+             total size is {}  speedup is {speedup:.2}",
+            group_digits(entries),
+            group_digits(self.regular_files),
+            group_digits(self.directories),
+            group_digits(self.symlinks),
+            group_digits(self.specials),
+            group_digits(self.files_copied),
+            group_digits(self.total_size),
+            group_digits(self.bytes_copied),
+            group_digits(self.total_size.saturating_sub(self.bytes_copied)),
+            group_digits(self.total_size),
+        )
+    }
+}
+
+/// Format `n` with thousands separators, as rsync does
+fn group_digits(n: u64) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    grouped
 }
 
 /// Main synchronization function
@@ -194,6 +268,11 @@ pub struct SyncStats {
 /// 5. Tracks statistics and handles errors
 /// 6. Returns comprehensive operation results
 #[allow(clippy::future_not_send)]
+#[cfg_attr(feature = "spans", tracing::instrument(
+    name = "sync",
+    skip_all,
+    fields(source = %args.source.display(), destination = %args.destination.display())
+))]
 pub async fn sync_files(args: &Args) -> Result<SyncStats> {
     let start_time = Instant::now();
 
@@ -203,15 +282,29 @@ pub async fn sync_files(args: &Args) -> Result<SyncStats> {
         args.destination.display()
     );
 
-    let mut stats = SyncStats {
-        files_copied: 0,
-        bytes_copied: 0,
-        duration: Duration::from_secs(0),
-    };
+    // Resolve --chown names before touching the destination
+    crate::ownership::validate_chown(args).await?;
+    crate::fs_profile::init(args)?;
+    crate::log_file::open(args)?;
+    crate::budget::init(args);
+    crate::affinity::pin_main_thread(args)?;
+
+    // SQEs for read/write carry the priority themselves; this covers the
+    // copies the kernel does on our behalf (copy_file_range, fsync)
+    if let Some(priority) = args.io_priority {
+        if let Err(e) = compio_fs_extended::ioprio::set_thread_priority(priority) {
+            tracing::warn!("Failed to set I/O priority: {e}");
+        }
+    }
+
+    // Fail once up front rather than once per file on a read-only destination
+    check_destination_writable(&args.destination)?;
+
+    let mut stats = SyncStats::default();
 
     // Initialize file operations with configured parameters
     // Queue depth and buffer size are validated by the CLI module
-    let mut file_ops = FileOperations::new(args.queue_depth, args.buffer_size_bytes())?;
+    let mut file_ops = FileOperations::new(args.queue_depth, args.effective_buffer_size())?;
 
     // Handle single file copy
     if args.is_file_copy() {
@@ -225,13 +318,23 @@ pub async fn sync_files(args: &Args) -> Result<SyncStats> {
         // Note: file size is now obtained within copy_file_with_metadata
 
         // Copy the file with metadata preservation
+        events::emit(|| Event::file_started(&args.source, &args.destination));
         match file_ops
             .copy_file_with_metadata(&args.source, &args.destination)
             .await
         {
             Ok(bytes_copied) => {
+                crate::progress::record_discovered(bytes_copied);
+                crate::progress::record_done(bytes_copied);
+                events::emit(|| {
+                    Event::file_completed(&args.source, &args.destination, bytes_copied, false)
+                });
                 stats.files_copied = 1;
                 stats.bytes_copied = bytes_copied;
+                stats.regular_files = 1;
+                stats.total_size = bytes_copied;
+                crate::systemd::record_file();
+                crate::systemd::record_bytes(bytes_copied);
                 info!(
                     "Successfully copied file with metadata: {} bytes",
                     bytes_copied
@@ -260,9 +363,19 @@ pub async fn sync_files(args: &Args) -> Result<SyncStats> {
         )
         .await?;
 
+        if args.delay_updates {
+            let renamed = crate::delay_updates::commit(&args.destination).await?;
+            info!("Moved {} delayed updates into place", renamed);
+        }
+
         // Update statistics
         stats.files_copied = dir_stats.files_copied;
         stats.bytes_copied = dir_stats.bytes_copied;
+        stats.regular_files = dir_stats.regular_files_found;
+        stats.directories = dir_stats.directories_found;
+        stats.symlinks = dir_stats.symlinks_found;
+        stats.specials = dir_stats.specials_found;
+        stats.total_size = dir_stats.total_size;
 
         info!(
             "Directory copy completed: {} files, {} directories, {} bytes, {} errors",
@@ -282,8 +395,13 @@ pub async fn sync_files(args: &Args) -> Result<SyncStats> {
     }
 
     stats.duration = start_time.elapsed();
+    crate::atime::report(&args.source);
 
-    info!("Synchronization completed in {:?}", stats.duration);
+    if args.deterministic {
+        info!("Synchronization completed");
+    } else {
+        info!("Synchronization completed in {:?}", stats.duration);
+    }
     info!(
         "Files copied: {}, Bytes copied: {}",
         stats.files_copied, stats.bytes_copied
@@ -291,3 +409,134 @@ pub async fn sync_files(args: &Args) -> Result<SyncStats> {
 
     Ok(stats)
 }
+
+/// Check that the destination can be written before doing any work
+///
+/// The nearest existing directory at or above `destination` is probed with
+/// `statvfs` and `faccessat(W_OK)`, so a read-only mount or a destination
+/// the user cannot write to fails once, before traversal, instead of
+/// producing an error for every file.
+///
+/// # Errors
+///
+/// Returns [`SyncError::ReadOnlyFilesystem`] if the directory is on a
+/// read-only mount and [`SyncError::PermissionDenied`] if it is not
+/// writable by the effective user.
+pub fn check_destination_writable(destination: &Path) -> Result<()> {
+    let Some(dir) = destination
+        .ancestors()
+        .map(|dir| {
+            if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            }
+        })
+        .find(|dir| dir.is_dir())
+    else {
+        return Ok(());
+    };
+    let Ok(dir_cstr) = CString::new(dir.as_os_str().as_bytes()) else {
+        return Ok(());
+    };
+
+    // SAFETY: dir_cstr is a valid NUL-terminated path and statvfs is plain old data
+    let mut vfs: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: both pointers are valid for the duration of the call
+    if unsafe { libc::statvfs(dir_cstr.as_ptr(), &raw mut vfs) } == 0
+        && vfs.f_flag & libc::ST_RDONLY != 0
+    {
+        return Err(SyncError::ReadOnlyFilesystem(format!(
+            "destination {} is on a read-only filesystem",
+            dir.display()
+        )));
+    }
+
+    // SAFETY: dir_cstr is a valid NUL-terminated path
+    let writable = unsafe {
+        libc::faccessat(
+            libc::AT_FDCWD,
+            dir_cstr.as_ptr(),
+            libc::W_OK,
+            libc::AT_EACCESS,
+        )
+    };
+    if writable != 0 {
+        let err = std::io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(libc::EROFS) => {
+                return Err(SyncError::ReadOnlyFilesystem(format!(
+                    "destination {} is on a read-only filesystem",
+                    dir.display()
+                )));
+            }
+            Some(libc::EACCES | libc::EPERM) => {
+                return Err(SyncError::PermissionDenied(format!(
+                    "cannot write to destination {}",
+                    dir.display()
+                )));
+            }
+            // Leave anything unexpected to the copy itself
+            _ => debug!(
+                "Destination write probe of {} failed: {}",
+                dir.display(),
+                err
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    use super::*;
+
+    #[test]
+    fn test_check_destination_writable_missing_path() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let destination = temp_dir.path().join("does/not/exist/yet");
+        check_destination_writable(&destination).unwrap();
+    }
+
+    #[test]
+    fn test_destination_errors_exit_code() {
+        let read_only = SyncError::ReadOnlyFilesystem("/mnt".to_string());
+        let denied = SyncError::PermissionDenied("/mnt".to_string());
+        let other = SyncError::FileSystem("boom".to_string());
+        assert_eq!(
+            read_only.exit_code(),
+            crate::error::EXIT_DESTINATION_NOT_WRITABLE
+        );
+        assert_eq!(
+            denied.exit_code(),
+            crate::error::EXIT_DESTINATION_NOT_WRITABLE
+        );
+        assert_eq!(other.exit_code(), crate::error::EXIT_FAILURE);
+    }
+
+    #[test]
+    fn test_stats_report() {
+        assert_eq!(group_digits(0), "0");
+        assert_eq!(group_digits(999), "999");
+        assert_eq!(group_digits(1_234_567), "1,234,567");
+
+        let stats = SyncStats {
+            files_copied: 3,
+            bytes_copied: 1000,
+            duration: Duration::from_secs(2),
+            regular_files: 4,
+            directories: 2,
+            symlinks: 1,
+            specials: 0,
+            total_size: 4000,
+        };
+        let report = stats.report();
+        assert!(report.contains("Number of files: 7 (reg: 4, dir: 2, link: 1, special: 0)"));
+        assert!(report.contains("Literal data: 1,000 bytes"));
+        assert!(report.contains("Matched data: 3,000 bytes"));
+        assert!(report.contains("Transfer rate: 500.00 bytes/sec"));
+        assert!(report.ends_with("total size is 4,000  speedup is 4.00"));
+    }
+}
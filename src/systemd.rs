@@ -0,0 +1,427 @@
+//! systemd service manager integration
+//!
+//! When arsync runs as a `Type=notify` systemd service, the service manager
+//! passes a datagram socket in `NOTIFY_SOCKET`. This module implements the
+//! small subset of the `sd_notify(3)` protocol arsync needs:
+//!
+//! - `READY=1` once startup and argument validation have completed
+//! - `STATUS=...` lines carrying live file/byte counts, and whether
+//!   `--watch` is copying or waiting for changes
+//! - `WATCHDOG=1` heartbeats when `WatchdogSec=` is configured
+//! - `STOPPING=1` when the run is finished
+//!
+//! Watchdog heartbeats are tied to forward progress rather than to a timer
+//! alone: a heartbeat is only sent if the copy counters advanced since the
+//! previous tick. A sync that is stuck (e.g. on a hung NFS server) therefore
+//! stops pinging the watchdog and systemd restarts the service. While
+//! `--watch` is waiting for the source to change there is nothing to make
+//! progress on, so heartbeats are sent regardless.
+//!
+//! When `NOTIFY_SOCKET` is not set every function here is a no-op, so the
+//! integration costs nothing outside of systemd.
+
+use std::io;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Files completed since startup, reported in `STATUS=` lines
+static FILES_DONE: AtomicU64 = AtomicU64::new(0);
+
+/// Bytes written since startup, reported in `STATUS=` lines
+static BYTES_DONE: AtomicU64 = AtomicU64::new(0);
+
+/// Set while `--watch` waits for changes, when no progress is expected
+static IDLE: AtomicBool = AtomicBool::new(false);
+
+/// Status update interval used when no watchdog is configured
+const DEFAULT_STATUS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Record that a file finished copying
+pub fn record_file() {
+    FILES_DONE.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Record that `bytes` were written to a destination file
+pub fn record_bytes(bytes: u64) {
+    BYTES_DONE.fetch_add(bytes, Ordering::Relaxed);
+}
+
+/// Record whether arsync is waiting for work rather than copying
+pub fn set_idle(idle: bool) {
+    IDLE.store(idle, Ordering::Relaxed);
+}
+
+/// Snapshot of the progress counters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Progress {
+    files: u64,
+    bytes: u64,
+    idle: bool,
+}
+
+impl Progress {
+    /// Read the process-wide counters
+    fn current() -> Self {
+        Self {
+            files: FILES_DONE.load(Ordering::Relaxed),
+            bytes: BYTES_DONE.load(Ordering::Relaxed),
+            idle: IDLE.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Connection to the systemd notification socket
+#[derive(Debug)]
+pub struct Notifier {
+    socket: UnixDatagram,
+    addr: SocketAddr,
+}
+
+impl Notifier {
+    /// Create a notifier from the `NOTIFY_SOCKET` environment variable
+    ///
+    /// Returns `None` when not running under a notify-aware service manager
+    /// or when the socket cannot be set up.
+    #[must_use]
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var("NOTIFY_SOCKET").ok()?;
+        match Self::connect(&path) {
+            Ok(notifier) => Some(notifier),
+            Err(e) => {
+                warn!("Ignoring NOTIFY_SOCKET={}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    /// Create a notifier for an explicit socket path
+    ///
+    /// A leading `@` denotes a Linux abstract socket, as in `NOTIFY_SOCKET`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the path is not a valid socket
+    /// address or an unbound datagram socket cannot be created.
+    pub fn connect(path: &str) -> io::Result<Self> {
+        let addr = match path.strip_prefix('@') {
+            Some(name) => SocketAddr::from_abstract_name(name.as_bytes())?,
+            None => SocketAddr::from_pathname(path)?,
+        };
+        let socket = UnixDatagram::unbound()?;
+        Ok(Self { socket, addr })
+    }
+
+    /// Send a raw newline-separated list of `KEY=VALUE` assignments
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the datagram cannot be sent.
+    pub fn notify(&self, state: &str) -> io::Result<()> {
+        self.socket.send_to_addr(state.as_bytes(), &self.addr)?;
+        Ok(())
+    }
+
+    /// Tell the service manager that startup is complete
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the datagram cannot be sent.
+    pub fn ready(&self) -> io::Result<()> {
+        self.notify("READY=1")
+    }
+
+    /// Publish a free-form status line shown by `systemctl status`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the datagram cannot be sent.
+    pub fn status(&self, status: &str) -> io::Result<()> {
+        // Newlines would start a new assignment in the protocol
+        self.notify(&format!("STATUS={}", status.replace('\n', " ")))
+    }
+
+    /// Send a watchdog keep-alive
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the datagram cannot be sent.
+    pub fn watchdog(&self) -> io::Result<()> {
+        self.notify("WATCHDOG=1")
+    }
+
+    /// Tell the service manager that the service is shutting down
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the datagram cannot be sent.
+    pub fn stopping(&self) -> io::Result<()> {
+        self.notify("STOPPING=1")
+    }
+}
+
+/// Parse the watchdog timeout configured by systemd
+///
+/// `usec` and `pid` are the values of `WATCHDOG_USEC` and `WATCHDOG_PID`.
+/// The watchdog only applies to this process if `WATCHDOG_PID` is unset or
+/// matches `own_pid`.
+#[must_use]
+pub fn parse_watchdog_timeout(
+    usec: Option<&str>,
+    pid: Option<&str>,
+    own_pid: u32,
+) -> Option<Duration> {
+    if let Some(pid) = pid {
+        if pid.trim().parse::<u32>().ok()? != own_pid {
+            return None;
+        }
+    }
+    let usec = usec?.trim().parse::<u64>().ok()?;
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
+
+/// Watchdog timeout for this process from the environment, if any
+#[must_use]
+pub fn watchdog_timeout() -> Option<Duration> {
+    parse_watchdog_timeout(
+        std::env::var("WATCHDOG_USEC").ok().as_deref(),
+        std::env::var("WATCHDOG_PID").ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+/// Format a `STATUS=` line from the progress counters
+fn status_line(progress: Progress) -> String {
+    let Progress { files, bytes, idle } = progress;
+    if idle {
+        format!("Watching for changes: {files} files, {bytes} bytes copied")
+    } else {
+        format!("Copying: {files} files, {bytes} bytes")
+    }
+}
+
+/// Background thread publishing status and progress-gated watchdog pings
+///
+/// The heartbeat runs on its own OS thread so that it keeps an accurate
+/// cadence regardless of how busy the `io_uring` runtime threads are.
+pub struct Heartbeat {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Heartbeat {
+    /// Start the heartbeat thread
+    ///
+    /// With a watchdog timeout configured, ticks happen at half the timeout
+    /// (as recommended by `sd_watchdog_enabled(3)`) and `WATCHDOG=1` is sent
+    /// only if progress was made since the previous tick.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the thread cannot be spawned.
+    pub fn start(notifier: Arc<Notifier>, watchdog: Option<Duration>) -> io::Result<Self> {
+        Self::start_with(notifier, watchdog, Progress::current)
+    }
+
+    /// Start the heartbeat thread, reading progress from `progress`
+    fn start_with(
+        notifier: Arc<Notifier>,
+        watchdog: Option<Duration>,
+        progress: impl Fn() -> Progress + Send + 'static,
+    ) -> io::Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let interval = watchdog.map_or(DEFAULT_STATUS_INTERVAL, |timeout| timeout / 2);
+        let thread_stop = Arc::clone(&stop);
+        let handle = std::thread::Builder::new()
+            .name("arsync-sd-notify".to_string())
+            .spawn(move || {
+                let mut last = progress();
+                while !thread_stop.load(Ordering::Relaxed) {
+                    std::thread::park_timeout(interval);
+                    if thread_stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let current = progress();
+                    let _ = notifier.status(&status_line(current));
+                    if watchdog.is_some() {
+                        let moved = (current.files, current.bytes) != (last.files, last.bytes);
+                        if !moved && !current.idle {
+                            debug!("No progress since last tick, withholding watchdog ping");
+                        } else {
+                            let _ = notifier.watchdog();
+                        }
+                    }
+                    last = current;
+                }
+            })?;
+        Ok(Self {
+            stop,
+            handle: Some(handle),
+        })
+    }
+
+    /// Stop the heartbeat thread and wait for it to exit
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// systemd integration for a single arsync run
+///
+/// Created at startup; does nothing when not running under systemd.
+pub struct ServiceNotifier {
+    notifier: Option<Arc<Notifier>>,
+    heartbeat: Option<Heartbeat>,
+}
+
+impl ServiceNotifier {
+    /// Connect to systemd (if present), signal readiness and start the heartbeat
+    #[must_use]
+    pub fn start() -> Self {
+        let Some(notifier) = Notifier::from_env().map(Arc::new) else {
+            return Self {
+                notifier: None,
+                heartbeat: None,
+            };
+        };
+
+        if let Err(e) = notifier.ready() {
+            warn!("Failed to send READY=1 to systemd: {}", e);
+        }
+
+        let watchdog = watchdog_timeout();
+        if let Some(timeout) = watchdog {
+            debug!("systemd watchdog enabled with timeout {:?}", timeout);
+        }
+        let heartbeat = match Heartbeat::start(Arc::clone(&notifier), watchdog) {
+            Ok(heartbeat) => Some(heartbeat),
+            Err(e) => {
+                warn!("Failed to start systemd heartbeat thread: {}", e);
+                None
+            }
+        };
+
+        Self {
+            notifier: Some(notifier),
+            heartbeat,
+        }
+    }
+
+    /// Stop the heartbeat and report the final status
+    pub fn finish(mut self, status: &str) {
+        if let Some(heartbeat) = self.heartbeat.take() {
+            heartbeat.stop();
+        }
+        if let Some(notifier) = &self.notifier {
+            let _ = notifier.status(status);
+            let _ = notifier.stopping();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::unwrap_used)]
+    #![allow(clippy::expect_used)]
+    use super::*;
+    use tempfile::TempDir;
+
+    fn recv(socket: &UnixDatagram) -> String {
+        let mut buf = [0u8; 256];
+        let n = socket.recv(&mut buf).expect("Failed to receive datagram");
+        String::from_utf8_lossy(&buf[..n]).into_owned()
+    }
+
+    #[test]
+    fn test_notifier_sends_ready_and_status() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let path = temp_dir.path().join("notify.sock");
+        let server = UnixDatagram::bind(&path).expect("Failed to bind socket");
+
+        let notifier = Notifier::connect(path.to_str().unwrap()).unwrap();
+        notifier.ready().unwrap();
+        notifier.status("line one\nline two").unwrap();
+
+        assert_eq!(recv(&server), "READY=1");
+        assert_eq!(recv(&server), "STATUS=line one line two");
+    }
+
+    #[test]
+    fn test_parse_watchdog_timeout() {
+        assert_eq!(
+            parse_watchdog_timeout(Some("30000000"), None, 42),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_watchdog_timeout(Some("30000000"), Some("42"), 42),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_watchdog_timeout(Some("30000000"), Some("7"), 42),
+            None
+        );
+        assert_eq!(parse_watchdog_timeout(Some("0"), None, 42), None);
+        assert_eq!(parse_watchdog_timeout(Some("bogus"), None, 42), None);
+        assert_eq!(parse_watchdog_timeout(None, None, 42), None);
+    }
+
+    #[test]
+    fn test_heartbeat_withholds_watchdog_without_progress() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let path = temp_dir.path().join("notify.sock");
+        let server = UnixDatagram::bind(&path).expect("Failed to bind socket");
+        server
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+
+        // A private counter, so other tests recording progress cannot interfere
+        let bytes = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&bytes);
+        let notifier = Arc::new(Notifier::connect(path.to_str().unwrap()).unwrap());
+        let heartbeat = Heartbeat::start_with(
+            Arc::clone(&notifier),
+            Some(Duration::from_millis(100)),
+            move || Progress {
+                files: 0,
+                bytes: counter.load(Ordering::Relaxed),
+                idle: false,
+            },
+        )
+        .unwrap();
+
+        // Ticks without progress publish status but never ping the watchdog
+        for _ in 0..3 {
+            let message = recv(&server);
+            assert_eq!(message, "STATUS=Copying: 0 files, 0 bytes");
+        }
+
+        // The first tick after progress pings it again
+        bytes.store(1, Ordering::Relaxed);
+        loop {
+            let message = recv(&server);
+            if message == "WATCHDOG=1" {
+                break;
+            }
+            assert!(message.starts_with("STATUS=Copying:"), "{message}");
+        }
+        heartbeat.stop();
+    }
+}
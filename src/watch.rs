@@ -0,0 +1,971 @@
+//! `--watch`: keep the destination in step with the source
+//!
+//! After the initial sync, changes under the source are picked up with
+//! inotify (fanotify would need `CAP_SYS_ADMIN`) and applied as they happen.
+//! The watches are set up before the initial sync starts, so nothing changed
+//! while it runs is missed.
+//!
+//! Events are collected until the source has been quiet for `--watch-delay`
+//! milliseconds and then applied as one batch:
+//!
+//! - A file written and closed, created, or whose attributes changed is
+//!   copied again
+//! - A directory created or moved into the source is synced in full and
+//!   watched; one whose attributes changed has its metadata copied
+//! - An entry deleted or moved out of the source is removed from the
+//!   destination
+//! - An entry renamed within the source is renamed in the destination, so it
+//!   is not copied again
+//!
+//! Changes to entries the `--exclude`/`--include` rules exclude are
+//! ignored, and excluded directories are not watched. A changed
+//! `.gitignore`, `.ignore` or `.cvsignore` file (with `--gitignore` or
+//! `--cvs-exclude`) takes effect at once, and its directory is synced again
+//! to copy what it no longer excludes; entries it now excludes are left at
+//! the destination.
+//!
+//! Repeated events for a path coalesce into one change. If the kernel's
+//! event queue overflows, the whole tree is synced again.
+//!
+//! `SIGTERM` and `SIGINT` stop the watch cleanly, so a systemd service is
+//! told `STOPPING=1` and exits successfully. They are blocked for the whole
+//! process and taken by the watch thread. One arriving during the initial
+//! sync stops it too: copies under way finish, and no further entries are
+//! started.
+
+use crate::cli::Args;
+use crate::error::{Result, SyncError};
+use crate::filter::Filter;
+use compio_fs_extended::directory::{DirectoryFd, EntryType};
+use compio_fs_extended::rename::{rename_at, RenameMode};
+use compio_fs_extended::unlink::{remove_dir_at, unlink_at};
+use compio_fs_extended::ExtendedError;
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::StreamExt;
+#[allow(clippy::disallowed_types)]
+use std::collections::HashMap;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Events each watched directory reports
+const WATCH_MASK: u32 = libc::IN_CLOSE_WRITE
+    | libc::IN_CREATE
+    | libc::IN_DELETE
+    | libc::IN_MOVED_FROM
+    | libc::IN_MOVED_TO
+    | libc::IN_ATTRIB
+    | libc::IN_ONLYDIR
+    | libc::IN_DONT_FOLLOW
+    | libc::IN_EXCL_UNLINK;
+
+/// Set once a stop signal has arrived
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Size of the fixed part of a `struct inotify_event`
+const EVENT_HEADER: usize = std::mem::size_of::<libc::inotify_event>();
+
+/// Outcome of waiting for events
+enum Wait {
+    /// Events are ready to read
+    Events,
+    /// The timeout passed without events
+    Quiet,
+    /// A stop signal arrived
+    Stop,
+}
+
+/// Signals that stop the watch
+fn stop_signals() -> libc::sigset_t {
+    // SAFETY: sigemptyset initializes the set before it is used
+    unsafe {
+        let mut set = std::mem::zeroed();
+        libc::sigemptyset(&raw mut set);
+        libc::sigaddset(&raw mut set, libc::SIGTERM);
+        libc::sigaddset(&raw mut set, libc::SIGINT);
+        set
+    }
+}
+
+/// Whether a stop signal has arrived, so no new work should start
+#[must_use]
+pub fn stop_requested() -> bool {
+    STOP_REQUESTED.load(Ordering::Relaxed)
+}
+
+/// Block `SIGTERM` and `SIGINT` so the watch thread can receive them
+///
+/// Must be called before any other thread starts, so that every thread
+/// inherits the mask.
+///
+/// # Errors
+///
+/// Returns an error if the signal mask cannot be changed.
+pub fn block_stop_signals() -> io::Result<()> {
+    let set = stop_signals();
+    // SAFETY: set is initialized; the old mask is not needed
+    let ret =
+        unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, &raw const set, std::ptr::null_mut()) };
+    if ret != 0 {
+        return Err(io::Error::from_raw_os_error(ret));
+    }
+    Ok(())
+}
+
+/// One change to apply to the destination, with paths relative to the roots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// Copy the entry (and, for a directory, everything below it) again
+    Updated(PathBuf),
+    /// Copy only the metadata of a directory
+    Attributes(PathBuf),
+    /// Remove the entry from the destination
+    Removed(PathBuf),
+    /// Rename an entry that was renamed within the source
+    Renamed {
+        /// Old path
+        from: PathBuf,
+        /// New path
+        to: PathBuf,
+    },
+    /// Events were lost; sync the whole tree again
+    Rescan,
+}
+
+/// Changes collected from one burst of events, coalesced as they arrive
+#[derive(Debug, Default)]
+pub struct Batch {
+    /// Changes in the order they must be applied
+    changes: Vec<Change>,
+    /// `IN_MOVED_FROM` paths waiting for their `IN_MOVED_TO`, by cookie
+    moves: Vec<(u32, PathBuf)>,
+}
+
+impl Batch {
+    /// Whether no change has been recorded
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && self.moves.is_empty()
+    }
+
+    /// Record that `path` must be copied again
+    ///
+    /// Nothing is recorded if `path` is already covered by an update of it
+    /// or a directory above it since it was last removed or renamed.
+    pub fn update(&mut self, path: PathBuf) {
+        let covered = self
+            .changes
+            .iter()
+            .rev()
+            .find(|change| change.touches(&path))
+            .is_some_and(|change| matches!(change, Change::Updated(dir) if path.starts_with(dir)));
+        if !covered {
+            self.changes.push(Change::Updated(path));
+        }
+    }
+
+    /// Record that the metadata of directory `path` changed
+    pub fn attributes(&mut self, path: PathBuf) {
+        let covered = self
+            .changes
+            .iter()
+            .rev()
+            .find(|change| change.touches(&path));
+        if !matches!(covered, Some(Change::Updated(dir) | Change::Attributes(dir)) if path.starts_with(dir))
+        {
+            self.changes.push(Change::Attributes(path));
+        }
+    }
+
+    /// Record that `path` is gone
+    ///
+    /// Earlier updates at or below `path` are dropped, unless a rename in
+    /// between may have carried them elsewhere.
+    pub fn remove(&mut self, path: PathBuf) {
+        self.take_below(&path);
+        self.changes.push(Change::Removed(path));
+    }
+
+    /// Take out the changes at or below `path` since the last rename
+    fn take_below(&mut self, path: &Path) -> Vec<Change> {
+        let start = self
+            .changes
+            .iter()
+            .rposition(|change| matches!(change, Change::Renamed { .. } | Change::Rescan))
+            .map_or(0, |index| index + 1);
+        let (kept, taken) = self
+            .changes
+            .split_off(start)
+            .into_iter()
+            .partition(|change| {
+                !matches!(
+                    change,
+                    Change::Updated(other) | Change::Attributes(other) | Change::Removed(other)
+                        if other.starts_with(path)
+                )
+            });
+        self.changes.extend::<Vec<_>>(kept);
+        taken
+    }
+
+    /// Record the first half of a rename
+    pub fn moved_from(&mut self, cookie: u32, path: PathBuf) {
+        self.moves.push((cookie, path));
+    }
+
+    /// Record the second half of a rename, returning the old path if the
+    /// first half was seen (the entry was renamed within the source)
+    pub fn moved_to(&mut self, cookie: u32, path: PathBuf) -> Option<PathBuf> {
+        match self.moves.iter().position(|(other, _)| *other == cookie) {
+            Some(index) => {
+                let (_, from) = self.moves.remove(index);
+                // Changes made before the rename now apply under the new name
+                let earlier = self.take_below(&from);
+                self.changes.push(Change::Renamed {
+                    from: from.clone(),
+                    to: path.clone(),
+                });
+                for change in earlier {
+                    let moved = |old: &Path| path.join(old.strip_prefix(&from).unwrap_or(old));
+                    match change {
+                        Change::Updated(old) => self.update(moved(&old)),
+                        Change::Attributes(old) => self.attributes(moved(&old)),
+                        Change::Removed(old) => self.changes.push(Change::Removed(moved(&old))),
+                        Change::Renamed { .. } | Change::Rescan => {}
+                    }
+                }
+                Some(from)
+            }
+            None => {
+                self.update(path);
+                None
+            }
+        }
+    }
+
+    /// Record that events were lost
+    pub fn rescan(&mut self) {
+        self.changes.clear();
+        self.changes.push(Change::Rescan);
+    }
+
+    /// The coalesced changes; entries moved out of the source are removed
+    pub fn finish(&mut self) -> Vec<Change> {
+        for (_, path) in std::mem::take(&mut self.moves) {
+            self.remove(path);
+        }
+        std::mem::take(&mut self.changes)
+    }
+}
+
+impl Change {
+    /// Whether this change involves `path`, an entry above it or below it
+    fn touches(&self, path: &Path) -> bool {
+        let related = |other: &Path| path.starts_with(other) || other.starts_with(path);
+        match self {
+            Self::Updated(other) | Self::Attributes(other) | Self::Removed(other) => related(other),
+            Self::Renamed { from, to } => related(from) || related(to),
+            Self::Rescan => true,
+        }
+    }
+}
+
+/// An inotify instance watching every directory of a tree
+struct Inotify {
+    /// The inotify descriptor
+    fd: OwnedFd,
+    /// signalfd receiving the stop signals
+    stop: OwnedFd,
+    /// Source root
+    root: PathBuf,
+    /// Directories the filter excludes are not watched
+    filter: Option<Arc<Filter>>,
+    /// Directory of each watch descriptor, relative to the root
+    #[allow(clippy::disallowed_types)]
+    watches: HashMap<i32, PathBuf>,
+}
+
+impl Inotify {
+    /// Watch `root` and every directory below it that `filter` does not
+    /// exclude
+    #[allow(clippy::disallowed_types)]
+    fn new(root: &Path, filter: Option<Arc<Filter>>) -> io::Result<Self> {
+        // SAFETY: plain syscall
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: inotify_init1 just returned this descriptor
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let set = stop_signals();
+        // SAFETY: set is initialized
+        let stop = unsafe { libc::signalfd(-1, &raw const set, libc::SFD_CLOEXEC) };
+        if stop < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut inotify = Self {
+            fd,
+            // SAFETY: signalfd just returned this descriptor
+            stop: unsafe { OwnedFd::from_raw_fd(stop) },
+            root: root.to_path_buf(),
+            filter,
+            watches: HashMap::new(),
+        };
+        inotify.watch_tree(Path::new(""))?;
+        Ok(inotify)
+    }
+
+    /// Watch directory `rel` and every directory below it, unless excluded
+    fn watch_tree(&mut self, rel: &Path) -> io::Result<()> {
+        let path = self.root.join(rel);
+        if self
+            .filter
+            .as_ref()
+            .is_some_and(|filter| filter.excludes_path(&path, true))
+        {
+            return Ok(());
+        }
+        let name = std::ffi::CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        // SAFETY: name is a valid C string
+        let wd = unsafe { libc::inotify_add_watch(self.fd.as_raw_fd(), name.as_ptr(), WATCH_MASK) };
+        if wd < 0 {
+            let err = io::Error::last_os_error();
+            // Gone or replaced by a non-directory before we got to it
+            if matches!(err.raw_os_error(), Some(libc::ENOENT | libc::ENOTDIR)) {
+                return Ok(());
+            }
+            return Err(err);
+        }
+        self.watches.insert(wd, rel.to_path_buf());
+        let Ok(entries) = std::fs::read_dir(&path) else {
+            return Ok(());
+        };
+        for entry in entries.flatten() {
+            if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                self.watch_tree(&rel.join(entry.file_name()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop watching directory `rel` and everything below it
+    fn unwatch_tree(&mut self, rel: &Path) {
+        let fd = self.fd.as_raw_fd();
+        self.watches.retain(|&wd, dir| {
+            if dir.starts_with(rel) {
+                // SAFETY: plain syscall; a watch the kernel already dropped
+                // only makes it fail
+                unsafe { libc::inotify_rm_watch(fd, wd) };
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// Point the watches below `from` at their new home `to`
+    fn rename_tree(&mut self, from: &Path, to: &Path) {
+        for dir in self.watches.values_mut() {
+            if let Ok(rest) = dir.strip_prefix(from) {
+                *dir = to.join(rest);
+            }
+        }
+    }
+
+    /// Wait up to `timeout` (forever if `None`) for events or a stop signal
+    fn wait(&self, timeout: Option<Duration>) -> io::Result<Wait> {
+        let pollfd = |fd: &OwnedFd| libc::pollfd {
+            fd: fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let mut pollfds = [pollfd(&self.fd), pollfd(&self.stop)];
+        let timeout = timeout.map_or(-1, |timeout| {
+            i32::try_from(timeout.as_millis()).unwrap_or(i32::MAX)
+        });
+        loop {
+            // SAFETY: pollfds is valid for the duration of the call
+            match unsafe { libc::poll(pollfds.as_mut_ptr(), 2, timeout) } {
+                0 => return Ok(Wait::Quiet),
+                n if n > 0 && pollfds[1].revents != 0 => {
+                    STOP_REQUESTED.store(true, Ordering::Relaxed);
+                    return Ok(Wait::Stop);
+                }
+                n if n > 0 => return Ok(Wait::Events),
+                _ => {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Read the pending events into `batch`
+    fn read(&mut self, batch: &mut Batch) -> io::Result<()> {
+        let mut buf = vec![0u8; 64 * 1024];
+        // SAFETY: buf is valid for writes of its whole length
+        let n = unsafe { libc::read(self.fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+        let n = usize::try_from(n).map_err(|_| io::Error::last_os_error())?;
+
+        let mut offset = 0;
+        while offset + EVENT_HEADER <= n {
+            // SAFETY: a whole header is in bounds; it may not be aligned
+            let event: libc::inotify_event =
+                unsafe { std::ptr::read_unaligned(buf.as_ptr().add(offset).cast()) };
+            let len = event.len as usize;
+            let name_bytes = &buf[offset + EVENT_HEADER..(offset + EVENT_HEADER + len).min(n)];
+            let name_len = name_bytes
+                .iter()
+                .position(|&b| b == 0)
+                .unwrap_or(name_bytes.len());
+            let name = std::ffi::OsStr::from_bytes(&name_bytes[..name_len]);
+            offset += EVENT_HEADER + len;
+            self.event(batch, &event, Path::new(name))?;
+        }
+        Ok(())
+    }
+
+    /// Add one event to `batch`
+    fn event(
+        &mut self,
+        batch: &mut Batch,
+        event: &libc::inotify_event,
+        name: &Path,
+    ) -> io::Result<()> {
+        let (wd, mask, cookie) = (event.wd, event.mask, event.cookie);
+        if mask & libc::IN_Q_OVERFLOW != 0 {
+            warn!("Watch events were lost; syncing the whole tree again");
+            batch.rescan();
+            return Ok(());
+        }
+        if mask & libc::IN_IGNORED != 0 {
+            self.watches.remove(&wd);
+            return Ok(());
+        }
+        let Some(dir) = self.watches.get(&wd) else {
+            return Ok(());
+        };
+        if name.as_os_str().is_empty() {
+            // Events about a watched directory itself are also reported to
+            // its parent, except for the root
+            if dir.as_os_str().is_empty() && mask & libc::IN_ATTRIB != 0 {
+                batch.attributes(PathBuf::new());
+            }
+            return Ok(());
+        }
+        let path = dir.join(name);
+        let is_dir = mask & libc::IN_ISDIR != 0;
+
+        // A changed rule file can include directories that were not watched
+        if let Some(filter) = self.filter.clone() {
+            if filter.reads_rule_file(name.as_os_str()) && mask & libc::IN_ATTRIB == 0 {
+                filter.rule_file_changed(&self.root.join(&path));
+                self.watch_tree(&dir.clone())?;
+            }
+        }
+
+        if mask & libc::IN_MOVED_FROM != 0 {
+            batch.moved_from(cookie, path);
+        } else if mask & libc::IN_MOVED_TO != 0 {
+            match batch.moved_to(cookie, path.clone()) {
+                Some(from) if is_dir => self.rename_tree(&from, &path),
+                None if is_dir => self.watch_tree(&path)?,
+                _ => {}
+            }
+        } else if mask & libc::IN_DELETE != 0 {
+            batch.remove(path);
+        } else if mask & libc::IN_CREATE != 0 {
+            if is_dir {
+                self.watch_tree(&path)?;
+            }
+            batch.update(path);
+        } else if mask & libc::IN_ATTRIB != 0 && is_dir {
+            batch.attributes(path);
+        } else if mask & (libc::IN_CLOSE_WRITE | libc::IN_ATTRIB) != 0 {
+            batch.update(path);
+        }
+        Ok(())
+    }
+
+    /// Collect bursts of events and send each as one batch, until a stop
+    /// signal arrives, the receiver goes away or reading events fails
+    fn run(
+        mut self,
+        delay: Duration,
+        batches: &UnboundedSender<io::Result<Vec<Change>>>,
+    ) -> io::Result<()> {
+        let mut batch = Batch::default();
+        let mut stopping = false;
+        while !stopping {
+            if let Wait::Stop = self.wait(None)? {
+                break;
+            }
+            self.read(&mut batch)?;
+            // Keep collecting until the source has been quiet for `delay`
+            loop {
+                match self.wait(Some(delay))? {
+                    Wait::Events => self.read(&mut batch)?,
+                    Wait::Quiet => break,
+                    // Apply what was already seen before stopping
+                    Wait::Stop => {
+                        stopping = true;
+                        break;
+                    }
+                }
+            }
+            if batch.is_empty() {
+                continue;
+            }
+            let changes = batch.finish();
+            for change in &changes {
+                if let Change::Removed(path) = change {
+                    self.unwatch_tree(path);
+                }
+            }
+            if batches.unbounded_send(Ok(changes)).is_err() {
+                return Ok(());
+            }
+        }
+        info!("Stop requested; no longer watching");
+        Ok(())
+    }
+}
+
+/// Source watches set up before the initial sync
+pub struct Watcher {
+    /// Batches of changes from the inotify thread, or why it stopped
+    batches: UnboundedReceiver<io::Result<Vec<Change>>>,
+}
+
+impl Watcher {
+    /// Start watching `args.source`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the watches or the thread reading events cannot be
+    /// set up (e.g. `fs.inotify.max_user_watches` is too low for the tree).
+    pub fn start(args: &Args) -> Result<Self> {
+        let inotify = Inotify::new(&args.source, args.filter.clone()).map_err(|e| {
+            SyncError::FileSystem(format!("Failed to watch {}: {e}", args.source.display()))
+        })?;
+        info!("Watching {} directories", inotify.watches.len());
+        let delay = Duration::from_millis(args.watch_delay);
+        let (sender, batches) = unbounded();
+        std::thread::Builder::new()
+            .name("arsync-watch".to_string())
+            .spawn(move || {
+                if let Err(e) = inotify.run(delay, &sender) {
+                    let _ = sender.unbounded_send(Err(e));
+                }
+            })
+            .map_err(|e| SyncError::FileSystem(format!("Failed to start watching: {e}")))?;
+        Ok(Self { batches })
+    }
+
+    /// Apply changes as they arrive, until a stop signal ends the watch
+    ///
+    /// Failures to apply a change are logged and do not stop the watch.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if events can no longer be read.
+    pub async fn run(mut self, args: &Args) -> Result<()> {
+        loop {
+            crate::systemd::set_idle(true);
+            let changes = match self.batches.next().await {
+                Some(Ok(changes)) => changes,
+                Some(Err(e)) => {
+                    return Err(SyncError::FileSystem(format!(
+                        "Stopped watching {}: {e}",
+                        args.source.display()
+                    )))
+                }
+                None => return Ok(()),
+            };
+            crate::systemd::set_idle(false);
+            info!("Applying {} changes", changes.len());
+            let resync = changed_rule_files(&changes, args);
+            for change in changes {
+                let Some(change) = filtered(change, args).await else {
+                    continue;
+                };
+                if let Err(e) = apply(&change, args).await {
+                    warn!("Failed to apply {change:?}: {e}");
+                }
+            }
+            for dir in resync {
+                if excluded(&dir, args).await {
+                    continue;
+                }
+                if let Err(e) = sync_entry(&dir, args).await {
+                    warn!("Failed to resync {}: {e}", dir.display());
+                }
+            }
+        }
+    }
+}
+
+/// Directories whose per-directory rule files (`--gitignore`,
+/// `--cvs-exclude`) `changes` touch
+///
+/// Their cached rules are dropped so the new ones apply, and the returned
+/// directories are synced again afterwards to copy what is no longer
+/// excluded.
+fn changed_rule_files(changes: &[Change], args: &Args) -> Vec<PathBuf> {
+    let Some(filter) = &args.filter else {
+        return Vec::new();
+    };
+    let mut dirs = Vec::new();
+    for change in changes {
+        let paths = match change {
+            Change::Updated(path) | Change::Removed(path) => [Some(path), None],
+            Change::Renamed { from, to } => [Some(from), Some(to)],
+            Change::Attributes(_) | Change::Rescan => continue,
+        };
+        for path in paths.into_iter().flatten() {
+            if !path
+                .file_name()
+                .is_some_and(|name| filter.reads_rule_file(name))
+            {
+                continue;
+            }
+            filter.rule_file_changed(&args.source.join(path));
+            let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+    }
+    dirs
+}
+
+/// `change` as far as the filter lets it through
+///
+/// Excluded entries are left alone at the destination, as in a full sync; a
+/// rename across the filter becomes a removal or a copy.
+async fn filtered(change: Change, args: &Args) -> Option<Change> {
+    match change {
+        Change::Updated(ref path) | Change::Attributes(ref path) | Change::Removed(ref path)
+            if excluded(path, args).await =>
+        {
+            None
+        }
+        Change::Renamed { from, to } => {
+            match (excluded(&from, args).await, excluded(&to, args).await) {
+                (false, false) => Some(Change::Renamed { from, to }),
+                (false, true) => Some(Change::Removed(from)),
+                (true, false) => Some(Change::Updated(to)),
+                (true, true) => None,
+            }
+        }
+        change => Some(change),
+    }
+}
+
+/// Whether the filter excludes `path`, itself or by a directory above it
+async fn excluded(path: &Path, args: &Args) -> bool {
+    let Some(filter) = &args.filter else {
+        return false;
+    };
+    let (src, dst) = (args.source.join(path), args.destination.join(path));
+    filter.read_rules_above(&src).await;
+    // A removed entry can only be judged by its destination copy
+    let metadata = match compio_fs_extended::metadata::symlink_metadata(&src).await {
+        Ok(metadata) => Ok(metadata),
+        Err(_) => compio_fs_extended::metadata::symlink_metadata(&dst).await,
+    };
+    filter.excludes_path(&src, metadata.is_ok_and(|metadata| metadata.is_dir()))
+}
+
+/// Apply one change to the destination
+async fn apply(change: &Change, args: &Args) -> Result<()> {
+    match change {
+        Change::Rescan => sync_entry(Path::new(""), args).await,
+        Change::Updated(path) => sync_entry(path, args).await,
+        Change::Attributes(path) => {
+            let (src, dst) = (args.source.join(path), args.destination.join(path));
+            if !dst.is_dir() {
+                return sync_entry(path, args).await;
+            }
+            let metadata = crate::directory::ExtendedMetadata::new(&src).await?;
+            crate::directory::preserve_directory_metadata(&src, &dst, &metadata, args).await?;
+            Ok(())
+        }
+        Change::Removed(path) => {
+            let dst = args.destination.join(path);
+            info!("Removing {}", dst.display());
+            let removed = match parent_fd(&dst).await {
+                Ok((dir, name)) => remove_tree(&dir, name).await,
+                Err(e) => Err(e),
+            };
+            match removed {
+                Err(ExtendedError::Io(e)) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+                Err(e) => Err(SyncError::FileSystem(format!(
+                    "Failed to remove {}: {e}",
+                    dst.display()
+                ))),
+                Ok(()) => Ok(()),
+            }
+        }
+        Change::Renamed { from, to } => {
+            let (old, new) = (args.destination.join(from), args.destination.join(to));
+            info!("Renaming {} to {}", old.display(), new.display());
+            if let Some(parent) = new.parent() {
+                compio_fs_extended::directory::create_dir_all(parent, 0o777)
+                    .await
+                    .map_err(|e| {
+                        SyncError::FileSystem(format!(
+                            "Failed to create directory {}: {e}",
+                            parent.display()
+                        ))
+                    })?;
+            }
+            match rename_entry(&old, &new).await {
+                Ok(()) => Ok(()),
+                // Not at the destination (yet): copy it instead
+                Err(e) => {
+                    debug!("Rename failed ({e}), copying {} instead", to.display());
+                    sync_entry(to, args).await
+                }
+            }
+        }
+    }
+}
+
+/// The open parent directory of `path`, and `path`'s name within it
+async fn parent_fd(path: &Path) -> std::result::Result<(DirectoryFd, &Path), ExtendedError> {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return Err(ExtendedError::Io(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "path has no parent",
+        )));
+    };
+    Ok((DirectoryFd::open(parent).await?, Path::new(name)))
+}
+
+/// Rename destination entry `old` to `new`, replacing what is there
+async fn rename_entry(old: &Path, new: &Path) -> std::result::Result<(), ExtendedError> {
+    let (old_dir, old_name) = parent_fd(old).await?;
+    let (new_dir, new_name) = parent_fd(new).await?;
+    rename_at(&old_dir, old_name, &new_dir, new_name, RenameMode::Replace).await
+}
+
+/// Remove entry `name` of `dir`, and everything below it if it is a directory
+async fn remove_tree(dir: &DirectoryFd, name: &Path) -> std::result::Result<(), ExtendedError> {
+    if !dir.symlink_metadata_at(name).await?.is_dir() {
+        return unlink_at(dir, name).await;
+    }
+    let child = dir.open_at(name).await?;
+    for entry in child.read_dir().await? {
+        let entry_name = Path::new(entry.name());
+        if entry.file_type() == Some(EntryType::Directory) {
+            Box::pin(remove_tree(&child, entry_name)).await?;
+        } else {
+            match unlink_at(&child, entry_name).await {
+                // A directory whose type getdents64 did not report
+                Err(ExtendedError::Io(e)) if e.raw_os_error() == Some(libc::EISDIR) => {
+                    Box::pin(remove_tree(&child, entry_name)).await?;
+                }
+                result => result?,
+            }
+        }
+    }
+    remove_dir_at(dir, name).await
+}
+
+/// Copy source entry `path` to the destination again
+async fn sync_entry(path: &Path, args: &Args) -> Result<()> {
+    let (src, dst) = (args.source.join(path), args.destination.join(path));
+    let metadata = match compio_fs_extended::metadata::symlink_metadata(&src).await {
+        Ok(metadata) => metadata,
+        // Gone again; the event removing it follows
+        Err(ExtendedError::Io(e)) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            return Err(SyncError::FileSystem(format!(
+                "Failed to stat {}: {e}",
+                src.display()
+            )))
+        }
+    };
+    if metadata.is_symlink() {
+        info!("Syncing {}", src.display());
+        let metadata = crate::directory::ExtendedMetadata { metadata };
+        crate::directory::copy_symlink(&src, None, &dst, &metadata, args).await?;
+        return Ok(());
+    }
+    if !metadata.is_file() && !metadata.is_dir() {
+        debug!("Not syncing special file {}", src.display());
+        return Ok(());
+    }
+    info!("Syncing {}", src.display());
+    // Files take the tree walk's path so their metadata is preserved the same way
+    if metadata.is_file() {
+        if let Some(parent) = dst.parent() {
+            compio_fs_extended::directory::create_dir_all(parent, 0o777)
+                .await
+                .map_err(|e| {
+                    SyncError::FileSystem(format!(
+                        "Failed to create directory {}: {e}",
+                        parent.display()
+                    ))
+                })?;
+        }
+        crate::copy::copy_file(&src, &dst, args).await?;
+        return Ok(());
+    }
+    let entry_args = Args {
+        source: src,
+        destination: dst,
+        ..args.clone()
+    };
+    crate::sync::sync_files(&entry_args).await.map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(s: &str) -> PathBuf {
+        PathBuf::from(s)
+    }
+
+    #[test]
+    fn test_batch_coalesces_updates() {
+        let mut batch = Batch::default();
+        batch.update(path("a"));
+        batch.update(path("a"));
+        batch.update(path("dir"));
+        batch.update(path("dir/file"));
+        assert_eq!(
+            batch.finish(),
+            [Change::Updated(path("a")), Change::Updated(path("dir"))]
+        );
+    }
+
+    #[test]
+    fn test_batch_remove_drops_earlier_updates() {
+        let mut batch = Batch::default();
+        batch.update(path("dir/file"));
+        batch.update(path("other"));
+        batch.remove(path("dir"));
+        batch.update(path("dir"));
+        assert_eq!(
+            batch.finish(),
+            [
+                Change::Updated(path("other")),
+                Change::Removed(path("dir")),
+                Change::Updated(path("dir")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_batch_pairs_renames() {
+        let mut batch = Batch::default();
+        batch.update(path("old"));
+        batch.moved_from(7, path("old"));
+        assert_eq!(batch.moved_to(7, path("new")), Some(path("old")));
+        // Moved out of the source: removed; moved in: copied
+        batch.moved_from(8, path("gone"));
+        assert_eq!(batch.moved_to(9, path("arrived")), None);
+        assert_eq!(
+            batch.finish(),
+            [
+                Change::Renamed {
+                    from: path("old"),
+                    to: path("new")
+                },
+                Change::Updated(path("new")),
+                Change::Updated(path("arrived")),
+                Change::Removed(path("gone")),
+            ]
+        );
+        assert!(batch.is_empty());
+    }
+
+    #[test]
+    fn test_batch_rename_carries_earlier_changes() {
+        let mut batch = Batch::default();
+        batch.update(path("dir/sub/file"));
+        batch.update(path("other"));
+        batch.moved_from(1, path("dir"));
+        batch.moved_to(1, path("renamed"));
+        assert_eq!(
+            batch.finish(),
+            [
+                Change::Updated(path("other")),
+                Change::Renamed {
+                    from: path("dir"),
+                    to: path("renamed")
+                },
+                Change::Updated(path("renamed/sub/file")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_batch_attributes_and_rescan() {
+        let mut batch = Batch::default();
+        batch.update(path("dir"));
+        batch.attributes(path("dir"));
+        batch.attributes(path("other"));
+        assert_eq!(
+            batch.finish(),
+            [
+                Change::Updated(path("dir")),
+                Change::Attributes(path("other"))
+            ]
+        );
+
+        batch.update(path("a"));
+        batch.rescan();
+        batch.remove(path("b"));
+        assert_eq!(batch.finish(), [Change::Rescan, Change::Removed(path("b"))]);
+    }
+
+    #[test]
+    fn test_excluded_directories_are_not_watched() {
+        use crate::filter::{Action, FilterSource};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        for dir in ["keep", "skip/deeper", "ignored"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        std::fs::write(root.join(".gitignore"), "ignored/\n").unwrap();
+        let sources = [FilterSource::Pattern(Action::Exclude, "skip/".to_string())];
+        let filter = Filter::load(root, &sources, true).unwrap().map(Arc::new);
+
+        let mut inotify = Inotify::new(root, filter).unwrap();
+        let watched = |inotify: &Inotify| {
+            let mut dirs: Vec<_> = inotify.watches.values().cloned().collect();
+            dirs.sort();
+            dirs
+        };
+        assert_eq!(watched(&inotify), [path(""), path("keep")]);
+
+        // No longer ignoring a directory starts watching it
+        std::fs::write(root.join(".gitignore"), "").unwrap();
+        // SAFETY: inotify_event is plain data
+        let mut event: libc::inotify_event = unsafe { std::mem::zeroed() };
+        event.wd = *inotify
+            .watches
+            .iter()
+            .find(|(_, dir)| dir.as_os_str().is_empty())
+            .unwrap()
+            .0;
+        event.mask = libc::IN_CLOSE_WRITE;
+        inotify
+            .event(&mut Batch::default(), &event, Path::new(".gitignore"))
+            .unwrap();
+        assert_eq!(watched(&inotify), [path(""), path("ignored"), path("keep")]);
+    }
+}
@@ -0,0 +1,70 @@
+//! Copies under injected I/O faults (`fault-injection` feature)
+//!
+//! Run with `cargo test --features fault-injection --test fault_injection_tests`.
+
+#![cfg(feature = "fault-injection")]
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// Several chunks of content that differs at every offset
+fn contents() -> Vec<u8> {
+    (0..3_000_000u32)
+        .map(|i| u8::try_from(i * 31 % 251).unwrap())
+        .collect()
+}
+
+/// Run arsync over `src` into `dst` with `faults` in `ARSYNC_FAULTS`
+fn arsync(faults: &str, extra: &[&str], src: &Path, dst: &Path) -> assert_cmd::assert::Assert {
+    Command::cargo_bin("arsync")
+        .unwrap()
+        .env("ARSYNC_FAULTS", faults)
+        .args(["-a", "--copy-method", "read-write"])
+        .args(extra)
+        .arg(format!("{}/", src.display()))
+        .arg(dst)
+        .assert()
+}
+
+#[test]
+fn test_short_completions_are_resumed() {
+    let temp_dir = TempDir::new().unwrap();
+    let (src, dst) = (temp_dir.path().join("src"), temp_dir.path().join("dst"));
+    std::fs::create_dir(&src).unwrap();
+    std::fs::write(src.join("big"), contents()).unwrap();
+    std::fs::write(src.join("small"), "small").unwrap();
+
+    arsync("short=1,seed=3", &[], &src, &dst).success();
+    assert_eq!(std::fs::read(dst.join("big")).unwrap(), contents());
+    assert_eq!(std::fs::read(dst.join("small")).unwrap(), b"small");
+
+    // Updating in place rewrites what differs through the same short writes
+    std::fs::write(dst.join("big"), vec![0u8; 4_000_000]).unwrap();
+    arsync("short=1,seed=4", &["--no-whole-file"], &src, &dst).success();
+    assert_eq!(std::fs::read(dst.join("big")).unwrap(), contents());
+}
+
+#[test]
+fn test_injected_eio_fails_copy_cleanly() {
+    let temp_dir = TempDir::new().unwrap();
+    let (src, dst) = (temp_dir.path().join("src"), temp_dir.path().join("dst"));
+    std::fs::create_dir(&src).unwrap();
+    std::fs::write(src.join("big"), contents()).unwrap();
+
+    // The failure is reported and no partial file is left under the name
+    arsync("eio=1", &[], &src, &dst)
+        .stderr(predicate::str::contains("Input/output error"))
+        .stderr(predicate::str::contains("panicked").not());
+    assert!(!dst.join("big").exists());
+    arsync("eio=1", &["--tmpfile"], &src, &dst)
+        .stderr(predicate::str::contains("Input/output error"));
+    assert!(!dst.join("big").exists());
+
+    // A run without faults then copies it
+    arsync("", &[], &src, &dst).success();
+    assert_eq!(std::fs::read(dst.join("big")).unwrap(), contents());
+}
@@ -69,3 +69,1384 @@ fn test_dry_run() {
     .assert()
     .success();
 }
+
+#[test]
+fn test_read_only_destination_aborts_early() {
+    // Use any read-only mount the test machine happens to have
+    let mounts = std::fs::read_to_string("/proc/mounts").unwrap_or_default();
+    let Some(read_only) = mounts.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let options = fields.get(3)?;
+        let mount_point = std::path::Path::new(fields.get(1)?);
+        (options.split(',').any(|o| o == "ro") && mount_point.is_dir())
+            .then(|| mount_point.to_path_buf())
+    }) else {
+        println!("Skipping test: no read-only mount available");
+        return;
+    };
+
+    let temp_dir = TempDir::new().unwrap();
+    std::fs::write(temp_dir.path().join("file.txt"), "content").unwrap();
+
+    let mut cmd = Command::cargo_bin("arsync").unwrap();
+    cmd.args([
+        temp_dir.path().to_str().unwrap(),
+        read_only.join("arsync-dest").to_str().unwrap(),
+    ])
+    .assert()
+    .code(3)
+    .stderr(predicate::str::contains("read-only filesystem"));
+}
+
+#[test]
+fn test_delay_updates_leaves_no_staging_dirs() {
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(src.join("sub")).unwrap();
+    std::fs::write(src.join("top.txt"), "top").unwrap();
+    std::fs::write(src.join("sub/nested.txt"), "nested").unwrap();
+    std::fs::create_dir_all(dst.join("sub")).unwrap();
+    std::fs::write(dst.join("sub/nested.txt"), "stale").unwrap();
+
+    let mut cmd = Command::cargo_bin("arsync").unwrap();
+    cmd.args([
+        "-a",
+        "--delay-updates",
+        src.to_str().unwrap(),
+        dst.to_str().unwrap(),
+    ])
+    .assert()
+    .success();
+
+    assert_eq!(std::fs::read(dst.join("top.txt")).unwrap(), b"top");
+    assert_eq!(
+        std::fs::read(dst.join("sub/nested.txt")).unwrap(),
+        b"nested"
+    );
+    assert!(!dst.join(".~tmp~").exists());
+    assert!(!dst.join("sub/.~tmp~").exists());
+}
+
+#[test]
+fn test_max_depth_limits_traversal() {
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(src.join("a/b")).unwrap();
+    std::fs::write(src.join("top.txt"), "top").unwrap();
+    std::fs::write(src.join("a/one.txt"), "one").unwrap();
+    std::fs::write(src.join("a/b/two.txt"), "two").unwrap();
+
+    let mut cmd = Command::cargo_bin("arsync").unwrap();
+    cmd.args([
+        "-a",
+        "--max-depth",
+        "1",
+        src.to_str().unwrap(),
+        dst.to_str().unwrap(),
+    ])
+    .assert()
+    .success();
+
+    assert!(dst.join("top.txt").exists());
+    assert!(dst.join("a").is_dir());
+    assert!(!dst.join("a/one.txt").exists());
+    assert!(!dst.join("a/b").exists());
+}
+
+#[test]
+fn test_prefetch_copies_tree() {
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(src.join("a")).unwrap();
+    std::fs::write(src.join("big.bin"), vec![7u8; 3 * 1024 * 1024]).unwrap();
+    for name in ["one.txt", "a/two.txt", "a/three.txt"] {
+        std::fs::write(src.join(name), name).unwrap();
+    }
+    std::os::unix::fs::symlink("one.txt", src.join("link")).unwrap();
+
+    Command::cargo_bin("arsync")
+        .unwrap()
+        .args([
+            "-a",
+            "--prefetch",
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    assert_eq!(
+        std::fs::read(dst.join("big.bin")).unwrap(),
+        std::fs::read(src.join("big.bin")).unwrap()
+    );
+    assert_eq!(
+        std::fs::read_to_string(dst.join("a/two.txt")).unwrap(),
+        "a/two.txt"
+    );
+    assert_eq!(
+        std::fs::read_link(dst.join("link")).unwrap(),
+        std::path::Path::new("one.txt")
+    );
+}
+
+#[test]
+fn test_deterministic_runs_produce_identical_logs() {
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(src.join("a/b")).unwrap();
+    for name in ["z.txt", "m.txt", "a/one.txt", "a/two.txt", "a/b/three.txt"] {
+        std::fs::write(src.join(name), name).unwrap();
+    }
+    std::fs::hard_link(src.join("z.txt"), src.join("a/z-link.txt")).unwrap();
+
+    let run = || {
+        let _ = std::fs::remove_dir_all(&dst);
+        let output = Command::cargo_bin("arsync")
+            .unwrap()
+            .args([
+                "-vv",
+                "-a",
+                "-H",
+                "--deterministic",
+                src.to_str().unwrap(),
+                dst.to_str().unwrap(),
+            ])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        (output.stdout, output.stderr)
+    };
+
+    let first = run();
+    assert!(!first.1.is_empty());
+    assert_eq!(first, run());
+}
+
+#[test]
+fn test_tree_deeper_than_files_in_flight() {
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    let deepest = (0..60).fold(src.clone(), |dir, level| dir.join(format!("d{level}")));
+    std::fs::create_dir_all(&deepest).unwrap();
+    std::fs::write(deepest.join("file.txt"), "deep").unwrap();
+
+    // Directories waiting on their children must not hold the permits
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("arsync"))
+        .args([
+            "-r",
+            "--max-files-in-flight",
+            "20",
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ])
+        .spawn()
+        .unwrap();
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(30);
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break Some(status);
+        }
+        if std::time::Instant::now() > deadline {
+            child.kill().unwrap();
+            child.wait().unwrap();
+            break None;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    };
+
+    assert!(status.is_some_and(|status| status.success()), "copy hung");
+    let copied = deepest.strip_prefix(&src).unwrap().join("file.txt");
+    assert_eq!(std::fs::read_to_string(dst.join(copied)).unwrap(), "deep");
+}
+
+#[test]
+fn test_hardlink_db_keeps_links_across_runs() {
+    use std::os::unix::fs::MetadataExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    let db = temp_dir.path().join("links.toml");
+    std::fs::create_dir_all(src.join("a")).unwrap();
+    std::fs::write(src.join("data.bin"), "shared content").unwrap();
+    std::fs::hard_link(src.join("data.bin"), src.join("a/link.bin")).unwrap();
+
+    let run = || {
+        Command::cargo_bin("arsync")
+            .unwrap()
+            .args([
+                "-a",
+                "-H",
+                "--hardlink-db",
+                db.to_str().unwrap(),
+                src.to_str().unwrap(),
+                dst.to_str().unwrap(),
+            ])
+            .assert()
+            .success();
+    };
+    let inodes = || {
+        (
+            std::fs::metadata(dst.join("data.bin")).unwrap().ino(),
+            std::fs::metadata(dst.join("a/link.bin")).unwrap().ino(),
+        )
+    };
+
+    run();
+    let (first, second) = inodes();
+    assert_eq!(first, second, "hardlinks should be preserved");
+    let saved = std::fs::read_to_string(&db).unwrap();
+    assert!(saved.contains("[inodes]"), "{saved}");
+
+    // A re-run reuses the recorded copy rather than copying it again
+    run();
+    assert_eq!(inodes(), (first, first));
+    assert_eq!(
+        std::fs::read(dst.join("a/link.bin")).unwrap(),
+        b"shared content"
+    );
+}
+
+#[test]
+fn test_unremovable_hardlinked_destination_is_reported() {
+    use std::os::unix::io::AsRawFd;
+
+    const FS_APPEND_FL: libc::c_int = 0x20;
+
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(&src).unwrap();
+    std::fs::create_dir_all(&dst).unwrap();
+    std::fs::write(src.join("data.txt"), "new content").unwrap();
+    std::fs::write(dst.join("data.txt"), "old").unwrap();
+    std::fs::hard_link(dst.join("data.txt"), dst.join("other.txt")).unwrap();
+
+    // An append-only directory refuses unlink, even for root
+    let dir = std::fs::File::open(&dst).unwrap();
+    let set_flags = |flags: libc::c_int| {
+        // SAFETY: FS_IOC_SETFLAGS reads a single int through the pointer
+        unsafe { libc::ioctl(dir.as_raw_fd(), libc::FS_IOC_SETFLAGS, &raw const flags) == 0 }
+    };
+    if !set_flags(FS_APPEND_FL) {
+        println!("Skipping test: cannot make the destination append-only");
+        return;
+    }
+
+    let output = Command::cargo_bin("arsync")
+        .unwrap()
+        .args(["-r", &format!("{}/", src.display()), dst.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(set_flags(0));
+
+    // Like any other failed file, it is reported and counted as an error
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Failed to unlink hardlinked destination"),
+        "{stderr}"
+    );
+    // The copy must not have written through the shared inode
+    assert_eq!(std::fs::read(dst.join("other.txt")).unwrap(), b"old");
+}
+
+#[test]
+fn test_dedupe_dest_links_identical_files() {
+    use std::os::unix::fs::MetadataExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(src.join("sub")).unwrap();
+    std::fs::write(src.join("a.txt"), "duplicated content").unwrap();
+    std::fs::write(src.join("sub/b.txt"), "duplicated content").unwrap();
+    std::fs::write(src.join("c.txt"), "different content!").unwrap();
+
+    // --deterministic copies one file at a time, so the second duplicate
+    // always finds the first already written
+    Command::cargo_bin("arsync")
+        .unwrap()
+        .args([
+            "-r",
+            "--dedupe-dest",
+            "--deterministic",
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    let ino = |name: &str| std::fs::metadata(dst.join(name)).unwrap().ino();
+    assert_eq!(ino("a.txt"), ino("sub/b.txt"));
+    assert_ne!(ino("a.txt"), ino("c.txt"));
+    assert_eq!(
+        std::fs::read(dst.join("sub/b.txt")).unwrap(),
+        b"duplicated content"
+    );
+}
+
+#[test]
+fn test_ignore_existing_and_existing() {
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    std::fs::create_dir_all(&src).unwrap();
+    std::fs::write(src.join("present.txt"), "new").unwrap();
+    std::fs::write(src.join("absent.txt"), "new").unwrap();
+
+    let run = |flag: &str, dst: &std::path::Path| {
+        std::fs::create_dir_all(dst).unwrap();
+        std::fs::write(dst.join("present.txt"), "old").unwrap();
+        Command::cargo_bin("arsync")
+            .unwrap()
+            .args(["-r", flag, src.to_str().unwrap(), dst.to_str().unwrap()])
+            .assert()
+            .success();
+    };
+
+    let dst = temp_dir.path().join("ignore");
+    run("--ignore-existing", &dst);
+    assert_eq!(std::fs::read(dst.join("present.txt")).unwrap(), b"old");
+    assert_eq!(std::fs::read(dst.join("absent.txt")).unwrap(), b"new");
+
+    let dst = temp_dir.path().join("existing");
+    run("--existing", &dst);
+    assert_eq!(std::fs::read(dst.join("present.txt")).unwrap(), b"new");
+    assert!(!dst.join("absent.txt").exists());
+}
+
+#[test]
+fn test_update_skips_newer_destination_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(&src).unwrap();
+    std::fs::create_dir_all(&dst).unwrap();
+    let set_mtime = |path: &std::path::Path, secs: u64| {
+        let time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs);
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(time)
+            .unwrap();
+    };
+
+    for name in ["newer.txt", "older.txt"] {
+        std::fs::write(src.join(name), "source").unwrap();
+        set_mtime(&src.join(name), 1_500_000_000);
+        std::fs::write(dst.join(name), "destination").unwrap();
+    }
+    set_mtime(&dst.join("newer.txt"), 1_600_000_000);
+    set_mtime(&dst.join("older.txt"), 1_400_000_000);
+
+    Command::cargo_bin("arsync")
+        .unwrap()
+        .args(["-r", "-u", src.to_str().unwrap(), dst.to_str().unwrap()])
+        .assert()
+        .success();
+
+    assert_eq!(
+        std::fs::read(dst.join("newer.txt")).unwrap(),
+        b"destination"
+    );
+    assert_eq!(std::fs::read(dst.join("older.txt")).unwrap(), b"source");
+}
+
+#[test]
+fn test_progress_prints_status_line_without_tty() {
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(src.join("sub")).unwrap();
+    std::fs::write(src.join("a.txt"), "aaaa").unwrap();
+    std::fs::write(src.join("b.txt"), "bb").unwrap();
+    std::fs::write(src.join("sub/c.txt"), "c").unwrap();
+
+    Command::cargo_bin("arsync")
+        .unwrap()
+        .args([
+            "-r",
+            "--progress",
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Progress: 3/3 files, 7 B/7 B"));
+}
+
+#[test]
+fn test_json_event_stream() {
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(&src).unwrap();
+    std::fs::write(src.join("a.txt"), "aaaa").unwrap();
+    std::fs::write(src.join("b.txt"), "bb").unwrap();
+
+    let output = Command::cargo_bin("arsync")
+        .unwrap()
+        .args(["-r", "--json", src.to_str().unwrap(), dst.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let events: Vec<serde_json::Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let count = |kind: &str| events.iter().filter(|e| e["event"] == kind).count();
+    assert_eq!(count("file_started"), 2);
+    assert_eq!(count("file_completed"), 2);
+    assert_eq!(count("error"), 0);
+
+    let summary = events.last().unwrap();
+    assert_eq!(summary["event"], "summary");
+    assert_eq!(summary["files"], 2);
+    assert_eq!(summary["bytes"], 6);
+}
+
+#[test]
+fn test_json_event_stream_is_not_mixed_with_logs() {
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(src.join("sub")).unwrap();
+    std::fs::write(src.join("a.txt"), "aaaa").unwrap();
+    std::fs::write(src.join("sub/b.txt"), "bb").unwrap();
+
+    let output = Command::cargo_bin("arsync")
+        .unwrap()
+        .args([
+            "-rvv",
+            "--json",
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(!output.stderr.is_empty(), "logs should go to stderr");
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    for line in stdout.lines() {
+        assert!(
+            serde_json::from_str::<serde_json::Value>(line).is_ok(),
+            "not a JSON event: {line}"
+        );
+    }
+    assert!(stdout.lines().last().unwrap().contains("\"summary\""));
+}
+
+#[test]
+fn test_stats_summary() {
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(src.join("sub")).unwrap();
+    std::fs::write(src.join("a.txt"), "aaaa").unwrap();
+    std::fs::write(src.join("sub/b.txt"), "bb").unwrap();
+    std::os::unix::fs::symlink("a.txt", src.join("link")).unwrap();
+
+    Command::cargo_bin("arsync")
+        .unwrap()
+        .args([
+            "-rl",
+            "--stats",
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Number of files: 5 (reg: 2, dir: 2, link: 1, special: 0)",
+        ))
+        .stdout(predicate::str::contains("Total file size: 6 bytes"))
+        .stdout(predicate::str::contains("Literal data: 6 bytes"));
+}
+
+#[test]
+fn test_itemize_changes() {
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(src.join("sub")).unwrap();
+    std::fs::write(src.join("sub/new.txt"), "new").unwrap();
+    std::fs::write(src.join("same.txt"), "same").unwrap();
+    std::os::unix::fs::symlink("same.txt", src.join("link")).unwrap();
+
+    let run = || {
+        let output = Command::cargo_bin("arsync")
+            .unwrap()
+            .args(["-a", "-i", src.to_str().unwrap(), dst.to_str().unwrap()])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let mut lines: Vec<String> = String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        lines.sort();
+        lines
+    };
+
+    assert_eq!(
+        run(),
+        [
+            ">f+++++++++ same.txt",
+            ">f+++++++++ sub/new.txt",
+            "cL+++++++++ link",
+            "cd+++++++++ sub/",
+        ]
+    );
+
+    // Rerun after changing one file: only its size and time differ
+    std::fs::write(src.join("sub/new.txt"), "newer").unwrap();
+    let lines = run();
+    assert!(lines.contains(&">f.st...... sub/new.txt".to_string()));
+    assert!(lines.contains(&">f......... same.txt".to_string()));
+}
+
+#[test]
+fn test_log_file_records_items() {
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    let log = temp_dir.path().join("arsync.log");
+    std::fs::create_dir_all(&src).unwrap();
+    std::fs::write(src.join("a.txt"), "aaaa").unwrap();
+    std::os::unix::fs::symlink("a.txt", src.join("link")).unwrap();
+
+    Command::cargo_bin("arsync")
+        .unwrap()
+        .args([
+            "-rl",
+            "--log-file",
+            log.to_str().unwrap(),
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_empty());
+    let contents = std::fs::read_to_string(&log).unwrap();
+    assert!(contents.contains("] >f+++++++++ a.txt\n"));
+    assert!(contents.contains("] cL+++++++++ link -> a.txt\n"));
+
+    // A second run appends, here with a custom format and a skipped file
+    Command::cargo_bin("arsync")
+        .unwrap()
+        .args([
+            "-r",
+            "--ignore-existing",
+            "--log-file",
+            log.to_str().unwrap(),
+            "--log-file-format",
+            "%o %n %l",
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+    let contents = std::fs::read_to_string(&log).unwrap();
+    assert!(contents.contains("] >f+++++++++ a.txt\n"));
+    assert!(contents.contains("] skip a.txt 4\n"));
+}
+
+#[test]
+fn test_io_priority() {
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(&src).unwrap();
+    let content = vec![7u8; 300 * 1024];
+    std::fs::write(src.join("data.bin"), &content).unwrap();
+
+    for priority in ["idle", "best-effort:6"] {
+        Command::cargo_bin("arsync")
+            .unwrap()
+            .args([
+                "-r",
+                "--copy-method",
+                "read-write",
+                "--io-priority",
+                priority,
+                src.to_str().unwrap(),
+                dst.to_str().unwrap(),
+            ])
+            .assert()
+            .success();
+        assert_eq!(std::fs::read(dst.join("data.bin")).unwrap(), content);
+    }
+
+    Command::cargo_bin("arsync")
+        .unwrap()
+        .args([
+            "-r",
+            "--io-priority",
+            "realtime",
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--io-priority"));
+}
+
+#[test]
+fn test_cpu_affinity() {
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(src.join("sub")).unwrap();
+    std::fs::write(src.join("a.txt"), "aaaa").unwrap();
+    std::fs::write(src.join("sub/b.txt"), "bb").unwrap();
+
+    Command::cargo_bin("arsync")
+        .unwrap()
+        .args([
+            "-r",
+            "--cpu-affinity",
+            "0",
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+    assert_eq!(
+        std::fs::read_to_string(dst.join("sub/b.txt")).unwrap(),
+        "bb"
+    );
+
+    Command::cargo_bin("arsync")
+        .unwrap()
+        .args([
+            "-r",
+            "--cpu-affinity",
+            "node:4096",
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("NUMA node 4096"));
+}
+
+#[test]
+fn test_max_memory_budget() {
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(&src).unwrap();
+    // Several files, each larger than the whole budget
+    let files: Vec<Vec<u8>> = (0..8u8)
+        .map(|i| (0..200 * 1024).map(|j| (j % 251) as u8 ^ i).collect())
+        .collect();
+    for (i, content) in files.iter().enumerate() {
+        std::fs::write(src.join(format!("file{i}")), content).unwrap();
+    }
+
+    Command::cargo_bin("arsync")
+        .unwrap()
+        .args([
+            "-r",
+            "--copy-method",
+            "read-write",
+            "--max-memory",
+            "64K",
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+    for (i, content) in files.iter().enumerate() {
+        assert_eq!(
+            &std::fs::read(dst.join(format!("file{i}"))).unwrap(),
+            content
+        );
+    }
+
+    Command::cargo_bin("arsync")
+        .unwrap()
+        .args([
+            "-r",
+            "--max-memory",
+            "lots",
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid size"));
+}
+
+#[test]
+fn test_max_device_bytes() {
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(&src).unwrap();
+    let files: Vec<Vec<u8>> = (0..6u8)
+        .map(|i| (0..300 * 1024).map(|j| (j % 239) as u8 ^ i).collect())
+        .collect();
+    for (i, content) in files.iter().enumerate() {
+        std::fs::write(src.join(format!("file{i}")), content).unwrap();
+    }
+
+    // Both the in-kernel and the read/write copy paths honour the cap
+    for (method, dst) in [("auto", dst.join("auto")), ("read-write", dst.join("rw"))] {
+        Command::cargo_bin("arsync")
+            .unwrap()
+            .args([
+                "-r",
+                "--copy-method",
+                method,
+                "--max-device-bytes",
+                "32K",
+                "--max-memory",
+                "48K",
+                src.to_str().unwrap(),
+                dst.to_str().unwrap(),
+            ])
+            .assert()
+            .success();
+        for (i, content) in files.iter().enumerate() {
+            assert_eq!(
+                &std::fs::read(dst.join(format!("file{i}"))).unwrap(),
+                content
+            );
+        }
+    }
+}
+
+#[test]
+fn test_no_whole_file_rewrites_only_changed_chunks() {
+    use std::os::unix::fs::MetadataExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(&src).unwrap();
+    std::fs::create_dir_all(&dst).unwrap();
+
+    // 1 MiB source; the destination differs in one 64 KiB chunk and has a
+    // stale tail past the source's end
+    let content: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+    let mut stale = content.clone();
+    stale[512 * 1024..512 * 1024 + 10].fill(0xff);
+    stale.extend_from_slice(b"stale tail");
+    std::fs::write(src.join("data.bin"), &content).unwrap();
+    std::fs::write(dst.join("data.bin"), &stale).unwrap();
+    let inode = std::fs::metadata(dst.join("data.bin")).unwrap().ino();
+
+    Command::cargo_bin("arsync")
+        .unwrap()
+        .args([
+            "-r",
+            "--no-whole-file",
+            "--buffer-size-kb",
+            "64",
+            "--stats",
+            &format!("{}/", src.display()),
+            dst.to_str().unwrap(),
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Literal data: 65,536 bytes"))
+        .stdout(predicate::str::contains("Matched data: 983,040 bytes"));
+
+    assert_eq!(std::fs::read(dst.join("data.bin")).unwrap(), content);
+    // Updated in place rather than replaced
+    assert_eq!(
+        std::fs::metadata(dst.join("data.bin")).unwrap().ino(),
+        inode
+    );
+}
+
+#[test]
+fn test_tmpfile_creates_and_replaces_atomically() {
+    use std::os::unix::fs::MetadataExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(src.join("sub")).unwrap();
+    std::fs::create_dir_all(&dst).unwrap();
+    std::fs::write(src.join("new.txt"), "new file").unwrap();
+    std::fs::write(src.join("sub/nested.txt"), "nested").unwrap();
+    std::fs::write(src.join("existing.txt"), "updated contents").unwrap();
+    std::fs::write(dst.join("existing.txt"), "old").unwrap();
+    let inode = std::fs::metadata(dst.join("existing.txt")).unwrap().ino();
+
+    Command::cargo_bin("arsync")
+        .unwrap()
+        .args([
+            "-a",
+            "--tmpfile",
+            &format!("{}/", src.display()),
+            dst.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    assert_eq!(
+        std::fs::read_to_string(dst.join("new.txt")).unwrap(),
+        "new file"
+    );
+    assert_eq!(
+        std::fs::read_to_string(dst.join("sub/nested.txt")).unwrap(),
+        "nested"
+    );
+    assert_eq!(
+        std::fs::read_to_string(dst.join("existing.txt")).unwrap(),
+        "updated contents"
+    );
+    // Replaced by a new inode rather than rewritten, and no temp names remain
+    assert_ne!(
+        std::fs::metadata(dst.join("existing.txt")).unwrap().ino(),
+        inode
+    );
+    let mut names: Vec<_> = std::fs::read_dir(&dst)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["existing.txt", "new.txt", "sub"]);
+}
+
+#[test]
+fn test_single_file_copy() {
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src.txt");
+    let dst = temp_dir.path().join("dst.txt");
+    std::fs::write(&src, "single file").unwrap();
+
+    Command::cargo_bin("arsync")
+        .unwrap()
+        .args(["-a", src.to_str().unwrap(), dst.to_str().unwrap()])
+        .assert()
+        .success();
+
+    assert_eq!(std::fs::read_to_string(&dst).unwrap(), "single file");
+}
+
+#[test]
+fn test_watch_applies_changes() {
+    use std::time::{Duration, Instant};
+
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(src.join("dir/sub")).unwrap();
+    std::fs::write(src.join("kept.txt"), "kept").unwrap();
+    std::fs::write(src.join("old.txt"), "renamed").unwrap();
+    std::fs::write(src.join("gone.txt"), "gone").unwrap();
+    std::fs::write(src.join("dir/sub/file.txt"), "file").unwrap();
+
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("arsync"))
+        .args([
+            "-a",
+            "--watch",
+            "--watch-delay",
+            "50",
+            &format!("{}/", src.display()),
+            dst.to_str().unwrap(),
+        ])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let wait_for = |done: &dyn Fn() -> bool| {
+        let deadline = Instant::now() + Duration::from_secs(20);
+        while !done() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        done()
+    };
+    let initial = wait_for(&|| dst.join("dir/sub/file.txt").exists());
+
+    std::fs::write(src.join("kept.txt"), "updated").unwrap();
+    std::fs::write(src.join("new.txt"), "new").unwrap();
+    std::fs::rename(src.join("old.txt"), src.join("new-name.txt")).unwrap();
+    std::fs::remove_file(src.join("gone.txt")).unwrap();
+    std::fs::write(src.join("dir/sub/added.txt"), "added").unwrap();
+    std::fs::rename(src.join("dir"), src.join("moved")).unwrap();
+    let applied = wait_for(&|| {
+        std::fs::read_to_string(dst.join("kept.txt")).is_ok_and(|s| s == "updated")
+            && dst.join("new.txt").exists()
+            && dst.join("new-name.txt").exists()
+            && !dst.join("gone.txt").exists()
+            && dst.join("moved/sub/added.txt").exists()
+            && !dst.join("dir").exists()
+    });
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert!(initial, "initial sync did not finish");
+    assert!(applied, "changes were not applied");
+    assert_eq!(std::fs::read_to_string(dst.join("new.txt")).unwrap(), "new");
+    assert_eq!(
+        std::fs::read_to_string(dst.join("new-name.txt")).unwrap(),
+        "renamed"
+    );
+    assert_eq!(
+        std::fs::read_to_string(dst.join("moved/sub/file.txt")).unwrap(),
+        "file"
+    );
+    assert_eq!(
+        std::fs::read_to_string(dst.join("moved/sub/added.txt")).unwrap(),
+        "added"
+    );
+}
+
+#[test]
+fn test_watch_munges_new_symlinks_and_removes_trees() {
+    use std::time::{Duration, Instant};
+
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(src.join("tree/sub")).unwrap();
+    std::fs::write(src.join("tree/sub/file.txt"), "file").unwrap();
+    std::os::unix::fs::symlink("gone", src.join("dangling")).unwrap();
+
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("arsync"))
+        .args([
+            "-a",
+            "--munge-links",
+            "--watch",
+            "--watch-delay",
+            "50",
+            &format!("{}/", src.display()),
+            dst.to_str().unwrap(),
+        ])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let wait_for = |done: &dyn Fn() -> bool| {
+        let deadline = Instant::now() + Duration::from_secs(20);
+        while !done() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        done()
+    };
+    let target = |name: &str| std::fs::read_link(dst.join(name)).ok();
+    let initial = wait_for(&|| dst.join("tree/sub/file.txt").exists());
+
+    std::os::unix::fs::symlink("/etc/passwd", src.join("link")).unwrap();
+    // Replaces a dangling destination symlink
+    std::fs::remove_file(src.join("dangling")).unwrap();
+    std::os::unix::fs::symlink("elsewhere", src.join("dangling")).unwrap();
+    std::fs::remove_dir_all(src.join("tree")).unwrap();
+    let applied = wait_for(&|| {
+        target("link").is_some()
+            && target("dangling") == Some("/rsyncd-munged/elsewhere".into())
+            && !dst.join("tree").exists()
+    });
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert!(initial, "initial sync did not finish");
+    assert!(applied, "changes were not applied");
+    assert_eq!(target("link"), Some("/rsyncd-munged//etc/passwd".into()));
+}
+
+#[test]
+fn test_watch_preserves_metadata_of_new_files() {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+    use std::time::{Duration, Instant, SystemTime};
+
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    let staging = temp_dir.path().join("staging.txt");
+    std::fs::create_dir_all(&src).unwrap();
+    std::fs::write(src.join("first.txt"), "first").unwrap();
+
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("arsync"))
+        .args([
+            "-a",
+            "-X",
+            "--watch",
+            "--watch-delay",
+            "50",
+            &format!("{}/", src.display()),
+            dst.to_str().unwrap(),
+        ])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let wait_for = |done: &dyn Fn() -> bool| {
+        let deadline = Instant::now() + Duration::from_secs(20);
+        while !done() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        done()
+    };
+    let initial = wait_for(&|| dst.join("first.txt").exists());
+
+    // Prepared outside the source, then moved in complete
+    let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_012_608_000);
+    std::fs::write(&staging, "late").unwrap();
+    std::fs::set_permissions(&staging, std::fs::Permissions::from_mode(0o640)).unwrap();
+    xattr::set(&staging, "user.watch", b"kept").unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(&staging)
+        .unwrap()
+        .set_modified(mtime)
+        .unwrap();
+    std::fs::rename(&staging, src.join("late.txt")).unwrap();
+    let applied = wait_for(&|| {
+        std::fs::symlink_metadata(dst.join("late.txt"))
+            .is_ok_and(|m| m.modified().is_ok_and(|t| t == mtime))
+    });
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert!(initial, "initial sync did not finish");
+    assert!(applied, "new file was not copied with its mtime");
+    let late = dst.join("late.txt");
+    assert_eq!(std::fs::read_to_string(&late).unwrap(), "late");
+    assert_eq!(std::fs::metadata(&late).unwrap().mode() & 0o7777, 0o640);
+    assert_eq!(
+        xattr::get(&late, "user.watch").unwrap().as_deref(),
+        Some(&b"kept"[..])
+    );
+}
+
+#[test]
+fn test_watch_notifies_systemd_and_stops_on_sigterm() {
+    use std::os::unix::net::UnixDatagram;
+    use std::time::Duration;
+
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(&src).unwrap();
+    std::fs::write(src.join("file.txt"), "file").unwrap();
+    let socket_path = temp_dir.path().join("notify.sock");
+    let socket = UnixDatagram::bind(&socket_path).unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(20)))
+        .unwrap();
+
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("arsync"))
+        .args([
+            "-a",
+            "--watch",
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ])
+        .env("NOTIFY_SOCKET", &socket_path)
+        .env("WATCHDOG_USEC", "200000")
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+
+    // Idle watches keep the watchdog fed and say so in their status
+    let mut messages = Vec::new();
+    let mut buf = [0u8; 256];
+    while !(messages
+        .iter()
+        .any(|m: &String| m.starts_with("STATUS=Watching"))
+        && messages.iter().filter(|m| *m == "WATCHDOG=1").count() >= 2)
+    {
+        let Ok(n) = socket.recv(&mut buf) else {
+            break;
+        };
+        messages.push(String::from_utf8_lossy(&buf[..n]).into_owned());
+    }
+
+    // SAFETY: plain syscall on our own child
+    unsafe { libc::kill(i32::try_from(child.id()).unwrap(), libc::SIGTERM) };
+    let status = child.wait().unwrap();
+    while let Ok(n) = socket.recv(&mut buf) {
+        let message = String::from_utf8_lossy(&buf[..n]).into_owned();
+        let stopping = message == "STOPPING=1";
+        messages.push(message);
+        if stopping {
+            break;
+        }
+    }
+
+    assert_eq!(messages.first().map(String::as_str), Some("READY=1"));
+    assert!(messages.iter().any(|m| m.starts_with("STATUS=Watching")));
+    assert!(messages.iter().filter(|m| *m == "WATCHDOG=1").count() >= 2);
+    assert_eq!(messages.last().map(String::as_str), Some("STOPPING=1"));
+    assert!(status.success(), "watch did not exit cleanly: {status}");
+}
+
+#[test]
+fn test_watch_stops_during_initial_sync() {
+    use std::time::{Duration, Instant};
+
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(&src).unwrap();
+    let total = 5_000;
+    for i in 0..total {
+        std::fs::write(src.join(format!("file{i:05}")), "data").unwrap();
+    }
+
+    // One entry at a time, so the initial sync is still running when asked
+    // to stop
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("arsync"))
+        .args([
+            "-a",
+            "--deterministic",
+            "--watch",
+            &format!("{}/", src.display()),
+            dst.to_str().unwrap(),
+        ])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    let deadline = Instant::now() + Duration::from_secs(20);
+    while !dst.join("file00000").exists() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    // SAFETY: plain syscall on our own child
+    unsafe { libc::kill(i32::try_from(child.id()).unwrap(), libc::SIGTERM) };
+    let status = child.wait().unwrap();
+
+    assert!(status.success(), "watch did not exit cleanly: {status}");
+    let copied = std::fs::read_dir(&dst).unwrap().count();
+    assert!(copied < total, "the initial sync ran to the end");
+}
+
+#[test]
+fn test_drop_privileges_keeps_only_chown_and_fowner() {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    // SAFETY: geteuid has no preconditions and cannot fail
+    if unsafe { libc::geteuid() } != 0 {
+        eprintln!("test_drop_privileges_keeps_only_chown_and_fowner: SKIPPED (requires root)");
+        return;
+    }
+    let temp_dir = TempDir::new().unwrap();
+    std::fs::set_permissions(temp_dir.path(), std::fs::Permissions::from_mode(0o755)).unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    let log = temp_dir.path().join("arsync.log");
+    std::fs::create_dir_all(&src).unwrap();
+    std::fs::create_dir_all(&dst).unwrap();
+    std::fs::write(src.join("owned.txt"), "owned").unwrap();
+    std::os::unix::fs::chown(src.join("owned.txt"), Some(1234), Some(1234)).unwrap();
+    std::fs::write(src.join("secret.txt"), "secret").unwrap();
+    std::fs::set_permissions(
+        src.join("secret.txt"),
+        std::fs::Permissions::from_mode(0o600),
+    )
+    .unwrap();
+    std::fs::set_permissions(&src, std::fs::Permissions::from_mode(0o755)).unwrap();
+    std::os::unix::fs::chown(&dst, Some(65534), None).unwrap();
+
+    Command::cargo_bin("arsync")
+        .unwrap()
+        .args([
+            "-a",
+            "--drop-privileges",
+            "65534",
+            "--log-file",
+            log.to_str().unwrap(),
+            &format!("{}/", src.display()),
+            dst.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    // Files are still given away, but root-only files can no longer be read
+    let owned = std::fs::metadata(dst.join("owned.txt")).unwrap();
+    assert_eq!((owned.uid(), owned.gid()), (1234, 1234));
+    assert!(!dst.join("secret.txt").exists());
+    // The log file was opened before dropping root
+    assert!(std::fs::read_to_string(&log).unwrap().contains("owned.txt"));
+}
+
+#[test]
+fn test_drop_privileges_unknown_user() {
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    std::fs::create_dir_all(&src).unwrap();
+
+    Command::cargo_bin("arsync")
+        .unwrap()
+        .args([
+            "-a",
+            "--drop-privileges",
+            "arsync-no-such-user",
+            src.to_str().unwrap(),
+            temp_dir.path().join("dst").to_str().unwrap(),
+        ])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_exclude_from_and_include_from_keep_order() {
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(src.join("target/debug")).unwrap();
+    std::fs::create_dir_all(src.join("docs")).unwrap();
+    for file in [
+        "main.o",
+        "keep.o",
+        "notes.txt",
+        "target/debug/app",
+        "docs/guide.md",
+        "docs/draft.md",
+    ] {
+        std::fs::write(src.join(file), file).unwrap();
+    }
+    let excludes = temp_dir.path().join("excludes");
+    std::fs::write(
+        &excludes,
+        "# build output\n*.o\ntarget/\n\n; docs\ndocs/*\n",
+    )
+    .unwrap();
+
+    // keep.o and docs/guide.md are included before the file excludes them
+    Command::cargo_bin("arsync")
+        .unwrap()
+        .args([
+            "-a",
+            "--include",
+            "keep.o",
+            "--include-from",
+            "-",
+            "--exclude-from",
+            excludes.to_str().unwrap(),
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ])
+        .write_stdin("docs/guide.md\n")
+        .assert()
+        .success();
+
+    let mut copied: Vec<_> = walkdir(&dst);
+    copied.sort();
+    assert_eq!(
+        copied,
+        ["docs", "docs/guide.md", "keep.o", "notes.txt"].map(std::path::PathBuf::from)
+    );
+}
+
+#[test]
+fn test_gitignore_skips_ignored_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(src.join("target/debug")).unwrap();
+    std::fs::create_dir_all(src.join("crate/src")).unwrap();
+    std::fs::write(src.join(".gitignore"), "target/\n*.log\n").unwrap();
+    std::fs::write(src.join("crate/.gitignore"), "!keep.log\n/generated.rs\n").unwrap();
+    for file in [
+        "target/debug/app",
+        "build.log",
+        "crate/keep.log",
+        "crate/generated.rs",
+        "crate/src/lib.rs",
+        "crate/src/generated.rs",
+    ] {
+        std::fs::write(src.join(file), file).unwrap();
+    }
+
+    Command::cargo_bin("arsync")
+        .unwrap()
+        .args([
+            "-a",
+            "--gitignore",
+            src.to_str().unwrap(),
+            dst.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    let mut copied: Vec<_> = walkdir(&dst);
+    copied.sort();
+    assert_eq!(
+        copied,
+        [
+            ".gitignore",
+            "crate",
+            "crate/.gitignore",
+            "crate/keep.log",
+            "crate/src",
+            "crate/src/generated.rs",
+            "crate/src/lib.rs",
+        ]
+        .map(std::path::PathBuf::from)
+    );
+}
+
+#[test]
+fn test_cvs_exclude_skips_junk_and_cvsignore() {
+    let temp_dir = TempDir::new().unwrap();
+    let src = temp_dir.path().join("src");
+    let dst = temp_dir.path().join("dst");
+    std::fs::create_dir_all(src.join(".git/objects")).unwrap();
+    std::fs::create_dir_all(src.join("lib")).unwrap();
+    std::fs::write(src.join(".cvsignore"), "*.gen\n").unwrap();
+    for file in [
+        ".git/HEAD",
+        "main.c",
+        "main.o",
+        "main.c~",
+        "notes.txt",
+        "core",
+        "table.gen",
+        "lib/util.c",
+        "lib/util.o",
+        "lib/table.gen",
+    ] {
+        std::fs::write(src.join(file), file).unwrap();
+    }
+
+    Command::cargo_bin("arsync")
+        .unwrap()
+        .args(["-a", "-C", src.to_str().unwrap(), dst.to_str().unwrap()])
+        .env("CVSIGNORE", "notes.*")
+        .assert()
+        .success();
+
+    let mut copied: Vec<_> = walkdir(&dst);
+    copied.sort();
+    assert_eq!(
+        copied,
+        [".cvsignore", "lib", "lib/table.gen", "lib/util.c", "main.c",]
+            .map(std::path::PathBuf::from)
+    );
+}
+
+/// Paths of everything below `root`, relative to it
+fn walkdir(root: &std::path::Path) -> Vec<std::path::PathBuf> {
+    let mut paths = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            paths.push(path.strip_prefix(root).unwrap().to_path_buf());
+            if path.is_dir() {
+                dirs.push(path);
+            }
+        }
+    }
+    paths
+}
@@ -6,8 +6,9 @@
 //! 1. With flag enabled: metadata IS preserved
 //! 2. With flag disabled: metadata IS NOT preserved (uses default/umask)
 
-use arsync::cli::Args;
+use arsync::cli::{Args, CopyMethod};
 use arsync::copy::copy_file;
+use arsync::crtime::CrtimeChange;
 use arsync::directory::{preserve_directory_metadata, ExtendedMetadata};
 use std::fs;
 use std::os::unix::fs::PermissionsExt;
@@ -395,3 +396,154 @@ async fn test_individual_flags_match_archive_components() {
 
     println!("✓ Verified --perms and --archive permission behavior matches");
 }
+
+/// Test: --fake-super restores ownership and mode recorded in `user.rsync.%stat`
+///
+/// Requirement: Copying a fake-super backup back with privileges applies the
+/// recorded values for real and does not carry the xattr over.
+#[compio::test]
+async fn test_fake_super_restores_recorded_stat() {
+    if !arsync::ownership::has_cap_chown() {
+        println!("Skipping test: requires CAP_CHOWN");
+        return;
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let src_path = temp_dir.path().join("source.txt");
+    let dst_path = temp_dir.path().join("destination.txt");
+    fs::write(&src_path, "Test content").unwrap();
+    if xattr::set(&src_path, "user.rsync.%stat", b"104750 0,0 1234:5678").is_err() {
+        println!("Skipping test: user xattrs not supported");
+        return;
+    }
+
+    let mut args = create_args_archive();
+    args.fake_super = true;
+    args.numeric_ids = true;
+    copy_file(&src_path, &dst_path, &args).await.unwrap();
+
+    let dst_metadata = fs::metadata(&dst_path).unwrap();
+    assert_eq!(dst_metadata.permissions().mode() & 0o7777, 0o4750);
+    assert_eq!(
+        std::os::unix::fs::MetadataExt::uid(&dst_metadata),
+        1234,
+        "uid should come from the recorded stat"
+    );
+    assert_eq!(std::os::unix::fs::MetadataExt::gid(&dst_metadata), 5678);
+    assert!(
+        xattr::get(&dst_path, "user.rsync.%stat").unwrap().is_none(),
+        "recorded stat should not be copied once applied"
+    );
+}
+
+/// Test: SELinux labels are preserved with -a and dropped with --no-selinux
+///
+/// Requirement: `security.selinux` is copied with archive mode even without
+/// -X, and `--no-selinux` keeps it out of the generic xattr copy too.
+#[compio::test]
+async fn test_selinux_label_preservation_and_opt_out() {
+    let temp_dir = TempDir::new().unwrap();
+    let src_path = temp_dir.path().join("source.txt");
+    fs::write(&src_path, "Test content").unwrap();
+    let label = b"system_u:object_r:user_home_t:s0\0";
+    if xattr::set(&src_path, "security.selinux", label).is_err() {
+        println!("Skipping test: cannot set security.selinux here");
+        return;
+    }
+
+    let labeled = temp_dir.path().join("labeled.txt");
+    copy_file(&src_path, &labeled, &create_args_archive())
+        .await
+        .unwrap();
+    if let Ok(Some(value)) = xattr::get(&labeled, "security.selinux") {
+        // With an SELinux policy loaded the kernel may canonicalize the label
+        if value != label {
+            println!("Skipping label comparison: label was rewritten by the policy");
+            return;
+        }
+    } else {
+        panic!("SELinux label should be preserved with --archive");
+    }
+
+    let mut args = create_args_archive();
+    args.xattrs = true;
+    args.no_selinux = true;
+    let unlabeled = temp_dir.path().join("unlabeled.txt");
+    copy_file(&src_path, &unlabeled, &args).await.unwrap();
+    assert_ne!(
+        xattr::get(&unlabeled, "security.selinux")
+            .unwrap()
+            .as_deref(),
+        Some(&label[..]),
+        "--no-selinux should not copy the source label"
+    );
+}
+
+/// Test: file capabilities survive a copy with --archive
+///
+/// Requirement: `security.capability` is applied after ownership and data so
+/// the kernel does not clear it, even without -X.
+#[compio::test]
+async fn test_file_capabilities_preserved_with_archive() {
+    let temp_dir = TempDir::new().unwrap();
+    let src_path = temp_dir.path().join("binary");
+    let dst_path = temp_dir.path().join("binary-copy");
+    fs::write(&src_path, "#!/bin/true\n").unwrap();
+
+    // VFS_CAP_REVISION_2 with cap_net_raw (13) permitted and effective
+    let mut caps = Vec::new();
+    for word in [0x0200_0001_u32, 1 << 13, 0, 0, 0] {
+        caps.extend_from_slice(&word.to_le_bytes());
+    }
+    if xattr::set(&src_path, "security.capability", &caps).is_err() {
+        println!("Skipping test: requires CAP_SETFCAP");
+        return;
+    }
+
+    copy_file(&src_path, &dst_path, &create_args_archive())
+        .await
+        .unwrap();
+
+    assert_eq!(
+        xattr::get(&dst_path, "security.capability").unwrap(),
+        Some(caps),
+        "file capabilities should be preserved"
+    );
+}
+
+/// Test: --crtimes reports what happened to the creation time
+///
+/// Requirement: Linux cannot set birth times, so a copy with --crtimes must
+/// still succeed and record whether the source creation time was kept.
+#[compio::test]
+async fn test_crtimes_outcome_recorded() {
+    let temp_dir = TempDir::new().unwrap();
+    let src_path = temp_dir.path().join("source.txt");
+    fs::write(&src_path, "Test content").unwrap();
+
+    let plain = temp_dir.path().join("plain.txt");
+    let outcome = copy_file(&src_path, &plain, &create_args_no_metadata())
+        .await
+        .unwrap();
+    assert_eq!(outcome.crtime, CrtimeChange::NotRequested);
+
+    // Auto takes the reflink or copy_file_range path, ReadWrite the buffered one
+    for method in [CopyMethod::Auto, CopyMethod::ReadWrite] {
+        let mut args = create_args_no_metadata();
+        args.crtimes = true;
+        args.copy_method = method.clone();
+        let with_crtimes = temp_dir.path().join(format!("crtimes-{method:?}.txt"));
+        let outcome = copy_file(&src_path, &with_crtimes, &args).await.unwrap();
+        assert_eq!(fs::read(&with_crtimes).unwrap(), b"Test content");
+
+        let expected = match (
+            fs::metadata(&src_path).unwrap().created(),
+            fs::metadata(&with_crtimes).unwrap().created(),
+        ) {
+            (Err(_), _) => CrtimeChange::NotRecorded,
+            (Ok(src), Ok(dst)) if src == dst => CrtimeChange::Preserved,
+            (Ok(_), _) => CrtimeChange::Unsupported,
+        };
+        assert_eq!(outcome.crtime, expected, "{method:?}");
+    }
+}
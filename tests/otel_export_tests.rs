@@ -0,0 +1,87 @@
+//! OpenTelemetry export of spans (`spans` feature)
+//!
+//! Run with `cargo test --features spans --test otel_export_tests`.
+
+#![cfg(feature = "spans")]
+#![allow(clippy::unwrap_used)]
+#![allow(clippy::expect_used)]
+
+use assert_cmd::Command;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use tempfile::TempDir;
+
+/// Answer OTLP/HTTP requests on `listener` with 200, returning each request's
+/// path and body; stops at the first connection that sends nothing
+fn collect(listener: &TcpListener) -> Vec<(String, Vec<u8>)> {
+    let mut requests = Vec::new();
+    while let Ok((stream, _)) = listener.accept() {
+        let mut reader = BufReader::new(stream);
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+            break;
+        }
+        let path = request_line
+            .split_whitespace()
+            .nth(1)
+            .unwrap_or_default()
+            .to_string();
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            if header.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        reader
+            .get_mut()
+            .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+            .unwrap();
+        requests.push((path, body));
+    }
+    requests
+}
+
+#[test]
+fn test_spans_are_exported_over_otlp() {
+    let temp_dir = TempDir::new().unwrap();
+    let (src, dst) = (temp_dir.path().join("src"), temp_dir.path().join("dst"));
+    std::fs::create_dir(&src).unwrap();
+    std::fs::write(src.join("file.txt"), "traced").unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let collector = std::thread::spawn(move || collect(&listener));
+
+    Command::cargo_bin("arsync")
+        .unwrap()
+        .env("OTEL_EXPORTER_OTLP_ENDPOINT", format!("http://{address}"))
+        .env_remove("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT")
+        .env_remove("OTEL_SERVICE_NAME")
+        .env_remove("OTEL_RESOURCE_ATTRIBUTES")
+        .args(["-a", &format!("{}/", src.display()), dst.to_str().unwrap()])
+        .assert()
+        .success();
+    // arsync sends its spans before exiting; this ends the collector
+    drop(TcpStream::connect(address).unwrap());
+
+    let requests = collector.join().unwrap();
+    let body: Vec<u8> = requests
+        .iter()
+        .filter(|(path, _)| path == "/v1/traces")
+        .flat_map(|(_, body)| body.clone())
+        .collect();
+    let contains = |needle: &[u8]| body.windows(needle.len()).any(|w| w == needle);
+    assert!(!body.is_empty(), "no spans were exported");
+    assert!(contains(b"arsync"), "service name missing");
+    assert!(contains(b"traverse"), "traverse span missing");
+    assert!(contains(b"copy_file"), "copy_file span missing");
+}